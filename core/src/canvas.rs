@@ -0,0 +1,214 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::{max, min};
+
+use na::{vector, Matrix2x3, Matrix4, Vector3};
+use nalgebra as na;
+
+use super::raster::{
+    get_triangle_bounding_box, interpolate_depth, to_raster_coord, Edges, BLOCK_SIZE,
+};
+
+/// Depth, the viewport maps normalized device coordinates from [-1, 1] to, same as the scene does.
+const DEPTH_RANGE: f32 = 255.0;
+
+/// Color and depth buffers of a single frame, which triangles in screen space are drawn into, tested against
+/// the depth and colored by a closure. It is the whole pipeline, which is left without the scene and the
+/// standard library, so transforming and lighting the vertices is up to the caller.
+pub struct Canvas {
+    width: u32,
+    height: u32,
+    color: Vec<u8>, // RGB of every pixel, rows going from the bottom, same as raster y.
+    depth: Vec<f32>, // Depth of every pixel, greater is closer.
+}
+
+impl Canvas {
+    /// Black canvas of width x height pixels with nothing drawn.
+    pub fn new(width: u32, height: u32) -> Self {
+        let n_pixels = (width * height) as usize;
+        return Self {
+            width,
+            height,
+            color: vec![0; 3 * n_pixels],
+            depth: vec![f32::MIN; n_pixels],
+        };
+    }
+
+    /// Fills the frame with the color and forgets the depth of everything drawn.
+    pub fn clear(&mut self, color: [u8; 3]) {
+        for pixel in self.color.chunks_exact_mut(3) {
+            pixel.copy_from_slice(&color);
+        }
+        self.depth.fill(f32::MIN);
+    }
+
+    pub fn get_width(&self) -> u32 {
+        return self.width;
+    }
+
+    pub fn get_height(&self) -> u32 {
+        return self.height;
+    }
+
+    /// RGB bytes of the frame, rows going from the bottom.
+    pub fn get_color(&self) -> &[u8] {
+        return &self.color;
+    }
+
+    pub fn get_depth(&self) -> &[f32] {
+        return &self.depth;
+    }
+
+    /// Color of the pixel at x, y from the bottom left corner, packed into 5 bits of red, 6 of green and 5 of
+    /// blue, which small SPI displays usually take.
+    pub fn get_rgb565(&self, x: u32, y: u32) -> u16 {
+        let index = 3 * (x + y * self.width) as usize;
+        let [r, g, b] = [0, 1, 2].map(|c| self.color[index + c] as u16);
+        return (r >> 3) << 11 | (g >> 2) << 5 | (b >> 3);
+    }
+
+    /// Draws the triangle of vertices in screen space - x, y in pixels and depth, see `to_screen`. Every
+    /// covered pixel, closer than what is already drawn there, is colored by the fragment closure, which gets
+    /// barycentric coordinates of the pixel and returns the color with channels in [0, 255], or None to skip
+    /// the pixel, leaving its depth as it was.
+    pub fn draw_triangle(
+        &mut self,
+        vertices: [Vector3<f32>; 3],
+        mut fragment: impl FnMut(Vector3<f32>) -> Option<Vector3<f32>>,
+    ) {
+        let coords =
+            Matrix2x3::from_columns(&vertices.map(|vertex| to_raster_coord(vertex.x, vertex.y)));
+        let edges = match Edges::new(coords) {
+            Some(edges) => edges,
+            None => return,
+        };
+        let z_values = vector![vertices[0].z, vertices[1].z, vertices[2].z];
+        let bbox = get_triangle_bounding_box(coords);
+        let x_min = max(0, bbox.ll.x);
+        let x_max = min(bbox.ur.x, self.width as i32 - 1);
+        let y_min = max(0, bbox.ll.y);
+        let y_max = min(bbox.ur.y, self.height as i32 - 1);
+        if x_min > x_max || y_min > y_max {
+            return;
+        }
+        let width = self.width as i32;
+        let (color, depth) = (&mut self.color, &mut self.depth);
+        for block_y in y_min / BLOCK_SIZE..=y_max / BLOCK_SIZE {
+            let block_y_min = max(y_min, block_y * BLOCK_SIZE);
+            let block_y_max = min(y_max, block_y * BLOCK_SIZE + BLOCK_SIZE - 1);
+            for block_x in x_min / BLOCK_SIZE..=x_max / BLOCK_SIZE {
+                let block_x_min = max(x_min, block_x * BLOCK_SIZE);
+                let block_x_max = min(x_max, block_x * BLOCK_SIZE + BLOCK_SIZE - 1);
+                let coverage =
+                    edges.classify_rect(block_x_min, block_x_max, block_y_min, block_y_max, 0);
+                edges.rasterize_rect(
+                    block_x_min,
+                    block_x_max,
+                    block_y_min,
+                    block_y_max,
+                    coverage,
+                    |x, y, bar_coord| {
+                        let index = (x + y * width) as usize;
+                        let z_value = interpolate_depth(z_values, bar_coord);
+                        if z_value <= depth[index] {
+                            return;
+                        }
+                        if let Some(fragment_color) = fragment(bar_coord) {
+                            depth[index] = z_value;
+                            for c in 0..3 {
+                                color[3 * index + c] = fragment_color[c].clamp(0.0, 255.0) as u8;
+                            }
+                        }
+                    },
+                );
+            }
+        }
+    }
+}
+
+/// Screen space position of the point on a frame of width x height pixels, transforming it into clip space
+/// with the matrix, e.g. projection times view times model, and mapping normalized device coordinates to the
+/// pixels and depth in [0, 255] the same way as the scene. Projections, which map closer points to greater
+/// depth like the ones of the scene, draw the closest triangle on top.
+pub fn to_screen(
+    clip_matrix: &Matrix4<f32>,
+    point: Vector3<f32>,
+    width: u32,
+    height: u32,
+) -> Vector3<f32> {
+    let clip = clip_matrix * point.push(1.0);
+    let ndc = clip.xyz() / clip.w;
+    let (w, h) = ((width - 1) as f32, (height - 1) as f32);
+    return vector![
+        (ndc.x + 1.0) * w / 2.0,
+        (ndc.y + 1.0) * h / 2.0,
+        (ndc.z + 1.0) * DEPTH_RANGE / 2.0
+    ];
+}
+
+/// Diffuse color of a surface with the normal, lit from the direction towards the light, both unit length,
+/// and the ambient fraction of the light in [0, 1]. Normals, interpolated across a triangle, are slightly
+/// shorter between the vertices, which only darkens it a little, so they can go without normalizing.
+pub fn shade_lambert(
+    color: Vector3<f32>,
+    normal: Vector3<f32>,
+    light_direction: Vector3<f32>,
+    ambient: f32,
+) -> Vector3<f32> {
+    let diffuse = normal.dot(&light_direction).max(0.0);
+    return color * (ambient + (1.0 - ambient) * diffuse);
+}
+
+#[cfg(all(test, feature = "fixed_point"))]
+mod tests {
+    use super::*;
+
+    /// FNV-1a hash of the bytes.
+    fn hash(bytes: impl Iterator<Item = u8>) -> u64 {
+        let mut hash = 0xcbf29ce484222325;
+        for byte in bytes {
+            hash = (hash ^ byte as u64).wrapping_mul(0x100000001b3);
+        }
+        return hash;
+    }
+
+    /// Hexagon, fanned around its center, so its triangles share edges, and a triangle, tilted in depth, which
+    /// cuts through it. Colors of the triangles are constant, so the frame only depends on the coverage, shared
+    /// edges and the depth test, which fixed point makes the same on every platform.
+    #[test]
+    fn renders_golden_scene() {
+        let mut canvas = Canvas::new(64, 48);
+        canvas.clear([0, 0, 0]);
+        let center = vector![31.5, 23.25, 128.0];
+        let corners = [
+            vector![52.25, 23.0, 100.0],
+            vector![42.0, 41.5, 140.0],
+            vector![21.125, 41.5, 90.0],
+            vector![10.75, 23.5, 150.0],
+            vector![21.0, 5.0, 110.0],
+            vector![42.5, 5.25, 130.0],
+        ];
+        for k in 0..6 {
+            let vertices = [center, corners[k], corners[(k + 1) % 6]];
+            let color = vector![40.0 * k as f32, 255.0 - 40.0 * k as f32, 128.0];
+            canvas.draw_triangle(vertices, |_| Some(color));
+        }
+        let tilted = [
+            vector![3.0, 3.0, 60.0],
+            vector![61.0, 12.0, 200.0],
+            vector![20.0, 45.0, 130.0],
+        ];
+        canvas.draw_triangle(tilted, |_| Some(vector![255.0, 255.0, 255.0]));
+        let color_hash = hash(canvas.get_color().iter().copied());
+        let depth_hash = hash(
+            canvas
+                .get_depth()
+                .iter()
+                .flat_map(|depth| depth.to_bits().to_le_bytes()),
+        );
+        assert_eq!(
+            (color_hash, depth_hash),
+            (14753936619422785760, 6970967063541479328)
+        );
+    }
+}
@@ -0,0 +1,28 @@
+//! Rasterization core of the renderer, building without the standard library, only with `alloc`, so it can
+//! run on microcontrollers, e.g. drawing frames for a small SPI display.
+//!
+//! [`raster`] holds the fixed-point edge functions, block coverage, barycentric coordinates, depth
+//! interpolation, multisampling and line rasterization, which the scene of `tiny_renderer` renders with, and
+//! [`canvas::Canvas`] is the smallest pipeline on top of them - color and depth buffers, which triangles in
+//! screen space are drawn into with a fragment closure.
+//!
+//! ```
+//! use nalgebra::{vector, Matrix4, Vector3};
+//! use tiny_renderer_core::canvas::{shade_lambert, to_screen, Canvas};
+//!
+//! let mut canvas = Canvas::new(160, 128);
+//! canvas.clear([0, 0, 0]);
+//! let triangle = [vector![-0.5, -0.5, 0.0], vector![0.5, -0.5, 0.0], vector![0.0, 0.5, 0.0]];
+//! let vertices = triangle.map(|point| to_screen(&Matrix4::identity(), point, 160, 128));
+//! let color = shade_lambert(vector![255.0, 128.0, 0.0], Vector3::z(), Vector3::z(), 0.1);
+//! canvas.draw_triangle(vertices, |_| Some(color));
+//! let pixel = canvas.get_rgb565(80, 64);
+//! # assert_eq!(pixel, 0xfc00);
+//! ```
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod canvas;
+pub mod raster;
@@ -0,0 +1,693 @@
+use core::cmp::{max, min};
+
+use na::{vector, Matrix2x3, Vector2, Vector3};
+use nalgebra as na;
+
+/// Maximum number of depth samples per pixel, supported by multisampling.
+pub const MAX_SAMPLES: usize = 8;
+
+/// Raster coordinates are fixed-point with this many bits of the fraction, so vertices are snapped to 1/16
+/// of a pixel and coverage is decided exactly in integers.
+pub const SUBPIXEL_BITS: u32 = 4;
+pub const SUBPIXEL_SCALE: i32 = 1 << SUBPIXEL_BITS;
+
+/// Bits of the fraction of barycentric coordinates and interpolated depth with `fixed_point` feature.
+#[cfg(feature = "fixed_point")]
+const FIXED_POINT_BITS: u32 = 16;
+
+/// Snaps position in pixels to the fixed-point raster coordinates. Pixel x, y is sampled at the integer
+/// position x, y.
+pub fn to_raster_coord(x: f32, y: f32) -> Vector2<i32> {
+    let scale = SUBPIXEL_SCALE as f32;
+    return vector![round(x * scale) as i32, round(y * scale) as i32];
+}
+
+/// Nearest integer, rounding halves away from zero the same as `f32::round`, which needs the standard library.
+fn round(x: f32) -> f32 {
+    // Values this large are already integers, so are infinities, NaN stays as it is.
+    if x.is_nan() || x.abs() >= (1 << f32::MANTISSA_DIGITS) as f32 {
+        return x;
+    }
+    // Both parts are exact below 2^24.
+    let truncated = x as i32;
+    let fraction = x - truncated as f32;
+    let rounded = match fraction {
+        fraction if fraction >= 0.5 => truncated + 1,
+        fraction if fraction <= -0.5 => truncated - 1,
+        _ => truncated,
+    };
+    return rounded as f32;
+}
+
+/// Pixel, closest to the fixed-point raster position.
+pub fn to_pixel(coord: Vector2<i32>) -> Vector2<i32> {
+    let half = SUBPIXEL_SCALE / 2;
+    return coord.map(|c| (c + half).div_euclid(SUBPIXEL_SCALE));
+}
+
+/// Simple bounding box struct for convenience.
+#[derive(Debug)]
+pub struct BoundingBox {
+    pub ll: Vector2<i32>, // lower left corner
+    pub ur: Vector2<i32>, // upper right corner
+}
+
+/// Helper used to find bounding box of a triangle in pixels. Can reach outside of the screen. Box is rounded
+/// outwards, so it holds every pixel, one of the samples of which can be covered.
+pub fn get_triangle_bounding_box(coords: Matrix2x3<i32>) -> BoundingBox {
+    let ll = vector![
+        min(min(coords.m11, coords.m12), coords.m13),
+        min(min(coords.m21, coords.m22), coords.m23)
+    ];
+    let ur = vector![
+        max(max(coords.m11, coords.m12), coords.m13),
+        max(max(coords.m21, coords.m22), coords.m23)
+    ];
+    return BoundingBox {
+        ll: ll.map(|c| c.div_euclid(SUBPIXEL_SCALE)),
+        ur: ur.map(|c| -(-c).div_euclid(SUBPIXEL_SCALE)),
+    };
+}
+
+/// Edge functions of a triangle in fixed-point raster coordinates. Each one is twice the signed area of the
+/// triangle, formed by an edge and the point, and the barycentric coordinate of the opposite vertex is its
+/// ratio to twice the area of the whole triangle. Edges are oriented, so that the inside is positive
+/// whatever the winding is.
+pub struct Edges {
+    coords: Matrix2x3<i64>,
+    area: i64,
+    // Values, edge functions have to reach for the point to be covered - 0 for top and left edges, which
+    // own the points exactly on them, and 1 for the rest, so the point on an edge, shared by two triangles,
+    // is covered by exactly one of them.
+    thresholds: Vector3<i64>,
+}
+
+impl Edges {
+    /// Edge functions of the triangle, None for a degenerate one.
+    pub fn new(coords: Matrix2x3<i32>) -> Option<Self> {
+        let coords = coords.map(|c| c as i64);
+        let a = coords.column(0);
+        let b = coords.column(1);
+        let c = coords.column(2);
+        let area = (b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x);
+        if area == 0 {
+            return None;
+        }
+        let mut thresholds = Vector3::zeros();
+        for k in 0..3 {
+            // Edge, opposite to the vertex k, going along the positive winding.
+            let from = coords.column((k + 1) % 3);
+            let to = coords.column((k + 2) % 3);
+            let direction = (to - from) * area.signum();
+            // Raster y goes up, inside is on the left of the edge, so left edges go down and top edges go
+            // in the negative x.
+            let is_top_left = direction.y < 0 || (direction.y == 0 && direction.x < 0);
+            thresholds[k] = if is_top_left { 0 } else { 1 };
+        }
+        return Some(Self {
+            coords,
+            area,
+            thresholds,
+        });
+    }
+
+    /// Edge functions at the fixed-point position, scaled to be positive inside.
+    fn evaluate(&self, x: i64, y: i64) -> Vector3<i64> {
+        let mut values = Vector3::zeros();
+        for k in 0..3 {
+            let from = self.coords.column((k + 1) % 3);
+            let to = self.coords.column((k + 2) % 3);
+            values[k] = ((to.x - from.x) * (y - from.y) - (to.y - from.y) * (x - from.x))
+                * self.area.signum();
+        }
+        return values;
+    }
+
+    /// Barycentric coordinates of the fixed-point position, if triangle covers it.
+    fn get_covered_bar_coord(&self, x: i64, y: i64) -> Option<Vector3<f32>> {
+        let values = self.evaluate(x, y);
+        if (0..3).any(|k| values[k] < self.thresholds[k]) {
+            return None;
+        }
+        return Some(to_bar_coord(values, self.area.abs()));
+    }
+
+    /// How the rectangle of pixels [x_min, x_max] x [y_min, y_max], grown by margin in raster units on every
+    /// side to hold the samples around the pixels, is covered. Edge functions are affine, so their extremes
+    /// over the rectangle are at its corners.
+    pub fn classify_rect(
+        &self,
+        x_min: i32,
+        x_max: i32,
+        y_min: i32,
+        y_max: i32,
+        margin: i64,
+    ) -> Coverage {
+        let scale = SUBPIXEL_SCALE as i64;
+        let xs = [x_min as i64 * scale - margin, x_max as i64 * scale + margin];
+        let ys = [y_min as i64 * scale - margin, y_max as i64 * scale + margin];
+        let corners = [(0, 0), (1, 0), (0, 1), (1, 1)].map(|(i, j)| self.evaluate(xs[i], ys[j]));
+        let mut coverage = Coverage::Inside;
+        for k in 0..3 {
+            let values = corners.map(|values| values[k]);
+            if values.iter().all(|&value| value < self.thresholds[k]) {
+                return Coverage::Outside;
+            }
+            if values.iter().any(|&value| value < self.thresholds[k]) {
+                coverage = Coverage::Partial;
+            }
+        }
+        return coverage;
+    }
+
+    /// Calls fragment closure with pixel coordinates and barycentric coordinates of every pixel of the
+    /// rectangle [x_min, x_max] x [y_min, y_max], that is covered by the triangle. Edge functions are stepped
+    /// exactly in integers from pixel to pixel, and pixels of a rectangle inside of every edge aren't tested.
+    pub fn rasterize_rect(
+        &self,
+        x_min: i32,
+        x_max: i32,
+        y_min: i32,
+        y_max: i32,
+        coverage: Coverage,
+        mut fragment: impl FnMut(i32, i32, Vector3<f32>),
+    ) {
+        if coverage == Coverage::Outside {
+            return;
+        }
+        let scale = SUBPIXEL_SCALE as i64;
+        let mut step_x = Vector3::zeros();
+        let mut step_y = Vector3::zeros();
+        for k in 0..3 {
+            let from = self.coords.column((k + 1) % 3);
+            let to = self.coords.column((k + 2) % 3);
+            step_x[k] = -(to.y - from.y) * self.area.signum() * scale;
+            step_y[k] = (to.x - from.x) * self.area.signum() * scale;
+        }
+        let test = coverage == Coverage::Partial;
+        let mut row_values = self.evaluate(x_min as i64 * scale, y_min as i64 * scale);
+        for y in y_min..=y_max {
+            self.rasterize_span(y, x_min, x_max, row_values, step_x, test, &mut fragment);
+            row_values += step_y;
+        }
+    }
+
+    /// Calls fragment closure for every pixel in [x_min, x_max] of the row y, that is covered, given the edge
+    /// functions at x_min and their step to the next pixel. Pixels are only tested, if test is set.
+    #[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
+    fn rasterize_span(
+        &self,
+        y: i32,
+        x_min: i32,
+        x_max: i32,
+        values: Vector3<i64>,
+        step_x: Vector3<i64>,
+        test: bool,
+        fragment: &mut impl FnMut(i32, i32, Vector3<f32>),
+    ) {
+        self.rasterize_span_scalar(y, x_min, x_max, values, step_x, test, fragment);
+    }
+
+    fn rasterize_span_scalar(
+        &self,
+        y: i32,
+        x_min: i32,
+        x_max: i32,
+        mut values: Vector3<i64>,
+        step_x: Vector3<i64>,
+        test: bool,
+        fragment: &mut impl FnMut(i32, i32, Vector3<f32>),
+    ) {
+        for x in x_min..=x_max {
+            if !test || (0..3).all(|k| values[k] >= self.thresholds[k]) {
+                fragment(x, y, to_bar_coord(values, self.area.abs()));
+            }
+            values += step_x;
+        }
+    }
+
+    /// Same as the scalar version, but tests and divides the edge functions for 4 pixels at a time with SSE2
+    /// lanes. Rows, where edge functions don't fit into 32 bits, e.g. of triangles, spanning about 2048 pixels
+    /// or more, go through the scalar version. Results match the scalar version exactly.
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    fn rasterize_span(
+        &self,
+        y: i32,
+        x_min: i32,
+        x_max: i32,
+        values: Vector3<i64>,
+        step_x: Vector3<i64>,
+        test: bool,
+        fragment: &mut impl FnMut(i32, i32, Vector3<f32>),
+    ) {
+        use core::arch::x86_64::*;
+
+        // Edge functions are affine, so the ends of the row bound them.
+        let last = values + step_x * (x_max - x_min) as i64;
+        let fits = |values: Vector3<i64>| {
+            return values.iter().all(|&value| i32::try_from(value).is_ok());
+        };
+        if !fits(values) || !fits(last) {
+            self.rasterize_span_scalar(y, x_min, x_max, values, step_x, test, fragment);
+            return;
+        }
+        let area = self.area.abs();
+
+        // SSE2 is always available on x86_64, so intrinsics are safe to call here.
+        unsafe {
+            let lane = |k: usize| {
+                // Lanes past the end of the row may overflow, but they are never covered.
+                let (v, s) = (values[k] as i32, step_x[k] as i32);
+                return (
+                    _mm_set_epi32(
+                        v.wrapping_add(s.wrapping_mul(3)),
+                        v.wrapping_add(s.wrapping_mul(2)),
+                        v.wrapping_add(s),
+                        v,
+                    ),
+                    _mm_set1_epi32(s.wrapping_mul(4)),
+                    // Comparing with threshold - 1, since there is only greater than.
+                    _mm_set1_epi32(self.thresholds[k] as i32 - 1),
+                );
+            };
+            let (mut values_0, step_0, threshold_0) = lane(0);
+            let (mut values_1, step_1, threshold_1) = lane(1);
+            let (mut values_2, step_2, threshold_2) = lane(2);
+            let area_lanes = _mm_set1_ps(area as f32);
+
+            let mut x = x_min;
+            while x <= x_max {
+                let mask = if test {
+                    let inside = _mm_and_si128(
+                        _mm_and_si128(
+                            _mm_cmpgt_epi32(values_0, threshold_0),
+                            _mm_cmpgt_epi32(values_1, threshold_1),
+                        ),
+                        _mm_cmpgt_epi32(values_2, threshold_2),
+                    );
+                    _mm_movemask_ps(_mm_castsi128_ps(inside))
+                } else {
+                    0b1111
+                };
+                if mask != 0 {
+                    let mut bars = [[0.0f32; 4]; 3];
+                    // Fixed-point barycentric coordinates are only rounded by the scalar conversion.
+                    if !cfg!(feature = "fixed_point") {
+                        for (bar, values) in bars.iter_mut().zip([values_0, values_1, values_2]) {
+                            _mm_storeu_ps(
+                                bar.as_mut_ptr(),
+                                _mm_div_ps(_mm_cvtepi32_ps(values), area_lanes),
+                            );
+                        }
+                    }
+                    for lane in 0..4 {
+                        if mask & (1 << lane) == 0 || x + lane > x_max {
+                            continue;
+                        }
+                        let bar_coord = if cfg!(feature = "fixed_point") {
+                            to_bar_coord(values + step_x * (x - x_min + lane) as i64, area)
+                        } else {
+                            let lane = lane as usize;
+                            vector![bars[0][lane], bars[1][lane], bars[2][lane]]
+                        };
+                        fragment(x + lane, y, bar_coord);
+                    }
+                }
+                values_0 = _mm_add_epi32(values_0, step_0);
+                values_1 = _mm_add_epi32(values_1, step_1);
+                values_2 = _mm_add_epi32(values_2, step_2);
+                x += 4;
+            }
+        }
+    }
+}
+
+/// Barycentric coordinates from the edge functions and twice the area of the triangle. With `fixed_point`
+/// feature they are rounded in integers to multiples of 1/65536, the last one taking the rest, so they sum
+/// to 1 exactly and come out the same on every platform.
+#[cfg(not(feature = "fixed_point"))]
+fn to_bar_coord(values: Vector3<i64>, area: i64) -> Vector3<f32> {
+    let area = area as f32;
+    return values.map(|value| value as f32 / area);
+}
+
+#[cfg(feature = "fixed_point")]
+fn to_bar_coord(values: Vector3<i64>, area: i64) -> Vector3<f32> {
+    let one = 1 << FIXED_POINT_BITS;
+    let round = |value: i64| (value * one + area / 2).div_euclid(area);
+    let (u, v) = (round(values.x), round(values.y));
+    return vector![u, v, one - u - v].map(|value| value as f32 / one as f32);
+}
+
+/// Depth of the point with the barycentric coordinates, interpolated from the depth of the vertices. With
+/// `fixed_point` feature both are rounded to multiples of 1/65536 and the depth is interpolated in integers,
+/// rounding the result half up, so depth tests come out the same on every platform.
+pub fn interpolate_depth(z_values: Vector3<f32>, bar_coord: Vector3<f32>) -> f32 {
+    #[cfg(feature = "fixed_point")]
+    {
+        let scale = (1 << FIXED_POINT_BITS) as f32;
+        let to_fixed = |value: f32| round(value * scale) as i64;
+        let depth = z_values.map(to_fixed).dot(&bar_coord.map(to_fixed));
+        let depth = (depth + (1 << (FIXED_POINT_BITS - 1))) >> FIXED_POINT_BITS;
+        return depth as f32 / scale;
+    }
+    #[cfg(not(feature = "fixed_point"))]
+    return bar_coord.dot(&z_values);
+}
+
+/// Side of the square blocks of pixels, which polygons are rasterized in, each one accepted or rejected as a
+/// whole by the edge functions at its corners, if possible.
+pub const BLOCK_SIZE: i32 = 8;
+
+/// Coverage of a rectangle of pixels by a triangle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Coverage {
+    Outside, // Outside of one of the edges, nothing is covered.
+    Partial, // Crossed by some of the edges, every pixel has to be tested.
+    Inside,  // Inside of every edge, everything is covered.
+}
+
+/// Calls fragment closure with x coordinate and barycentric coordinates of every pixel in [x_min, x_max]
+/// of the row y, that is covered by the triangle.
+#[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
+pub fn rasterize_row(
+    coords: Matrix2x3<i32>,
+    y: i32,
+    x_min: i32,
+    x_max: i32,
+    fragment: impl FnMut(i32, Vector3<f32>),
+) {
+    if let Some(edges) = Edges::new(coords) {
+        rasterize_row_scalar(&edges, y, x_min, x_max, fragment);
+    }
+}
+
+fn rasterize_row_scalar(
+    edges: &Edges,
+    y: i32,
+    x_min: i32,
+    x_max: i32,
+    mut fragment: impl FnMut(i32, Vector3<f32>),
+) {
+    let scale = SUBPIXEL_SCALE as i64;
+    for x in x_min..=x_max {
+        if let Some(bar_coord) = edges.get_covered_bar_coord(x as i64 * scale, y as i64 * scale) {
+            fragment(x, bar_coord);
+        }
+    }
+}
+
+/// Same as the scalar version, but evaluates edge functions for 4 pixels at a time with SSE2 integer lanes,
+/// stepping them by a constant from pixel to pixel. Edge functions of triangles, spanning about 2048 pixels
+/// or more, may not fit into 32 bits, so these go through the scalar version. Results match the scalar
+/// version exactly.
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+pub fn rasterize_row(
+    coords: Matrix2x3<i32>,
+    y: i32,
+    x_min: i32,
+    x_max: i32,
+    mut fragment: impl FnMut(i32, Vector3<f32>),
+) {
+    use core::arch::x86_64::*;
+
+    let edges = match Edges::new(coords) {
+        Some(edges) => edges,
+        None => return,
+    };
+    let bbox = get_triangle_bounding_box(coords);
+    let span = max(bbox.ur.x - bbox.ll.x, bbox.ur.y - bbox.ll.y);
+    // Fixed-point barycentric coordinates are only rounded by the scalar version.
+    if cfg!(feature = "fixed_point")
+        || span >= 2000
+        || x_min < bbox.ll.x
+        || x_max > bbox.ur.x
+        || y < bbox.ll.y
+        || y > bbox.ur.y
+    {
+        rasterize_row_scalar(&edges, y, x_min, x_max, fragment);
+        return;
+    }
+
+    let scale = SUBPIXEL_SCALE as i64;
+    let start = edges.evaluate(x_min as i64 * scale, y as i64 * scale);
+    // Moving one pixel right changes each edge function by the negated y extent of its edge.
+    let mut steps = Vector3::zeros();
+    for k in 0..3 {
+        let from = edges.coords.column((k + 1) % 3);
+        let to = edges.coords.column((k + 2) % 3);
+        steps[k] = -(to.y - from.y) * edges.area.signum() * scale;
+    }
+    let area = edges.area.abs() as f32;
+
+    // SSE2 is always available on x86_64, so intrinsics are safe to call here.
+    unsafe {
+        let lane = |k: usize| {
+            // Lanes past the end of the row may overflow, but they are never covered.
+            let (v, s) = (start[k] as i32, steps[k] as i32);
+            return (
+                _mm_set_epi32(
+                    v.wrapping_add(s.wrapping_mul(3)),
+                    v.wrapping_add(s.wrapping_mul(2)),
+                    v.wrapping_add(s),
+                    v,
+                ),
+                _mm_set1_epi32(s.wrapping_mul(4)),
+                // Comparing with threshold - 1, since there is only greater than.
+                _mm_set1_epi32(edges.thresholds[k] as i32 - 1),
+            );
+        };
+        let (mut values_0, step_0, threshold_0) = lane(0);
+        let (mut values_1, step_1, threshold_1) = lane(1);
+        let (mut values_2, step_2, threshold_2) = lane(2);
+        let area = _mm_set1_ps(area);
+
+        let mut x = x_min;
+        while x <= x_max {
+            let inside = _mm_and_si128(
+                _mm_and_si128(
+                    _mm_cmpgt_epi32(values_0, threshold_0),
+                    _mm_cmpgt_epi32(values_1, threshold_1),
+                ),
+                _mm_cmpgt_epi32(values_2, threshold_2),
+            );
+            let mask = _mm_movemask_ps(_mm_castsi128_ps(inside));
+            if mask != 0 {
+                let mut bars = [[0.0f32; 4]; 3];
+                _mm_storeu_ps(
+                    bars[0].as_mut_ptr(),
+                    _mm_div_ps(_mm_cvtepi32_ps(values_0), area),
+                );
+                _mm_storeu_ps(
+                    bars[1].as_mut_ptr(),
+                    _mm_div_ps(_mm_cvtepi32_ps(values_1), area),
+                );
+                _mm_storeu_ps(
+                    bars[2].as_mut_ptr(),
+                    _mm_div_ps(_mm_cvtepi32_ps(values_2), area),
+                );
+                for lane in 0..4 {
+                    if mask & (1 << lane) != 0 && x + lane <= x_max {
+                        let lane = lane as usize;
+                        fragment(
+                            x + lane as i32,
+                            vector![bars[0][lane], bars[1][lane], bars[2][lane]],
+                        );
+                    }
+                }
+            }
+            values_0 = _mm_add_epi32(values_0, step_0);
+            values_1 = _mm_add_epi32(values_1, step_1);
+            values_2 = _mm_add_epi32(values_2, step_2);
+            x += 4;
+        }
+    }
+}
+
+/// Triangle coordinates in pixels instead of fixed-point units.
+fn to_pixel_coords(coords: Matrix2x3<i32>) -> Matrix2x3<f32> {
+    return coords.map(|c| c as f32 / SUBPIXEL_SCALE as f32);
+}
+
+/// Screen space derivatives of the barycentric coordinates along x and y. Barycentric coordinates are
+/// affine in screen space, so these are constant over the whole triangle.
+pub fn get_barycentric_derivatives(coords: Matrix2x3<i32>) -> (Vector3<f32>, Vector3<f32>) {
+    let coords = to_pixel_coords(coords);
+    let bx_ax = coords.m12 - coords.m11;
+    let cx_ax = coords.m13 - coords.m11;
+    let by_ay = coords.m22 - coords.m21;
+    let cy_ay = coords.m23 - coords.m21;
+    let area = bx_ax * cy_ay - cx_ax * by_ay;
+    if area == 0.0 {
+        return (Vector3::zeros(), Vector3::zeros());
+    }
+    let d_dx = vector![0.0, cy_ay / area, -by_ay / area];
+    let d_dy = vector![0.0, -cx_ax / area, bx_ax / area];
+    return (
+        vector![-(d_dx.y + d_dx.z), d_dx.y, d_dx.z],
+        vector![-(d_dy.y + d_dy.z), d_dy.y, d_dy.z],
+    );
+}
+
+/// Sample positions relative to the pixel center for the supported multisampling levels, using the usual
+/// rotated grid patterns, so that near-horizontal and near-vertical edges get distinct coverage values.
+pub fn get_sample_offsets(samples: u32) -> &'static [(f32, f32)] {
+    match samples {
+        1 => return &[(0.0, 0.0)],
+        2 => return &[(0.25, 0.25), (-0.25, -0.25)],
+        4 => {
+            return &[
+                (-0.125, -0.375),
+                (0.375, -0.125),
+                (-0.375, 0.125),
+                (0.125, 0.375),
+            ]
+        }
+        8 => {
+            return &[
+                (0.0625, -0.1875),
+                (-0.0625, 0.1875),
+                (0.3125, 0.0625),
+                (-0.1875, -0.3125),
+                (-0.3125, 0.3125),
+                (-0.4375, -0.0625),
+                (0.1875, 0.4375),
+                (0.4375, -0.4375),
+            ]
+        }
+        _ => panic!("Provided number of samples per pixel is not supported!"),
+    }
+}
+
+/// Barycentric coordinates of an arbitrary point in pixels inside of the rasterized triangle.
+pub fn to_barycentric_coord_at(point: Vector2<f32>, coords: Matrix2x3<i32>) -> Vector3<f32> {
+    // Points, snapped to the raster, are evaluated exactly in fixed point.
+    #[cfg(feature = "fixed_point")]
+    if let Some(edges) = Edges::new(coords) {
+        let point = to_raster_coord(point.x, point.y);
+        return to_bar_coord(
+            edges.evaluate(point.x as i64, point.y as i64),
+            edges.area.abs(),
+        );
+    }
+    let coords = to_pixel_coords(coords);
+    let raw_cross = vector![
+        coords.m12 - coords.m11,
+        coords.m13 - coords.m11,
+        coords.m11 - point.x
+    ]
+    .cross(&vector![
+        coords.m22 - coords.m21,
+        coords.m23 - coords.m21,
+        coords.m21 - point.y
+    ]);
+    if raw_cross.z == 0.0 {
+        // Degenerate triangle, returning something with negative coordinate.
+        return vector![-1.0, 1.0, 1.0];
+    }
+    return vector![
+        1.0 - (raw_cross.x + raw_cross.y) / raw_cross.z,
+        raw_cross.x / raw_cross.z,
+        raw_cross.y / raw_cross.z
+    ];
+}
+
+/// Multisampled version of rasterize_row - calls fragment closure for every pixel in [x_min, x_max] of the
+/// row y, which has at least one sample covered by the triangle, passing the coverage mask together with
+/// barycentric coordinates of each sample. Sample offsets are multiples of 1/16 of a pixel, so samples are
+/// tested exactly in fixed point, same as pixels without multisampling.
+pub fn rasterize_row_multisampled(
+    edges: &Edges,
+    y: i32,
+    x_min: i32,
+    x_max: i32,
+    sample_offsets: &[(f32, f32)],
+    mut fragment: impl FnMut(i32, u32, &[Vector3<f32>; MAX_SAMPLES]),
+) {
+    let scale = SUBPIXEL_SCALE as i64;
+    let mut sample_bar_coords = [Vector3::zeros(); MAX_SAMPLES];
+    for x in x_min..=x_max {
+        let mut mask = 0;
+        for (sample, offset) in sample_offsets.iter().enumerate() {
+            let offset = to_raster_coord(offset.0, offset.1);
+            let sample_x = x as i64 * scale + offset.x as i64;
+            let sample_y = y as i64 * scale + offset.y as i64;
+            if let Some(bar_coord) = edges.get_covered_bar_coord(sample_x, sample_y) {
+                sample_bar_coords[sample] = bar_coord;
+                mask |= 1 << sample;
+            }
+        }
+        if mask != 0 {
+            fragment(x, mask, &sample_bar_coords);
+        }
+    }
+}
+
+/// Blend function, writing source color over the rgb destination with given opacity of the source.
+pub fn blend_over(destination: &mut [f32], source: Vector3<f32>, alpha: f32) {
+    for c in 0..3 {
+        destination[c] = match alpha >= 1.0 {
+            true => source[c],
+            false => alpha * source[c] + (1.0 - alpha) * destination[c],
+        };
+    }
+}
+
+/// How polygons are rasterized - filled, as their edges or as their vertices only.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RenderMode {
+    #[default]
+    Fill,
+    Wireframe,
+    Points,
+}
+
+impl RenderMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "fill" => return Some(RenderMode::Fill),
+            "wireframe" => return Some(RenderMode::Wireframe),
+            "points" => return Some(RenderMode::Points),
+            _ => return None,
+        }
+    }
+
+    /// Mode, following this one, cycling back to the first.
+    pub fn next(&self) -> Self {
+        match self {
+            RenderMode::Fill => return RenderMode::Wireframe,
+            RenderMode::Wireframe => return RenderMode::Points,
+            RenderMode::Points => return RenderMode::Fill,
+        }
+    }
+}
+
+/// Bresenham line between two pixels, calling closure with coordinates of every pixel of the line together
+/// with the fraction of the way from the start, both ends included.
+pub fn rasterize_line(from: Vector2<i32>, to: Vector2<i32>, mut pixel: impl FnMut(i32, i32, f32)) {
+    let dx = (to.x - from.x).abs();
+    let dy = -(to.y - from.y).abs();
+    let step_x = if from.x < to.x { 1 } else { -1 };
+    let step_y = if from.y < to.y { 1 } else { -1 };
+    // Every step advances along the major axis, so the number of steps gives the fraction exactly.
+    let n_steps = max(dx, -dy).max(1) as f32;
+    let mut error = dx + dy;
+    let (mut x, mut y) = (from.x, from.y);
+    let mut step = 0;
+    loop {
+        pixel(x, y, step as f32 / n_steps);
+        if x == to.x && y == to.y {
+            break;
+        }
+        let doubled_error = 2 * error;
+        if doubled_error >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if doubled_error <= dx {
+            error += dx;
+            y += step_y;
+        }
+        step += 1;
+    }
+}
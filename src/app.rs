@@ -1,16 +1,39 @@
-use std::fs::File;
-use std::io::BufReader;
+use std::f32::consts::TAU;
+use std::path::{Path, PathBuf};
 use std::{collections::HashMap, time};
 
-use na::vector;
+use image::{DynamicImage, Rgb, RgbImage};
+use na::{vector, Vector3};
 use nalgebra as na;
-use obj::raw::parse_obj;
-use show_image::{create_window, event, ImageInfo, ImageView, WindowOptions};
+use tiny_renderer::scene::{
+    find_image, generate_normals, load_cube_map, load_gltf, load_image, load_mtl_maps,
+    load_obj_with_colors, load_palette, load_ply, load_stl, subdivide, triangulate, AlphaSettings,
+    AovExport, Background, BloomSettings, BuiltinPostPass, CullMode, DebugView, Dithering,
+    DofSettings, Filter, FogSettings, FramePass, FrameStats, GltfModel, InstanceGrid, Light,
+    Material, MotionBlurSettings, NormalGeneration, NormalMapConvention, Palette, Projection,
+    ReferenceMode, RenderMode, RendererError, Scene, SceneGraph, ShadowSettings, SsaoSettings,
+    TaaSettings, TargetView, TextureKind, Timeline, ToneMapping, Tunables, Wrap,
+    BUILTIN_PIPELINE_NAMES,
+};
 
-use crate::scene::Scene;
+use crate::bench::{print_frame_stats, run_bench};
+use crate::overlay::{draw_divider, draw_inset, draw_text};
+use crate::panel::{Panel, PanelValues, Setting};
+#[cfg(all(feature = "minifb", not(feature = "viewer")))]
+use crate::present::MinifbPresenter;
+#[cfg(feature = "viewer")]
+use crate::present::WindowPresenter;
+use crate::present::{ImageSequence, Input, Key, Presenter};
+use crate::render_thread::{RenderThread, SplitFrame};
+use crate::watch::FileWatcher;
 
 const CAMERA_SPEED: f32 = 3.0;
 const LIGHT_SOURCE_SPEED: f32 = 3.0;
+/// Longest time between checks of the window for inputs, while a frame renders.
+const INPUT_POLL_INTERVAL: time::Duration = time::Duration::from_millis(5);
+
+/// Name of the render target of the security camera, shown in the corner of the frame.
+pub const SECURITY_CAMERA_TARGET: &str = "security_camera";
 
 #[derive(PartialEq, Eq, Hash)]
 enum Action {
@@ -18,32 +41,194 @@ enum Action {
     CameraRight,
     LightLeft,
     LightRight,
+    LightUp,
+    LightDown,
+    LightBrighter,
+    LightDimmer,
+    NextPipeline,
+    NextRenderMode,
+    NextShading,
+    // Index into the list of built-in pipelines.
+    SelectPipeline(usize),
+    SelectDebugView(DebugView),
+    Screenshot,
+    TogglePanel,
+    // Moving the selection of the panel up and down and adjusting the selected setting.
+    PanelUp,
+    PanelDown,
+    PanelDecrease,
+    PanelIncrease,
     ExitApp,
 }
 
 pub struct Params {
     pub width: u32,
     pub height: u32,
-    pub print_fps: bool,
+    pub show_stats: bool, // Whether frame rate, timings and counters of the renderer are drawn over the frame.
     pub asset_path: String,
     pub shader_pipeline_name: String,
+    pub cull_mode: CullMode,
+    pub render_mode: RenderMode,
+    pub render_scale: u32,
+    pub msaa_samples: u32,
+    pub texture_filter: Filter,
+    pub max_anisotropy: u32, // Most lookups per pixel of anisotropic filtering.
+    pub texture_wrap: Wrap,
+    // Direction of the green channel of the tangent space normal map.
+    pub normal_map_convention: NormalMapConvention,
+    pub lights: Vec<Light>,
+    // Starting placement of the main light, which the keys move from there.
+    pub main_light: MainLight,
+    // Image, projected by every spot light, if set.
+    pub gobo_path: Option<String>,
+    // Position and size in pixels of the camera, looking at the model, which is shown in the corner of the frame.
+    pub security_camera: Option<(Vector3<f32>, u32)>,
+    pub shadow: ShadowSettings,
+    pub alpha: AlphaSettings,
+    pub fog: Option<FogSettings>,
+    pub ssao: Option<SsaoSettings>,
+    pub dof: Option<DofSettings>,
+    pub motion_blur: Option<MotionBlurSettings>,
+    pub taa: Option<TaaSettings>,
+    pub bloom: Option<BloomSettings>,
+    pub post_passes: Vec<BuiltinPostPass>,
+    pub disabled_passes: Vec<FramePass>, // Passes of the frame, skipped whatever the other settings.
+    pub gamma_correction: bool,
+    pub tone_mapping: ToneMapping,
+    pub exposure: f32,
+    pub deferred: bool,
+    // Whether frames are ray traced instead of rasterized, or show the difference between the two.
+    pub reference_mode: ReferenceMode,
+    // Either a folder with px, nx, py, ny, pz, nz images of the cube faces, or an equirectangular panorama.
+    pub skybox_path: Option<String>,
+    // Hex color, top and bottom colors of a vertical gradient or a path to an image, which the frame is filled
+    // with behind the model, when there is no skybox.
+    pub background: Option<String>,
+    // Name of a built-in palette or a path to a palette file, which the displayed frame is quantized to, and
+    // how the error is dithered.
+    pub palette: Option<String>,
+    pub dithering: Dithering,
+    // JSON file with camera and light keyframes, overriding the interactive controls.
+    pub timeline_path: Option<String>,
+    // Whether normals of the model are kept or replaced by generated smooth or flat ones.
+    pub normal_generation: NormalGeneration,
+    // Number of times every polygon is split into 4 before rendering, and how far the displacement map moves
+    // the vertices.
+    pub subdivision_levels: u32,
+    pub displacement_scale: f32,
+    // Grid of copies of the model, drawn in its place, if set.
+    pub instance_grid: Option<InstanceGrid>,
+    // JSON file with the scene graph, which copies of the model are drawn at, replacing the instance grid.
+    pub graph_path: Option<String>,
+    // Materials, given to the objects in order and repeated, when there are more objects than materials.
+    pub materials: Vec<Material>,
+    // Number of coarser levels of detail, generated at load time and drawn in place of the model, as it gets
+    // smaller on the screen.
+    pub lod_levels: u32,
+    pub projection: Projection,
+    // Frames are saved as images instead of being shown in a window, when set.
+    pub image_sequence: Option<ImageSequence>,
+    // Output variables, like depth and normals, which are saved next to every frame, when set.
+    pub aov_export: Option<AovExport>,
+    // File names of the maps in the asset folder, overriding the material library and the default names.
+    pub map_file_names: HashMap<TextureKind, String>,
+    // Number of frames to render without presenting them, printing timings, instead of running the viewer.
+    pub bench_frames: Option<u32>,
+    // Whether stats, averaged over a second, are printed every second.
+    pub print_stats: bool,
+    // Whether the model and maps are reloaded, when files in the asset folder change.
+    pub watch_assets: bool,
+    // JSON file with constants of the shaders, overriding the command line and re-read, when it changes.
+    pub tunables_path: Option<String>,
+    // Pipeline name or tunables file, which the part of the frame right of the divider is rendered with, and
+    // the position of the divider as a fraction of the frame width, if the frame is split.
+    pub split_screen: Option<(String, f32)>,
+}
+
+/// Main light, placed on the unit sphere around the model by angles in radians.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MainLight {
+    pub azimuth: f32,   // Angle around the vertical axis, 0 being in front of the model.
+    pub elevation: f32, // Angle above the horizon.
+    pub intensity: f32,
+}
+
+impl Default for MainLight {
+    fn default() -> Self {
+        return MainLight {
+            azimuth: 0.0,
+            elevation: 0.0,
+            intensity: 1.0,
+        };
+    }
+}
+
+impl MainLight {
+    /// Parses light from azimuth and elevation in degrees and optional intensity, e.g. "45,30" or "45,30,1.5".
+    pub fn from_spec(spec: &str) -> Option<Self> {
+        let values: Vec<f32> = spec
+            .split(',')
+            .map(|value| value.parse().ok())
+            .collect::<Option<_>>()?;
+        let (azimuth, elevation, intensity) = match values[..] {
+            [azimuth, elevation] => (azimuth, elevation, 1.0),
+            [azimuth, elevation, intensity] => (azimuth, elevation, intensity),
+            _ => return None,
+        };
+        if !(-90.0..=90.0).contains(&elevation) || intensity < 0.0 {
+            return None;
+        }
+        return Some(MainLight {
+            azimuth: azimuth.to_radians(),
+            elevation: elevation.to_radians().clamp(-1.5, 1.5),
+            intensity,
+        });
+    }
 }
 
 /// Buffer for storing previous frame's events of interets.
 struct FrameActionBuffer {
     pub actions: HashMap<Action, bool>,
+    pub window_size: Option<(u32, u32)>, // Size of the window in pixels, if it was resized.
+    pub click: Option<(u32, u32)>,       // Pixel of the frame, which was clicked last.
+    pub drag: Option<(u32, u32)>,        // Pixel of the frame, the cursor was dragged to last.
+    pub pointer_inputs: Vec<Input>, // Clicks, drags and releases, kept for the panel until it's drawn.
 }
 
 impl FrameActionBuffer {
     fn new() -> Self {
+        let mut actions = HashMap::from([
+            (Action::CameraLeft, false),
+            (Action::CameraRight, false),
+            (Action::LightLeft, false),
+            (Action::LightRight, false),
+            (Action::LightUp, false),
+            (Action::LightDown, false),
+            (Action::LightBrighter, false),
+            (Action::LightDimmer, false),
+            (Action::NextPipeline, false),
+            (Action::NextRenderMode, false),
+            (Action::NextShading, false),
+            (Action::Screenshot, false),
+            (Action::TogglePanel, false),
+            (Action::PanelUp, false),
+            (Action::PanelDown, false),
+            (Action::PanelDecrease, false),
+            (Action::PanelIncrease, false),
+            (Action::ExitApp, false),
+        ]);
+        for index in 0..BUILTIN_PIPELINE_NAMES.len() {
+            actions.insert(Action::SelectPipeline(index), false);
+        }
+        for debug_view in DebugView::ALL {
+            actions.insert(Action::SelectDebugView(debug_view), false);
+        }
         return Self {
-            actions: HashMap::from([
-                (Action::CameraLeft, false),
-                (Action::CameraRight, false),
-                (Action::LightLeft, false),
-                (Action::LightRight, false),
-                (Action::ExitApp, false),
-            ]),
+            actions,
+            window_size: None,
+            click: None,
+            drag: None,
+            pointer_inputs: Vec::new(),
         };
     }
 
@@ -51,89 +236,357 @@ impl FrameActionBuffer {
         for (_, value) in self.actions.iter_mut() {
             *value = false;
         }
+        self.window_size = None;
+        self.click = None;
+        self.drag = None;
     }
 
-    fn process_window_event(&mut self, window_event: event::WindowEvent) {
-        if let event::WindowEvent::KeyboardInput(event) = window_event {
-            match (
-                event.input.key_code,
-                event.input.state.is_pressed(),
-                event.input.state.is_released(),
-            ) {
-                (Some(event::VirtualKeyCode::A), true, _) => {
-                    *self.actions.entry(Action::CameraLeft).or_insert(true) = true;
-                }
-                (Some(event::VirtualKeyCode::D), true, _) => {
-                    *self.actions.entry(Action::CameraRight).or_insert(true) = true;
-                }
-                (Some(event::VirtualKeyCode::Q), true, _) => {
-                    *self.actions.entry(Action::LightLeft).or_insert(true) = true;
-                }
-                (Some(event::VirtualKeyCode::E), true, _) => {
-                    *self.actions.entry(Action::LightRight).or_insert(true) = true;
-                }
-                (Some(event::VirtualKeyCode::Escape), _, true) => {
-                    *self.actions.entry(Action::ExitApp).or_insert(true) = true;
-                }
-                _ => (),
+    fn process_input(&mut self, input: Input) {
+        let (key, modifiers) = match input {
+            Input::Resized(width, height) => {
+                self.window_size = Some((width, height));
+                return;
             }
-        }
+            Input::Clicked(x, y) => {
+                self.click = Some((x, y));
+                self.pointer_inputs.push(input);
+                return;
+            }
+            Input::Dragged(x, y) => {
+                self.drag = Some((x, y));
+                self.pointer_inputs.push(input);
+                return;
+            }
+            Input::Released(..) => {
+                self.pointer_inputs.push(input);
+                return;
+            }
+            Input::KeyReleased(Key::Escape) => {
+                *self.actions.entry(Action::ExitApp).or_insert(true) = true;
+                return;
+            }
+            Input::KeyReleased(_) => return,
+            Input::KeyPressed(key, modifiers) => (key, modifiers),
+        };
+        let action = match key {
+            Key::Letter('a') => Action::CameraLeft,
+            Key::Letter('d') => Action::CameraRight,
+            Key::Letter('q') => Action::LightLeft,
+            Key::Letter('e') => Action::LightRight,
+            Key::Tab => Action::NextPipeline,
+            Key::Letter('r') => Action::NextRenderMode,
+            Key::Letter('f') => Action::NextShading,
+            Key::Function(12) | Key::Letter('s') => Action::Screenshot,
+            Key::Letter('p') => Action::TogglePanel,
+            // Arrow keys with shift move the light, even while the panel takes the arrow keys.
+            Key::Up if modifiers.shift => Action::LightUp,
+            Key::Down if modifiers.shift => Action::LightDown,
+            Key::Left if modifiers.shift => Action::LightLeft,
+            Key::Right if modifiers.shift => Action::LightRight,
+            Key::Up => Action::PanelUp,
+            Key::Down => Action::PanelDown,
+            Key::Left => Action::PanelDecrease,
+            Key::Right => Action::PanelIncrease,
+            Key::Plus => Action::LightBrighter,
+            Key::Minus => Action::LightDimmer,
+            // Number keys pick pipelines, function keys pick debug views, F1 being the shaded frame.
+            Key::Digit(digit) if (1..=BUILTIN_PIPELINE_NAMES.len() as u32).contains(&digit) => {
+                Action::SelectPipeline(digit as usize - 1)
+            }
+            Key::Function(number) if (1..=DebugView::ALL.len() as u32).contains(&number) => {
+                Action::SelectDebugView(DebugView::ALL[number as usize - 1])
+            }
+            _ => return,
+        };
+        *self.actions.entry(action).or_insert(true) = true;
     }
 }
 
-/// Actualy launches the window, showing images.
-/// Takes struct, defining execution params.
-pub fn run(params: Params) -> Result<(), Box<dyn std::error::Error>> {
-    let obj_path = params.asset_path.clone() + "/model.obj";
-    let texture_path = params.asset_path.clone() + "/texture.tga";
-    let normal_map_path = params.asset_path.clone() + "/normal_map.tga";
-    let normal_map_tangent_path = params.asset_path.clone() + "/normal_map_tangent.tga";
-    let specular_map_path = params.asset_path.clone() + "/specular_map.tga";
+/// Name of the screenshot in the working directory, stamped with the time it's taken at.
+fn get_screenshot_path() -> String {
+    let timestamp = time::SystemTime::now()
+        .duration_since(time::UNIX_EPOCH)
+        .unwrap_or_default();
+    return format!(
+        "screenshot_{}_{:03}.png",
+        timestamp.as_secs(),
+        timestamp.subsec_millis()
+    );
+}
 
+/// Loads the mesh of the asset from model.gltf or model.glb together with its skin, if there is one, otherwise
+/// from model.ply, model.stl or model.obj, with vertex colors, if the model has them.
+fn load_model(asset_path: &str) -> Result<GltfModel, Box<dyn std::error::Error>> {
+    for gltf_name in ["model.gltf", "model.glb"] {
+        let gltf_path = format!("{}/{}", asset_path, gltf_name);
+        if Path::new(&gltf_path).exists() {
+            println!("loading model from: {}", gltf_path);
+            let (obj, skin, vertex_colors) = load_gltf(&gltf_path)?;
+            if let Some(skin) = &skin {
+                println!(
+                    "model has a skin with {} joints and {:.2}s animation",
+                    skin.joints.len(),
+                    skin.get_duration()
+                );
+            }
+            return Ok((obj, skin, vertex_colors));
+        }
+    }
+    let ply_path = format!("{}/model.ply", asset_path);
+    if Path::new(&ply_path).exists() {
+        println!("loading model from: {}", ply_path);
+        let (obj, vertex_colors) = load_ply(&ply_path)?;
+        return Ok((obj, None, vertex_colors));
+    }
+    let stl_path = format!("{}/model.stl", asset_path);
+    if Path::new(&stl_path).exists() {
+        println!("loading model from: {}", stl_path);
+        return Ok((load_stl(&stl_path)?, None, Vec::new()));
+    }
+    let obj_path = format!("{}/model.obj", asset_path);
     println!("loading model from: {}", obj_path);
-    let obj = parse_obj(BufReader::new(File::open(obj_path)?))?;
+    let (obj, vertex_colors) = load_obj_with_colors(&obj_path)?;
+    return Ok((obj, None, vertex_colors));
+}
+
+/// Window to show the frames in, when the app is built with a windowing backend.
+#[cfg(feature = "viewer")]
+fn create_window_presenter(
+    width: u32,
+    height: u32,
+) -> Result<Box<dyn Presenter>, Box<dyn std::error::Error>> {
+    return Ok(Box::new(WindowPresenter::new(width, height)?));
+}
+
+#[cfg(all(feature = "minifb", not(feature = "viewer")))]
+fn create_window_presenter(
+    width: u32,
+    height: u32,
+) -> Result<Box<dyn Presenter>, Box<dyn std::error::Error>> {
+    return Ok(Box::new(MinifbPresenter::new(width, height)?));
+}
+
+#[cfg(not(any(feature = "viewer", feature = "minifb")))]
+fn create_window_presenter(
+    _width: u32,
+    _height: u32,
+) -> Result<Box<dyn Presenter>, Box<dyn std::error::Error>> {
+    return Err(
+        "Built without the viewer or minifb feature, frames can only be saved with -O".into(),
+    );
+}
+
+/// Path of the map of the kind - file name, given on the command line, relative to the asset folder, or
+/// the file, material library of the model references, or the image named after the map in the asset folder.
+fn find_map_path(
+    asset_path: &str,
+    map_file_names: &HashMap<TextureKind, String>,
+    mtl_maps: &HashMap<TextureKind, String>,
+    kind: TextureKind,
+) -> Option<String> {
+    if let Some(file_name) = map_file_names.get(&kind) {
+        return Some(
+            Path::new(asset_path)
+                .join(file_name)
+                .to_string_lossy()
+                .into_owned(),
+        );
+    }
+    if let Some(path) = mtl_maps.get(&kind) {
+        return Some(path.clone());
+    }
+    return find_image(asset_path, kind.get_name());
+}
+
+/// Loads the maps of the asset, which it has, leaving it to the scene to substitute fallbacks for the rest.
+/// Maps, which are named explicitly, have to exist.
+fn load_maps(
+    asset_path: &str,
+    map_file_names: &HashMap<TextureKind, String>,
+    material_libraries: &[String],
+) -> Result<HashMap<TextureKind, RgbImage>, RendererError> {
+    let mut mtl_maps = HashMap::new();
+    for library in material_libraries {
+        let mtl_path = format!("{}/{}", asset_path, library);
+        println!("loading material library from: {}", mtl_path);
+        for (kind, path) in load_mtl_maps(&mtl_path)? {
+            mtl_maps.entry(kind).or_insert(path);
+        }
+    }
+    let mut maps = HashMap::new();
+    for kind in TextureKind::ALL {
+        let path = match find_map_path(asset_path, map_file_names, &mtl_maps, kind) {
+            Some(path) => path,
+            None => {
+                println!("no {} in: {}", kind.get_name(), asset_path);
+                continue;
+            }
+        };
+        let image = load_image(&path)?;
+        println!(
+            "loaded {} from: {}, {} x {}",
+            kind.get_name(),
+            path,
+            image.width(),
+            image.height()
+        );
+        maps.insert(kind, image);
+    }
+    // Alpha channel of the texture becomes a separate grayscale opacity map, unless asset has one.
+    if !maps.contains_key(&TextureKind::AlphaMap) {
+        if let Some(texture) = maps.get(&TextureKind::Diffuse) {
+            if texture.color().has_alpha() {
+                let rgba = texture.to_rgba8();
+                let alpha_map = RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+                    let alpha = rgba.get_pixel(x, y)[3];
+                    return Rgb([alpha, alpha, alpha]);
+                });
+                maps.insert(TextureKind::AlphaMap, DynamicImage::ImageRgb8(alpha_map));
+            }
+        }
+    }
+    return Ok(maps
+        .into_iter()
+        .map(|(kind, image)| (kind, image.into_rgb8()))
+        .collect());
+}
+
+/// Loads the mesh of the asset and prepares it for rendering, replacing its normals and subdividing it, as the
+/// params ask, where the skin and vertex colors of the model allow it.
+fn load_prepared_model(
+    asset_path: &str,
+    normal_generation: NormalGeneration,
+    subdivision_levels: u32,
+) -> Result<GltfModel, Box<dyn std::error::Error>> {
+    let (mut obj, skin, vertex_colors) = load_model(asset_path)?;
+    let (n_split, n_dropped) = triangulate(&mut obj);
+    if n_split > 0 {
+        println!("triangulated {} polygons with more than 3 corners", n_split);
+    }
+    if n_dropped > 0 {
+        println!("dropped {} degenerate polygons", n_dropped);
+    }
+    if !vertex_colors.is_empty() {
+        println!("model has colors for {} vertices", vertex_colors.len());
+    }
+    // Skin weights are indexed the same as normals, so normals of skinned models are kept.
+    let normal_generation = match skin {
+        Some(_) if normal_generation != NormalGeneration::Auto => {
+            println!("keeping normals of the skinned model");
+            NormalGeneration::Auto
+        }
+        _ => normal_generation,
+    };
+    let n_generated = generate_normals(&mut obj, normal_generation);
+    if n_generated > 0 {
+        println!("generated normals for {} polygons", n_generated);
+    }
+    // Skin weights and vertex colors are indexed by positions, so subdivided positions wouldn't have any.
+    match skin {
+        Some(_) if subdivision_levels > 0 => {
+            println!("skipping subdivision of the skinned model")
+        }
+        None if subdivision_levels > 0 && !vertex_colors.is_empty() => {
+            println!("skipping subdivision of the model with vertex colors")
+        }
+        _ => {
+            for _ in 0..subdivision_levels {
+                subdivide(&mut obj);
+            }
+        }
+    }
     println!("number of vertices in a model: {}", obj.positions.len());
     println!("number of polygons in a model: {}", obj.polygons.len());
+    return Ok((obj, skin, vertex_colors));
+}
 
-    println!("loading texture from: {}", texture_path);
-    let texture = image::open(texture_path)?.into_rgb8();
-    println!(
-        "dimensions of loaded texture are: {} x {}",
-        texture.width(),
-        texture.height()
-    );
+/// Model, if it was reloaded, and the maps, reloaded together with it.
+type ReloadedAssets = (Option<GltfModel>, HashMap<TextureKind, RgbImage>);
 
-    println!("loading normal map from: {}", normal_map_path);
-    let normal_map = image::open(normal_map_path)?.into_rgb8();
-    println!(
-        "dimensions of loaded normal map are: {} x {}",
-        normal_map.width(),
-        normal_map.height()
-    );
+/// Extensions of the files, which the model is loaded from, glTF buffers included.
+const MODEL_EXTENSIONS: [&str; 6] = ["gltf", "glb", "bin", "ply", "stl", "obj"];
 
-    println!(
-        "loading normal map in tangent coordinates from: {}",
-        normal_map_tangent_path
-    );
-    let normal_map_tangent = image::open(normal_map_tangent_path)?.into_rgb8();
-    println!(
-        "dimensions of loaded normal map in tangent coordinates are: {} x {}",
-        normal_map.width(),
-        normal_map.height()
-    );
+/// Loads the assets again, after the files changed - the model, if one of its files did, and the maps, which
+/// may have changed with it, in any case. Material libraries of the reloaded model replace the kept ones.
+fn reload_assets(
+    asset_path: &str,
+    map_file_names: &HashMap<TextureKind, String>,
+    normal_generation: NormalGeneration,
+    subdivision_levels: u32,
+    changed_paths: &[PathBuf],
+    material_libraries: &mut Vec<String>,
+) -> Result<ReloadedAssets, Box<dyn std::error::Error>> {
+    for path in changed_paths {
+        println!("asset changed: {}", path.display());
+    }
+    let model_changed = changed_paths.iter().any(|path| {
+        let extension = path.extension().and_then(|extension| extension.to_str());
+        return extension.is_some_and(|extension| MODEL_EXTENSIONS.contains(&extension));
+    });
+    let mut model = None;
+    if model_changed {
+        let (obj, skin, vertex_colors) =
+            load_prepared_model(asset_path, normal_generation, subdivision_levels)?;
+        *material_libraries = obj.material_libraries.clone();
+        model = Some((obj, skin, vertex_colors));
+    }
+    let maps = load_maps(asset_path, map_file_names, material_libraries)?;
+    return Ok((model, maps));
+}
 
-    println!("loading specular map from: {}", specular_map_path);
-    let specular_map = image::open(specular_map_path)?.into_rgb8();
-    println!(
-        "dimensions of loaded specular map are: {} x {}",
-        specular_map.width(),
-        specular_map.height()
-    );
+/// Applies the settings, which the parts of a split frame may differ in - pipeline, exposure, tone mapping and
+/// shadows, while the light and the render scale are shared.
+fn apply_split_values(scene: &mut Scene, values: &PanelValues) -> Result<(), RendererError> {
+    scene.set_builtin_shader_pipeline(BUILTIN_PIPELINE_NAMES[values.pipeline_index].to_string())?;
+    scene.set_exposure(values.exposure);
+    scene.set_tone_mapping(values.tone_mapping);
+    scene.set_shadow_settings(values.shadow);
+    return Ok(());
+}
+
+/// Reads the tunables, failing for files, which can't be read or are malformed.
+fn load_tunables(tunables_path: &str) -> Result<Tunables, Box<dyn std::error::Error>> {
+    return Ok(Tunables::parse(&std::fs::read_to_string(tunables_path)?)
+        .ok_or(format!("Malformed tunables in {}", tunables_path))?);
+}
+
+/// Applies the settings of the panel, which the scene keeps, light being set every frame and pipeline being
+/// switched, when it changes.
+fn apply_panel_values(scene: &mut Scene, values: &PanelValues) {
+    scene.set_exposure(values.exposure);
+    scene.set_tone_mapping(values.tone_mapping);
+    scene.set_render_scale(values.render_scale);
+    scene.set_shadow_settings(values.shadow);
+}
+
+/// Actualy launches the window, showing images.
+/// Takes struct, defining execution params.
+pub fn run(params: Params) -> Result<(), Box<dyn std::error::Error>> {
+    let (obj, skin, vertex_colors) = load_prepared_model(
+        &params.asset_path,
+        params.normal_generation,
+        params.subdivision_levels,
+    )?;
+    // Material libraries of the model are kept, so its maps can be reloaded, after the model moves to the scene.
+    let mut material_libraries = obj.material_libraries.clone();
+    let mut maps = load_maps(
+        &params.asset_path,
+        &params.map_file_names,
+        &material_libraries,
+    )?;
+    let texture = maps.remove(&TextureKind::Diffuse);
+    let normal_map = maps.remove(&TextureKind::NormalMap);
+    let normal_map_tangent = maps.remove(&TextureKind::NormalMapTangent);
+    let specular_map = maps.remove(&TextureKind::SpecularMap);
 
     println!(
         "cooking up a scene with '{}' shader pipeline",
         params.shader_pipeline_name
     );
+    let mut pipeline_index = BUILTIN_PIPELINE_NAMES
+        .iter()
+        .position(|name| *name == params.shader_pipeline_name)
+        .unwrap_or(0);
     let mut scene = Scene::new(
         params.width,
         params.height,
@@ -143,107 +596,601 @@ pub fn run(params: Params) -> Result<(), Box<dyn std::error::Error>> {
         normal_map_tangent,
         specular_map,
         params.shader_pipeline_name,
-    );
+    )?;
+    scene.set_cull_mode(params.cull_mode);
+    scene.set_render_mode(params.render_mode);
+    scene.set_skin(skin);
+    scene.set_vertex_colors(vertex_colors);
+    scene.set_deferred(params.deferred);
+    scene.set_reference_mode(params.reference_mode);
+    scene.set_shadow_settings(params.shadow);
+    scene.set_fog(params.fog);
+    scene.set_ssao(params.ssao);
+    scene.set_dof(params.dof);
+    scene.set_motion_blur(params.motion_blur);
+    scene.set_taa(params.taa);
+    scene.set_bloom(params.bloom);
+    for post_pass in params.post_passes {
+        scene.add_post_pass(post_pass);
+    }
+    for pass in params.disabled_passes {
+        scene.set_pass_enabled(pass, false);
+    }
+    scene.set_gamma_correction(params.gamma_correction);
+    scene.set_tone_mapping(params.tone_mapping);
+    scene.set_exposure(params.exposure);
+    match params.projection {
+        Projection::Fixed => (),
+        Projection::Perspective { fov_y, near, far } => scene.set_perspective(fov_y, near, far),
+        Projection::Orthographic {
+            width,
+            height,
+            near,
+            far,
+        } => scene.set_orthographic(width, height, near, far),
+    }
+    // Camera controls orbit at the distance, from which the model, assumed to fit the unit sphere, is in view.
+    let camera_distance = params.projection.get_framing_distance();
+    if let Some(skybox_path) = &params.skybox_path {
+        println!("loading skybox from: {}", skybox_path);
+        scene.set_skybox(Some(load_cube_map(skybox_path)?));
+    }
+    if let Some(background) = &params.background {
+        let background = match Background::from_spec(background) {
+            Some(background) => background,
+            None => {
+                println!("loading background from: {}", background);
+                Background::from_image(load_image(background)?.into_rgb8())
+            }
+        };
+        scene.set_background(background);
+    }
+    if let Some(palette) = &params.palette {
+        let palette = match Palette::from_name(palette) {
+            Some(palette) => palette,
+            None => {
+                println!("loading palette from: {}", palette);
+                load_palette(palette)?
+            }
+        };
+        scene.set_palette(Some(palette), params.dithering);
+    }
+    let timeline = match &params.timeline_path {
+        Some(timeline_path) => {
+            println!("loading timeline from: {}", timeline_path);
+            let timeline = Timeline::parse(&std::fs::read_to_string(timeline_path)?)
+                .ok_or(format!("Malformed timeline in {}", timeline_path))?;
+            println!("timeline is {:.2}s long", timeline.get_duration());
+            Some(timeline)
+        }
+        None => None,
+    };
+    let gobo = match &params.gobo_path {
+        Some(gobo_path) => Some(load_image(gobo_path)?.into_rgb8()),
+        None => None,
+    };
+    for (index, light) in params.lights.into_iter().enumerate() {
+        if light.get_spot_matrix().is_some() {
+            scene.set_light_gobo(index, gobo.clone());
+        }
+        scene.add_light(light);
+    }
+    if let Some((position, size)) = params.security_camera {
+        let view = TargetView {
+            look_from: position,
+            look_at: vector![0.0, 0.0, 0.0],
+            up: vector![0.0, 1.0, 0.0],
+            projection: params.projection,
+        };
+        scene.add_render_target(SECURITY_CAMERA_TARGET, size, size, view);
+    }
+    scene.set_render_scale(params.render_scale);
+    // Settings, tweakable live from the panel, starting from the ones given on the command line.
+    let mut panel = Panel::new();
+    let mut panel_values = PanelValues {
+        pipeline_index,
+        light_azimuth: params.main_light.azimuth,
+        light_elevation: params.main_light.elevation,
+        light_intensity: params.main_light.intensity,
+        exposure: params.exposure,
+        tone_mapping: params.tone_mapping,
+        render_scale: params.render_scale,
+        shadow: params.shadow,
+    };
+    // Constants of the shaders, tuned in a file, override the command line and are re-read, when it changes.
+    if let Some(tunables_path) = &params.tunables_path {
+        println!("loading tunables from: {}", tunables_path);
+        panel_values.apply_tunables(&load_tunables(tunables_path)?);
+        pipeline_index = panel_values.pipeline_index;
+        scene.set_builtin_shader_pipeline(BUILTIN_PIPELINE_NAMES[pipeline_index].to_string())?;
+        apply_panel_values(&mut scene, &panel_values);
+    }
+    scene.set_msaa_samples(params.msaa_samples);
+    scene.set_alpha_settings(params.alpha);
+    // Maps of the metallic-roughness material and opacity, which are left, are optional, since not every asset
+    // has them.
+    for (kind, image) in maps {
+        scene.set_texture(kind, image);
+    }
+    for kind in TextureKind::ALL {
+        scene.set_texture_filter(kind, params.texture_filter);
+        scene.set_texture_max_anisotropy(kind, params.max_anisotropy);
+        scene.set_texture_wrap(kind, params.texture_wrap);
+    }
+    scene.set_normal_map_convention(params.normal_map_convention);
+    scene.set_displacement_scale(params.displacement_scale);
+    if params.lod_levels > 0 {
+        let lod_begin_time = time::Instant::now();
+        let lod_polygons = scene.generate_lods(params.lod_levels);
+        println!(
+            "generated levels of detail with {:?} polygons in {:?}",
+            lod_polygons,
+            lod_begin_time.elapsed()
+        );
+    }
+    let mut object_count = 1;
+    if let Some(instance_grid) = params.instance_grid {
+        let instances = instance_grid.get_instances(&scene.get_bounding_sphere());
+        println!("drawing {} instances of the model", instances.len());
+        object_count = instances.len();
+        scene.set_instances(instances);
+    }
+    if let Some(graph_path) = &params.graph_path {
+        println!("loading scene graph from: {}", graph_path);
+        let graph = SceneGraph::parse(&std::fs::read_to_string(graph_path)?)
+            .ok_or(format!("Malformed scene graph in {}", graph_path))?;
+        object_count = graph.get_instances().len().max(1);
+        println!("drawing the model at {} nodes", object_count);
+        scene.set_graph(graph);
+    }
+    if !params.materials.is_empty() {
+        for id in 0..object_count as u32 {
+            *scene.material_mut(id) = params.materials[id as usize % params.materials.len()];
+        }
+    }
+
+    if let Some(n_frames) = params.bench_frames {
+        println!(
+            "benchmarking {} frames of {} x {}",
+            n_frames, params.width, params.height
+        );
+        run_bench(&mut scene, n_frames, camera_distance);
+        return Ok(());
+    }
 
-    let window_options: WindowOptions = WindowOptions {
-        size: Some([params.width, params.height]),
-        ..Default::default()
+    // Clicking on the frame picks the polygon under the cursor, which only the window can do. Output variables
+    // but depth are read from the same ID buffer.
+    scene.set_picking(params.image_sequence.is_none() || params.aov_export.is_some());
+    let mut presenter: Box<dyn Presenter> = match params.image_sequence {
+        Some(image_sequence) => Box::new(image_sequence),
+        None => create_window_presenter(params.width, params.height)?,
     };
-    let window = create_window("output", window_options)?;
-    let event_channel = window.event_channel()?;
+    // Offline presenters advance time by a fixed step every frame instead of the real time.
+    let frame_interval = presenter.get_frame_interval();
+
+    // Scene moves onto its own thread, the window is handled here, while it renders.
+    let render_thread = RenderThread::spawn(scene, params.aov_export);
+
+    // Files, which are reloaded, when they change on disk.
+    let mut asset_watcher = params
+        .watch_assets
+        .then(|| FileWatcher::new(&[&params.asset_path]))
+        .transpose()?;
+    let mut tunables_watcher = params
+        .tunables_path
+        .as_ref()
+        .map(|tunables_path| FileWatcher::new(&[tunables_path]))
+        .transpose()?;
+    let lod_levels = params.lod_levels;
+    // Right part of a split frame overrides the settings of the left one by its own tunables.
+    let split_tunables = match &params.split_screen {
+        Some((spec, _)) if BUILTIN_PIPELINE_NAMES.contains(&spec.as_str()) => Some(Tunables {
+            pipeline: Some(spec.clone()),
+            ..Default::default()
+        }),
+        Some((spec, _)) => {
+            println!(
+                "loading tunables of the right part of the frame from: {}",
+                spec
+            );
+            Some(load_tunables(spec)?)
+        }
+        None => None,
+    };
+    let mut split_divider = params
+        .split_screen
+        .as_ref()
+        .map_or(0.5, |(_, divider)| *divider);
+    let mut frame_width = params.width;
 
     // Buffer for tracking actionable window events.
     let mut frame_action_buffer = FrameActionBuffer::new();
     // Variables for convenience.
     let mut camera_angle: f32 = 0.0;
-    let mut light_direction_angle: f32 = 0.0;
     // Stats.
     let mut exit = false;
     let mut frame_counter_time_begin = time::Instant::now();
+    let animation_time_begin = time::Instant::now();
     let mut frame_counter: u32 = 0;
+    let mut fps: u32 = 0;
+    let mut stats_sum = FrameStats::default(); // Stats of the frames, counted for the frame rate.
     let mut frame_begin_time;
     let mut frame_time = 0.0;
+    let mut frame_index: u32 = 0;
+    let mut pointer_panel_setting = None; // Setting, changed with the pointer on the panel of the last frame.
     while !exit {
         frame_begin_time = time::Instant::now();
+        let actions = &frame_action_buffer.actions;
 
-        // Clearing z-buffer and resetting rendered data to (0, 0, 0).
-        scene.clear();
+        // Panel only takes the arrow keys, while it's shown.
+        if *actions.get(&Action::TogglePanel).unwrap() {
+            panel.visible = !panel.visible;
+        }
+        let mut panel_steps = 0;
+        if panel.visible {
+            for (action, rows, steps) in [
+                (Action::PanelUp, -1, 0),
+                (Action::PanelDown, 1, 0),
+                (Action::PanelDecrease, 0, -1),
+                (Action::PanelIncrease, 0, 1),
+            ] {
+                if *actions.get(&action).unwrap() {
+                    panel.select(rows);
+                    panel_steps += steps;
+                }
+            }
+        }
+        let mut panel_setting = pointer_panel_setting.take();
+        if panel_steps != 0 {
+            panel_setting = Some(panel.adjust(&mut panel_values, panel_steps));
+        }
 
-        // Movement speed is proportional to previous frame dt for a smoother experience.
-        if *frame_action_buffer
-            .actions
-            .get(&Action::CameraRight)
-            .unwrap()
+        // Reloading the files, which changed on disk, keeping the old ones, while the new ones can't be read,
+        // e.g. because they are only partially written.
+        let mut tunables_changed = false;
+        if let (Some(watcher), Some(tunables_path)) = (&mut tunables_watcher, &params.tunables_path)
         {
+            if !watcher.poll().is_empty() {
+                match load_tunables(tunables_path) {
+                    Ok(tunables) => {
+                        println!("reloaded tunables from: {}", tunables_path);
+                        panel_values.apply_tunables(&tunables);
+                        tunables_changed = true;
+                    }
+                    Err(error) => println!("keeping the tunables: {}", error),
+                }
+            }
+        }
+        let mut model_reload = None;
+        let mut maps_reload = None;
+        if let Some(watcher) = &mut asset_watcher {
+            let changed_paths = watcher.poll();
+            if !changed_paths.is_empty() {
+                match reload_assets(
+                    &params.asset_path,
+                    &params.map_file_names,
+                    params.normal_generation,
+                    params.subdivision_levels,
+                    &changed_paths,
+                    &mut material_libraries,
+                ) {
+                    Ok((model, maps)) => (model_reload, maps_reload) = (model, Some(maps)),
+                    Err(error) => println!("keeping the assets: {}", error),
+                }
+            }
+        }
+
+        // Switching pipeline between the frames, keeping the camera and light as they are.
+        let mut next_pipeline_index = match panel_setting {
+            Some(Setting::Pipeline) => panel_values.pipeline_index,
+            _ if tunables_changed => panel_values.pipeline_index,
+            _ => pipeline_index,
+        };
+        if *actions.get(&Action::NextPipeline).unwrap() {
+            next_pipeline_index = (pipeline_index + 1) % BUILTIN_PIPELINE_NAMES.len();
+        }
+        for index in 0..BUILTIN_PIPELINE_NAMES.len() {
+            if *actions.get(&Action::SelectPipeline(index)).unwrap() {
+                next_pipeline_index = index;
+            }
+        }
+        let mut pipeline_switch = None;
+        if next_pipeline_index != pipeline_index {
+            pipeline_index = next_pipeline_index;
+            println!(
+                "switching to '{}' shader pipeline",
+                BUILTIN_PIPELINE_NAMES[pipeline_index]
+            );
+            pipeline_switch = Some(BUILTIN_PIPELINE_NAMES[pipeline_index].to_string());
+            panel_values.pipeline_index = pipeline_index;
+        }
+        let next_render_mode = *actions.get(&Action::NextRenderMode).unwrap();
+        let next_shading = *actions.get(&Action::NextShading).unwrap();
+        let debug_view = DebugView::ALL
+            .into_iter()
+            .rfind(|debug_view| *actions.get(&Action::SelectDebugView(*debug_view)).unwrap());
+        let screenshot = *actions.get(&Action::Screenshot).unwrap();
+
+        // Movement speed is proportional to previous frame dt for a smoother experience.
+        if *actions.get(&Action::CameraRight).unwrap() {
             camera_angle += CAMERA_SPEED * frame_time;
         }
-        if *frame_action_buffer
-            .actions
-            .get(&Action::CameraLeft)
-            .unwrap()
-        {
+        if *actions.get(&Action::CameraLeft).unwrap() {
             camera_angle -= CAMERA_SPEED * frame_time;
         }
         // Direction is FROM surface TO source, so negative of true direction.
         // This simplifies math inside shaders somewhat by removing the need to place minus at some critical spots.
         // Easier to think of this as light source position on a unit sphere.
-        if *frame_action_buffer
-            .actions
-            .get(&Action::LightRight)
-            .unwrap()
-        {
-            light_direction_angle += LIGHT_SOURCE_SPEED * frame_time;
-        }
-        if *frame_action_buffer.actions.get(&Action::LightLeft).unwrap() {
-            light_direction_angle -= LIGHT_SOURCE_SPEED * frame_time;
-        }
-        let look_from = vector![camera_angle.sin(), 0.0, camera_angle.cos()];
-        let look_at = vector![0.0, 0.0, 0.0];
-        let up = vector![0.0, 1.0, 0.0];
-        let light_direction = vector![
-            light_direction_angle.sin(),
-            0.0,
-            light_direction_angle.cos()
+        // Arrow keys move the light around its unit sphere, unless the panel takes them.
+        let light_action = |action: Action, arrow_action: Action| {
+            return *actions.get(&action).unwrap()
+                || (!panel.visible && *actions.get(&arrow_action).unwrap());
+        };
+        if light_action(Action::LightRight, Action::PanelIncrease) {
+            panel_values.light_azimuth += LIGHT_SOURCE_SPEED * frame_time;
+        }
+        if light_action(Action::LightLeft, Action::PanelDecrease) {
+            panel_values.light_azimuth -= LIGHT_SOURCE_SPEED * frame_time;
+        }
+        if light_action(Action::LightUp, Action::PanelUp) {
+            panel_values.light_elevation += LIGHT_SOURCE_SPEED * frame_time;
+        }
+        if light_action(Action::LightDown, Action::PanelDown) {
+            panel_values.light_elevation -= LIGHT_SOURCE_SPEED * frame_time;
+        }
+        panel_values.light_azimuth = panel_values.light_azimuth.rem_euclid(TAU);
+        panel_values.light_elevation = panel_values.light_elevation.clamp(-1.5, 1.5);
+        if *actions.get(&Action::LightBrighter).unwrap() {
+            panel_values.light_intensity += 0.1;
+        }
+        if *actions.get(&Action::LightDimmer).unwrap() {
+            panel_values.light_intensity = (panel_values.light_intensity - 0.1).max(0.0);
+        }
+        let mut look_from = vector![camera_angle.sin(), 0.0, camera_angle.cos()] * camera_distance;
+        let mut look_at = vector![0.0, 0.0, 0.0];
+        let mut up = vector![0.0, 1.0, 0.0];
+        let (azimuth, elevation) = (panel_values.light_azimuth, panel_values.light_elevation);
+        let mut light_direction = vector![
+            azimuth.sin() * elevation.cos(),
+            elevation.sin(),
+            azimuth.cos() * elevation.cos()
         ];
-        scene.set_light_direction(light_direction);
-        scene.set_camera(look_from, look_at, up);
-        scene.render();
-
-        // Getting rendered data as a data slice and feeding it into window.
-        let data = scene.get_frame_buffer();
-        // let data = scene.get_z_buffer();
-        // let data = scene.get_shadow_buffer();
-        let image_view =
-            ImageView::new(ImageInfo::rgb8(params.width, params.height), data.as_raw());
-        window.set_image("image", image_view)?;
-
-        // Unloading all the garbage from event channel, that has piled up, looking for actionable events.
+        let mut light_intensity = 1.0;
+        // Keyframes take over the parts of the scene they animate.
+        let animation_time = match frame_interval {
+            Some(frame_interval) => frame_index as f32 * frame_interval,
+            None => animation_time_begin.elapsed().as_secs_f32(),
+        };
+        if let Some(timeline) = &timeline {
+            if let Some(camera) = timeline.get_camera(animation_time) {
+                (look_from, look_at, up) = (camera.look_from, camera.look_at, camera.up);
+            }
+            if let Some(light) = timeline.get_light(animation_time) {
+                (light_direction, light_intensity) = (light.direction, light.intensity);
+            }
+        }
+        // Divider of the split frame follows the cursor, while it's dragged.
+        let drag = frame_action_buffer
+            .drag
+            .filter(|&(x, y)| !panel.contains(x, y));
+        if let Some((x, _)) = drag {
+            split_divider = x as f32 / frame_width as f32;
+        }
+        let split = split_tunables.as_ref().map(|tunables| {
+            let left_values = panel_values;
+            let mut right_values = panel_values;
+            right_values.apply_tunables(tunables);
+            return SplitFrame {
+                apply: Box::new(move |scene: &mut Scene| apply_split_values(scene, &right_values)),
+                restore: Box::new(move |scene: &mut Scene| apply_split_values(scene, &left_values)),
+                divider: split_divider,
+            };
+        });
+        let click = frame_action_buffer
+            .click
+            .filter(|&(x, y)| !panel.contains(x, y));
+        let window_size = frame_action_buffer.window_size;
+        let values = panel_values;
+        render_thread.request_frame(Box::new(move |scene: &mut Scene| {
+            if let Some((obj, skin, vertex_colors)) = model_reload {
+                scene.set_model(obj);
+                scene.set_skin(skin);
+                scene.set_vertex_colors(vertex_colors);
+                if lod_levels > 0 {
+                    scene.generate_lods(lod_levels);
+                }
+            }
+            for (kind, image) in maps_reload.into_iter().flatten() {
+                scene.set_texture(kind, image);
+            }
+            if tunables_changed {
+                apply_panel_values(scene, &values);
+            }
+            match panel_setting {
+                Some(Setting::Exposure) => scene.set_exposure(values.exposure),
+                Some(Setting::ToneMapping) => scene.set_tone_mapping(values.tone_mapping),
+                Some(Setting::RenderScale) => scene.set_render_scale(values.render_scale),
+                Some(Setting::ShadowBias | Setting::ShadowSlopeBias | Setting::PcfRadius) => {
+                    scene.set_shadow_settings(values.shadow)
+                }
+                // Pipeline is switched together with the other ways of switching it, light is set every frame.
+                _ => (),
+            }
+            if let Some(pipeline_name) = pipeline_switch {
+                scene.set_builtin_shader_pipeline(pipeline_name)?;
+            }
+            if next_render_mode {
+                scene.set_render_mode(scene.get_render_mode().next());
+                println!("switching to {:?} render mode", scene.get_render_mode());
+            }
+            if next_shading {
+                scene.set_shading(scene.get_shading().next());
+                println!("switching to {:?} shading", scene.get_shading());
+            }
+            if let Some(debug_view) = debug_view {
+                scene.set_debug_view(debug_view);
+                println!("showing {:?} debug view", debug_view);
+            }
+
+            // Picking in the frame, which was clicked, before it's cleared.
+            if let Some((x, y)) = click {
+                match scene.pick(x, y) {
+                    Some(hit) => println!(
+                        "picked object {} polygon {} of lod {} at barycentric ({:.3}, {:.3}, {:.3}), model position ({:.3}, {:.3}, {:.3})",
+                        hit.object,
+                        hit.polygon,
+                        hit.lod,
+                        hit.bar_coord.x,
+                        hit.bar_coord.y,
+                        hit.bar_coord.z,
+                        hit.position.x,
+                        hit.position.y,
+                        hit.position.z
+                    ),
+                    None => println!("picked nothing at ({}, {})", x, y),
+                }
+            }
+
+            // Rendering at the pixel size of the window, skipping sizes too small to render, e.g. when minimized.
+            if let Some((width, height)) = window_size {
+                if width > 1 && height > 1 {
+                    scene.set_resolution(width, height);
+                }
+            }
+
+            scene.set_light_direction(light_direction);
+            scene.set_light_intensity(light_intensity * values.light_intensity);
+            scene.set_camera(look_from, look_at, up);
+            scene.set_animation_time(animation_time);
+            return Ok(());
+        }), split);
+
+        // Collecting the inputs for the next frame, while this one renders, and leaving right away on exit.
         frame_action_buffer.reset();
-        for window_event in event_channel.try_iter() {
-            frame_action_buffer.process_window_event(window_event);
+        let frame = loop {
+            if let Some(frame) = render_thread.wait_frame(INPUT_POLL_INTERVAL)? {
+                break Some(frame);
+            }
+            for input in presenter.poll_inputs() {
+                frame_action_buffer.process_input(input);
+            }
+            if *frame_action_buffer.actions.get(&Action::ExitApp).unwrap() {
+                break None;
+            }
+        };
+        let frame = match frame {
+            Some(frame) => frame,
+            None => break,
+        };
+        stats_sum += frame.stats;
+        let reference_error = frame.reference_error;
+
+        // Feeding the frame into window.
+        let mut data = frame.image;
+        frame_width = data.width();
+        if let Some(inset) = &frame.inset {
+            let x = data.width().saturating_sub(inset.width() + 8);
+            draw_inset(&mut data, x, 8, inset);
+        }
+        let divider = (split_divider.clamp(0.0, 1.0) * data.width() as f32) as u32;
+        if split_tunables.is_some() {
+            draw_divider(&mut data, divider);
+        }
+        if screenshot {
+            let screenshot_path = get_screenshot_path();
+            match data.save(&screenshot_path) {
+                Ok(()) => println!("saved screenshot to: {}", screenshot_path),
+                Err(error) => println!("failed to save screenshot: {}", error),
+            }
+        }
+        // Stats go on top of the frame after the screenshot, so they don't end up in it.
+        if params.show_stats {
+            let stats = frame.stats;
+            let mut timing_line = format!(
+                "FPS {}  FRAME {:.1} MS  RENDER {:.1} MS",
+                fps,
+                frame_time * 1000.0,
+                frame.render_time * 1000.0
+            );
+            if let Some(reference_error) = reference_error {
+                timing_line += &format!("  REFERENCE ERROR {:.2}", reference_error);
+            }
+            let lines = [
+                timing_line,
+                format!(
+                    "POLYGONS {}  CULLED {}  RASTERIZED {}",
+                    stats.polygons_submitted, stats.polygons_culled, stats.polygons_rasterized
+                ),
+                format!(
+                    "FRAGMENTS {}  DEPTH REJECTED {}  DISCARDED {}",
+                    stats.fragments_shaded,
+                    stats.fragments_depth_rejected,
+                    stats.fragments_discarded
+                ),
+                format!(
+                    "LIGHT AZIMUTH {:.0} DEG  ELEVATION {:.0} DEG  INTENSITY {:.1}",
+                    panel_values.light_azimuth.to_degrees().rem_euclid(360.0),
+                    panel_values.light_elevation.to_degrees(),
+                    panel_values.light_intensity
+                ),
+            ];
+            draw_text(&mut data, 8, 8, 2, &lines);
+            // Pipelines of both parts of a split frame are named along the bottom.
+            if let Some(tunables) = &split_tunables {
+                let left_name = BUILTIN_PIPELINE_NAMES[pipeline_index];
+                let right_name = tunables.pipeline.as_deref().unwrap_or(left_name);
+                let y = data.height().saturating_sub(24);
+                draw_text(&mut data, 8, y, 2, &[left_name.to_string()]);
+                draw_text(&mut data, divider + 8, y, 2, &[right_name.to_string()]);
+            }
+        }
+        let pointer_inputs = std::mem::take(&mut frame_action_buffer.pointer_inputs);
+        if panel.visible {
+            let panel_y = if params.show_stats { 80 } else { 8 };
+            pointer_panel_setting =
+                panel.draw(&mut data, panel_y, &mut panel_values, &pointer_inputs);
+        }
+        presenter.present(&data)?;
+
+        for input in presenter.poll_inputs() {
+            frame_action_buffer.process_input(input);
         }
         // If found Exit action, leaving the app in the beginning of the next frame.
         if *frame_action_buffer.actions.get(&Action::ExitApp).unwrap() {
             exit = true;
         }
 
-        if params.print_fps {
-            // Counting frames to printout stats every seconds.
-            frame_counter += 1;
-            if time::Instant::now()
-                .duration_since(frame_counter_time_begin)
-                .as_secs_f32()
-                > 1.0
-            {
-                println!("FPS --- {}", frame_counter);
-                frame_counter_time_begin = time::Instant::now();
-                frame_counter = 0;
+        // Counting frames to update the frame rate every second.
+        frame_counter += 1;
+        if time::Instant::now()
+            .duration_since(frame_counter_time_begin)
+            .as_secs_f32()
+            > 1.0
+        {
+            if params.print_stats {
+                let elapsed = frame_counter_time_begin.elapsed();
+                println!("fps {}", frame_counter);
+                print_frame_stats(
+                    &stats_sum.get_average(frame_counter),
+                    elapsed / frame_counter,
+                );
+                if let Some(reference_error) = reference_error {
+                    println!("reference  error {:.3}", reference_error);
+                }
             }
+            fps = frame_counter;
+            frame_counter_time_begin = time::Instant::now();
+            frame_counter = 0;
+            stats_sum = FrameStats::default();
         }
 
-        frame_time = time::Instant::now()
-            .duration_since(frame_begin_time)
-            .as_secs_f32();
+        frame_time = match frame_interval {
+            Some(frame_interval) => frame_interval,
+            None => time::Instant::now()
+                .duration_since(frame_begin_time)
+                .as_secs_f32(),
+        };
+        frame_index += 1;
     }
 
     return Ok(());
@@ -0,0 +1,69 @@
+use std::f32::consts::PI;
+use std::time::{Duration, Instant};
+
+use na::vector;
+use nalgebra as na;
+use tiny_renderer::scene::{FrameStats, Scene};
+
+/// Renders the frames without presenting them, orbiting the camera once around the model over all of them,
+/// so the workload is the same from run to run, and prints frame times and the time of the render stages.
+pub fn run_bench(scene: &mut Scene, n_frames: u32, camera_distance: f32) {
+    let mut frame_times = Vec::with_capacity(n_frames as usize);
+    let mut stats_sum = FrameStats::default();
+    for frame in 0..n_frames {
+        let camera_angle = 2.0 * PI * frame as f32 / n_frames as f32;
+        scene.set_camera(
+            vector![camera_angle.sin(), 0.0, camera_angle.cos()] * camera_distance,
+            vector![0.0, 0.0, 0.0],
+            vector![0.0, 1.0, 0.0],
+        );
+        let begin_time = Instant::now();
+        scene.clear();
+        scene.render();
+        frame_times.push(begin_time.elapsed());
+        stats_sum += scene.get_stats();
+    }
+
+    let total_time: Duration = frame_times.iter().sum();
+    println!(
+        "frame      min {:.2} ms  avg {:.2} ms  max {:.2} ms",
+        to_ms(*frame_times.iter().min().unwrap()),
+        to_ms(total_time / n_frames),
+        to_ms(*frame_times.iter().max().unwrap())
+    );
+    print_frame_stats(&stats_sum.get_average(n_frames), total_time / n_frames);
+}
+
+/// Prints counters and time of every render stage with its share of the frame time.
+pub fn print_frame_stats(stats: &FrameStats, frame_time: Duration) {
+    println!(
+        "polygons   submitted {}  culled {}  rasterized {}",
+        stats.polygons_submitted, stats.polygons_culled, stats.polygons_rasterized
+    );
+    println!(
+        "fragments  shaded {}  depth rejected {}  discarded {}  hi-z blocks culled {}",
+        stats.fragments_shaded,
+        stats.fragments_depth_rejected,
+        stats.fragments_discarded,
+        stats.hiz_blocks_culled
+    );
+    for (name, time) in [
+        ("clear", stats.clear_time),
+        ("shadow", stats.shadow_time),
+        ("vertex", stats.vertex_time),
+        ("raster", stats.raster_time),
+        ("fragment", stats.fragment_time),
+        ("post", stats.post_time),
+    ] {
+        println!(
+            "{:<10} avg {:.2} ms  {:.1}%",
+            name,
+            to_ms(time),
+            100.0 * time.as_secs_f64() / frame_time.as_secs_f64().max(f64::EPSILON)
+        );
+    }
+}
+
+fn to_ms(time: Duration) -> f64 {
+    return time.as_secs_f64() * 1000.0;
+}
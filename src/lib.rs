@@ -0,0 +1,37 @@
+//! Software renderer, rasterizing textured models on the CPU with programmable shader pipelines.
+//!
+//! Everything lives in [`scene`] - [`scene::Scene`] owns the model, its textures, lights, camera and the
+//! buffers it renders into, and doesn't depend on any windowing, so it can be embedded into any application,
+//! which can show or save an RGB image. Interactive viewer is the binary of the crate, built with the default
+//! `viewer` feature. Rasterization itself comes from the `no_std` core crate, `tiny_renderer_core`, which can
+//! be used on its own on targets without the standard library.
+//!
+//! ```no_run
+//! use nalgebra::vector;
+//! use tiny_renderer::scene::{load_image, load_obj, Scene};
+//!
+//! let obj = load_obj("assets/diablo/model.obj")?;
+//! let texture = load_image("assets/diablo/texture.tga")?.into_rgb8();
+//! let normal_map = load_image("assets/diablo/normal_map.tga")?.into_rgb8();
+//! let normal_map_tangent = load_image("assets/diablo/normal_map_tangent.tga")?.into_rgb8();
+//! let specular_map = load_image("assets/diablo/specular_map.tga")?.into_rgb8();
+//! let mut scene = Scene::new(
+//!     800,
+//!     800,
+//!     obj,
+//!     Some(texture),
+//!     Some(normal_map),
+//!     Some(normal_map_tangent),
+//!     Some(specular_map),
+//!     "phong".to_string(),
+//! )?;
+//! scene.set_perspective(0.7, 0.1, 10.0);
+//! scene.set_camera(vector![0.0, 0.0, 3.0], vector![0.0, 0.0, 0.0], vector![0.0, 1.0, 0.0]);
+//! scene.render();
+//! scene.get_frame_buffer().save("frame.png")?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+pub mod scene;
+#[cfg(target_arch = "wasm32")]
+pub mod web;
@@ -1,16 +1,79 @@
 mod app;
-mod scene;
+mod bench;
+mod overlay;
+mod panel;
+mod present;
+mod render_thread;
+mod watch;
 
+use std::collections::HashMap;
 use std::env;
 
+use nalgebra::Vector3;
+use present::ImageSequence;
+use tiny_renderer::scene::{
+    AlphaMode, AlphaSettings, AovExport, BloomSettings, BuiltinPostPass, CullMode, Dithering,
+    DofSettings, Filter, FogSettings, FramePass, InstanceGrid, Light, Material, MotionBlurSettings,
+    NormalGeneration, NormalMapConvention, Projection, ReferenceMode, RenderMode, ShadowSettings,
+    SsaoSettings, TaaSettings, TextureKind, ToneMapping, Wrap,
+};
+
 const WIDTH: u32 = 800;
 const HEIGHT: u32 = 800;
 
-#[show_image::main]
+#[cfg_attr(feature = "viewer", show_image::main)]
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Default values.
     let mut asset_path = String::from("assets/diablo");
     let mut shader_pipeline_name = String::from("default");
+    let mut cull_mode = CullMode::default();
+    let mut render_mode = RenderMode::default();
+    let mut render_scale = 1;
+    let mut msaa_samples = 1;
+    let mut texture_filter = Filter::default();
+    let mut max_anisotropy = 16;
+    let mut texture_wrap = Wrap::default();
+    let mut normal_map_convention = NormalMapConvention::default();
+    let mut lights = Vec::new();
+    let mut main_light = app::MainLight::default();
+    let mut gobo_path = None;
+    let mut security_camera = None;
+    let mut materials = Vec::new();
+    let mut shadow = ShadowSettings::default();
+    let mut alpha = AlphaSettings::default();
+    let mut fog = None;
+    let mut ssao = None;
+    let mut bloom = None;
+    let mut dof = None;
+    let mut motion_blur = None;
+    let mut taa = None;
+    let mut post_passes = Vec::new();
+    let mut disabled_passes = Vec::new();
+    let mut skybox_path = None;
+    let mut background = None;
+    let mut palette = None;
+    let mut dithering = Dithering::default();
+    let mut gamma_correction = true;
+    let mut tone_mapping = ToneMapping::default();
+    let mut exposure = 1.0;
+    let mut deferred = false;
+    let mut reference_mode = ReferenceMode::default();
+    let mut timeline_path = None;
+    let mut normal_generation = NormalGeneration::default();
+    let mut subdivision_levels = 0;
+    let mut displacement_scale = 0.0;
+    let mut instance_grid = None;
+    let mut graph_path = None;
+    let mut lod_levels = 2;
+    let mut projection = Projection::default();
+    let mut image_sequence = None;
+    let mut aov_export = None;
+    let mut map_file_names = HashMap::new();
+    let mut bench_frames = None;
+    let mut print_stats = false;
+    let mut watch_assets = false;
+    let mut tunables_path = None;
+    let mut split_screen = None;
 
     let args: Vec<String> = env::args().collect();
     for i in 1..args.len() {
@@ -21,6 +84,354 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             "-s" => {
                 shader_pipeline_name = args[i + 1].clone();
             }
+            "-c" => {
+                cull_mode = CullMode::from_name(&args[i + 1])
+                    .expect("Provided cull mode is not supported!");
+            }
+            "-d" => {
+                render_mode = RenderMode::from_name(&args[i + 1])
+                    .expect("Provided render mode is not supported!");
+            }
+            "-r" => {
+                render_scale = args[i + 1]
+                    .parse()
+                    .expect("Render scale should be a positive integer!");
+            }
+            "-m" => {
+                msaa_samples = args[i + 1]
+                    .parse()
+                    .expect("Number of samples should be a positive integer!");
+            }
+            "-f" => {
+                // Anisotropic filter takes an optional max anisotropy after a colon.
+                let (name, max) = match args[i + 1].split_once(':') {
+                    Some((name, max)) => (name, Some(max)),
+                    None => (args[i + 1].as_str(), None),
+                };
+                texture_filter =
+                    Filter::from_name(name).expect("Provided texture filter is not supported!");
+                if let Some(max) = max {
+                    max_anisotropy = max
+                        .parse()
+                        .ok()
+                        .filter(|&max_anisotropy| max_anisotropy > 0)
+                        .expect("Max anisotropy should be a positive integer!");
+                }
+            }
+            "-w" => {
+                texture_wrap = Wrap::from_name(&args[i + 1])
+                    .expect("Provided texture wrap mode is not supported!");
+            }
+            "-N" => {
+                normal_map_convention = NormalMapConvention::from_name(&args[i + 1])
+                    .expect("Provided normal map convention is not supported!");
+            }
+            "-l" => {
+                lights.push(
+                    Light::from_spec(&args[i + 1]).expect("Provided light spec is not supported!"),
+                );
+            }
+            "-Y" => {
+                main_light = app::MainLight::from_spec(&args[i + 1])
+                    .expect("Provided main light spec is not supported!");
+            }
+            "-C" => {
+                gobo_path = Some(args[i + 1].clone());
+            }
+            "-T" => {
+                let values: Vec<f32> = args[i + 1]
+                    .split(',')
+                    .map(|value| {
+                        value
+                            .parse()
+                            .expect("Security camera parameters should be numbers!")
+                    })
+                    .collect();
+                assert!(
+                    values.len() == 3 || values.len() == 4,
+                    "Security camera should be given as x,y,z or x,y,z,size!"
+                );
+                let size = values.get(3).map_or(200, |&size| size as u32);
+                security_camera = Some((Vector3::new(values[0], values[1], values[2]), size));
+            }
+            "-G" => {
+                materials.push(
+                    Material::from_spec(&args[i + 1])
+                        .expect("Provided material spec is not supported!"),
+                );
+            }
+            "-k" => {
+                shadow.pcf_radius = args[i + 1]
+                    .parse()
+                    .expect("PCF radius should be a non-negative integer!");
+            }
+            "-K" => {
+                let values: Vec<u32> = args[i + 1]
+                    .split(',')
+                    .map(|value| {
+                        value
+                            .parse()
+                            .expect("Shadow cascades should be non-negative integers!")
+                    })
+                    .collect();
+                shadow.cascades = values[0];
+                if let Some(&resolution) = values.get(1) {
+                    shadow.cascade_resolution = resolution;
+                }
+            }
+            "-o" => {
+                shadow.cube_resolution = args[i + 1]
+                    .parse()
+                    .expect("Cube shadow map resolution should be a non-negative integer!");
+            }
+            "-b" => {
+                let biases: Vec<f32> = args[i + 1]
+                    .split(',')
+                    .map(|bias| bias.parse().expect("Shadow bias should be a number!"))
+                    .collect();
+                shadow.bias = biases[0];
+                shadow.slope_bias = *biases.get(1).unwrap_or(&0.0);
+            }
+            "-e" => {
+                skybox_path = Some(args[i + 1].clone());
+            }
+            "-E" => {
+                background = Some(args[i + 1].clone());
+            }
+            "-Q" => {
+                let (name, dithering_name) = match args[i + 1].split_once(',') {
+                    Some((name, dithering_name)) => (name, Some(dithering_name)),
+                    None => (args[i + 1].as_str(), None),
+                };
+                palette = Some(String::from(name));
+                if let Some(dithering_name) = dithering_name {
+                    dithering = Dithering::from_name(dithering_name)
+                        .expect("Provided dithering is not supported!");
+                }
+            }
+            "-a" => {
+                ssao = Some(SsaoSettings {
+                    radius: args[i + 1]
+                        .parse()
+                        .expect("SSAO radius should be a positive number!"),
+                    ..Default::default()
+                });
+            }
+            "-g" => {
+                gamma_correction = match args[i + 1].as_str() {
+                    "on" => true,
+                    "off" => false,
+                    _ => panic!("Gamma correction should be either on or off!"),
+                };
+            }
+            "-z" => {
+                deferred = match args[i + 1].as_str() {
+                    "on" => true,
+                    "off" => false,
+                    _ => panic!("Deferred shading should be either on or off!"),
+                };
+            }
+            "-t" => {
+                tone_mapping = ToneMapping::from_name(&args[i + 1])
+                    .expect("Provided tone mapping is not supported!");
+            }
+            "-x" => {
+                exposure = args[i + 1]
+                    .parse()
+                    .expect("Exposure should be a positive number!");
+            }
+            "-i" => {
+                let values: Vec<f32> = args[i + 1]
+                    .split(',')
+                    .map(|value| value.parse().expect("Bloom parameters should be numbers!"))
+                    .collect();
+                let mut settings = BloomSettings {
+                    intensity: values[0],
+                    ..Default::default()
+                };
+                if let Some(&threshold) = values.get(1) {
+                    settings.threshold = threshold;
+                }
+                bloom = Some(settings);
+            }
+            "-F" => {
+                let values: Vec<f32> = args[i + 1]
+                    .split(',')
+                    .map(|value| {
+                        value
+                            .parse()
+                            .expect("Depth of field parameters should be numbers!")
+                    })
+                    .collect();
+                let mut settings = DofSettings {
+                    focal_distance: values[0],
+                    ..Default::default()
+                };
+                if let Some(&aperture) = values.get(1) {
+                    settings.aperture = aperture;
+                }
+                if let Some(&max_radius) = values.get(2) {
+                    settings.max_radius = max_radius;
+                }
+                dof = Some(settings);
+            }
+            "-B" => {
+                let values: Vec<f32> = args[i + 1]
+                    .split(',')
+                    .map(|value| {
+                        value
+                            .parse()
+                            .expect("Motion blur parameters should be numbers!")
+                    })
+                    .collect();
+                let mut settings = MotionBlurSettings {
+                    strength: values[0],
+                    ..Default::default()
+                };
+                if let Some(&samples) = values.get(1) {
+                    settings.samples = samples as u32;
+                }
+                motion_blur = Some(settings);
+            }
+            "-A" => {
+                let values: Vec<f32> = args[i + 1]
+                    .split(',')
+                    .map(|value| {
+                        value
+                            .parse()
+                            .expect("Temporal anti-aliasing parameters should be numbers!")
+                    })
+                    .collect();
+                let mut settings = TaaSettings {
+                    blend: values[0],
+                    ..Default::default()
+                };
+                if let Some(&max_frames) = values.get(1) {
+                    settings.max_frames = max_frames as u32;
+                }
+                taa = Some(settings);
+            }
+            "-v" => {
+                post_passes.push(
+                    BuiltinPostPass::from_name(&args[i + 1])
+                        .expect("Provided post-processing pass is not supported!"),
+                );
+            }
+            "-y" => {
+                fog = Some(
+                    FogSettings::from_spec(&args[i + 1])
+                        .expect("Provided fog spec is not supported!"),
+                );
+            }
+            "-u" => {
+                alpha.mode = AlphaMode::from_spec(&args[i + 1])
+                    .expect("Provided alpha mode is not supported!");
+            }
+            "-q" => {
+                normal_generation = NormalGeneration::from_name(&args[i + 1])
+                    .expect("Provided normal generation mode is not supported!");
+            }
+            "-j" => {
+                timeline_path = Some(args[i + 1].clone());
+            }
+            "-h" => {
+                projection = Projection::from_spec(&args[i + 1])
+                    .expect("Provided projection spec is not supported!");
+            }
+            "-R" => {
+                reference_mode = ReferenceMode::from_name(&args[i + 1])
+                    .expect("Provided reference mode is not supported!");
+            }
+            "-O" => {
+                image_sequence = Some(
+                    ImageSequence::from_spec(&args[i + 1])
+                        .expect("Provided image sequence spec is not supported!"),
+                );
+            }
+            "-X" => {
+                aov_export = Some(
+                    AovExport::from_spec(&args[i + 1])
+                        .expect("Provided AOV export spec is not supported!"),
+                );
+            }
+            "-M" => {
+                for assignment in args[i + 1].split(',') {
+                    let (name, file_name) = assignment
+                        .split_once('=')
+                        .expect("Map file names should be given as name=file!");
+                    let kind =
+                        TextureKind::from_name(name).expect("Provided map name is not supported!");
+                    map_file_names.insert(kind, file_name.to_string());
+                }
+            }
+            "--bench" => {
+                bench_frames = Some(
+                    args[i + 1]
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|&n_frames| n_frames > 0)
+                        .expect("Number of frames should be a positive integer!"),
+                );
+            }
+            "-S" => {
+                subdivision_levels = args[i + 1]
+                    .parse()
+                    .expect("Subdivision levels should be a non-negative integer!");
+            }
+            "-D" => {
+                displacement_scale = args[i + 1]
+                    .parse()
+                    .expect("Displacement scale should be a number!");
+            }
+            "-L" => {
+                lod_levels = args[i + 1]
+                    .parse()
+                    .expect("Levels of detail should be a non-negative integer!");
+            }
+            "-I" => {
+                instance_grid = Some(
+                    InstanceGrid::from_spec(&args[i + 1])
+                        .expect("Provided instance grid spec is not supported!"),
+                );
+            }
+            "-H" => {
+                graph_path = Some(args[i + 1].clone());
+            }
+            "-P" => {
+                print_stats = true;
+            }
+            "-W" => {
+                watch_assets = true;
+            }
+            "-U" => {
+                tunables_path = Some(args[i + 1].clone());
+            }
+            "-V" => {
+                split_screen = Some(match args[i + 1].split_once(':') {
+                    Some((spec, divider)) => (
+                        spec.to_string(),
+                        divider
+                            .parse()
+                            .ok()
+                            .filter(|divider| (0.0..=1.0).contains(divider))
+                            .expect("Divider should be a number in [0, 1]!"),
+                    ),
+                    None => (args[i + 1].clone(), 0.5),
+                });
+            }
+            "-n" => {
+                alpha.opacity = args[i + 1]
+                    .parse()
+                    .expect("Opacity should be a number in [0, 1]!");
+            }
+            "-J" => {
+                disabled_passes = args[i + 1]
+                    .split(',')
+                    .map(|name| {
+                        FramePass::from_name(name).expect("Provided frame pass is not supported!")
+                    })
+                    .collect();
+            }
             _ => (),
         }
     }
@@ -28,9 +439,58 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let params = app::Params {
         width: WIDTH,
         height: HEIGHT,
-        print_fps: true,
+        // Saved frames are kept clean of the stats.
+        show_stats: image_sequence.is_none(),
         asset_path,
         shader_pipeline_name,
+        cull_mode,
+        render_mode,
+        render_scale,
+        msaa_samples,
+        texture_filter,
+        max_anisotropy,
+        texture_wrap,
+        normal_map_convention,
+        lights,
+        main_light,
+        gobo_path,
+        security_camera,
+        materials,
+        shadow,
+        alpha,
+        fog,
+        ssao,
+        dof,
+        motion_blur,
+        taa,
+        bloom,
+        post_passes,
+        disabled_passes,
+        skybox_path,
+        background,
+        palette,
+        dithering,
+        gamma_correction,
+        tone_mapping,
+        exposure,
+        deferred,
+        reference_mode,
+        timeline_path,
+        normal_generation,
+        subdivision_levels,
+        displacement_scale,
+        instance_grid,
+        graph_path,
+        lod_levels,
+        projection,
+        image_sequence,
+        aov_export,
+        map_file_names,
+        bench_frames,
+        print_stats,
+        watch_assets,
+        tunables_path,
+        split_screen,
     };
 
     app::run(params)?;
@@ -0,0 +1,127 @@
+use image::{Rgb, RgbImage};
+
+/// Size of a glyph of the built-in bitmap font in font pixels, not counting spacing.
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+
+/// Rows of the glyph from top to bottom, the highest of the 5 bits being the leftmost pixel. Lowercase
+/// letters are drawn as uppercase, unknown characters as a box.
+fn get_glyph(character: char) -> [u8; 7] {
+    match character.to_ascii_uppercase() {
+        '0' => return [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => return [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => return [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => return [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => return [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => return [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => return [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => return [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => return [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => return [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        'A' => return [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'B' => return [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => return [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => return [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C],
+        'E' => return [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => return [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => return [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => return [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => return [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => return [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => return [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => return [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => return [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => return [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => return [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => return [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => return [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => return [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => return [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => return [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => return [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => return [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => return [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => return [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => return [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04],
+        'Z' => return [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        '.' => return [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        ',' => return [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08],
+        ':' => return [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        '/' => return [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+        '-' => return [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        '_' => return [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F],
+        '%' => return [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03],
+        '>' => return [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08],
+        '<' => return [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02],
+        '(' => return [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+        ')' => return [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+        ' ' => return [0x00; 7],
+        _ => return [0x1F, 0x11, 0x11, 0x11, 0x11, 0x11, 0x1F],
+    }
+}
+
+/// Draws lines of text with the top left corner at x, y of the image, every font pixel being a scale x scale
+/// square. Text gets a dark drop shadow to stay readable over bright parts of the frame, anything outside of
+/// the image is clipped.
+pub fn draw_text(image: &mut RgbImage, x: u32, y: u32, scale: u32, lines: &[String]) {
+    let line_height = (GLYPH_HEIGHT + 2) * scale;
+    let advance = (GLYPH_WIDTH + 1) * scale;
+    for (shadow_offset, color) in [(scale, Rgb([0, 0, 0])), (0, Rgb([255, 255, 255]))] {
+        for (line_index, line) in lines.iter().enumerate() {
+            let line_y = y + line_index as u32 * line_height + shadow_offset;
+            for (char_index, character) in line.chars().enumerate() {
+                let glyph_x = x + char_index as u32 * advance + shadow_offset;
+                let glyph = get_glyph(character);
+                for row in 0..GLYPH_HEIGHT {
+                    for column in 0..GLYPH_WIDTH {
+                        if glyph[row as usize] & (0x10 >> column) == 0 {
+                            continue;
+                        }
+                        fill_square(
+                            image,
+                            glyph_x + column * scale,
+                            line_y + row * scale,
+                            scale,
+                            color,
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Draws the inset with the top left corner at x, y of the image inside of a white border, anything outside
+/// of the image is clipped.
+pub fn draw_inset(image: &mut RgbImage, x: u32, y: u32, inset: &RgbImage) {
+    let border = 2;
+    fill_square(
+        image,
+        x.saturating_sub(border),
+        y.saturating_sub(border),
+        inset.width().max(inset.height()) + 2 * border,
+        Rgb([255, 255, 255]),
+    );
+    for j in 0..inset.height().min(image.height().saturating_sub(y)) {
+        for i in 0..inset.width().min(image.width().saturating_sub(x)) {
+            image.put_pixel(x + i, y + j, *inset.get_pixel(i, j));
+        }
+    }
+}
+
+/// Draws a vertical line over the columns x - 1 and x, e.g. between the two parts of a split frame.
+pub fn draw_divider(image: &mut RgbImage, x: u32) {
+    for y in 0..image.height() {
+        for i in x.saturating_sub(1)..(x + 1).min(image.width()) {
+            image.put_pixel(i, y, Rgb([255, 255, 255]));
+        }
+    }
+}
+
+fn fill_square(image: &mut RgbImage, x: u32, y: u32, size: u32, color: Rgb<u8>) {
+    for j in y..(y + size).min(image.height()) {
+        for i in x..(x + size).min(image.width()) {
+            image.put_pixel(i, j, color);
+        }
+    }
+}
@@ -0,0 +1,236 @@
+#[cfg(feature = "egui")]
+mod gui;
+
+use std::f32::consts::TAU;
+
+use image::RgbImage;
+use tiny_renderer::scene::{ShadowSettings, ToneMapping, Tunables, BUILTIN_PIPELINE_NAMES};
+
+#[cfg(feature = "egui")]
+use self::gui::GuiPanel;
+#[cfg(not(feature = "egui"))]
+use crate::overlay::draw_text;
+use crate::present::Input;
+
+/// Parameter, tweakable from the panel while the app is running.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Setting {
+    Pipeline,
+    LightAzimuth,
+    LightElevation,
+    LightIntensity,
+    Exposure,
+    ToneMapping,
+    RenderScale,
+    ShadowBias,
+    ShadowSlopeBias,
+    PcfRadius,
+}
+
+impl Setting {
+    const ALL: [Setting; 10] = [
+        Setting::Pipeline,
+        Setting::LightAzimuth,
+        Setting::LightElevation,
+        Setting::LightIntensity,
+        Setting::Exposure,
+        Setting::ToneMapping,
+        Setting::RenderScale,
+        Setting::ShadowBias,
+        Setting::ShadowSlopeBias,
+        Setting::PcfRadius,
+    ];
+
+    fn get_name(&self) -> &'static str {
+        match self {
+            Setting::Pipeline => return "pipeline",
+            Setting::LightAzimuth => return "light azimuth",
+            Setting::LightElevation => return "light elevation",
+            Setting::LightIntensity => return "light intensity",
+            Setting::Exposure => return "exposure",
+            Setting::ToneMapping => return "tone mapping",
+            Setting::RenderScale => return "render scale",
+            Setting::ShadowBias => return "shadow bias",
+            Setting::ShadowSlopeBias => return "shadow slope bias",
+            Setting::PcfRadius => return "pcf radius",
+        }
+    }
+}
+
+/// Current values of the settings, applied to the scene by the app whenever one of them changes.
+#[derive(Clone, Copy)]
+pub struct PanelValues {
+    pub pipeline_index: usize, // Index into the list of built-in pipelines.
+    pub light_azimuth: f32,    // Angle of the main light around the vertical axis in radians.
+    pub light_elevation: f32,  // Angle of the main light above the horizon in radians.
+    pub light_intensity: f32,
+    pub exposure: f32,
+    pub tone_mapping: ToneMapping,
+    pub render_scale: u32,
+    pub shadow: ShadowSettings,
+}
+
+impl PanelValues {
+    /// Takes over the values, which the tunables set, keeping the rest.
+    pub fn apply_tunables(&mut self, tunables: &Tunables) {
+        if let Some(pipeline) = &tunables.pipeline {
+            self.pipeline_index = BUILTIN_PIPELINE_NAMES
+                .iter()
+                .position(|name| name == pipeline)
+                .unwrap_or(self.pipeline_index);
+        }
+        if let Some(light_azimuth) = tunables.light_azimuth {
+            self.light_azimuth = light_azimuth.to_radians();
+        }
+        if let Some(light_elevation) = tunables.light_elevation {
+            self.light_elevation = light_elevation.to_radians();
+        }
+        self.light_intensity = tunables.light_intensity.unwrap_or(self.light_intensity);
+        self.exposure = tunables.exposure.unwrap_or(self.exposure);
+        self.tone_mapping = tunables.tone_mapping.unwrap_or(self.tone_mapping);
+        self.render_scale = tunables.render_scale.unwrap_or(self.render_scale);
+        self.shadow.bias = tunables.shadow_bias.unwrap_or(self.shadow.bias);
+        self.shadow.slope_bias = tunables.shadow_slope_bias.unwrap_or(self.shadow.slope_bias);
+        self.shadow.pcf_radius = tunables.pcf_radius.unwrap_or(self.shadow.pcf_radius);
+    }
+}
+
+/// List of settings, drawn over the frame, one of which is selected and adjusted with the arrow keys. With
+/// `egui` feature it's an egui panel, whose widgets also take clicks and drags.
+pub struct Panel {
+    pub visible: bool,
+    selected: usize, // Index of the selected setting.
+    #[cfg(feature = "egui")]
+    gui: GuiPanel,
+}
+
+impl Panel {
+    pub fn new() -> Self {
+        return Panel {
+            visible: false,
+            selected: 0,
+            #[cfg(feature = "egui")]
+            gui: GuiPanel::new(),
+        };
+    }
+
+    /// Draws the panel into the image at the height y, handing it the clicks, drags and releases since the
+    /// last frame. Returns the setting, which they changed, if any.
+    #[cfg(feature = "egui")]
+    pub fn draw(
+        &mut self,
+        image: &mut RgbImage,
+        y: u32,
+        values: &mut PanelValues,
+        pointer_inputs: &[Input],
+    ) -> Option<Setting> {
+        return self
+            .gui
+            .draw(image, y, values, self.selected, pointer_inputs);
+    }
+
+    #[cfg(not(feature = "egui"))]
+    pub fn draw(
+        &mut self,
+        image: &mut RgbImage,
+        y: u32,
+        values: &mut PanelValues,
+        _pointer_inputs: &[Input],
+    ) -> Option<Setting> {
+        draw_text(image, 8, y, 2, &self.get_lines(values));
+        return None;
+    }
+
+    /// Whether the pixel of the frame is covered by the shown panel, which takes the clicks and drags there.
+    #[cfg(feature = "egui")]
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        return self.visible && self.gui.contains(x, y);
+    }
+
+    #[cfg(not(feature = "egui"))]
+    pub fn contains(&self, _x: u32, _y: u32) -> bool {
+        return false;
+    }
+
+    /// Moves the selection by the number of rows, wrapping around.
+    pub fn select(&mut self, rows: i32) {
+        let n_rows = Setting::ALL.len() as i32;
+        self.selected = (self.selected as i32 + rows).rem_euclid(n_rows) as usize;
+    }
+
+    /// Steps the selected setting up or down by one step, returning it, so the app can apply it.
+    pub fn adjust(&self, values: &mut PanelValues, steps: i32) -> Setting {
+        let setting = Setting::ALL[self.selected];
+        let step = steps as f32;
+        match setting {
+            Setting::Pipeline => {
+                let n_pipelines = BUILTIN_PIPELINE_NAMES.len() as i32;
+                values.pipeline_index =
+                    (values.pipeline_index as i32 + steps).rem_euclid(n_pipelines) as usize;
+            }
+            Setting::LightAzimuth => {
+                values.light_azimuth = (values.light_azimuth + 0.1 * step).rem_euclid(TAU);
+            }
+            Setting::LightElevation => {
+                values.light_elevation = (values.light_elevation + 0.1 * step).clamp(-1.5, 1.5);
+            }
+            Setting::LightIntensity => {
+                values.light_intensity = (values.light_intensity + 0.1 * step).max(0.0);
+            }
+            Setting::Exposure => {
+                values.exposure = (values.exposure + 0.1 * step).max(0.0);
+            }
+            Setting::ToneMapping => {
+                let operators = [ToneMapping::Clamp, ToneMapping::Reinhard, ToneMapping::Aces];
+                let index = operators
+                    .iter()
+                    .position(|operator| *operator == values.tone_mapping)
+                    .unwrap_or(0) as i32;
+                values.tone_mapping = operators[(index + steps).rem_euclid(3) as usize];
+            }
+            Setting::RenderScale => {
+                values.render_scale = (values.render_scale as i32 + steps).clamp(1, 4) as u32;
+            }
+            Setting::ShadowBias => {
+                values.shadow.bias = (values.shadow.bias + 0.5 * step).max(0.0);
+            }
+            Setting::ShadowSlopeBias => {
+                values.shadow.slope_bias = (values.shadow.slope_bias + 0.5 * step).max(0.0);
+            }
+            Setting::PcfRadius => {
+                values.shadow.pcf_radius = (values.shadow.pcf_radius as i32 + steps).max(0) as u32;
+            }
+        }
+        return setting;
+    }
+
+    /// Rows of the panel as text, the selected one marked with an arrow.
+    #[cfg(not(feature = "egui"))]
+    pub fn get_lines(&self, values: &PanelValues) -> Vec<String> {
+        return Setting::ALL
+            .iter()
+            .enumerate()
+            .map(|(index, setting)| {
+                let value = match setting {
+                    Setting::Pipeline => BUILTIN_PIPELINE_NAMES[values.pipeline_index].to_string(),
+                    Setting::LightAzimuth => format!(
+                        "{:.0} DEG",
+                        values.light_azimuth.to_degrees().rem_euclid(360.0)
+                    ),
+                    Setting::LightElevation => {
+                        format!("{:.0} DEG", values.light_elevation.to_degrees())
+                    }
+                    Setting::LightIntensity => format!("{:.1}", values.light_intensity),
+                    Setting::Exposure => format!("{:.1}", values.exposure),
+                    Setting::ToneMapping => format!("{:?}", values.tone_mapping),
+                    Setting::RenderScale => format!("{}", values.render_scale),
+                    Setting::ShadowBias => format!("{:.1}", values.shadow.bias),
+                    Setting::ShadowSlopeBias => format!("{:.1}", values.shadow.slope_bias),
+                    Setting::PcfRadius => format!("{}", values.shadow.pcf_radius),
+                };
+                let marker = if index == self.selected { ">" } else { " " };
+                return format!("{} {}: {}", marker, setting.get_name(), value);
+            })
+            .collect();
+    }
+}
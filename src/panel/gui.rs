@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+use egui::epaint::{ColorImage, ImageData, Mesh, Primitive, TextureId};
+use egui::{ComboBox, DragValue, Event, Grid, Pos2, RawInput, Rect, Slider, Ui, Window};
+use image::RgbImage;
+use tiny_renderer::scene::{ToneMapping, BUILTIN_PIPELINE_NAMES};
+
+use super::{PanelValues, Setting};
+use crate::present::Input;
+
+/// Panel of egui, laid out over the frame and painted into it in software, since the presenter only takes
+/// finished frames. Takes the settings from clicks and drags, besides the arrow keys of the panel.
+pub struct GuiPanel {
+    context: egui::Context,
+    textures: HashMap<TextureId, ColorImage>, // Font atlas and any other texture, egui asked to keep.
+    rect: Option<Rect>,                       // Area of the panel in the last frame.
+    start_time: Instant,
+}
+
+impl GuiPanel {
+    pub fn new() -> Self {
+        return GuiPanel {
+            context: egui::Context::default(),
+            textures: HashMap::new(),
+            rect: None,
+            start_time: Instant::now(),
+        };
+    }
+
+    /// Whether the pixel of the frame is covered by the panel, so clicks and drags there are the panel's.
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        let position = Pos2::new(x as f32, y as f32);
+        return self.rect.is_some_and(|rect| rect.contains(position));
+    }
+
+    /// Lays the panel out at the height y, with the selected row marked, taking the pointer inputs since the
+    /// last frame, and paints it into the image. Returns the setting, which was changed, if any.
+    pub fn draw(
+        &mut self,
+        image: &mut RgbImage,
+        y: u32,
+        values: &mut PanelValues,
+        selected: usize,
+        pointer_inputs: &[Input],
+    ) -> Option<Setting> {
+        let screen_size = egui::vec2(image.width() as f32, image.height() as f32);
+        let raw_input = RawInput {
+            screen_rect: Some(Rect::from_min_size(Pos2::ZERO, screen_size)),
+            time: Some(self.start_time.elapsed().as_secs_f64()),
+            events: pointer_inputs.iter().flat_map(to_events).collect(),
+            ..Default::default()
+        };
+        let mut changed = None;
+        let mut rect = None;
+        let output = self.context.run_ui(raw_input, |ui| {
+            let response = Window::new("settings")
+                .fixed_pos([8.0, y as f32])
+                .resizable(false)
+                .collapsible(false)
+                .show(ui.ctx(), |ui| {
+                    Grid::new("settings").num_columns(2).show(ui, |ui| {
+                        for (index, setting) in Setting::ALL.iter().enumerate() {
+                            let marker = if index == selected { ">" } else { " " };
+                            ui.label(format!("{} {}", marker, setting.get_name()));
+                            if add_widget(ui, *setting, values) {
+                                changed = Some(*setting);
+                            }
+                            ui.end_row();
+                        }
+                    });
+                });
+            rect = response.map(|response| response.response.rect);
+        });
+        self.rect = rect;
+        for (id, delta) in output.textures_delta.set {
+            let ImageData::Color(patch) = delta.image;
+            match (delta.pos, self.textures.get_mut(&id)) {
+                (Some([x, y]), Some(texture)) => {
+                    for row in 0..patch.size[1] {
+                        let start = x + (y + row) * texture.size[0];
+                        texture.pixels[start..start + patch.size[0]].copy_from_slice(
+                            &patch.pixels[row * patch.size[0]..(row + 1) * patch.size[0]],
+                        );
+                    }
+                }
+                _ => {
+                    self.textures.insert(id, patch.as_ref().clone());
+                }
+            }
+        }
+        let primitives = self
+            .context
+            .tessellate(output.shapes, output.pixels_per_point);
+        for primitive in primitives {
+            if let Primitive::Mesh(mesh) = &primitive.primitive {
+                if let Some(texture) = self.textures.get(&mesh.texture_id) {
+                    draw_mesh(image, mesh, primitive.clip_rect, texture);
+                }
+            }
+        }
+        for id in output.textures_delta.free {
+            self.textures.remove(&id);
+        }
+        return changed;
+    }
+}
+
+/// Events of egui for the input of the presenter, positions being pixels of the frame.
+fn to_events(input: &Input) -> Vec<Event> {
+    let button = |x: u32, y: u32, pressed: bool| {
+        return Event::PointerButton {
+            pos: Pos2::new(x as f32, y as f32),
+            button: egui::PointerButton::Primary,
+            pressed,
+            modifiers: Default::default(),
+        };
+    };
+    match *input {
+        Input::Clicked(x, y) => {
+            return vec![
+                Event::PointerMoved(Pos2::new(x as f32, y as f32)),
+                button(x, y, true),
+            ];
+        }
+        Input::Dragged(x, y) => return vec![Event::PointerMoved(Pos2::new(x as f32, y as f32))],
+        Input::Released(x, y) => return vec![button(x, y, false)],
+        _ => return Vec::new(),
+    }
+}
+
+/// Adds the widget of the setting to the row, returning whether the user changed the value.
+fn add_widget(ui: &mut Ui, setting: Setting, values: &mut PanelValues) -> bool {
+    match setting {
+        Setting::Pipeline => {
+            let mut changed = false;
+            ComboBox::from_id_salt("pipeline")
+                .selected_text(BUILTIN_PIPELINE_NAMES[values.pipeline_index])
+                .show_ui(ui, |ui| {
+                    for (index, name) in BUILTIN_PIPELINE_NAMES.iter().enumerate() {
+                        changed |= ui
+                            .selectable_value(&mut values.pipeline_index, index, *name)
+                            .changed();
+                    }
+                });
+            return changed;
+        }
+        Setting::LightAzimuth => {
+            let mut degrees = values.light_azimuth.to_degrees();
+            let changed = ui
+                .add(Slider::new(&mut degrees, 0.0..=360.0).suffix(" deg"))
+                .changed();
+            if changed {
+                values.light_azimuth = degrees.to_radians();
+            }
+            return changed;
+        }
+        Setting::LightElevation => {
+            let range = (-1.5f32).to_degrees()..=1.5f32.to_degrees();
+            let mut degrees = values.light_elevation.to_degrees();
+            let changed = ui
+                .add(Slider::new(&mut degrees, range).suffix(" deg"))
+                .changed();
+            if changed {
+                values.light_elevation = degrees.to_radians();
+            }
+            return changed;
+        }
+        Setting::LightIntensity => return add_drag_value(ui, &mut values.light_intensity, 0.01),
+        Setting::Exposure => return add_drag_value(ui, &mut values.exposure, 0.01),
+        Setting::ToneMapping => {
+            let mut changed = false;
+            ComboBox::from_id_salt("tone mapping")
+                .selected_text(format!("{:?}", values.tone_mapping))
+                .show_ui(ui, |ui| {
+                    for operator in [ToneMapping::Clamp, ToneMapping::Reinhard, ToneMapping::Aces] {
+                        let name = format!("{:?}", operator);
+                        changed |= ui
+                            .selectable_value(&mut values.tone_mapping, operator, name)
+                            .changed();
+                    }
+                });
+            return changed;
+        }
+        Setting::RenderScale => {
+            return ui
+                .add(Slider::new(&mut values.render_scale, 1..=4))
+                .changed();
+        }
+        Setting::ShadowBias => return add_drag_value(ui, &mut values.shadow.bias, 0.05),
+        Setting::ShadowSlopeBias => return add_drag_value(ui, &mut values.shadow.slope_bias, 0.05),
+        Setting::PcfRadius => {
+            return ui
+                .add(DragValue::new(&mut values.shadow.pcf_radius).speed(0.05))
+                .changed();
+        }
+    }
+}
+
+/// Non-negative value, changed by dragging across it by the speed per pixel.
+fn add_drag_value(ui: &mut Ui, value: &mut f32, speed: f32) -> bool {
+    let drag_value = DragValue::new(value)
+        .speed(speed)
+        .range(0.0..=f32::INFINITY)
+        .fixed_decimals(2);
+    return ui.add(drag_value).changed();
+}
+
+/// Blends the triangles of the mesh with premultiplied alpha into the image, inside of the clip rectangle.
+/// Pixels are covered by their centers, colors and texture coordinates are interpolated linearly and the
+/// texture is sampled at the nearest texel, which is all the flat shapes and text of the panel need.
+fn draw_mesh(image: &mut RgbImage, mesh: &Mesh, clip_rect: Rect, texture: &ColorImage) {
+    let bounds = clip_rect.intersect(Rect::from_min_size(
+        Pos2::ZERO,
+        egui::vec2(image.width() as f32, image.height() as f32),
+    ));
+    let [texture_width, texture_height] = texture.size;
+    for triangle in mesh.indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|k| mesh.vertices[triangle[k] as usize]);
+        let area = (b.pos - a.pos).x * (c.pos - a.pos).y - (b.pos - a.pos).y * (c.pos - a.pos).x;
+        if area == 0.0 {
+            continue;
+        }
+        let min = a.pos.min(b.pos).min(c.pos).max(bounds.min);
+        let max = a.pos.max(b.pos).max(c.pos).min(bounds.max);
+        if min.x >= max.x || min.y >= max.y {
+            continue;
+        }
+        for y in min.y.floor() as u32..max.y.ceil() as u32 {
+            for x in min.x.floor() as u32..max.x.ceil() as u32 {
+                let p = Pos2::new(x as f32 + 0.5, y as f32 + 0.5);
+                let edge = |u: Pos2, v: Pos2| {
+                    return ((v - u).x * (p - u).y - (v - u).y * (p - u).x) / area;
+                };
+                let weights = [edge(b.pos, c.pos), edge(c.pos, a.pos), edge(a.pos, b.pos)];
+                if weights.iter().any(|weight| *weight < 0.0) {
+                    continue;
+                }
+                let uv = (a.uv.to_vec2() * weights[0]
+                    + b.uv.to_vec2() * weights[1]
+                    + c.uv.to_vec2() * weights[2])
+                    .to_pos2();
+                let texel_x = ((uv.x * texture_width as f32) as usize).min(texture_width - 1);
+                let texel_y = ((uv.y * texture_height as f32) as usize).min(texture_height - 1);
+                let texel = texture.pixels[texel_x + texel_y * texture_width];
+                // Channels of the vertex color times the texel, both premultiplied, in [0, 1].
+                let channel = |k: usize| {
+                    let vertex = weights[0] * a.color[k] as f32
+                        + weights[1] * b.color[k] as f32
+                        + weights[2] * c.color[k] as f32;
+                    return vertex / 255.0 * texel[k] as f32 / 255.0;
+                };
+                let alpha = channel(3);
+                let pixel = image.get_pixel_mut(x, y);
+                for k in 0..3 {
+                    let blended = channel(k) * 255.0 + pixel[k] as f32 * (1.0 - alpha);
+                    pixel[k] = blended.round().clamp(0.0, 255.0) as u8;
+                }
+            }
+        }
+    }
+}
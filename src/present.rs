@@ -0,0 +1,67 @@
+#[cfg(all(feature = "minifb", not(feature = "viewer")))]
+mod minifb_window;
+mod sequence;
+#[cfg(feature = "viewer")]
+mod window;
+
+use std::error::Error;
+
+use image::RgbImage;
+
+#[cfg(all(feature = "minifb", not(feature = "viewer")))]
+pub use self::minifb_window::MinifbPresenter;
+pub use self::sequence::ImageSequence;
+#[cfg(feature = "viewer")]
+pub use self::window::WindowPresenter;
+
+/// Key, the app reacts to, independent of the backend, which presents the frames.
+// Without a window only the exit key is ever pressed.
+#[cfg_attr(not(any(feature = "viewer", feature = "minifb")), allow(dead_code))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Key {
+    Letter(char),  // Lowercase latin letter.
+    Digit(u32),    // Number keys above the letters.
+    Function(u32), // F1 is 1.
+    Tab,
+    Up,
+    Down,
+    Left,
+    Right,
+    Plus,  // Also the equals key, which plus shares on most layouts, and the numpad plus.
+    Minus, // Also the numpad minus.
+    Escape,
+}
+
+/// Modifier keys, held while a key is pressed.
+#[cfg_attr(not(any(feature = "viewer", feature = "minifb")), allow(dead_code))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Modifiers {
+    pub shift: bool,
+}
+
+/// Input of the user, collected by the backend between the frames.
+#[cfg_attr(not(any(feature = "viewer", feature = "minifb")), allow(dead_code))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Input {
+    KeyPressed(Key, Modifiers),
+    KeyReleased(Key),
+    Resized(u32, u32),  // New size of the output in pixels.
+    Clicked(u32, u32),  // Left click at the pixel of the output, from the upper left corner.
+    Dragged(u32, u32),  // Cursor moved to the pixel of the output with the left button held.
+    Released(u32, u32), // Left button released at the pixel of the output.
+}
+
+/// Backend, presenting rendered frames to the user and collecting the user's input.
+pub trait Presenter {
+    /// Shows the frame, replacing the previous one.
+    fn present(&mut self, frame: &RgbImage) -> Result<(), Box<dyn Error>>;
+
+    /// Inputs, which happened since the previous call.
+    fn poll_inputs(&mut self) -> Vec<Input>;
+
+    /// Fixed time between the frames in seconds for backends, which aren't presenting in real time, so motion
+    /// doesn't depend on how long rendering takes.
+    fn get_frame_interval(&self) -> Option<f32> {
+        return None;
+    }
+}
@@ -0,0 +1,128 @@
+use std::error::Error;
+
+use image::RgbImage;
+use minifb::{KeyRepeat, MouseButton, MouseMode, Window, WindowOptions};
+
+use super::{Input, Key, Modifiers, Presenter};
+
+/// Backend, showing frames in a window of minifb, which draws them in software without a GPU, for targets,
+/// where show-image doesn't build.
+pub struct MinifbPresenter {
+    window: Window,
+    buffer: Vec<u32>,     // Frame, converted to 0RGB pixels, as minifb takes them.
+    size: (usize, usize), // Size of the window, reported as resized, when it changes.
+    cursor: Option<(u32, u32)>, // Last cursor position with the left button held.
+}
+
+impl MinifbPresenter {
+    pub fn new(width: u32, height: u32) -> Result<Self, Box<dyn Error>> {
+        let window_options = WindowOptions {
+            resize: true,
+            ..Default::default()
+        };
+        let mut window = Window::new("output", width as usize, height as usize, window_options)?;
+        // App paces itself, while it waits for the frames.
+        window.set_target_fps(0);
+        return Ok(MinifbPresenter {
+            window,
+            buffer: Vec::new(),
+            size: (width as usize, height as usize),
+            cursor: None,
+        });
+    }
+}
+
+impl Presenter for MinifbPresenter {
+    fn present(&mut self, frame: &RgbImage) -> Result<(), Box<dyn Error>> {
+        self.buffer.clear();
+        self.buffer.extend(frame.pixels().map(|pixel| {
+            let [r, g, b] = pixel.0.map(u32::from);
+            return (r << 16) | (g << 8) | b;
+        }));
+        let (width, height) = (frame.width() as usize, frame.height() as usize);
+        self.window
+            .update_with_buffer(&self.buffer, width, height)?;
+        return Ok(());
+    }
+
+    fn poll_inputs(&mut self) -> Vec<Input> {
+        self.window.update();
+        let mut inputs = Vec::new();
+        // Closing the window quits the app the same way the escape key does.
+        if !self.window.is_open() {
+            inputs.push(Input::KeyReleased(Key::Escape));
+            return inputs;
+        }
+        let modifiers = Modifiers {
+            shift: self.window.is_key_down(minifb::Key::LeftShift)
+                || self.window.is_key_down(minifb::Key::RightShift),
+        };
+        for key in self.window.get_keys_pressed(KeyRepeat::No) {
+            if let Some(key) = to_key(key) {
+                inputs.push(Input::KeyPressed(key, modifiers));
+            }
+        }
+        for key in self.window.get_keys_released() {
+            if let Some(key) = to_key(key) {
+                inputs.push(Input::KeyReleased(key));
+            }
+        }
+        let size = self.window.get_size();
+        if size != self.size {
+            self.size = size;
+            inputs.push(Input::Resized(size.0 as u32, size.1 as u32));
+        }
+        let position = self.window.get_mouse_pos(MouseMode::Discard);
+        match (self.window.get_mouse_down(MouseButton::Left), position) {
+            (true, Some((x, y))) => {
+                let position = (x as u32, y as u32);
+                match self.cursor {
+                    None => inputs.push(Input::Clicked(position.0, position.1)),
+                    Some(cursor) if cursor != position => {
+                        inputs.push(Input::Dragged(position.0, position.1))
+                    }
+                    _ => (),
+                }
+                self.cursor = Some(position);
+            }
+            (true, None) => (),
+            (false, _) => {
+                if let Some(cursor) = self.cursor.take() {
+                    let (x, y) = position.map_or(cursor, |(x, y)| (x as u32, y as u32));
+                    inputs.push(Input::Released(x, y));
+                }
+            }
+        }
+        return inputs;
+    }
+}
+
+/// Key of the app for the key of the window, if it's one of the keys the app knows.
+fn to_key(key: minifb::Key) -> Option<Key> {
+    use minifb::Key::*;
+    let letters = [
+        A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    ];
+    if let Some(index) = letters.iter().position(|letter| *letter == key) {
+        return Some(Key::Letter((b'a' + index as u8) as char));
+    }
+    let digits = [Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9];
+    if let Some(index) = digits.iter().position(|digit| *digit == key) {
+        return Some(Key::Digit(index as u32 + 1));
+    }
+    let functions = [F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12];
+    if let Some(index) = functions.iter().position(|function| *function == key) {
+        return Some(Key::Function(index as u32 + 1));
+    }
+    match key {
+        Tab => return Some(Key::Tab),
+        Up => return Some(Key::Up),
+        Down => return Some(Key::Down),
+        Left => return Some(Key::Left),
+        Right => return Some(Key::Right),
+        Equal | NumPadPlus => return Some(Key::Plus),
+        Minus | NumPadMinus => return Some(Key::Minus),
+        Escape => return Some(Key::Escape),
+        _ => return None,
+    }
+}
@@ -0,0 +1,62 @@
+use std::error::Error;
+use std::fs;
+
+use image::RgbImage;
+
+use super::{Input, Key, Presenter};
+
+/// Backend without a window, saving every frame as a numbered PNG into a directory, e.g. for making videos or
+/// running where no display is available. Asks the app to exit after the last frame.
+pub struct ImageSequence {
+    directory: String,
+    frame_count: u32,
+    frames_per_second: f32,
+    frame_index: u32, // Index of the next frame to be saved.
+}
+
+impl ImageSequence {
+    /// Parses the sequence from a spec like `frames:120:30`, returning None for malformed specs. Frame rate is
+    /// optional and is 30 by default. Supported forms:
+    /// - directory:frame_count
+    /// - directory:frame_count:frames_per_second
+    pub fn from_spec(spec: &str) -> Option<Self> {
+        let parts: Vec<&str> = spec.split(':').collect();
+        let frames_per_second = match parts.get(2) {
+            Some(part) => part.parse().ok()?,
+            None => 30.0,
+        };
+        if parts.len() < 2 || parts.len() > 3 || parts[0].is_empty() {
+            return None;
+        }
+        return Some(ImageSequence {
+            directory: parts[0].to_string(),
+            frame_count: parts[1].parse().ok()?,
+            frames_per_second,
+            frame_index: 0,
+        });
+    }
+}
+
+impl Presenter for ImageSequence {
+    fn present(&mut self, frame: &RgbImage) -> Result<(), Box<dyn Error>> {
+        if self.frame_index == 0 {
+            fs::create_dir_all(&self.directory)?;
+        }
+        let path = format!("{}/frame_{:05}.png", self.directory, self.frame_index);
+        frame.save(&path)?;
+        println!("saved frame to: {}", path);
+        self.frame_index += 1;
+        return Ok(());
+    }
+
+    fn poll_inputs(&mut self) -> Vec<Input> {
+        if self.frame_index >= self.frame_count {
+            return vec![Input::KeyReleased(Key::Escape)];
+        }
+        return Vec::new();
+    }
+
+    fn get_frame_interval(&self) -> Option<f32> {
+        return Some(1.0 / self.frames_per_second);
+    }
+}
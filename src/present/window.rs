@@ -0,0 +1,119 @@
+use std::error::Error;
+use std::sync::mpsc::Receiver;
+
+use image::RgbImage;
+use show_image::event::{MouseButton, VirtualKeyCode, WindowEvent};
+use show_image::{create_window, ImageInfo, ImageView, WindowOptions, WindowProxy};
+
+use super::{Input, Key, Modifiers, Presenter};
+
+/// Backend, showing frames in a window of show-image.
+pub struct WindowPresenter {
+    window: WindowProxy,
+    event_channel: Receiver<WindowEvent>,
+}
+
+impl WindowPresenter {
+    pub fn new(width: u32, height: u32) -> Result<Self, Box<dyn Error>> {
+        // Panning and zooming with the mouse would move the frame away from the pixels, which clicks and drags
+        // are reported at.
+        let window_options = WindowOptions {
+            size: Some([width, height]),
+            default_controls: false,
+            ..Default::default()
+        };
+        let window = create_window("output", window_options)?;
+        let event_channel = window.event_channel()?;
+        return Ok(WindowPresenter {
+            window,
+            event_channel,
+        });
+    }
+}
+
+impl Presenter for WindowPresenter {
+    fn present(&mut self, frame: &RgbImage) -> Result<(), Box<dyn Error>> {
+        let image_view = ImageView::new(
+            ImageInfo::rgb8(frame.width(), frame.height()),
+            frame.as_raw(),
+        );
+        self.window.set_image("image", image_view)?;
+        return Ok(());
+    }
+
+    fn poll_inputs(&mut self) -> Vec<Input> {
+        let mut inputs = Vec::new();
+        // Unloading all the garbage from event channel, that has piled up, looking for actionable events.
+        for window_event in self.event_channel.try_iter() {
+            match window_event {
+                WindowEvent::KeyboardInput(event) => {
+                    let key = match event.input.key_code.and_then(to_key) {
+                        Some(key) => key,
+                        None => continue,
+                    };
+                    if event.input.state.is_pressed() {
+                        let modifiers = Modifiers {
+                            shift: event.input.modifiers.shift(),
+                        };
+                        inputs.push(Input::KeyPressed(key, modifiers));
+                    }
+                    if event.input.state.is_released() {
+                        inputs.push(Input::KeyReleased(key));
+                    }
+                }
+                WindowEvent::Resized(event) => {
+                    inputs.push(Input::Resized(event.size.x, event.size.y));
+                }
+                WindowEvent::MouseButton(event)
+                    if event.button == MouseButton::Left && event.state.is_pressed() =>
+                {
+                    // Frames are rendered at the pixel size of the window, so positions match the pixels.
+                    let (x, y) = (event.position.x.max(0.0), event.position.y.max(0.0));
+                    inputs.push(Input::Clicked(x as u32, y as u32));
+                }
+                WindowEvent::MouseButton(event)
+                    if event.button == MouseButton::Left && event.state.is_released() =>
+                {
+                    let (x, y) = (event.position.x.max(0.0), event.position.y.max(0.0));
+                    inputs.push(Input::Released(x as u32, y as u32));
+                }
+                WindowEvent::MouseMove(event) if event.buttons.is_pressed(MouseButton::Left) => {
+                    let (x, y) = (event.position.x.max(0.0), event.position.y.max(0.0));
+                    inputs.push(Input::Dragged(x as u32, y as u32));
+                }
+                _ => (),
+            }
+        }
+        return inputs;
+    }
+}
+
+/// Key of the app for the key code of the window, if it's one of the keys the app knows.
+fn to_key(key_code: VirtualKeyCode) -> Option<Key> {
+    use VirtualKeyCode::*;
+    let letters = [
+        A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    ];
+    if let Some(index) = letters.iter().position(|key| *key == key_code) {
+        return Some(Key::Letter((b'a' + index as u8) as char));
+    }
+    let digits = [Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9];
+    if let Some(index) = digits.iter().position(|key| *key == key_code) {
+        return Some(Key::Digit(index as u32 + 1));
+    }
+    let functions = [F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12];
+    if let Some(index) = functions.iter().position(|key| *key == key_code) {
+        return Some(Key::Function(index as u32 + 1));
+    }
+    match key_code {
+        Tab => return Some(Key::Tab),
+        Up => return Some(Key::Up),
+        Down => return Some(Key::Down),
+        Left => return Some(Key::Left),
+        Right => return Some(Key::Right),
+        Plus | Equals | NumpadAdd => return Some(Key::Plus),
+        Minus | NumpadSubtract => return Some(Key::Minus),
+        Escape => return Some(Key::Escape),
+        _ => return None,
+    }
+}
@@ -0,0 +1,135 @@
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender};
+use std::thread::{self, JoinHandle};
+use std::time;
+
+use image::RgbImage;
+use tiny_renderer::scene::{AovExport, FrameStats, RendererError, Scene};
+
+use crate::app::SECURITY_CAMERA_TARGET;
+
+/// Change of the scene, applied by the render thread right before it renders the requested frame.
+pub type SceneUpdate = Box<dyn FnOnce(&mut Scene) -> Result<(), RendererError> + Send>;
+
+/// Settings of the part of the frame right of the divider, which is rendered as a second frame and spliced
+/// into the first one, so two pipelines or parameter sets are compared side by side.
+pub struct SplitFrame {
+    pub apply: SceneUpdate, // Switches the scene to the settings of the right part.
+    pub restore: SceneUpdate, // Switches the scene back to the settings of the left part.
+    pub divider: f32,       // Position of the divider as a fraction of the frame width.
+}
+
+/// Update of the scene for the requested frame and the settings of its right part, if it's split.
+type FrameRequest = (SceneUpdate, Option<SplitFrame>);
+
+/// Frame, rendered by the render thread, with what the app shows about it.
+pub struct RenderedFrame {
+    pub image: RgbImage,
+    pub inset: Option<RgbImage>, // Frame of the security camera, shown in the corner, if there is one.
+    pub stats: FrameStats,
+    pub render_time: f32, // Time of Scene::render in seconds, both renders of a split frame included.
+    pub reference_error: Option<f32>,
+}
+
+/// Thread, owning the scene and rendering a frame for every request, so the app keeps handling the window,
+/// while a frame renders. Frames are handed out as copies, so the presented frame stays intact, while the
+/// next one renders into the buffers of the scene.
+pub struct RenderThread {
+    requests: Option<SyncSender<FrameRequest>>,
+    frames: Receiver<Result<RenderedFrame, RendererError>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl RenderThread {
+    /// Moves the scene onto a new thread, saving output variables of every frame, if set.
+    pub fn spawn(mut scene: Scene, aov_export: Option<AovExport>) -> Self {
+        let (requests, request_receiver) = mpsc::sync_channel::<FrameRequest>(1);
+        let (frame_sender, frames) = mpsc::sync_channel(1);
+        let thread = thread::spawn(move || {
+            // Running until the app drops its end of the requests.
+            for (frame_index, (update, split)) in request_receiver.into_iter().enumerate() {
+                let frame =
+                    render_frame(&mut scene, update, split, &aov_export, frame_index as u32);
+                if frame_sender.send(frame).is_err() {
+                    return;
+                }
+            }
+        });
+        return Self {
+            requests: Some(requests),
+            frames,
+            thread: Some(thread),
+        };
+    }
+
+    /// Asks for the next frame, rendered after applying the update to the scene, with the right part rendered
+    /// with other settings, if it's split.
+    pub fn request_frame(&self, update: SceneUpdate, split: Option<SplitFrame>) {
+        // Thread only stops with a panic, which joining it reports.
+        let _ = self.requests.as_ref().unwrap().send((update, split));
+    }
+
+    /// Requested frame, waiting for it at most the timeout, None if it isn't ready by then.
+    pub fn wait_frame(
+        &self,
+        timeout: time::Duration,
+    ) -> Result<Option<RenderedFrame>, RendererError> {
+        match self.frames.recv_timeout(timeout) {
+            Ok(frame) => return frame.map(Some),
+            Err(RecvTimeoutError::Timeout) => return Ok(None),
+            Err(RecvTimeoutError::Disconnected) => panic!("Render thread has stopped!"),
+        }
+    }
+}
+
+impl Drop for RenderThread {
+    fn drop(&mut self) {
+        // Letting the thread finish the frame it renders, so output variables aren't left half written.
+        self.requests = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn render_frame(
+    scene: &mut Scene,
+    update: SceneUpdate,
+    split: Option<SplitFrame>,
+    aov_export: &Option<AovExport>,
+    frame_index: u32,
+) -> Result<RenderedFrame, RendererError> {
+    update(scene)?;
+    // Clearing z-buffer and resetting rendered data to (0, 0, 0).
+    scene.clear();
+    let render_begin_time = time::Instant::now();
+    scene.render();
+    let mut render_time = render_begin_time.elapsed().as_secs_f32();
+    if let Some(aov_export) = aov_export {
+        aov_export.save_frame(scene, frame_index)?;
+    }
+    // Stats and output variables are the ones of the left part.
+    let mut image = scene.get_frame_buffer();
+    let stats = scene.get_stats();
+    if let Some(split) = split {
+        (split.apply)(scene)?;
+        scene.clear();
+        let render_begin_time = time::Instant::now();
+        scene.render();
+        render_time += render_begin_time.elapsed().as_secs_f32();
+        let right = scene.get_frame_buffer();
+        (split.restore)(scene)?;
+        let divider = (split.divider.clamp(0.0, 1.0) * image.width() as f32) as u32;
+        for y in 0..image.height() {
+            for x in divider..image.width() {
+                image.put_pixel(x, y, *right.get_pixel(x, y));
+            }
+        }
+    }
+    return Ok(RenderedFrame {
+        image,
+        inset: scene.get_render_target_image(SECURITY_CAMERA_TARGET),
+        stats,
+        render_time,
+        reference_error: scene.get_reference_error(),
+    });
+}
@@ -1,23 +1,160 @@
+//! Scene, holding the model and everything it is rendered with, together with the built-in shader pipelines,
+//! rasterizer and post-processing.
+
 // @TODO similarly to shader.rs crate, this crate, which is closely coupled to it is also hot garbage,
 // requiring some refactoring.
 
+mod aov;
+mod asset;
+mod background;
+mod bloom;
+mod camera;
+mod debug;
+mod deferred;
+mod displacement;
+mod dither;
+mod dof;
+mod error;
+mod fog;
+mod frame_graph;
+mod frustum;
+mod gltf;
+mod graph;
+mod hiz;
+mod ibl;
+mod instance;
+mod json;
+mod light;
+mod lod;
+mod material;
+mod motion_blur;
+mod normals;
+mod picking;
+mod pipeline;
+mod ply;
+mod post;
+mod projection;
+mod raytrace;
 mod shader;
+mod shadow;
+mod skin;
+mod ssao;
+mod stats;
+mod stl;
+mod taa;
+mod tangent;
+mod target;
+mod texture;
+mod timeline;
+mod tonemap;
+mod triangulate;
+mod tunables;
 mod util;
 
-use self::shader::ShaderPipeline;
-use util::Model;
+use self::debug::normalize_depth;
+use self::deferred::{apply_deferred_lighting, GBufferTexel};
+use self::displacement::get_displacement_vertices;
+use self::fog::{apply_fog, get_view_depth};
+use self::frame_graph::FrameGraph;
+use self::frustum::{Containment, Frustum};
+use self::hiz::HiZ;
+use self::instance::get_drawn_instances;
+use self::light::Gobo;
+use self::lod::{generate_lods, select_lod};
+use self::picking::IdTexel;
+use self::projection::PROJECTION_DISTANCE;
+use self::raytrace::{Bvh, BvhTriangle, ReferenceTracer, REFERENCE_DIFF_SCALE};
+use self::shader::{
+    default_prepare, early_depth_test, get_camera_basis, store_vertex_uvs, BuiltinPipeline,
+};
+use self::shadow::{CascadeView, CascadedShadowMap, CubeShadowMap};
+use self::stats::StageTimer;
+use self::taa::{get_jitter, TaaHistory};
+use self::tangent::generate_tangents;
+use self::texture::{encode_srgb, TexCoord, Texture};
+use util::{get_constant_texture, get_flat_normal_texture};
 
-use std::{
-    cmp::{max, min},
-    thread::available_parallelism,
+pub use self::aov::{save_aov, Aov, AovExport, AovFormat};
+pub use self::asset::{
+    find_image, load_cube_map, load_image, load_mtl_maps, load_obj, load_obj_with_colors,
+    load_palette, ColoredMesh, IMAGE_EXTENSIONS,
+};
+pub use self::background::Background;
+pub use self::bloom::BloomSettings;
+pub use self::camera::Camera;
+pub use self::debug::DebugView;
+pub use self::displacement::subdivide;
+pub use self::dither::{Dithering, Palette};
+pub use self::dof::DofSettings;
+pub use self::error::RendererError;
+pub use self::fog::FogSettings;
+pub use self::frame_graph::{FramePass, FrameResource};
+pub use self::frustum::BoundingSphere;
+pub use self::gltf::{load_gltf, GltfModel};
+pub use self::graph::{SceneGraph, SceneNode};
+pub use self::ibl::ImageBasedLighting;
+pub use self::instance::{Instance, InstanceGrid};
+pub use self::light::{Attenuation, Light};
+pub use self::material::Material;
+pub use self::motion_blur::MotionBlurSettings;
+pub use self::normals::{generate_normals, NormalGeneration};
+pub use self::picking::PickHit;
+pub use self::pipeline::{ShaderPipeline, ShaderVaryings};
+pub use self::ply::load_ply;
+pub use self::post::{BuiltinPostPass, PostFrame, PostPass};
+pub use self::projection::Projection;
+pub use self::raytrace::ReferenceMode;
+pub use self::shader::{
+    AlphaMode, AlphaSettings, CullMode, DepthTarget, ShadowSettings, StencilState, StencilTest,
+    Targets, Uniforms, BUILTIN_PIPELINE_NAMES,
 };
+pub use self::skin::Skin;
+pub use self::ssao::SsaoSettings;
+pub use self::stats::FrameStats;
+pub use self::stl::load_stl;
+pub use self::taa::TaaSettings;
+pub use self::target::{RenderTarget, TargetView};
+pub use self::texture::{CubeMap, Filter, NormalMapConvention, Wrap};
+pub use self::timeline::Timeline;
+pub use self::tonemap::ToneMapping;
+pub use self::triangulate::triangulate;
+pub use self::tunables::Tunables;
+pub use self::util::{Model, Shading, TextureKind};
+pub use tiny_renderer_core::raster::{to_raster_coord, RenderMode, SUBPIXEL_BITS};
 
-use image::{ImageBuffer, Rgb, RgbImage};
-use na::{vector, Matrix2x3, Vector2, Vector3};
+use std::cmp::{max, min};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use image::{ImageBuffer, Rgb, Rgb32FImage, RgbImage};
+use na::{point, vector, Matrix2x3, Matrix4, Point3, Vector2, Vector3};
 use nalgebra as na;
 use obj::raw::object::Polygon;
 use obj::raw::RawObj;
-use threadpool::ThreadPool;
+use rayon::prelude::*;
+use tiny_renderer_core::raster::{
+    blend_over, get_barycentric_derivatives, get_sample_offsets, get_triangle_bounding_box,
+    rasterize_line, rasterize_row_multisampled, to_barycentric_coord_at, to_pixel, Coverage, Edges,
+    BLOCK_SIZE, MAX_SAMPLES, SUBPIXEL_SCALE,
+};
+
+/// Side of the square bins, which polygons are sorted into before rasterization. A row of bins makes a tile,
+/// spanning the whole width of the frame, so each tile owns a contiguous slice of the frame buffer and depth
+/// buffers, and its bins are rasterized one after another from left to right.
+const BIN_SIZE: u32 = 64;
+
+/// Object safe counterpart of ShaderPipeline, so that scene can store pipelines regardless of their varyings
+/// type, while the render loop itself is still generic over the pipeline.
+trait ErasedPipeline: Send + Sync {
+    fn render_passes(&self, scene: &mut Scene);
+}
+
+impl<P: ShaderPipeline> ErasedPipeline for P {
+    fn render_passes(&self, scene: &mut Scene) {
+        scene.render_pipeline(self);
+    }
+}
 
 /// Scene, holding its width, height and private flat array(vec) of pixel data,
 /// showing the rendered image.
@@ -25,115 +162,535 @@ use threadpool::ThreadPool;
 pub struct Scene {
     width: u32,
     height: u32,
+    // Supersampling factor - frame is rendered at render_scale times the resolution along each axis and
+    // downsampled back, when requested.
+    render_scale: u32,
+    // Number of depth and color samples per pixel for multisampling, fragment shader still runs once per pixel.
+    msaa_samples: u32,
     model: Model,
-    // Pipeline, specifying vertex and fragment shaders
-    shader_pipeline: ShaderPipeline,
-    // Lighting and camera settings.
+    // Hierarchy of nodes, which copies of the model are drawn at, and the copies with the transforms of the
+    // nodes composed from the root by the last render, sharing the geometry of the model. Model is drawn once
+    // as it is, when there are none.
+    graph: SceneGraph,
+    instances: Vec<Instance>,
+    // Level of detail every instance was drawn with by the last render, for picking.
+    instance_lods: Vec<usize>,
+    // Green channel convention of the tangent space normal map, applied to every map set.
+    normal_map_convention: NormalMapConvention,
+    // Whether colors are decoded to linear before shading and encoded back to sRGB in the output.
+    gamma_correction: bool,
+    // Operator, mapping high dynamic range colors to the displayable range, and the scale applied before it.
+    tone_mapping: ToneMapping,
+    exposure: f32,
+    // Whether polygons are filled or drawn as edges or vertices.
+    render_mode: RenderMode,
+    // Buffer, returned by get_frame_buffer in place of the shaded frame.
+    debug_view: DebugView,
+    // Fill of the frame behind the model, unless there is a skybox.
+    background: Background,
+    // Colors, which the displayed frame is quantized to, if limited, and how the error is dithered.
+    palette: Option<Palette>,
+    dithering: Dithering,
+    // Whether the model is shaded by the deferred path instead of the pipeline, lighting a G-buffer of the
+    // closest surface of every pixel.
+    deferred: bool,
+    // Whether the frame is ray traced instead of rasterized, or compared to the ray traced one, and the mean
+    // absolute difference per channel, found by the last comparison.
+    reference_mode: ReferenceMode,
+    reference_error: Option<f32>,
+    // Fog, blended over the covered samples after all the passes, if enabled.
+    fog: Option<FogSettings>,
+    // Screen-space ambient occlusion, applied after all the passes, if enabled.
+    ssao: Option<SsaoSettings>,
+    // Blur away from the focal plane, applied after ambient occlusion, if enabled.
+    dof: Option<DofSettings>,
+    // Blur along the motion of the pixels since the previous frame, applied after depth of field, if enabled,
+    // and the camera transform of the previous frame, it is found from.
+    motion_blur: Option<MotionBlurSettings>,
+    previous_vpmv_matrix: Option<Matrix4<f32>>,
+    // Temporal anti-aliasing, blending frames with jittered projection into the history before the effects,
+    // if enabled, and the number of the frame, picking its jitter.
+    taa: Option<TaaSettings>,
+    taa_history: TaaHistory,
+    frame_index: u32,
+    camera_moved: bool, // Whether the camera moved since the previous frame, so the history is rejected.
+    // Glow around overexposed pixels, applied after motion blur, if enabled.
+    bloom: Option<BloomSettings>,
+    // User effects, applied after the built-in ones in the order they were added.
+    post_passes: Vec<Box<dyn PostPass>>,
+    // Passes of the frame, left out of the frame graph, whatever the settings, e.g. for comparing the frame
+    // without them.
+    disabled_passes: HashSet<FramePass>,
+    // Pipeline, specifying vertex and fragment shaders, and constants shared by its stages.
+    shader_pipeline: Arc<dyn ErasedPipeline>,
+    uniforms: Uniforms,
+    // Lighting and camera settings. Main light is directional and is the one casting shadows.
     light_direction: Vector3<f32>,
+    light_intensity: f32,
+    lights: Vec<Light>,
     look_from: Vector3<f32>,
     look_at: Vector3<f32>,
     up: Vector3<f32>,
-    // u8 version of z-buffer directly passed to image_show.
-    depth_data: Vec<u8>,
+    projection: Projection,
+    // Fat buffers, written to by the fragment shaders.
+    z_buffer: Vec<f32>,
+    shadow_buffer: Vec<f32>,
+    // Stencil value of every pixel, shared by its samples when multisampling.
+    stencil_buffer: Vec<u8>,
     // Storing flat array.
-    frame_buffer: Vec<u8>,
-    // Threadpool for multi-threaded fragment shader execution.
-    thread_pool: ThreadPool,
+    frame_buffer: Vec<f32>,
+    // Color of every sample, when multisampling, resolved into the frame buffer at the end of the render.
+    sample_buffer: Vec<f32>,
+    // Surface attributes of every pixel, only allocated for the deferred path.
+    gbuffer: Vec<GBufferTexel>,
+    // Polygon, drawn at every pixel, only allocated when picking is enabled.
+    picking: bool,
+    id_buffer: Vec<IdTexel>,
+    // Motion of every pixel on the screen since the previous frame, only allocated for motion blur.
+    velocity_buffer: Vec<Vector2<f32>>,
+    // Work done by the last render.
+    stats: FrameStats,
+    clear_time: Duration, // Time of the last clear, reported with the render after it.
 }
 
 impl Scene {
     /// Generates new Scene struct with specified width and height.
-    /// Pixel data format is assumed to be rgb8.
+    /// Pixel data format is assumed to be rgb8, maps, which asset doesn't have, may be None and get fallbacks.
+    /// Fails for pipeline names, which aren't built-in.
     pub fn new(
         width: u32,
         height: u32,
-        obj: RawObj,
-        texture: RgbImage,
-        normal_map: RgbImage,
-        normal_map_tangent: RgbImage,
-        specular_map: RgbImage,
+        mut obj: RawObj,
+        texture: Option<RgbImage>,
+        normal_map: Option<RgbImage>,
+        normal_map_tangent: Option<RgbImage>,
+        specular_map: Option<RgbImage>,
         shader_pipeline_name: String,
-    ) -> Self {
+    ) -> Result<Self, RendererError> {
+        check_builtin_pipeline_name(&shader_pipeline_name)?;
+        // Missing maps are replaced by a white texture, unperturbed normals and the sharpest specular exponent,
+        // which leaves a barely visible highlight.
+        let mut diffuse_texture = texture.map_or_else(|| get_constant_texture(255), Texture::new);
+        // Diffuse colors are authored in sRGB, while the rest of the maps store plain data.
+        diffuse_texture.srgb = true;
+        let bounding_sphere = BoundingSphere::from_points(
+            obj.positions
+                .iter()
+                .map(|position| point![position.0, position.1, position.2]),
+        );
+        // Shaders expect every polygon to be a triangle with positions, uvs and normals.
+        triangulate(&mut obj);
+        generate_normals(&mut obj, NormalGeneration::Auto);
+        let tangents = generate_tangents(&obj, &obj.polygons);
+        let displacement_vertices = get_displacement_vertices(&obj);
         let model = Model {
             obj,
-            texture,
-            normal_map,
-            normal_map_tangent,
-            specular_map,
+            texture: diffuse_texture,
+            has_normal_map: normal_map.is_some(),
+            normal_map: normal_map.map_or_else(get_flat_normal_texture, Texture::new),
+            normal_map_tangent: normal_map_tangent
+                .map_or_else(get_flat_normal_texture, Texture::new),
+            specular_map: specular_map.map_or_else(|| get_constant_texture(255), Texture::new),
+            // Dielectric surface of medium roughness without any occlusion.
+            metallic_map: get_constant_texture(0),
+            roughness_map: get_constant_texture(128),
+            ao_map: get_constant_texture(255),
+            alpha_map: get_constant_texture(255),
+            displacement_map: get_constant_texture(0),
+            displacement_scale: 0.0,
+            displacement_vertices,
+            bounding_sphere,
+            skin: None,
+            instance: None,
+            lods: Vec::new(),
+            lod: 0,
+            tangents,
+            shading: Shading::default(),
+            vertex_colors: Vec::new(),
         };
         let frame_buffer_size = (width * height) as usize;
-        let shader_pipeline = ShaderPipeline::new(shader_pipeline_name, width, height);
+        let render_scale = 1;
+        let msaa_samples = 1;
+        let shader_pipeline = Arc::new(BuiltinPipeline::new(shader_pipeline_name));
         let light_direction = vector![0.0, 0.0, -1.0];
         let look_from = vector![0.0, 0.0, 1.0];
         let look_at = vector![0.0, 0.0, 0.0];
         let up = vector![0.0, 1.0, 0.0];
-        let depth_data: Vec<u8> = vec![0; 3 * frame_buffer_size];
-        let frame_buffer: Vec<u8> = vec![0; 3 * frame_buffer_size];
-        let n_threads = available_parallelism().unwrap().get();
-        println!("scene is creating thread pool with {} threads", n_threads);
-        let thread_pool = ThreadPool::new(n_threads);
-        return Scene {
+        let z_buffer: Vec<f32> = vec![f32::MIN; frame_buffer_size];
+        let shadow_buffer: Vec<f32> = vec![f32::MIN; frame_buffer_size];
+        let frame_buffer: Vec<f32> = vec![0.0; 3 * frame_buffer_size];
+        println!(
+            "scene is rendering tiles on {} threads",
+            rayon::current_num_threads()
+        );
+        return Ok(Scene {
             width,
             height,
+            render_scale,
+            msaa_samples,
             model,
+            graph: SceneGraph::default(),
+            instances: Vec::new(),
+            instance_lods: Vec::new(),
+            normal_map_convention: NormalMapConvention::default(),
+            gamma_correction: true,
+            tone_mapping: ToneMapping::default(),
+            exposure: 1.0,
+            render_mode: RenderMode::default(),
+            debug_view: DebugView::default(),
+            background: Background::default(),
+            palette: None,
+            dithering: Dithering::default(),
+            deferred: false,
+            reference_mode: ReferenceMode::default(),
+            reference_error: None,
+            fog: None,
+            ssao: None,
+            dof: None,
+            motion_blur: None,
+            previous_vpmv_matrix: None,
+            taa: None,
+            taa_history: TaaHistory::default(),
+            frame_index: 0,
+            camera_moved: false,
+            bloom: None,
+            post_passes: Vec::new(),
+            disabled_passes: HashSet::new(),
             shader_pipeline,
+            uniforms: Uniforms::default(),
             light_direction,
+            light_intensity: 1.0,
+            lights: Vec::new(),
             look_from,
             look_at,
             up,
-            depth_data,
+            projection: Projection::default(),
+            z_buffer,
+            shadow_buffer,
+            stencil_buffer: vec![0; frame_buffer_size],
             frame_buffer,
-            thread_pool,
+            sample_buffer: Vec::new(),
+            gbuffer: Vec::new(),
+            picking: false,
+            id_buffer: Vec::new(),
+            velocity_buffer: Vec::new(),
+            stats: FrameStats::default(),
+            clear_time: Duration::ZERO,
+        });
+    }
+
+    /// Width of the internal buffers, which are rendered into.
+    fn render_width(&self) -> u32 {
+        return self.width * self.render_scale;
+    }
+
+    /// Height of the internal buffers, which are rendered into.
+    fn render_height(&self) -> u32 {
+        return self.height * self.render_scale;
+    }
+
+    /// Sets size of the output frame in pixels, reallocating all the internal buffers. Projection follows the
+    /// aspect of the new frame.
+    pub fn set_resolution(&mut self, width: u32, height: u32) {
+        assert!(
+            width > 1 && height > 1,
+            "Frame should be at least 2x2 pixels!"
+        );
+        self.width = width;
+        self.height = height;
+        self.allocate_buffers();
+    }
+
+    /// Sets supersampling factor, reallocating all the internal buffers, e.g. 2 renders 4 samples per pixel.
+    pub fn set_render_scale(&mut self, render_scale: u32) {
+        assert!(render_scale > 0, "Render scale should be positive!");
+        self.render_scale = render_scale;
+        self.allocate_buffers();
+    }
+
+    /// Sets number of multisampling samples per pixel (1, 2, 4 or 8), reallocating depth and sample buffers.
+    pub fn set_msaa_samples(&mut self, msaa_samples: u32) {
+        // Validating the number of samples early.
+        get_sample_offsets(msaa_samples);
+        self.msaa_samples = msaa_samples;
+        self.allocate_buffers();
+    }
+
+    /// Allocates all the internal buffers according to the render scale and multisampling settings.
+    fn allocate_buffers(&mut self) {
+        let render_buffer_size = (self.render_width() * self.render_height()) as usize;
+        let samples = self.msaa_samples as usize;
+        self.z_buffer = vec![f32::MIN; samples * render_buffer_size];
+        self.shadow_buffer = vec![f32::MIN; render_buffer_size];
+        self.stencil_buffer = vec![0; render_buffer_size];
+        self.frame_buffer = vec![0.0; 3 * render_buffer_size];
+        self.sample_buffer = match samples {
+            1 => Vec::new(),
+            _ => vec![0.0; 3 * samples * render_buffer_size],
         };
+        self.gbuffer = Vec::new();
+        if self.deferred {
+            self.gbuffer = vec![GBufferTexel::default(); render_buffer_size];
+        }
+        self.id_buffer = Vec::new();
+        if self.picking {
+            self.id_buffer = vec![IdTexel::default(); render_buffer_size];
+        }
+        self.velocity_buffer = Vec::new();
+        if self.motion_blur.is_some() || self.taa.is_some() {
+            self.velocity_buffer = vec![Vector2::zeros(); render_buffer_size];
+        }
+        // Motion since the previous frame and the history are lost with the resolution.
+        self.previous_vpmv_matrix = None;
+        self.taa_history.reset();
+    }
+
+    /// Averages colors of all the samples of each pixel into the frame buffer.
+    fn resolve_samples(&mut self) {
+        let samples = self.msaa_samples as usize;
+        self.frame_buffer
+            .par_chunks_mut(3)
+            .zip(self.sample_buffer.par_chunks(3 * samples))
+            .for_each(|(pixel, pixel_samples)| {
+                for c in 0..3 {
+                    let mut sum = 0.0;
+                    for sample in 0..samples {
+                        sum += pixel_samples[3 * sample + c];
+                    }
+                    pixel[c] = sum / samples as f32;
+                }
+            });
+    }
+
+    /// Box filter, averaging every render_scale x render_scale block of rendered rgb data into a single
+    /// pixel of the output resolution.
+    fn downsample(&self, data: &[f32]) -> Vec<f32> {
+        let scale = self.render_scale as usize;
+        if scale == 1 {
+            return data.to_vec();
+        }
+        let width = self.width as usize;
+        let render_width = self.render_width() as usize;
+        let n_samples = (scale * scale) as f32;
+        let mut output = vec![0.0; 3 * (self.width * self.height) as usize];
+        for y in 0..self.height as usize {
+            for x in 0..width {
+                let mut sum = [0.0; 3];
+                for sy in 0..scale {
+                    for sx in 0..scale {
+                        let index = (x * scale + sx) + (y * scale + sy) * render_width;
+                        for c in 0..3 {
+                            sum[c] += data[3 * index + c];
+                        }
+                    }
+                }
+                for c in 0..3 {
+                    output[3 * (x + y * width) + c] = sum[c] / n_samples;
+                }
+            }
+        }
+        return output;
     }
 
     /// Get rendered scene as a slice of color values of size 3 * (number of pixels).
     /// Flips the image, so (0, 0) is the lower left corner.
+    /// High dynamic range colors are scaled by exposure, tone mapped and, optionally, encoded to sRGB and
+    /// quantized to the palette. With a debug view enabled, returns the chosen buffer instead.
     pub fn get_frame_buffer(&self) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
-        let mut buffer: ImageBuffer<Rgb<u8>, Vec<u8>> =
-            ImageBuffer::from_vec(self.width, self.height, self.frame_buffer.clone()).unwrap();
-        image::imageops::flip_vertical_in_place(&mut buffer);
-        return buffer;
+        match self.debug_view {
+            DebugView::Depth => return self.get_z_buffer(),
+            DebugView::ShadowMap => return self.get_shadow_buffer(),
+            // Debug pipelines output data rather than colors, so it's shown as is.
+            DebugView::Normals | DebugView::Uvs => {
+                return self.to_image(&self.frame_buffer, |value| value.clamp(0.0, 255.0));
+            }
+            DebugView::Color => {}
+        }
+        let mut image = self.to_image(&self.frame_buffer, |value| self.get_display_value(value));
+        if let Some(palette) = &self.palette {
+            palette.apply(self.dithering, &mut image);
+        }
+        return image;
     }
 
-    /// Get image, representing z-buffer values.
-    /// Lazy in a sense, that color data for the image is calculated only if this call is made.
-    pub fn get_z_buffer(&mut self) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
-        for i in 0..self.shader_pipeline.buffer.z_buffer.len() {
-            self.depth_data[3 * i + 0] = self.shader_pipeline.buffer.z_buffer[i] as u8;
-            self.depth_data[3 * i + 1] = self.shader_pipeline.buffer.z_buffer[i] as u8;
-            self.depth_data[3 * i + 2] = self.shader_pipeline.buffer.z_buffer[i] as u8;
+    /// Maps high dynamic range color channel into [0, 255] for display - scaled by exposure, tone mapped and,
+    /// optionally, encoded to sRGB.
+    fn get_display_value(&self, value: f32) -> f32 {
+        let mut value = self.tone_mapping.apply(value / 255.0 * self.exposure);
+        if self.gamma_correction {
+            value = encode_srgb(value);
         }
-        let mut buffer: ImageBuffer<Rgb<u8>, Vec<u8>> =
-            ImageBuffer::from_vec(self.width, self.height, self.depth_data.clone()).unwrap();
-        image::imageops::flip_vertical_in_place(&mut buffer);
-        return buffer;
+        return value * 255.0;
     }
 
-    /// Get image, representing shadow-buffer values.
+    /// Get image, representing z-buffer values, normalized to the depth range of the rendered geometry.
     /// Lazy in a sense, that color data for the image is calculated only if this call is made.
-    pub fn get_shadow_buffer(&mut self) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
-        for i in 0..self.shader_pipeline.buffer.shadow_buffer.len() {
-            self.depth_data[3 * i + 0] = self.shader_pipeline.buffer.shadow_buffer[i] as u8;
-            self.depth_data[3 * i + 1] = self.shader_pipeline.buffer.shadow_buffer[i] as u8;
-            self.depth_data[3 * i + 2] = self.shader_pipeline.buffer.shadow_buffer[i] as u8;
-        }
+    pub fn get_z_buffer(&self) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+        // Showing only the first sample, when multisampling.
+        let data = normalize_depth(&self.z_buffer, self.msaa_samples as usize);
+        return self.to_image(&data, |value| value);
+    }
+
+    /// Get image, representing shadow-buffer values, normalized the same way as the z-buffer.
+    /// Lazy in a sense, that color data for the image is calculated only if this call is made.
+    pub fn get_shadow_buffer(&self) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+        let data = normalize_depth(&self.shadow_buffer, 1);
+        return self.to_image(&data, |value| value);
+    }
+
+    /// Downsamples rgb data of the render resolution, mapping every value into [0, 255] for the output image.
+    /// Flips the image, so (0, 0) is the lower left corner.
+    fn to_image(
+        &self,
+        data: &[f32],
+        map: impl Fn(f32) -> f32 + Sync,
+    ) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+        let data: Vec<u8> = self
+            .downsample(data)
+            .par_iter()
+            .map(|&value| map(value).round() as u8)
+            .collect();
         let mut buffer: ImageBuffer<Rgb<u8>, Vec<u8>> =
-            ImageBuffer::from_vec(self.width, self.height, self.depth_data.clone()).unwrap();
+            ImageBuffer::from_vec(self.width, self.height, data).unwrap();
         image::imageops::flip_vertical_in_place(&mut buffer);
         return buffer;
     }
 
+    /// Get color of the render target, mapped for display the same way as the frame, or None, if there is no
+    /// target with the name. (0, 0) is the lower left corner.
+    pub fn get_render_target_image(&self, name: &str) -> Option<RgbImage> {
+        let target = self.uniforms.render_targets.get(name)?;
+        let (width, height) = (target.get_width(), target.get_height());
+        let mut image = RgbImage::from_fn(width, height, |x, y| {
+            let color = target.sample(vector![
+                (x as f32 + 0.5) / width as f32,
+                (y as f32 + 0.5) / height as f32
+            ]);
+            return Rgb(color
+                .map(|value| self.get_display_value(value).round() as u8)
+                .into());
+        });
+        image::imageops::flip_vertical_in_place(&mut image);
+        return Some(image);
+    }
+
     /// Sets all rendered pixels data to (0, 0, 0) and clears z-buffer.
     pub fn clear(&mut self) {
-        let frame_buffer_size = (self.width * self.height) as usize;
+        let timer = StageTimer::start();
+        let frame_buffer_size = (self.render_width() * self.render_height()) as usize;
         for i in 0..frame_buffer_size {
-            self.shader_pipeline.buffer.z_buffer[i] = f32::MIN;
-            self.shader_pipeline.buffer.shadow_buffer[i] = f32::MIN;
-            self.frame_buffer[3 * i + 0] = 0;
-            self.frame_buffer[3 * i + 1] = 0;
-            self.frame_buffer[3 * i + 2] = 0;
+            self.shadow_buffer[i] = f32::MIN;
+            self.frame_buffer[3 * i + 0] = 0.0;
+            self.frame_buffer[3 * i + 1] = 0.0;
+            self.frame_buffer[3 * i + 2] = 0.0;
         }
+        self.z_buffer.fill(f32::MIN);
+        self.stencil_buffer.fill(0);
+        self.sample_buffer.fill(0.0);
+        self.id_buffer.fill(IdTexel::default());
+        self.velocity_buffer.fill(Vector2::zeros());
+        self.clear_time = timer.elapsed();
+    }
+
+    /// Adding a target of the size in pixels, which the model is rendered into from the camera of the view
+    /// before every frame, replacing the target with the same name. Shaders find it in Uniforms::render_targets
+    /// by the name, targets are rendered in the order of their names and see the ones before them.
+    pub fn add_render_target(&mut self, name: &str, width: u32, height: u32, view: TargetView) {
+        self.uniforms
+            .render_targets
+            .insert(name.to_string(), RenderTarget::new(width, height, view));
+    }
+
+    /// Removing the target with the name, if there is one.
+    pub fn remove_render_target(&mut self, name: &str) {
+        self.uniforms.render_targets.remove(name);
+    }
+
+    /// Target with the name, e.g. to move its camera.
+    pub fn render_target_mut(&mut self, name: &str) -> Option<&mut RenderTarget> {
+        return self.uniforms.render_targets.get_mut(name);
+    }
+
+    /// Replacing the mesh of the model, keeping its textures and settings, e.g. when its file changes on disk.
+    /// Skin, vertex colors and levels of detail belong to the old mesh, so they are removed.
+    pub fn set_model(&mut self, mut obj: RawObj) {
+        triangulate(&mut obj);
+        generate_normals(&mut obj, NormalGeneration::Auto);
+        self.model.tangents = generate_tangents(&obj, &obj.polygons);
+        self.model.displacement_vertices = get_displacement_vertices(&obj);
+        self.model.obj = obj;
+        self.model.skin = None;
+        self.model.vertex_colors = Vec::new();
+        self.model.lods = Vec::new();
+        self.model.lod = 0;
+        self.update_bounding_sphere();
+    }
+
+    /// Setting skeleton, deforming the model, e.g. one loaded together with the mesh from glTF.
+    pub fn set_skin(&mut self, skin: Option<Skin>) {
+        self.model.skin = skin;
+        self.update_bounding_sphere();
+    }
+
+    /// Scaling the heights of the displacement map, which move the vertices along their normals in model units,
+    /// 0 by default, which turns displacement off.
+    pub fn set_displacement_scale(&mut self, displacement_scale: f32) {
+        self.model.displacement_scale = displacement_scale;
+        self.update_bounding_sphere();
+    }
+
+    /// Drawing copies of the model with their own transforms and tints in its place, or the model itself once,
+    /// when the list is empty. Replaces the scene graph with a flat one.
+    pub fn set_instances(&mut self, instances: Vec<Instance>) {
+        self.set_graph(SceneGraph::from_instances(&instances));
+    }
+
+    /// Drawing copies of the model at the nodes of the graph, placed relative to their parents, e.g. a turret on
+    /// a tank or a moon orbiting a planet. Model is drawn once as it is, when no node draws it.
+    pub fn set_graph(&mut self, graph: SceneGraph) {
+        self.graph = graph;
+        self.instances = self.graph.get_instances();
+    }
+
+    /// Mutable access to the scene graph, e.g. for moving its nodes between the frames.
+    pub fn graph_mut(&mut self) -> &mut SceneGraph {
+        return &mut self.graph;
+    }
+
+    /// Simplifying the model into coarser levels of detail, each with about a quarter of the polygons of the
+    /// previous one, which are drawn in place of the model, as it gets smaller on the screen. Returns the number
+    /// of polygons of every level.
+    pub fn generate_lods(&mut self, levels: u32) -> Vec<usize> {
+        self.model.lods = generate_lods(&self.model.obj, levels);
+        for lod in &self.model.lods {
+            for (key, tangent) in generate_tangents(&self.model.obj, lod) {
+                self.model.tangents.entry(key).or_insert(tangent);
+            }
+        }
+        return self.model.lods.iter().map(|lod| lod.len()).collect();
+    }
+
+    /// Bounds of the model as it is, before being moved to the instances.
+    pub fn get_bounding_sphere(&self) -> BoundingSphere {
+        return self.model.bounding_sphere;
+    }
+
+    /// Posing the skin by its animation at the time in seconds, looping the animation, and turning spinning
+    /// nodes of the scene graph.
+    pub fn set_animation_time(&mut self, time: f32) {
+        self.graph.set_time(time);
+        if let Some(skin) = &mut self.model.skin {
+            skin.pose(time);
+            self.update_bounding_sphere();
+        }
+    }
+
+    /// Fitting bounding sphere to the vertices as they are, so culling keeps up with the animation.
+    fn update_bounding_sphere(&mut self) {
+        let model = &self.model;
+        self.model.bounding_sphere = BoundingSphere::from_points(
+            (0..model.obj.positions.len()).map(|index| model.get_vertex_position_at_index(index)),
+        );
     }
 
     /// Settign light parameters for the scene.
@@ -141,6 +698,32 @@ impl Scene {
         self.light_direction = light_direction;
     }
 
+    /// Scaling the main light, 1 by default.
+    pub fn set_light_intensity(&mut self, light_intensity: f32) {
+        self.light_intensity = light_intensity;
+    }
+
+    /// Adding a light source in addition to the main light.
+    pub fn add_light(&mut self, light: Light) {
+        self.lights.push(light);
+    }
+
+    /// Removing all the lights, added in addition to the main light.
+    pub fn clear_lights(&mut self) {
+        self.lights.clear();
+        self.uniforms.light_gobos.clear();
+    }
+
+    /// Setting image, projected by the additional light with the index, e.g. a window frame or foliage, which
+    /// filters the color of the light. Only spot lights project it, across their outer cone.
+    pub fn set_light_gobo(&mut self, index: usize, image: Option<RgbImage>) {
+        let gobos = &mut self.uniforms.light_gobos;
+        if gobos.len() <= index {
+            gobos.resize_with(index + 1, || None);
+        }
+        gobos[index] = image.map(Gobo::new);
+    }
+
     /// Setting camera parameters for the scene,
     pub fn set_camera(&mut self, look_from: Vector3<f32>, look_at: Vector3<f32>, up: Vector3<f32>) {
         self.look_from = look_from;
@@ -148,122 +731,1505 @@ impl Scene {
         self.up = up;
     }
 
-    pub fn render(&mut self) {
-        // Simple local bounding box struct for convenience.
-        #[derive(Debug)]
-        struct BoundingBox {
-            ll: Vector2<i32>, // lower left corner
-            ur: Vector2<i32>, // upper right corner
-        }
-
-        // Helper used to find bounding box of a triangle. Can reach outside of the screen.
-        fn get_triangle_bounding_box(coords: Matrix2x3<i32>) -> BoundingBox {
-            return BoundingBox {
-                ll: vector![
-                    min(min(coords.m11, coords.m12), coords.m13),
-                    min(min(coords.m21, coords.m22), coords.m23)
-                ],
-                ur: vector![
-                    max(max(coords.m11, coords.m12), coords.m13),
-                    max(max(coords.m21, coords.m22), coords.m23)
+    /// Placing the camera at the pose, e.g. one blended between two viewpoints. Field of view of the pose only
+    /// replaces the one of perspective projection.
+    pub fn set_camera_pose(&mut self, camera: Camera) {
+        self.set_camera(camera.position, camera.get_look_at(), camera.get_up());
+        if let Projection::Perspective { fov_y, .. } = &mut self.projection {
+            *fov_y = camera.fov_y;
+        }
+    }
+
+    /// Pose of the camera, as placed by set_camera or set_camera_pose. Field of view is the one of perspective
+    /// projection, the equivalent one of the fixed projection and zero for orthographic projection.
+    pub fn get_camera_pose(&self) -> Camera {
+        let fov_y = match self.projection {
+            Projection::Fixed => 2.0 * (1.0 / PROJECTION_DISTANCE).atan(),
+            Projection::Perspective { fov_y, .. } => fov_y,
+            Projection::Orthographic { .. } => 0.0,
+        };
+        return Camera::look_at(self.look_from, self.look_at, self.up, fov_y);
+    }
+
+    /// Setting perspective projection with vertical field of view in radians, horizontal one following the
+    /// aspect of the frame. Geometry closer than near or farther than far from the camera is culled.
+    pub fn set_perspective(&mut self, fov_y: f32, near: f32, far: f32) {
+        self.projection = Projection::Perspective { fov_y, near, far };
+    }
+
+    /// Setting parallel projection of the width x height rectangle around the camera, e.g for technical
+    /// illustration, where sizes shouldn't depend on the distance.
+    pub fn set_orthographic(&mut self, width: f32, height: f32, near: f32, far: f32) {
+        self.projection = Projection::Orthographic {
+            width,
+            height,
+            near,
+            far,
+        };
+    }
+
+    /// Setting whether polygons are shaded smoothly with vertex normals or as flat facets.
+    pub fn set_shading(&mut self, shading: Shading) {
+        self.model.shading = shading;
+    }
+
+    pub fn get_shading(&self) -> Shading {
+        return self.model.shading;
+    }
+
+    /// Setting colors of the vertices in [0, 1], one per position of the mesh and sRGB encoded, like the diffuse
+    /// texture, which they multiply, e.g. for photogrammetry scans. Empty list removes them.
+    pub fn set_vertex_colors(&mut self, vertex_colors: Vec<Vector3<f32>>) {
+        assert!(
+            vertex_colors.is_empty() || vertex_colors.len() == self.model.obj.positions.len(),
+            "There should be a vertex color for every position of the mesh!"
+        );
+        self.model.vertex_colors = vertex_colors;
+    }
+
+    /// Setting backface culling mode, e.g. to disable culling for double-sided geometry.
+    pub fn set_cull_mode(&mut self, cull_mode: CullMode) {
+        self.uniforms.cull_mode = cull_mode;
+    }
+
+    /// Setting environment, drawn in the background instead of the flat black, reflected by the shiny
+    /// materials and lighting the pbr pipeline through its prefiltered maps, which are computed here.
+    pub fn set_skybox(&mut self, mut skybox: Option<CubeMap>) {
+        if let Some(skybox) = &mut skybox {
+            skybox.set_srgb(self.gamma_correction);
+        }
+        self.uniforms.ibl = skybox.as_ref().map(ImageBasedLighting::new);
+        self.uniforms.environment = skybox;
+    }
+
+    /// Setting color, gradient or image, which the frame is filled with behind the model instead of the flat
+    /// black. Skybox takes precedence over it.
+    pub fn set_background(&mut self, mut background: Background) {
+        background.set_srgb(self.gamma_correction);
+        self.background = background;
+    }
+
+    /// Enabling gamma-correct rendering - color textures, skybox and background are decoded from sRGB, so that
+    /// shading and blending happens in linear space, and the frame buffer is encoded back to sRGB, when it is
+    /// returned.
+    pub fn set_gamma_correction(&mut self, gamma_correction: bool) {
+        self.gamma_correction = gamma_correction;
+        self.model.texture.srgb = gamma_correction;
+        if let Some(environment) = &mut self.uniforms.environment {
+            environment.set_srgb(gamma_correction);
+            // Maps are prefiltered from the decoded environment.
+            self.uniforms.ibl = Some(ImageBasedLighting::new(environment));
+        }
+        self.background.set_srgb(gamma_correction);
+    }
+
+    /// Setting operator, which maps high dynamic range colors to the displayable range.
+    pub fn set_tone_mapping(&mut self, tone_mapping: ToneMapping) {
+        self.tone_mapping = tone_mapping;
+    }
+
+    /// Setting factor, scaling colors before tone mapping.
+    pub fn set_exposure(&mut self, exposure: f32) {
+        self.exposure = exposure;
+    }
+
+    /// Switching between filled, wireframe and point cloud rendering.
+    pub fn set_render_mode(&mut self, render_mode: RenderMode) {
+        self.render_mode = render_mode;
+    }
+
+    pub fn get_render_mode(&self) -> RenderMode {
+        return self.render_mode;
+    }
+
+    /// Switching between shading with the pipeline and the deferred path, which writes surface attributes into a
+    /// G-buffer and lights every pixel once with the specular lighting model. Deferred path ignores the pipeline
+    /// and draws every polygon as opaque.
+    pub fn set_deferred(&mut self, deferred: bool) {
+        self.deferred = deferred;
+        self.allocate_buffers();
+    }
+
+    /// Choosing whether frames are rasterized, ray traced as the ground truth, e.g. for validating shading,
+    /// shadows and perspective, or show the absolute difference between the two.
+    pub fn set_reference_mode(&mut self, reference_mode: ReferenceMode) {
+        self.reference_mode = reference_mode;
+    }
+
+    /// Mean absolute difference per channel in [0, 255] between the rasterized and ray traced frames, found by
+    /// the last render, when comparing them.
+    pub fn get_reference_error(&self) -> Option<f32> {
+        return self.reference_error;
+    }
+
+    /// Enabling or disabling the ID buffer, which records the polygon drawn at every pixel for Scene::pick.
+    pub fn set_picking(&mut self, picking: bool) {
+        self.picking = picking;
+        self.allocate_buffers();
+    }
+
+    /// What the last rendered frame shows at the pixel, (0, 0) being the upper left corner of the image from
+    /// get_frame_buffer. None for the background, translucent polygons and when picking is disabled.
+    pub fn pick(&self, x: u32, y: u32) -> Option<PickHit> {
+        if self.id_buffer.is_empty() || x >= self.width || y >= self.height {
+            return None;
+        }
+        let texel = self.id_buffer[self.get_render_index(x, y)];
+        let polygon = texel.polygon?;
+        let lod = self.instance_lods[texel.instance as usize];
+        let indices = self.get_drawn_polygon(texel.instance, polygon);
+        let mut position = (0..3).fold(Point3::origin(), |position, k| {
+            position
+                + self.model.get_vertex_position_at_index(indices[k].0).coords * texel.bar_coord[k]
+        });
+        if let Some(instance) = self.instances.get(texel.instance as usize) {
+            position = instance.transform_position(position);
+        }
+        return Some(PickHit {
+            object: texel.instance,
+            lod: lod as u32,
+            polygon,
+            bar_coord: texel.bar_coord,
+            position,
+        });
+    }
+
+    /// Index of the rendered pixel at the center of the block of the output pixel, (0, 0) being the upper left
+    /// corner of the image from get_frame_buffer, while rows of the buffers go bottom up.
+    fn get_render_index(&self, x: u32, y: u32) -> usize {
+        let scale = self.render_scale;
+        let render_x = x * scale + scale / 2;
+        let render_y = (self.height - 1 - y) * scale + scale / 2;
+        return (render_x + render_y * self.render_width()) as usize;
+    }
+
+    /// Position, texture and normal indices of the corners of the polygon, as the instance was drawn by the last
+    /// render.
+    fn get_drawn_polygon(&self, instance: u32, polygon: u32) -> &Vec<(usize, usize, usize)> {
+        let lod = self.instance_lods[instance as usize];
+        match &self.model.get_lod_polygons(lod)[polygon as usize] {
+            Polygon::PTN(indices) => return indices,
+            _ => panic!("Encountered some garbage, while looking through polygons."),
+        }
+    }
+
+    /// Output variable of the last rendered frame at the output resolution, taken at the center of the rendered
+    /// block of every pixel, (0, 0) being the upper left corner. Everything but depth comes from the ID buffer,
+    /// so it's None, unless picking is enabled. Translucent polygons are only seen in depth.
+    pub fn get_aov(&self, aov: Aov) -> Option<Rgb32FImage> {
+        if aov != Aov::Depth && self.id_buffer.is_empty() {
+            return None;
+        }
+        let samples = self.msaa_samples as usize;
+        let image = Rgb32FImage::from_fn(self.width, self.height, |x, y| {
+            let index = self.get_render_index(x, y);
+            if aov == Aov::Depth {
+                let depth = match self.z_buffer[index * samples] {
+                    f32::MIN => f32::INFINITY,
+                    z_value => get_view_depth(&self.projection, z_value),
+                };
+                return Rgb([depth; 3]);
+            }
+            let texel = self.id_buffer[index];
+            let polygon = match texel.polygon {
+                Some(polygon) => polygon,
+                None => return Rgb([0.0; 3]),
+            };
+            let indices = self.get_drawn_polygon(texel.instance, polygon);
+            let bar_coord = texel.bar_coord;
+            let value = match aov {
+                Aov::Normal => {
+                    let normals = self.model.get_polygon_normals(
+                        vector![indices[0].0, indices[1].0, indices[2].0],
+                        vector![indices[0].2, indices[1].2, indices[2].2],
+                    );
+                    let normal = (0..3).fold(Vector3::zeros(), |normal, k| {
+                        normal + normals[k] * bar_coord[k]
+                    });
+                    match self.instances.get(texel.instance as usize) {
+                        Some(instance) => instance.transform_normal(normal),
+                        None => normal.normalize(),
+                    }
+                }
+                Aov::Albedo => {
+                    let mut uvs = Matrix2x3::zeros();
+                    store_vertex_uvs(
+                        &mut uvs,
+                        &self.model.obj.tex_coords,
+                        vector![indices[0].1, indices[1].1, indices[2].1],
+                    );
+                    let uv = TexCoord {
+                        uv: uvs * bar_coord,
+                        ..Default::default()
+                    };
+                    let mut color = self.model.get_color_at_uv(uv) / 255.0;
+                    if !self.model.vertex_colors.is_empty() {
+                        let colors = (0..3).map(|k| {
+                            self.model.get_vertex_color_at_index(indices[k].0) * bar_coord[k]
+                        });
+                        color.component_mul_assign(&colors.sum());
+                    }
+                    color
+                }
+                _ => vector![
+                    (texel.instance + 1) as f32,
+                    (polygon + 1) as f32,
+                    self.instance_lods[texel.instance as usize] as f32
                 ],
             };
+            return Rgb(value.into());
+        });
+        return Some(image);
+    }
+
+    /// Limiting the displayed frame to the colors of the palette, with the error of every pixel dithered, or
+    /// showing the full colors, when None.
+    pub fn set_palette(&mut self, palette: Option<Palette>, dithering: Dithering) {
+        self.palette = palette;
+        self.dithering = dithering;
+    }
+
+    /// Choosing the buffer, shown by get_frame_buffer. Normals and uvs are rendered by debug pipelines in
+    /// place of the scene's pipeline and without any effects.
+    pub fn set_debug_view(&mut self, debug_view: DebugView) {
+        self.debug_view = debug_view;
+    }
+
+    /// Enabling or disabling depth-based fog, or changing its color and density.
+    pub fn set_fog(&mut self, fog: Option<FogSettings>) {
+        self.fog = fog;
+    }
+
+    /// Enabling or disabling screen-space ambient occlusion.
+    pub fn set_ssao(&mut self, ssao: Option<SsaoSettings>) {
+        self.ssao = ssao;
+    }
+
+    /// Enabling or disabling depth of field, blurring the frame away from the focal distance.
+    pub fn set_dof(&mut self, dof: Option<DofSettings>) {
+        self.dof = dof;
+    }
+
+    /// Enabling or disabling motion blur, reallocating the velocity buffer, which the passes of the frame write
+    /// the motion of the pixels since the previous frame into. Motion comes from the camera, the instances and
+    /// the skin stay where they are in the previous frame, and the background doesn't move.
+    pub fn set_motion_blur(&mut self, motion_blur: Option<MotionBlurSettings>) {
+        self.motion_blur = motion_blur;
+        self.allocate_buffers();
+    }
+
+    /// Enabling or disabling temporal anti-aliasing, reallocating the velocity buffer, which reprojects the
+    /// history, when the camera moves. Every frame is rendered with a different subpixel offset, so the history
+    /// converges to a smooth image over the frames, while the camera stays still.
+    pub fn set_taa(&mut self, taa: Option<TaaSettings>) {
+        self.taa = taa;
+        self.allocate_buffers();
+    }
+
+    /// Enabling or disabling bloom of the pixels, brighter than the threshold.
+    pub fn set_bloom(&mut self, bloom: Option<BloomSettings>) {
+        self.bloom = bloom;
+    }
+
+    /// Appending effect to the chain of post-processing passes.
+    pub fn add_post_pass(&mut self, post_pass: impl PostPass + 'static) {
+        self.post_passes.push(Box::new(post_pass));
+    }
+
+    /// Removing all the added post-processing passes.
+    pub fn clear_post_passes(&mut self) {
+        self.post_passes.clear();
+    }
+
+    /// Enabling or disabling the pass of the frame, which the settings would otherwise run. Passes after it
+    /// see the resources it writes as they were before it, and passes, which only it needed, are skipped.
+    /// Passes, without which a kept pass has nothing to read, e.g. resolve with multisampling, still run.
+    pub fn set_pass_enabled(&mut self, pass: FramePass, enabled: bool) {
+        match enabled {
+            true => self.disabled_passes.remove(&pass),
+            false => self.disabled_passes.insert(pass),
+        };
+    }
+
+    /// Setting shadow buffer filtering and bias, used by the pipelines with shadows.
+    pub fn set_shadow_settings(&mut self, shadow: ShadowSettings) {
+        self.uniforms.shadow = shadow;
+    }
+
+    /// Lighting coefficients of the object, being the instance with the same index, or the model itself with
+    /// index 0, when it isn't instanced. Objects without a material of their own get the default one, which is
+    /// added here on the first access.
+    pub fn material_mut(&mut self, id: u32) -> &mut Material {
+        let materials = &mut self.uniforms.materials;
+        if materials.len() <= id as usize {
+            materials.resize(id as usize + 1, Material::default());
         }
+        return &mut materials[id as usize];
+    }
 
-        // Getting barycentric coordinates for a point in relation to a rasterized triangle coordinates.
-        fn to_barycentric_coord(
-            internal_point: Vector2<i32>,
-            coords: Matrix2x3<i32>,
-        ) -> Vector3<f32> {
-            let raw_cross = vector![
-                (coords.m12 - coords.m11) as f32,
-                (coords.m13 - coords.m11) as f32,
-                (coords.m11 - internal_point.x) as f32
-            ]
-            .cross(&vector![
-                (coords.m22 - coords.m21) as f32,
-                (coords.m23 - coords.m21) as f32,
-                (coords.m21 - internal_point.y) as f32
-            ]);
-            if raw_cross.z.abs() < 1.0 {
-                // Degenerate triangle, returning something with negative coordinate.
-                return vector![-1.0, 1.0, 1.0];
+    /// Setting alpha mode and opacity of the material.
+    pub fn set_alpha_settings(&mut self, alpha: AlphaSettings) {
+        self.uniforms.alpha = alpha;
+    }
+
+    /// Replacing the pipeline, used for rendering, e.g. with a user-defined one. Buffers and camera settings
+    /// are kept, so this can be done between any two frames.
+    pub fn set_shader_pipeline(&mut self, shader_pipeline: impl ShaderPipeline + 'static) {
+        self.shader_pipeline = Arc::new(shader_pipeline);
+    }
+
+    /// Switching to one of the built-in pipelines by its name, keeping the current one for unknown names.
+    pub fn set_builtin_shader_pipeline(
+        &mut self,
+        shader_pipeline_name: String,
+    ) -> Result<(), RendererError> {
+        check_builtin_pipeline_name(&shader_pipeline_name)?;
+        self.set_shader_pipeline(BuiltinPipeline::new(shader_pipeline_name));
+        return Ok(());
+    }
+
+    /// Replacing one of the model textures, keeping its sampler and color space settings.
+    pub fn set_texture(&mut self, kind: TextureKind, image: RgbImage) {
+        let texture = self.model.get_texture_mut(kind);
+        let sampler = texture.sampler;
+        let srgb = texture.srgb;
+        *texture = Texture::new(image);
+        texture.sampler = sampler;
+        texture.srgb = srgb;
+        if kind == TextureKind::NormalMap {
+            self.model.has_normal_map = true;
+        }
+        if kind == TextureKind::NormalMapTangent {
+            self.set_normal_map_convention(self.normal_map_convention);
+        }
+        if kind == TextureKind::DisplacementMap {
+            self.update_bounding_sphere();
+        }
+    }
+
+    /// Setting convention of the green channel of the tangent space normal map, OpenGL by default. Auto
+    /// guesses it from the map, also for maps set later.
+    pub fn set_normal_map_convention(&mut self, normal_map_convention: NormalMapConvention) {
+        self.normal_map_convention = normal_map_convention;
+        let texture = &mut self.model.normal_map_tangent;
+        texture.flip_green = normal_map_convention.is_green_flipped(&texture.image);
+    }
+
+    /// Setting filtering, used when sampling one of the model textures.
+    pub fn set_texture_filter(&mut self, kind: TextureKind, filter: Filter) {
+        self.model.get_texture_mut(kind).sampler.filter = filter;
+    }
+
+    /// Setting the largest anisotropy, which anisotropic filtering resolves for one of the model textures, 16
+    /// by default.
+    pub fn set_texture_max_anisotropy(&mut self, kind: TextureKind, max_anisotropy: u32) {
+        assert!(max_anisotropy > 0, "Max anisotropy should be positive!");
+        self.model.get_texture_mut(kind).sampler.max_anisotropy = max_anisotropy;
+    }
+
+    /// Setting wrap mode, used for uvs outside of [0, 1] when sampling one of the model textures.
+    pub fn set_texture_wrap(&mut self, kind: TextureKind, wrap: Wrap) {
+        self.model.get_texture_mut(kind).sampler.wrap = wrap;
+    }
+
+    /// Renders cube shadow maps of the positional lights, reusing the ones from the previous frame, when
+    /// resolution is the same.
+    fn render_light_shadow_maps(&mut self) {
+        let resolution = self.uniforms.shadow.cube_resolution;
+        let mut shadow_maps = std::mem::take(&mut self.uniforms.light_shadow_maps);
+        shadow_maps.resize_with(self.lights.len(), || None);
+        for (light, shadow_map) in self.lights.iter().zip(shadow_maps.iter_mut()) {
+            let position = match light.get_position() {
+                Some(position) if resolution > 0 => position,
+                _ => {
+                    *shadow_map = None;
+                    continue;
+                }
+            };
+            if !matches!(shadow_map, Some(map) if map.get_resolution() == resolution) {
+                *shadow_map = Some(CubeShadowMap::new(resolution));
             }
-            return vector![
-                1.0 - (raw_cross.x + raw_cross.y) / raw_cross.z,
-                raw_cross.x / raw_cross.z,
-                raw_cross.y / raw_cross.z
-            ];
+            shadow_map
+                .as_mut()
+                .unwrap()
+                .render(&mut self.model, &self.instances, position);
+        }
+        self.uniforms.light_shadow_maps = shadow_maps;
+    }
+
+    /// Bounds of every drawn instance of the model, or of the model itself, when it isn't instanced.
+    fn get_instance_bounding_spheres(&self) -> Vec<BoundingSphere> {
+        return get_drawn_instances(&self.instances)
+            .iter()
+            .map(|instance| match instance {
+                Some(instance) => instance.transform_bounding_sphere(&self.model.bounding_sphere),
+                None => self.model.bounding_sphere,
+            })
+            .collect();
+    }
+
+    /// Renders cascades of the main light over the view frustum, reusing them from the previous frame, when
+    /// their number and resolution are the same.
+    fn render_cascaded_shadow_map(&mut self) {
+        let shadow = self.uniforms.shadow;
+        if shadow.cascades == 0 {
+            self.uniforms.cascaded_shadow_map = None;
+            return;
         }
+        let mut cascaded_shadow_map = match self.uniforms.cascaded_shadow_map.take() {
+            Some(map)
+                if map.get_count() == shadow.cascades
+                    && map.get_resolution() == shadow.cascade_resolution =>
+            {
+                map
+            }
+            _ => CascadedShadowMap::new(shadow.cascades, shadow.cascade_resolution),
+        };
+        let view = CascadeView {
+            position: self.look_from,
+            basis: get_camera_basis(self.look_from, self.look_at, self.up),
+            projection: self.projection,
+            aspect: self.render_width() as f32 / self.render_height() as f32,
+        };
+        let bounding_sphere = BoundingSphere::from_spheres(&self.get_instance_bounding_spheres());
+        cascaded_shadow_map.render(
+            &mut self.model,
+            &self.instances,
+            &bounding_sphere,
+            self.light_direction,
+            &view,
+        );
+        self.uniforms.cascaded_shadow_map = Some(cascaded_shadow_map);
+    }
 
-        // Applying all passes of the shader pipeline.
-        for pass in &self.shader_pipeline.passes {
-            // Filling the buffer with pass constants.
-            (pass.prepare)(
-                &mut self.shader_pipeline.buffer,
+    /// Moving the gobos with their lights, which may have changed since the last frame.
+    fn update_light_gobos(&mut self) {
+        for (light, gobo) in self.lights.iter().zip(self.uniforms.light_gobos.iter_mut()) {
+            if let Some(gobo) = gobo {
+                gobo.update(light);
+            }
+        }
+    }
+
+    /// Fills every sample, which isn't covered by the model yet, with the environment color in the direction of
+    /// the view ray through the pixel, as if skybox was infinitely far away, or with the background without one.
+    fn render_background(&mut self) {
+        if self.uniforms.environment.is_none() && self.background.is_black() {
+            return;
+        }
+        let width = self.render_width() as usize;
+        let height = self.render_height() as usize;
+        let samples = self.msaa_samples as usize;
+        let (new_x, new_y, new_z) = get_camera_basis(self.look_from, self.look_at, self.up);
+        let projection = self.projection;
+        let aspect = width as f32 / height as f32;
+        let environment = self.uniforms.environment.as_ref();
+        let background = &self.background;
+        let srgb = self.gamma_correction;
+        let color_buffer = match samples {
+            1 => &mut self.frame_buffer,
+            _ => &mut self.sample_buffer,
+        };
+        let z_buffer = &self.z_buffer;
+        color_buffer
+            .par_chunks_mut(3 * samples * width)
+            .enumerate()
+            .for_each(|(y, row)| {
+                for x in 0..width {
+                    let pixel_index = x + y * width;
+                    let mut color = None;
+                    for sample in 0..samples {
+                        if z_buffer[pixel_index * samples + sample] != f32::MIN {
+                            continue;
+                        }
+                        // View rays go from the center of projection through the pixel in normalized device
+                        // coordinates, which is the same for every sample of the pixel.
+                        let color = *color.get_or_insert_with(|| {
+                            let u = 2.0 * x as f32 / (width - 1) as f32 - 1.0;
+                            let v = 2.0 * y as f32 / (height - 1) as f32 - 1.0;
+                            let skybox = match environment {
+                                Some(skybox) => skybox,
+                                None => {
+                                    return background
+                                        .get_color(vector![u, v].add_scalar(1.0) / 2.0, srgb)
+                                }
+                            };
+                            let ray = projection.get_view_ray(u, v, aspect);
+                            let direction = new_x * ray.x + new_y * ray.y + new_z * ray.z;
+                            return skybox.sample_direction(direction);
+                        });
+                        for c in 0..3 {
+                            row[3 * (x * samples + sample) + c] = color[c];
+                        }
+                    }
+                }
+            });
+    }
+
+    /// Renders the model into every render target with the pipeline of the scene, swapping the camera and the
+    /// buffers of the target in for the ones of the frame. Targets skip supersampling, multisampling, picking
+    /// and the deferred path, which only the frame has.
+    fn render_render_targets(&mut self) {
+        let mut names: Vec<String> = self.uniforms.render_targets.keys().cloned().collect();
+        names.sort();
+        for name in names {
+            let mut target = self.uniforms.render_targets.remove(&name).unwrap();
+            let frame_state = (
                 self.width,
                 self.height,
-                self.light_direction,
+                self.render_scale,
+                self.msaa_samples,
                 self.look_from,
                 self.look_at,
                 self.up,
+                self.projection,
             );
-            // Drawing all polygons of the model.
-            for polygon in &self.model.obj.polygons {
-                // Indices are &Vec((usize, usize, usize)), where first item corresponds to indices for
-                // positions, second to indices for texture uv coords and third to indices for normals
-                // which results in a bloated call to vertex shader.
-                let indices: &Vec<(usize, usize, usize)> = match polygon {
-                    Polygon::PTN(indices) => &indices,
+            let size = (target.get_width() * target.get_height()) as usize;
+            let (color, depth) = target.take_buffers();
+            let frame_buffers = (
+                std::mem::replace(&mut self.frame_buffer, color),
+                std::mem::replace(&mut self.z_buffer, depth),
+                std::mem::replace(&mut self.shadow_buffer, vec![f32::MIN; size]),
+                std::mem::replace(&mut self.stencil_buffer, vec![0; size]),
+                std::mem::take(&mut self.sample_buffer),
+                std::mem::take(&mut self.gbuffer),
+                std::mem::take(&mut self.id_buffer),
+                std::mem::take(&mut self.velocity_buffer),
+            );
+            (self.width, self.height) = (target.get_width(), target.get_height());
+            (self.render_scale, self.msaa_samples) = (1, 1);
+            let view = target.view;
+            (self.look_from, self.look_at, self.up) = (view.look_from, view.look_at, view.up);
+            self.projection = view.projection;
+            self.frame_buffer.fill(0.0);
+            self.z_buffer.fill(f32::MIN);
+
+            self.render_background();
+            let shader_pipeline = self.shader_pipeline.clone();
+            shader_pipeline.render_passes(self);
+
+            let color = std::mem::replace(&mut self.frame_buffer, frame_buffers.0);
+            let depth = std::mem::replace(&mut self.z_buffer, frame_buffers.1);
+            target.set_buffers(color, depth);
+            self.shadow_buffer = frame_buffers.2;
+            self.stencil_buffer = frame_buffers.3;
+            self.sample_buffer = frame_buffers.4;
+            self.gbuffer = frame_buffers.5;
+            self.id_buffer = frame_buffers.6;
+            self.velocity_buffer = frame_buffers.7;
+            (
+                self.width,
+                self.height,
+                self.render_scale,
+                self.msaa_samples,
+                self.look_from,
+                self.look_at,
+                self.up,
+                self.projection,
+            ) = frame_state;
+            self.uniforms.render_targets.insert(name, target);
+        }
+    }
+
+    /// Counters of the last render.
+    pub fn get_stats(&self) -> FrameStats {
+        return self.stats;
+    }
+
+    /// Renders the model with every pass of the pipeline.
+    pub fn render(&mut self) {
+        self.stats = FrameStats {
+            clear_time: std::mem::take(&mut self.clear_time),
+            ..Default::default()
+        };
+        self.reference_error = None;
+        self.update_light_gobos();
+        // Walking the graph from the root, composing transforms of the nodes into the copies of the model.
+        self.instances = self.graph.get_instances();
+        // Ray traced frame needs nothing from the rasterizer, effects included.
+        if self.reference_mode == ReferenceMode::Trace {
+            let timer = StageTimer::start();
+            self.frame_buffer = self.trace_reference();
+            self.stats.fragment_time += timer.elapsed();
+            return;
+        }
+        let passes = self.get_frame_graph().schedule().unwrap_or_else(|passes| {
+            panic!(
+                "Passes {:?} of the frame depend on each other in a cycle!",
+                passes
+            )
+        });
+        for pass in passes {
+            let timer = StageTimer::start();
+            self.run_frame_pass(pass);
+            match pass {
+                FramePass::Shadows => self.stats.shadow_time += timer.elapsed(),
+                FramePass::Background | FramePass::DeferredLighting | FramePass::ReferenceDiff => {
+                    self.stats.fragment_time += timer.elapsed()
+                }
+                // Pipeline passes count towards the stages of the frame themselves.
+                FramePass::RenderTargets
+                | FramePass::Camera
+                | FramePass::Geometry
+                | FramePass::DebugView => {}
+                _ => self.stats.post_time += timer.elapsed(),
+            }
+        }
+        // Jitter and the velocity buffer only apply to the passes of the frame, looking from the camera.
+        self.uniforms.previous_vpmv_matrix = None;
+        self.uniforms.jitter = Vector2::zeros();
+    }
+
+    /// Passes of the frame, which the settings enable. Graph orders them by the resources they use, e.g. the
+    /// skybox or background before the geometry, so that translucent polygons can blend over it, and fog over
+    /// the samples before they are resolved, while the effects follow in a fixed order.
+    fn get_frame_graph(&self) -> FrameGraph {
+        let mut passes = vec![FramePass::Shadows];
+        if self.debug_view.get_pipeline_name().is_some() {
+            passes.push(FramePass::DebugView);
+            if self.msaa_samples > 1 {
+                passes.push(FramePass::Resolve);
+            }
+        } else {
+            passes.extend([
+                FramePass::RenderTargets,
+                FramePass::Camera,
+                FramePass::Background,
+                FramePass::Geometry,
+            ]);
+            let optional_passes = [
+                (FramePass::DeferredLighting, self.deferred),
+                (FramePass::Fog, self.fog.is_some()),
+                (FramePass::Resolve, self.msaa_samples > 1),
+                (FramePass::Taa, self.taa.is_some()),
+                (FramePass::Ssao, self.ssao.is_some()),
+                (FramePass::Dof, self.dof.is_some()),
+                (FramePass::MotionBlur, self.motion_blur.is_some()),
+                (FramePass::Bloom, self.bloom.is_some()),
+                (FramePass::PostPasses, !self.post_passes.is_empty()),
+                (
+                    FramePass::ReferenceDiff,
+                    self.reference_mode == ReferenceMode::Diff,
+                ),
+            ];
+            for (pass, enabled) in optional_passes {
+                if enabled {
+                    passes.push(pass);
+                }
+            }
+        }
+        let mut graph = FrameGraph::new(self.msaa_samples > 1);
+        for pass in passes {
+            graph.add_pass(pass);
+            if self.disabled_passes.contains(&pass) {
+                graph.skip_pass(pass);
+            }
+        }
+        // Passes, updating the color in place, in the order their results are meant to stack.
+        graph.add_ordering(&[
+            FramePass::Geometry,
+            FramePass::DeferredLighting,
+            FramePass::Fog,
+            FramePass::Taa,
+            FramePass::Ssao,
+            FramePass::Dof,
+            FramePass::MotionBlur,
+            FramePass::Bloom,
+            FramePass::PostPasses,
+            FramePass::ReferenceDiff,
+        ]);
+        return graph;
+    }
+
+    fn run_frame_pass(&mut self, pass: FramePass) {
+        match pass {
+            FramePass::Shadows => {
+                self.render_light_shadow_maps();
+                self.render_cascaded_shadow_map();
+            }
+            FramePass::RenderTargets => self.render_render_targets(),
+            FramePass::Camera => self.prepare_camera(),
+            FramePass::Background => self.render_background(),
+            FramePass::Geometry => {
+                let shader_pipeline = self.shader_pipeline.clone();
+                match self.deferred {
+                    true => {
+                        BuiltinPipeline::new("deferred_geometry".to_string()).render_passes(self)
+                    }
+                    false => shader_pipeline.render_passes(self),
+                }
+            }
+            FramePass::DeferredLighting => {
+                let color_buffer = match self.msaa_samples {
+                    1 => &mut self.frame_buffer,
+                    _ => &mut self.sample_buffer,
+                };
+                apply_deferred_lighting(
+                    &self.uniforms,
+                    &self.gbuffer,
+                    &self.z_buffer,
+                    self.msaa_samples as usize,
+                    color_buffer,
+                );
+            }
+            FramePass::DebugView => {
+                let pipeline_name = self.debug_view.get_pipeline_name().unwrap();
+                BuiltinPipeline::new(pipeline_name.to_string()).render_passes(self);
+            }
+            FramePass::Fog => {
+                let color_buffer = match self.msaa_samples {
+                    1 => &mut self.frame_buffer,
+                    _ => &mut self.sample_buffer,
+                };
+                apply_fog(
+                    self.fog.unwrap(),
+                    &self.projection,
+                    color_buffer,
+                    &self.z_buffer,
+                );
+            }
+            FramePass::Resolve => self.resolve_samples(),
+            FramePass::Taa => {
+                let width = self.render_width() as usize;
+                let height = self.render_height() as usize;
+                self.taa_history.accumulate(
+                    self.taa.unwrap(),
+                    &mut self.frame_buffer,
+                    &self.velocity_buffer,
+                    self.camera_moved,
+                    width,
+                    height,
+                );
+            }
+            FramePass::Ssao => self.ssao.unwrap().apply(&mut self.get_post_frame()),
+            FramePass::Dof => self.dof.unwrap().apply(&mut self.get_post_frame()),
+            FramePass::MotionBlur => self.motion_blur.unwrap().apply(&mut self.get_post_frame()),
+            FramePass::Bloom => self.bloom.unwrap().apply(&mut self.get_post_frame()),
+            FramePass::PostPasses => {
+                let post_passes = std::mem::take(&mut self.post_passes);
+                let mut frame = self.get_post_frame();
+                for post_pass in &post_passes {
+                    post_pass.apply(&mut frame);
+                }
+                self.post_passes = post_passes;
+            }
+            FramePass::ReferenceDiff => {
+                let reference = self.trace_reference();
+                let mut error_sum = 0.0;
+                for (color, reference_color) in self.frame_buffer.iter_mut().zip(reference) {
+                    let difference = (*color - reference_color).abs();
+                    error_sum += difference;
+                    *color = difference * REFERENCE_DIFF_SCALE;
+                }
+                self.reference_error = Some(error_sum / self.frame_buffer.len() as f32);
+            }
+        }
+    }
+
+    /// Sets up the jitter and the previous transform of the camera for the passes of the frame, looking from the
+    /// camera. First frame after enabling the velocity buffer doesn't move.
+    fn prepare_camera(&mut self) {
+        let vpmv_matrix = self.get_camera_vpmv_matrix();
+        self.camera_moved = self
+            .previous_vpmv_matrix
+            .is_some_and(|previous_vpmv_matrix| previous_vpmv_matrix != vpmv_matrix);
+        if self.taa.is_some() {
+            self.frame_index = self.frame_index.wrapping_add(1);
+            self.uniforms.jitter = get_jitter(self.frame_index);
+        }
+        if !self.velocity_buffer.is_empty() {
+            // Previous transform is jittered the same way, so the jitter isn't taken for motion.
+            let jitter_matrix = Matrix4::new_translation(&self.uniforms.jitter.push(0.0));
+            self.uniforms.previous_vpmv_matrix =
+                Some(jitter_matrix * self.previous_vpmv_matrix.unwrap_or(vpmv_matrix));
+        }
+        self.previous_vpmv_matrix = Some(vpmv_matrix);
+    }
+
+    /// Buffers of the resolved frame, handed to the post-processing passes.
+    fn get_post_frame(&mut self) -> PostFrame<'_> {
+        return PostFrame {
+            width: self.render_width() as usize,
+            height: self.render_height() as usize,
+            color: &mut self.frame_buffer,
+            depth: &self.z_buffer,
+            depth_stride: self.msaa_samples as usize,
+            projection: self.projection,
+            velocity: &self.velocity_buffer,
+        };
+    }
+
+    /// Triangles of every instance of the model, as the vertex stage sees them, for ray tracing.
+    fn get_reference_triangles(&mut self) -> Vec<BvhTriangle> {
+        let mut triangles = Vec::new();
+        for (index, instance) in get_drawn_instances(&self.instances).into_iter().enumerate() {
+            self.model.instance = instance;
+            let model = &self.model;
+            let material = self
+                .uniforms
+                .materials
+                .get(index)
+                .copied()
+                .unwrap_or_default();
+            for polygon in &model.obj.polygons {
+                let indices = match polygon {
+                    Polygon::PTN(indices) => indices,
                     _ => panic!("Encountered some garbage, while looking through polygons."),
                 };
+                triangles.push(BvhTriangle {
+                    positions: [0, 1, 2].map(|k| model.get_vertex_position_at_index(indices[k].0)),
+                    normals: model.get_polygon_normals(
+                        vector![indices[0].0, indices[1].0, indices[2].0],
+                        vector![indices[0].2, indices[1].2, indices[2].2],
+                    ),
+                    // Flipping v the same way the vertex stage does.
+                    uvs: [0, 1, 2].map(|k| {
+                        let (u, v, _) = model.obj.tex_coords[indices[k].1];
+                        return vector![u, 1.0 - v];
+                    }),
+                    colors: [0, 1, 2].map(|k| model.get_vertex_color_at_index(indices[k].0)),
+                    tint: instance.map_or(Vector3::repeat(1.0), |instance| instance.tint),
+                    material,
+                });
+            }
+        }
+        self.model.instance = None;
+        return triangles;
+    }
+
+    /// Ray traces the frame at the render resolution with a ray through every pixel of the camera, sampling
+    /// pixels at the same positions as the rasterizer.
+    fn trace_reference(&mut self) -> Vec<f32> {
+        let bvh = Bvh::new(self.get_reference_triangles());
+        let tracer = ReferenceTracer {
+            bvh: &bvh,
+            model: &self.model,
+            light_direction: self.light_direction,
+            light_intensity: self.light_intensity,
+            lights: &self.lights,
+            gobos: &self.uniforms.light_gobos,
+            environment: self.uniforms.environment.as_ref(),
+        };
+        let width = self.render_width() as usize;
+        let height = self.render_height() as usize;
+        let aspect = width as f32 / height as f32;
+        let projection = self.projection;
+        let look_from = self.look_from;
+        let (new_x, new_y, new_z) = get_camera_basis(self.look_from, self.look_at, self.up);
+        let to_model = |view: Vector3<f32>| new_x * view.x + new_y * view.y + new_z * view.z;
+        let (background, srgb) = (&self.background, self.gamma_correction);
+        let mut frame = vec![0.0; 3 * width * height];
+        frame
+            .par_chunks_mut(3 * width)
+            .enumerate()
+            .for_each(|(y, row)| {
+                for x in 0..width {
+                    let u = 2.0 * x as f32 / (width - 1) as f32 - 1.0;
+                    let v = 2.0 * y as f32 / (height - 1) as f32 - 1.0;
+                    let view_origin = projection.get_view_ray_origin(u, v);
+                    let view_ray = projection.get_view_ray(u, v, aspect);
+                    // Distance in front of the camera grows along the ray, which always points to -z.
+                    let (t_min, t_max) = match projection.get_depth_range() {
+                        Some((near, far)) => (
+                            (-near - view_origin.z) / view_ray.z,
+                            (-far - view_origin.z) / view_ray.z,
+                        ),
+                        None => (0.0, f32::MAX),
+                    };
+                    let color = tracer.trace(
+                        Point3::from(look_from + to_model(view_origin)),
+                        to_model(view_ray),
+                        t_min,
+                        t_max,
+                        background.get_color(vector![u, v].add_scalar(1.0) / 2.0, srgb),
+                    );
+                    row[3 * x..3 * x + 3].copy_from_slice(color.as_slice());
+                }
+            });
+        return frame;
+    }
+
+    /// Transform of the model into the frame buffer of the camera, as the passes looking from it see it.
+    fn get_camera_vpmv_matrix(&self) -> Matrix4<f32> {
+        let mut camera = Uniforms {
+            projection: self.projection,
+            ..Default::default()
+        };
+        default_prepare(
+            &mut camera,
+            self.render_width(),
+            self.render_height(),
+            self.light_direction,
+            self.look_from,
+            self.look_at,
+            self.up,
+        );
+        return camera.vpmv_matrix;
+    }
+
+    /// Vertex shaders run in parallel over the polygons, after which polygons are binned into square bins and
+    /// rows of bins are rasterized in parallel, walking every polygon of a bin in blocks of 8x8 pixels.
+    fn render_pipeline<P: ShaderPipeline>(&mut self, pipeline: &P) {
+        let width = self.render_width();
+        let height = self.render_height();
+        let tile_size = (width * BIN_SIZE) as usize;
+        let n_tiles = height.div_ceil(BIN_SIZE) as usize;
+        let bins_x = width.div_ceil(BIN_SIZE) as usize;
+
+        // Bounds of every instance and its level of detail, picked by its size on the screen as seen by the
+        // camera, so every pass, including the ones looking from the light, draws the same polygons.
+        let drawn_instances = get_drawn_instances(&self.instances);
+        let bounding_spheres = self.get_instance_bounding_spheres();
+        let vpmv_matrix = self.get_camera_vpmv_matrix();
+        let (right, _, _) = get_camera_basis(self.look_from, self.look_at, self.up);
+        self.instance_lods = bounding_spheres
+            .iter()
+            .map(|sphere| select_lod(&vpmv_matrix, right, sphere, self.model.lods.len()))
+            .collect();
 
-                if !(pass.vertex)(
-                    &mut self.shader_pipeline.buffer,
-                    &self.model,
-                    vector![indices[0].0, indices[1].0, indices[2].0],
-                    vector![indices[0].1, indices[1].1, indices[2].1],
-                    vector![indices[0].2, indices[1].2, indices[2].2],
-                ) {
-                    // Vertex shader decided, that whole polygon shouldn't be rendered.
+        // Applying all passes of the shader pipeline.
+        for pass in 0..pipeline.get_pass_count() {
+            let timer = StageTimer::start();
+            // Filling the buffer with pass constants, passes looking from the light may replace the projection.
+            self.uniforms.projection = self.projection;
+            pipeline.prepare(
+                pass,
+                &mut self.uniforms,
+                width,
+                height,
+                self.light_direction,
+                self.look_from,
+                self.look_at,
+                self.up,
+            );
+            self.uniforms.light_intensity = self.light_intensity;
+            self.uniforms.t_lights = self
+                .lights
+                .iter()
+                .map(|light| light.transformed(&self.uniforms.m_matrix))
+                .collect();
+            // Running vertex shaders for all polygons of every instance of the model, the model is moved to
+            // each instance in turn, so the shaders see the instance's vertices.
+            let mut polygons: Vec<(u32, u32, P::Varyings)> = Vec::new();
+            for (instance_index, instance) in drawn_instances.iter().enumerate() {
+                self.model.instance = *instance;
+                self.model.lod = self.instance_lods[instance_index];
+                let uniforms = &self.uniforms;
+                let model = &self.model;
+
+                // Skipping the instance out of view, only testing single polygons against the frustum if the
+                // instance crosses its boundary.
+                let frustum = Frustum::from_matrix(
+                    &uniforms.vpmv_matrix,
+                    width,
+                    height,
+                    uniforms.projection.is_depth_clipped(),
+                );
+                let containment = frustum.classify_sphere(&bounding_spheres[instance_index]);
+                let lod_polygons = model.get_lod_polygons(model.lod);
+                self.stats.polygons_submitted += lod_polygons.len();
+                if containment == Containment::Outside {
+                    self.stats.polygons_culled += lod_polygons.len();
                     continue;
                 }
 
-                let vertex_t_raster = self.shader_pipeline.buffer.vertex_t_raster;
-                let bbox = get_triangle_bounding_box(vertex_t_raster);
+                let instance_polygons: Vec<(u32, u32, P::Varyings)> = lod_polygons
+                    .par_iter()
+                    .enumerate()
+                    .filter_map(|(polygon_index, polygon)| {
+                        // Indices are &Vec((usize, usize, usize)), where first item corresponds to indices for
+                        // positions, second to indices for texture uv coords and third to indices for normals
+                        // which results in a bloated call to vertex shader.
+                        let indices: &Vec<(usize, usize, usize)> = match polygon {
+                            Polygon::PTN(indices) => indices,
+                            _ => {
+                                panic!("Encountered some garbage, while looking through polygons.")
+                            }
+                        };
+
+                        if containment == Containment::Intersecting
+                            && frustum.is_polygon_outside(
+                                [0, 1, 2].map(|k| model.get_vertex_position_at_index(indices[k].0)),
+                            )
+                        {
+                            return None;
+                        }
+
+                        let mut varyings = P::Varyings::default();
+                        if !pipeline.vertex(
+                            pass,
+                            uniforms,
+                            &mut varyings,
+                            model,
+                            vector![indices[0].0, indices[1].0, indices[2].0],
+                            vector![indices[0].1, indices[1].1, indices[2].1],
+                            vector![indices[0].2, indices[1].2, indices[2].2],
+                        ) {
+                            // Vertex shader decided, that whole polygon shouldn't be rendered.
+                            return None;
+                        }
+                        let (dx, dy) = get_barycentric_derivatives(varyings.get_raster_coords());
+                        varyings.set_bar_coord_derivatives(dx, dy);
+                        return Some((instance_index as u32, polygon_index as u32, varyings));
+                    })
+                    .collect();
+                self.stats.polygons_culled += lod_polygons.len() - instance_polygons.len();
+                polygons.extend(instance_polygons);
+            }
+            self.model.instance = None;
+            self.model.lod = 0;
+            let uniforms = &self.uniforms;
+            let model = &self.model;
+            let vertex_time = timer.elapsed();
+
+            // Deferring translucent polygons after the opaque ones and sorting them from back to front, so each
+            // one blends over everything behind it.
+            let (mut polygons, mut translucent): (Vec<_>, Vec<_>) = polygons
+                .into_iter()
+                .partition(|(_, _, varyings)| !varyings.is_translucent());
+            translucent.sort_by(|(_, _, a), (_, _, b)| a.get_depth().total_cmp(&b.get_depth()));
+            polygons.append(&mut translucent);
+
+            // Shadow buffer always needs filled polygons, whatever is shown on the screen.
+            let render_mode = match pipeline.get_depth_target(pass) {
+                DepthTarget::ZBuffer => self.render_mode,
+                DepthTarget::ShadowBuffer => RenderMode::Fill,
+            };
+            // Samples reach up to half a pixel from the pixel center.
+            let margin = match self.msaa_samples {
+                1 => 0,
+                _ => SUBPIXEL_SCALE as i64 / 2,
+            };
 
-                // Accounting for possibility that bbox can reach outside of the screen.
+            // Binning polygons into the bins they overlap, keeping the submission order inside of every bin.
+            // Filled polygons, spanning several bins, skip the bins, which are outside of one of their edges.
+            let mut bins: Vec<Vec<usize>> = vec![Vec::new(); n_tiles * bins_x];
+            for (index, (_, _, varyings)) in polygons.iter().enumerate() {
+                let coords = varyings.get_raster_coords();
+                let bbox = get_triangle_bounding_box(coords);
                 let x_min = max(0, bbox.ll.x);
-                let x_max = min(bbox.ur.x, (self.width - 1) as i32);
+                let x_max = min(bbox.ur.x, (width - 1) as i32);
                 let y_min = max(0, bbox.ll.y);
-                let y_max = min(bbox.ur.y, (self.height - 1) as i32);
-                for i in x_min..=x_max {
-                    for j in y_min..=y_max {
-                        let bar_coord = to_barycentric_coord(vector![i, j], vertex_t_raster);
-
-                        // If any of the coordinates are negative, point is not in the triangle, so skipping it.
-                        if bar_coord.x < 0.0 || bar_coord.y < 0.0 || bar_coord.z < 0.0 {
-                            continue;
+                let y_max = min(bbox.ur.y, (height - 1) as i32);
+                if x_min > x_max || y_min > y_max {
+                    continue;
+                }
+                let (bin_x_min, bin_x_max) = (x_min as u32 / BIN_SIZE, x_max as u32 / BIN_SIZE);
+                let (bin_y_min, bin_y_max) = (y_min as u32 / BIN_SIZE, y_max as u32 / BIN_SIZE);
+                let edges = match bin_x_min < bin_x_max || bin_y_min < bin_y_max {
+                    true if render_mode == RenderMode::Fill => Edges::new(coords),
+                    _ => None,
+                };
+                let mut is_binned = false;
+                for bin_y in bin_y_min..=bin_y_max {
+                    for bin_x in bin_x_min..=bin_x_max {
+                        if let Some(edges) = &edges {
+                            let (x, y) = ((bin_x * BIN_SIZE) as i32, (bin_y * BIN_SIZE) as i32);
+                            let size = BIN_SIZE as i32;
+                            let coverage =
+                                edges.classify_rect(x, x + size - 1, y, y + size - 1, margin);
+                            if coverage == Coverage::Outside {
+                                continue;
+                            }
                         }
+                        bins[bin_x as usize + bin_y as usize * bins_x].push(index);
+                        is_binned = true;
+                    }
+                }
+                if is_binned {
+                    self.stats.polygons_rasterized += 1;
+                }
+            }
+
+            let raster_time = timer.elapsed() - vertex_time;
+
+            // Splitting the depth target of the pass between the tiles, other fat buffers are read only.
+            // Color goes straight to the frame buffer, unless there are several samples per pixel.
+            let samples = self.msaa_samples as usize;
+            let sample_offsets = get_sample_offsets(self.msaa_samples);
+            let (depth_buffer, shadow_buffer, depth_samples): (&mut Vec<f32>, &[f32], usize) =
+                match pipeline.get_depth_target(pass) {
+                    DepthTarget::ZBuffer => (&mut self.z_buffer, &self.shadow_buffer, samples),
+                    DepthTarget::ShadowBuffer => (&mut self.shadow_buffer, &[], 1),
+                };
+            let color_buffer = match samples {
+                1 => &mut self.frame_buffer,
+                _ => &mut self.sample_buffer,
+            };
+            // G-buffer is split between the tiles the same way, when there is one.
+            let gbuffer_tiles: Vec<Option<&mut [GBufferTexel]>> = match self.gbuffer.is_empty() {
+                true => (0..n_tiles).map(|_| None).collect(),
+                false => self.gbuffer.chunks_mut(tile_size).map(Some).collect(),
+            };
+            // So is the ID buffer, which only records what is seen on the screen.
+            let id_buffer_tiles: Vec<Option<&mut [IdTexel]>> =
+                match (pipeline.get_depth_target(pass), self.id_buffer.is_empty()) {
+                    (DepthTarget::ZBuffer, false) => {
+                        self.id_buffer.chunks_mut(tile_size).map(Some).collect()
+                    }
+                    _ => (0..n_tiles).map(|_| None).collect(),
+                };
+            // And the velocity buffer.
+            let velocity_tiles: Vec<Option<&mut [Vector2<f32>]>> = match (
+                pipeline.get_depth_target(pass),
+                self.velocity_buffer.is_empty(),
+            ) {
+                (DepthTarget::ZBuffer, false) => self
+                    .velocity_buffer
+                    .chunks_mut(tile_size)
+                    .map(Some)
+                    .collect(),
+                _ => (0..n_tiles).map(|_| None).collect(),
+            };
+            // Stencil buffer is always there.
+            let tile_attachments: Vec<_> = self
+                .stencil_buffer
+                .chunks_mut(tile_size)
+                .zip(gbuffer_tiles.into_iter().zip(id_buffer_tiles))
+                .zip(velocity_tiles)
+                .map(|((stencil, (gbuffer, id_buffer)), velocity)| {
+                    (stencil, gbuffer, id_buffer, velocity)
+                })
+                .collect();
+            let stencil_state = pipeline.get_stencil_state(pass);
+            let instances = &self.instances;
+            let tile_stats = depth_buffer
+                .par_chunks_mut(depth_samples * tile_size)
+                .zip(color_buffer.par_chunks_mut(3 * samples * tile_size))
+                .zip(bins.par_chunks(bins_x))
+                .zip(tile_attachments.into_par_iter())
+                .enumerate()
+                .map(|(tile_index, (((depth, color), tile_bins), attachments))| {
+                    let (stencil, gbuffer, id_buffer, velocity) = attachments;
+                    let mut targets = Targets {
+                        width,
+                        offset: tile_index * tile_size,
+                        depth,
+                        shadow_buffer,
+                        samples: depth_samples,
+                        sample_bar_coords: [Vector3::zeros(); MAX_SAMPLES],
+                        sample_mask: 1,
+                        depth_write: true,
+                        alpha: 1.0,
+                        gbuffer,
+                        stencil,
+                        id_buffer,
+                        instance: 0,
+                        polygon: 0,
+                        velocity,
+                        vertex_velocities: Matrix2x3::zeros(),
+                    };
+                    let tile_y_min = tile_index as i32 * BIN_SIZE as i32;
+                    let tile_y_max = min(tile_y_min + BIN_SIZE as i32, height as i32) - 1;
+                    let mut hiz = HiZ::new(
+                        width as usize,
+                        (tile_y_max - tile_y_min + 1) as usize,
+                        depth_samples,
+                    );
 
-                        // If fragment shader returns true, getting color from the pipeline and coloring the
-                        // pixel, else skipping the pixel.
-                        if !(pass.fragment)(
-                            &mut self.shader_pipeline.buffer,
-                            &self.model,
+                    // Running fragment shader and storing resulting color in every sample, that passed the
+                    // depth test.
+                    let mut fragments_shaded = 0;
+                    let mut fragments_depth_rejected = 0;
+                    let mut fragments_discarded = 0;
+                    let mut hiz_blocks_culled = 0;
+                    let mut shade = |targets: &mut Targets,
+                                     varyings: &P::Varyings,
+                                     i: i32,
+                                     j: i32,
+                                     bar_coord: Vector3<f32>| {
+                        let pixel_index = (i + (j - tile_y_min) * width as i32) as usize;
+                        // Stencil test goes before the depth test, whatever is masked out is never shaded.
+                        if !stencil_state.test.passes(targets.stencil[pixel_index]) {
+                            return;
+                        }
+                        // Early depth test, skipping the fragment stage, when every sample is already hidden.
+                        if let Some(z_values) = varyings.get_z_values() {
+                            if !early_depth_test(
+                                targets,
+                                z_values,
+                                bar_coord,
+                                vector![i as u32, j as u32],
+                            ) {
+                                fragments_depth_rejected += 1;
+                                return;
+                            }
+                        }
+                        // If fragment shader returns a color, coloring the pixel, else skipping it.
+                        fragments_shaded += 1;
+                        targets.alpha = 1.0;
+                        let fragment_color = match pipeline.fragment(
+                            pass,
+                            uniforms,
+                            varyings,
+                            targets,
+                            model,
                             vector![i as u32, j as u32],
                             bar_coord,
                         ) {
-                            continue;
+                            Some(color) => color,
+                            None => {
+                                fragments_discarded += 1;
+                                return;
+                            }
+                        };
+                        let fragment_color = match instances.get(targets.instance as usize) {
+                            Some(instance) => fragment_color.component_mul(&instance.tint),
+                            None => fragment_color,
+                        };
+                        if let Some(value) = stencil_state.write {
+                            targets.stencil[pixel_index] = value;
+                        }
+                        for sample in 0..samples {
+                            if targets.sample_mask & (1 << sample) == 0 {
+                                continue;
+                            }
+                            let sample_index = pixel_index * samples + sample;
+                            blend_over(
+                                &mut color[3 * sample_index..3 * sample_index + 3],
+                                fragment_color,
+                                targets.alpha,
+                            );
+                        }
+                    };
+
+                    for (bin_x, bin) in tile_bins.iter().enumerate() {
+                        let bin_x_min = bin_x as i32 * BIN_SIZE as i32;
+                        let bin_x_max = min(bin_x_min + BIN_SIZE as i32, width as i32) - 1;
+                        for &index in bin {
+                            let (instance_index, polygon_index, varyings) = &polygons[index];
+                            targets.instance = *instance_index;
+                            targets.polygon = *polygon_index;
+                            targets.vertex_velocities = varyings.get_velocities();
+                            targets.depth_write = !varyings.is_translucent();
+                            let vertex_t_raster = varyings.get_raster_coords();
+
+                            // Edges and vertices are shaded like the rest of the polygon with barycentric
+                            // coordinates of the pixel, every sample of the pixel is covered.
+                            if render_mode != RenderMode::Fill {
+                                let mut shade_pixel = |i: i32, j: i32, bar_coord: Vector3<f32>| {
+                                    if i < bin_x_min
+                                        || i > bin_x_max
+                                        || j < tile_y_min
+                                        || j > tile_y_max
+                                    {
+                                        return;
+                                    }
+                                    targets.sample_mask = (1 << samples) - 1;
+                                    targets.sample_bar_coords = [bar_coord; MAX_SAMPLES];
+                                    shade(&mut targets, varyings, i, j, bar_coord);
+                                };
+                                let vertex_bar_coords = [Vector3::x(), Vector3::y(), Vector3::z()];
+                                for k in 0..3 {
+                                    let from = to_pixel(vertex_t_raster.column(k).into());
+                                    if render_mode == RenderMode::Points {
+                                        shade_pixel(from.x, from.y, vertex_bar_coords[k]);
+                                        continue;
+                                    }
+                                    let to = to_pixel(vertex_t_raster.column((k + 1) % 3).into());
+                                    rasterize_line(from, to, |i, j, t| {
+                                        let bar_coord = vertex_bar_coords[k] * (1.0 - t)
+                                            + vertex_bar_coords[(k + 1) % 3] * t;
+                                        shade_pixel(i, j, bar_coord);
+                                    });
+                                }
+                                continue;
+                            }
+                            let edges = match Edges::new(vertex_t_raster) {
+                                Some(edges) => edges,
+                                None => continue,
+                            };
+                            let bbox = get_triangle_bounding_box(vertex_t_raster);
+
+                            // Accounting for possibility that bbox can reach outside of the bin.
+                            let x_min = max(bin_x_min, bbox.ll.x);
+                            let x_max = min(bbox.ur.x, bin_x_max);
+                            let y_min = max(tile_y_min, bbox.ll.y);
+                            let y_max = min(bbox.ur.y, tile_y_max);
+                            if x_min > x_max || y_min > y_max {
+                                continue;
+                            }
+                            // Only polygons, spanning several blocks, are worth testing against the
+                            // hierarchical depth, refreshing a block costs about as much as shading a small
+                            // polygon.
+                            let max_depth = varyings
+                                .get_z_values()
+                                .filter(|_| {
+                                    (x_max - x_min + 1) * (y_max - y_min + 1)
+                                        >= 4 * BLOCK_SIZE * BLOCK_SIZE
+                                })
+                                .map(|z_values| z_values.max());
+                            // Walking the blocks of the polygon, which are shared with the hierarchical depth,
+                            // and skipping the ones outside of the polygon or hidden behind what is drawn.
+                            for block_y in
+                                y_min.div_euclid(BLOCK_SIZE)..=y_max.div_euclid(BLOCK_SIZE)
+                            {
+                                let block_y_min = max(y_min, block_y * BLOCK_SIZE);
+                                let block_y_max = min(y_max, block_y * BLOCK_SIZE + BLOCK_SIZE - 1);
+                                for block_x in
+                                    x_min.div_euclid(BLOCK_SIZE)..=x_max.div_euclid(BLOCK_SIZE)
+                                {
+                                    let block_x_min = max(x_min, block_x * BLOCK_SIZE);
+                                    let block_x_max =
+                                        min(x_max, block_x * BLOCK_SIZE + BLOCK_SIZE - 1);
+                                    let coverage = edges.classify_rect(
+                                        block_x_min,
+                                        block_x_max,
+                                        block_y_min,
+                                        block_y_max,
+                                        margin,
+                                    );
+                                    if coverage == Coverage::Outside {
+                                        continue;
+                                    }
+                                    if let Some(max_depth) = max_depth {
+                                        let (x, y) = (
+                                            block_x_min as usize,
+                                            (block_y_min - tile_y_min) as usize,
+                                        );
+                                        if hiz.is_occluded(targets.depth, x, y, max_depth) {
+                                            hiz_blocks_culled += 1;
+                                            continue;
+                                        }
+                                    }
+                                    if samples == 1 {
+                                        edges.rasterize_rect(
+                                            block_x_min,
+                                            block_x_max,
+                                            block_y_min,
+                                            block_y_max,
+                                            coverage,
+                                            |i, j, bar_coord| {
+                                                shade(&mut targets, varyings, i, j, bar_coord);
+                                            },
+                                        );
+                                        continue;
+                                    }
+                                    for j in block_y_min..=block_y_max {
+                                        rasterize_row_multisampled(
+                                            &edges,
+                                            j,
+                                            block_x_min,
+                                            block_x_max,
+                                            sample_offsets,
+                                            |i, mask, sample_bar_coords| {
+                                                targets.sample_mask = mask;
+                                                targets.sample_bar_coords = *sample_bar_coords;
+                                                // Shading at the pixel center if it is covered, otherwise at
+                                                // the first covered sample, so attributes are never
+                                                // extrapolated.
+                                                let bar_coord = if mask == (1 << samples) - 1 {
+                                                    to_barycentric_coord_at(
+                                                        vector![i as f32, j as f32],
+                                                        vertex_t_raster,
+                                                    )
+                                                } else {
+                                                    sample_bar_coords
+                                                        [mask.trailing_zeros() as usize]
+                                                };
+                                                shade(&mut targets, varyings, i, j, bar_coord);
+                                            },
+                                        );
+                                    }
+                                }
+                            }
+                            hiz.invalidate(
+                                x_min as usize,
+                                x_max as usize,
+                                (y_min - tile_y_min) as usize,
+                                (y_max - tile_y_min) as usize,
+                            );
                         }
-                        let fragment_color = self.shader_pipeline.buffer.fragment_color;
-                        let pixel_index = (i + j * self.width as i32) as usize;
-                        self.frame_buffer[3 * pixel_index + 0] = fragment_color.x;
-                        self.frame_buffer[3 * pixel_index + 1] = fragment_color.y;
-                        self.frame_buffer[3 * pixel_index + 2] = fragment_color.z;
                     }
-                }
-            }
+                    return FrameStats {
+                        fragments_shaded,
+                        fragments_depth_rejected,
+                        fragments_discarded,
+                        hiz_blocks_culled,
+                        ..Default::default()
+                    };
+                })
+                .reduce(FrameStats::default, |a, b| a + b);
+            self.stats += tile_stats;
+            let fragment_time = timer.elapsed() - vertex_time - raster_time;
+            self.stats.add_pass_times(
+                pipeline.get_depth_target(pass),
+                vertex_time,
+                raster_time,
+                fragment_time,
+            );
         }
     }
 }
+
+/// Built-in pipelines panic on unknown names, so names coming from the outside are checked first.
+fn check_builtin_pipeline_name(shader_pipeline_name: &str) -> Result<(), RendererError> {
+    if !BUILTIN_PIPELINE_NAMES.contains(&shader_pipeline_name) {
+        return Err(RendererError::UnknownPipelineName(
+            shader_pipeline_name.to_string(),
+        ));
+    }
+    return Ok(());
+}
+
+#[cfg(all(test, feature = "fixed_point"))]
+mod tests {
+    use super::*;
+    use nalgebra::vector;
+    use obj::raw::parse_obj;
+
+    /// Quad, tilted in depth and split into two triangles along a shared edge, and a triangle, which cuts
+    /// through it, so the frame depends on the shared edge, the depth test and the hierarchical depth blocks.
+    const GOLDEN_OBJ: &str = "
+v -0.8 -0.6 -0.3
+v 0.8 -0.6 0.3
+v 0.8 0.6 -0.1
+v -0.8 0.6 0.1
+v -0.5 -0.7 0.4
+v 0.7 0.1 -0.5
+v -0.2 0.7 0.2
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+f 1/1 2/2 3/3
+f 1/1 3/3 4/4
+f 5/1 6/2 7/4
+";
+
+    /// FNV-1a hash of the bytes.
+    fn hash(bytes: &[u8]) -> u64 {
+        let mut hash = 0xcbf29ce484222325;
+        for byte in bytes {
+            hash = (hash ^ *byte as u64).wrapping_mul(0x100000001b3);
+        }
+        return hash;
+    }
+
+    /// Frame of a small scene through the binned rasterizer, with and without multisampling, is the same
+    /// on every platform, which fixed point coverage and depth make it.
+    #[test]
+    fn renders_golden_scene() {
+        let obj = parse_obj(GOLDEN_OBJ.as_bytes()).unwrap();
+        let mut scene =
+            Scene::new(64, 48, obj, None, None, None, None, "phong".to_string()).unwrap();
+        scene.set_perspective(0.9, 0.1, 10.0);
+        scene.set_camera(
+            vector![0.3, 0.2, 3.0],
+            vector![0.0, 0.0, 0.0],
+            vector![0.0, 1.0, 0.0],
+        );
+        scene.set_light_direction(vector![0.5, 0.3, 1.0].normalize());
+        scene.clear();
+        scene.render();
+        let single_sampled = hash(scene.get_frame_buffer().as_raw());
+        scene.set_msaa_samples(4);
+        scene.clear();
+        scene.render();
+        let multisampled = hash(scene.get_frame_buffer().as_raw());
+        assert_eq!(
+            (single_sampled, multisampled),
+            (279943518023169446, 18278082151366517054)
+        );
+    }
+}
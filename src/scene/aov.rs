@@ -0,0 +1,192 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+
+use image::{DynamicImage, ImageBuffer, ImageError, Rgb, Rgb32FImage};
+
+use super::error::RendererError;
+use super::Scene;
+
+/// Thousandths of a unit of view space depth per step of a 16-bit PNG, so depths up to 65 units fit.
+const PNG_DEPTH_SCALE: f32 = 1000.0;
+
+/// Arbitrary output variable - per-pixel data of the frame besides its color, e.g. for synthetic training
+/// data or compositing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Aov {
+    Depth, // Distance in front of the camera in view space in every channel, infinite for the background.
+    Normal, // Interpolated normal in model space, without normal maps, zero for the background.
+    Albedo, // Linear color of the diffuse texture times vertex colors in [0, 1], zero for the background.
+    // Index of the instance plus one, index of the polygon in its level of detail plus one and the level of
+    // detail, zero for the background.
+    ObjectId,
+}
+
+impl Aov {
+    pub const ALL: [Aov; 4] = [Aov::Depth, Aov::Normal, Aov::Albedo, Aov::ObjectId];
+
+    /// Parses output variable from its command line name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        return Aov::ALL.into_iter().find(|aov| aov.get_name() == name);
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Aov::Depth => return "depth",
+            Aov::Normal => return "normal",
+            Aov::Albedo => return "albedo",
+            Aov::ObjectId => return "id",
+        }
+    }
+
+    /// Maps a channel value into [0, 1] for formats without floating point channels. Depth is stored in
+    /// thousandths of a unit, object IDs as they are, both saturating at the largest value.
+    fn get_unorm(&self, value: f32) -> f32 {
+        match self {
+            Aov::Depth => return (value * PNG_DEPTH_SCALE / u16::MAX as f32).min(1.0),
+            Aov::Normal => return value * 0.5 + 0.5,
+            Aov::Albedo => return value.clamp(0.0, 1.0),
+            Aov::ObjectId => return (value / u16::MAX as f32).min(1.0),
+        }
+    }
+}
+
+/// File format, which output variables are saved in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AovFormat {
+    #[default]
+    Pfm, // Portable float map, 32-bit floats without compression.
+    Exr,   // OpenEXR, 32-bit floats.
+    Png16, // 16-bit PNG, with values mapped into the range of the format.
+}
+
+impl AovFormat {
+    /// Parses format from its command line name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "pfm" => return Some(AovFormat::Pfm),
+            "exr" => return Some(AovFormat::Exr),
+            "png16" => return Some(AovFormat::Png16),
+            _ => return None,
+        }
+    }
+
+    pub fn get_extension(&self) -> &'static str {
+        match self {
+            AovFormat::Pfm => return "pfm",
+            AovFormat::Exr => return "exr",
+            AovFormat::Png16 => return "png",
+        }
+    }
+}
+
+/// Saves the output variable, returned by Scene::get_aov, in the format. (0, 0) of the image is the upper left
+/// corner.
+pub fn save_aov(
+    aov: Aov,
+    image: &Rgb32FImage,
+    format: AovFormat,
+    path: &str,
+) -> Result<(), RendererError> {
+    let to_io_error = |source| RendererError::Io {
+        path: path.into(),
+        source,
+    };
+    let to_renderer_error = |error| match error {
+        ImageError::IoError(source) => to_io_error(source),
+        source => RendererError::UnsupportedTextureFormat {
+            path: path.into(),
+            source,
+        },
+    };
+    match format {
+        AovFormat::Pfm => {
+            let mut writer = BufWriter::new(File::create(path).map_err(to_io_error)?);
+            // Negative scale marks little endian values, rows go from the bottom.
+            write!(writer, "PF\n{} {}\n-1.0\n", image.width(), image.height())
+                .map_err(to_io_error)?;
+            for row in image.rows().rev() {
+                for value in row.flat_map(|pixel| pixel.0) {
+                    writer
+                        .write_all(&value.to_le_bytes())
+                        .map_err(to_io_error)?;
+                }
+            }
+            return writer.flush().map_err(to_io_error);
+        }
+        AovFormat::Exr => {
+            return DynamicImage::ImageRgb32F(image.clone())
+                .save(path)
+                .map_err(to_renderer_error);
+        }
+        AovFormat::Png16 => {
+            let data: Vec<u16> = image
+                .as_raw()
+                .iter()
+                .map(|&value| (aov.get_unorm(value) * u16::MAX as f32).round() as u16)
+                .collect();
+            let png: ImageBuffer<Rgb<u16>, Vec<u16>> =
+                ImageBuffer::from_raw(image.width(), image.height(), data).unwrap();
+            return png.save(path).map_err(to_renderer_error);
+        }
+    }
+}
+
+/// Output variables, which are saved for every rendered frame, numbered like the frames of an image sequence.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AovExport {
+    pub directory: String,
+    pub format: AovFormat,
+    pub aovs: Vec<Aov>,
+}
+
+impl AovExport {
+    /// Parses export from a spec like `aovs:exr:depth,normal`, returning None for malformed specs. Format is
+    /// pfm and all the output variables are saved by default. Supported forms:
+    /// - directory
+    /// - directory:format
+    /// - directory:format:aov,aov,...
+    pub fn from_spec(spec: &str) -> Option<Self> {
+        let parts: Vec<&str> = spec.split(':').collect();
+        if parts.len() > 3 || parts[0].is_empty() {
+            return None;
+        }
+        let format = match parts.get(1) {
+            Some(name) => AovFormat::from_name(name)?,
+            None => AovFormat::default(),
+        };
+        let aovs = match parts.get(2) {
+            Some(names) => names
+                .split(',')
+                .map(Aov::from_name)
+                .collect::<Option<_>>()?,
+            None => Aov::ALL.to_vec(),
+        };
+        return Some(AovExport {
+            directory: parts[0].to_string(),
+            format,
+            aovs,
+        });
+    }
+
+    /// Saves output variables of the last frame, rendered by the scene, into files like `depth_00000.pfm`.
+    /// Variables, which the scene doesn't have, are skipped.
+    pub fn save_frame(&self, scene: &Scene, frame_index: u32) -> Result<(), RendererError> {
+        fs::create_dir_all(&self.directory).map_err(|source| RendererError::Io {
+            path: self.directory.clone().into(),
+            source,
+        })?;
+        for &aov in &self.aovs {
+            if let Some(image) = scene.get_aov(aov) {
+                let path = format!(
+                    "{}/{}_{:05}.{}",
+                    self.directory,
+                    aov.get_name(),
+                    frame_index,
+                    self.format.get_extension()
+                );
+                save_aov(aov, &image, self.format, &path)?;
+            }
+        }
+        return Ok(());
+    }
+}
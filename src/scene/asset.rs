@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use image::io::Reader;
+use image::{DynamicImage, ImageError, Rgb};
+use na::Vector3;
+use nalgebra as na;
+use obj::raw::{parse_obj, RawObj};
+
+use super::background::parse_hex_color;
+use super::dither::Palette;
+use super::error::RendererError;
+use super::texture::CubeMap;
+use super::util::TextureKind;
+
+/// Extensions of the image files, which are looked for, when a map is given only by its name.
+pub const IMAGE_EXTENSIONS: &[&str] = &["tga", "png", "jpg", "jpeg", "bmp"];
+
+/// Mesh with vertex colors, sRGB encoded in [0, 1] per position, empty if it doesn't have them.
+pub type ColoredMesh = (RawObj, Vec<Vector3<f32>>);
+
+/// Loads the mesh from a Wavefront OBJ file.
+pub fn load_obj(path: &str) -> Result<RawObj, RendererError> {
+    return load_obj_with_colors(path).map(|(obj, _)| obj);
+}
+
+/// Loads the mesh from a Wavefront OBJ file together with vertex colors of the common `v x y z r g b`
+/// extension, sRGB encoded in [0, 1] per position. Colors are empty, if no vertex has one, otherwise vertices
+/// without a color are white.
+pub fn load_obj_with_colors(path: &str) -> Result<ColoredMesh, RendererError> {
+    if !Path::new(path).exists() {
+        return Err(RendererError::AssetNotFound(path.into()));
+    }
+    let text = fs::read_to_string(path).map_err(|source| RendererError::Io {
+        path: path.into(),
+        source,
+    })?;
+    // Colors are cut off the vertex lines, which the obj parser would reject otherwise.
+    let mut stripped = String::with_capacity(text.len());
+    let mut colors = Vec::new();
+    let mut has_colors = false;
+    for line in text.lines() {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.first() == Some(&"v") {
+            let color: Option<Vec<f32>> = match words.len() {
+                7 => words[4..].iter().map(|word| word.parse().ok()).collect(),
+                _ => None,
+            };
+            match color {
+                Some(color) => {
+                    colors.push(Vector3::from_column_slice(&color));
+                    stripped.push_str(&words[..4].join(" "));
+                    stripped.push('\n');
+                    has_colors = true;
+                    continue;
+                }
+                None => colors.push(Vector3::repeat(1.0)),
+            }
+        }
+        stripped.push_str(line);
+        stripped.push('\n');
+    }
+    if !has_colors {
+        colors.clear();
+    }
+    let obj = parse_obj(stripped.as_bytes()).map_err(|source| RendererError::InvalidObj {
+        path: path.into(),
+        source,
+    })?;
+    return Ok((obj, colors));
+}
+
+/// Mesh without any vertices, which loaders of other formats fill in.
+pub(crate) fn new_raw_obj() -> RawObj {
+    return RawObj {
+        name: None,
+        material_libraries: Vec::new(),
+        positions: Vec::new(),
+        tex_coords: Vec::new(),
+        normals: Vec::new(),
+        param_vertices: Vec::new(),
+        points: Vec::new(),
+        lines: Vec::new(),
+        polygons: Vec::new(),
+        groups: HashMap::new(),
+        meshes: HashMap::new(),
+        smoothing_groups: HashMap::new(),
+        merging_groups: HashMap::new(),
+    };
+}
+
+/// Loads a texture or any other image in one of the formats of the image crate, keeping its channels, so
+/// alpha can still be split off. Format is detected from the contents of the file, falling back to the
+/// extension, and images, which are stored bottom row first, like most TGA files, come out top row first.
+pub fn load_image(path: &str) -> Result<DynamicImage, RendererError> {
+    if !Path::new(path).exists() {
+        return Err(RendererError::AssetNotFound(path.into()));
+    }
+    let to_io_error = |source| RendererError::Io {
+        path: path.into(),
+        source,
+    };
+    let reader = Reader::open(path)
+        .map_err(to_io_error)?
+        .with_guessed_format()
+        .map_err(to_io_error)?;
+    return reader.decode().map_err(|error| match error {
+        ImageError::IoError(source) => to_io_error(source),
+        source => RendererError::UnsupportedTextureFormat {
+            path: path.into(),
+            source,
+        },
+    });
+}
+
+/// Whether the image has floating point channels, e.g. decoded from a Radiance HDR or OpenEXR file.
+fn is_hdr_image(image: &DynamicImage) -> bool {
+    return matches!(
+        image,
+        DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_)
+    );
+}
+
+/// Loads cube map from a folder with 6 cube faces, named by their axis with any image extension, or from a
+/// single equirectangular panorama. Floating point images, like .hdr panoramas, give a high dynamic range cube
+/// map, the rest are 8-bit.
+pub fn load_cube_map(path: &str) -> Result<CubeMap, RendererError> {
+    if !Path::new(path).is_dir() {
+        let panorama = load_image(path)?;
+        if is_hdr_image(&panorama) {
+            return Ok(CubeMap::from_equirectangular_hdr(&panorama.into_rgb32f()));
+        }
+        return Ok(CubeMap::from_equirectangular(&panorama.into_rgb8()));
+    }
+    let mut faces = Vec::new();
+    for face_name in ["px", "nx", "py", "ny", "pz", "nz"] {
+        let face_path = fs::read_dir(path)
+            .map_err(|source| RendererError::Io {
+                path: path.into(),
+                source,
+            })?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .find(|entry_path| entry_path.file_stem().is_some_and(|stem| stem == face_name))
+            .ok_or(RendererError::AssetNotFound(
+                Path::new(path).join(face_name),
+            ))?;
+        faces.push(load_image(&face_path.to_string_lossy())?);
+    }
+    if faces.iter().any(is_hdr_image) {
+        return Ok(CubeMap::from_hdr_faces(
+            faces.into_iter().map(|face| face.into_rgb32f()).collect(),
+        ));
+    }
+    return Ok(CubeMap::from_faces(
+        faces.into_iter().map(|face| face.into_rgb8()).collect(),
+    ));
+}
+
+/// Loads a palette from an image, taking every distinct color of it, e.g. a swatch strip, or from a text file
+/// with one hex color per line, like the .hex palettes of Lospec, where `#` before the color is optional and
+/// lines starting with `;` are comments.
+pub fn load_palette(path: &str) -> Result<Palette, RendererError> {
+    let extension = Path::new(path)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+    let colors: Vec<Rgb<u8>> = match extension {
+        Some(extension) if IMAGE_EXTENSIONS.contains(&extension.as_str()) => {
+            load_image(path)?.into_rgb8().pixels().copied().collect()
+        }
+        _ => {
+            if !Path::new(path).exists() {
+                return Err(RendererError::AssetNotFound(path.into()));
+            }
+            let text = fs::read_to_string(path).map_err(|source| RendererError::Io {
+                path: path.into(),
+                source,
+            })?;
+            let mut colors = Vec::new();
+            for line in text.lines().map(str::trim) {
+                if line.is_empty() || line.starts_with(';') {
+                    continue;
+                }
+                let color = parse_hex_color(line).ok_or_else(|| RendererError::InvalidPalette {
+                    path: path.into(),
+                    message: format!("'{}' is not a hex color", line),
+                })?;
+                colors.push(color);
+            }
+            colors
+        }
+    };
+    if colors.is_empty() {
+        return Err(RendererError::InvalidPalette {
+            path: path.into(),
+            message: String::from("no colors"),
+        });
+    }
+    return Ok(Palette::new(&colors));
+}
+
+/// Path of the image in the directory with the file name stem and any of `IMAGE_EXTENSIONS`, if there is
+/// one.
+pub fn find_image(directory: &str, stem: &str) -> Option<String> {
+    return IMAGE_EXTENSIONS
+        .iter()
+        .map(|extension| Path::new(directory).join(format!("{}.{}", stem, extension)))
+        .find(|path| path.exists())
+        .map(|path| path.to_string_lossy().into_owned());
+}
+
+/// Paths of the maps, which the first material of a Wavefront MTL file references, relative to the
+/// directory of the file. Bump maps are taken to be normal maps in tangent coordinates, the only kind of
+/// normal map the format has.
+pub fn load_mtl_maps(path: &str) -> Result<HashMap<TextureKind, String>, RendererError> {
+    if !Path::new(path).exists() {
+        return Err(RendererError::AssetNotFound(path.into()));
+    }
+    let text = fs::read_to_string(path).map_err(|source| RendererError::Io {
+        path: path.into(),
+        source,
+    })?;
+    let directory = Path::new(path).parent().unwrap_or(Path::new(""));
+    let mut maps = HashMap::new();
+    let mut n_materials = 0;
+    for line in text.lines() {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let kind = match words.first() {
+            Some(&"newmtl") => {
+                n_materials += 1;
+                continue;
+            }
+            Some(&"map_Kd") => TextureKind::Diffuse,
+            Some(&"map_Ks") => TextureKind::SpecularMap,
+            Some(&"map_Bump" | &"map_bump" | &"bump" | &"norm") => TextureKind::NormalMapTangent,
+            Some(&"map_d") => TextureKind::AlphaMap,
+            Some(&"disp") => TextureKind::DisplacementMap,
+            _ => continue,
+        };
+        // Options like `-bm 1.0` come before the file name, which is the last argument.
+        if n_materials <= 1 && words.len() > 1 {
+            let file = directory.join(words[words.len() - 1]);
+            maps.insert(kind, file.to_string_lossy().into_owned());
+        }
+    }
+    return Ok(maps);
+}
@@ -0,0 +1,86 @@
+use image::{Rgb, RgbImage};
+use na::{vector, Vector2, Vector3};
+use nalgebra as na;
+
+use super::texture::{decode_srgb, Sampler, Texture, Wrap};
+
+/// What the frame is filled with, where the model doesn't cover it, unless there is a skybox.
+pub enum Background {
+    Color(Rgb<u8>),
+    Gradient { top: Rgb<u8>, bottom: Rgb<u8> }, // Vertical gradient from the top to the bottom of the frame.
+    Image(Texture),                             // Image, stretched over the whole frame.
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        return Background::Color(Rgb([0, 0, 0]));
+    }
+}
+
+impl Background {
+    /// Parses a background of colors from a spec like `87ceeb,1e3250`, returning None for malformed specs.
+    /// Supported forms:
+    /// - color
+    /// - top_color,bottom_color
+    pub fn from_spec(spec: &str) -> Option<Self> {
+        match spec.split_once(',') {
+            Some((top, bottom)) => {
+                return Some(Background::Gradient {
+                    top: parse_hex_color(top)?,
+                    bottom: parse_hex_color(bottom)?,
+                })
+            }
+            None => return Some(Background::Color(parse_hex_color(spec)?)),
+        }
+    }
+
+    /// Background of the image, filtered bilinearly, when stretched over frames of another size.
+    pub fn from_image(image: RgbImage) -> Self {
+        let mut texture = Texture::new(image);
+        texture.sampler = Sampler {
+            wrap: Wrap::Clamp,
+            ..Default::default()
+        };
+        return Background::Image(texture);
+    }
+
+    /// Whether the background is flat black, which cleared frames already are.
+    pub fn is_black(&self) -> bool {
+        return matches!(self, Background::Color(Rgb([0, 0, 0])));
+    }
+
+    /// Decoding the image from sRGB, when it is sampled, the same way as textures are, for gamma-correct
+    /// rendering.
+    pub fn set_srgb(&mut self, srgb: bool) {
+        if let Background::Image(texture) = self {
+            texture.srgb = srgb;
+        }
+    }
+
+    /// Color at uv in [0, 1] of the frame, where (0, 0) is the bottom left corner, with channels in [0, 255].
+    /// Colors of the spec are decoded to linear, if srgb is set, the image - according to set_srgb.
+    pub fn get_color(&self, uv: Vector2<f32>, srgb: bool) -> Vector3<f32> {
+        let decode = |color: Rgb<u8>| match srgb {
+            true => Vector3::from(color.0.map(decode_srgb)),
+            false => Vector3::from(color.0.map(|value| value as f32)),
+        };
+        match self {
+            Background::Color(color) => return decode(*color),
+            Background::Gradient { top, bottom } => {
+                return decode(*bottom).lerp(&decode(*top), uv.y.clamp(0.0, 1.0));
+            }
+            // Image rows go from the top.
+            Background::Image(texture) => return texture.sample_vertex(vector![uv.x, 1.0 - uv.y]),
+        }
+    }
+}
+
+/// Parses a color like `#1e3250`, where `#` is optional.
+pub fn parse_hex_color(text: &str) -> Option<Rgb<u8>> {
+    let hex = text.trim_start_matches('#');
+    if hex.len() != 6 {
+        return None;
+    }
+    let color = u32::from_str_radix(hex, 16).ok()?;
+    return Some(Rgb([(color >> 16) as u8, (color >> 8) as u8, color as u8]));
+}
@@ -0,0 +1,153 @@
+use rayon::prelude::*;
+
+/// Weights of the separable Gaussian kernel, used to blur every level.
+const BLUR_WEIGHTS: [f32; 5] = [1.0 / 16.0, 4.0 / 16.0, 6.0 / 16.0, 4.0 / 16.0, 1.0 / 16.0];
+
+/// Parameters of the bloom, spreading light of the overexposed pixels onto their surroundings.
+#[derive(Clone, Copy, Debug)]
+pub struct BloomSettings {
+    pub threshold: f32, // Brightness, where 1.0 is white, above which pixels start to glow.
+    pub intensity: f32, // Scale of the glow, added back into the frame.
+    pub levels: u32, // Number of successively halved levels, each one spreading the glow twice as far.
+}
+
+impl Default for BloomSettings {
+    fn default() -> Self {
+        return BloomSettings {
+            threshold: 1.0,
+            intensity: 0.5,
+            levels: 5,
+        };
+    }
+}
+
+/// Rgb image of floats, one of the levels of the bloom chain.
+struct Level {
+    data: Vec<f32>,
+    width: usize,
+    height: usize,
+}
+
+impl Level {
+    /// Averages 2x2 blocks, clamping at the border for odd sizes.
+    fn downsample(&self) -> Level {
+        let width = self.width.div_ceil(2);
+        let height = self.height.div_ceil(2);
+        let mut data = vec![0.0; 3 * width * height];
+        data.par_chunks_mut(3 * width)
+            .enumerate()
+            .for_each(|(y, row)| {
+                for x in 0..width {
+                    for (sx, sy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                        let ix = (2 * x + sx).min(self.width - 1);
+                        let iy = (2 * y + sy).min(self.height - 1);
+                        for c in 0..3 {
+                            row[3 * x + c] += 0.25 * self.data[3 * (ix + iy * self.width) + c];
+                        }
+                    }
+                }
+            });
+        return Level {
+            data,
+            width,
+            height,
+        };
+    }
+
+    /// Separable Gaussian blur, horizontal pass followed by the vertical one.
+    fn blur(&mut self) {
+        let (width, height) = (self.width, self.height);
+        let radius = BLUR_WEIGHTS.len() as i32 / 2;
+        let mut horizontal = vec![0.0; self.data.len()];
+        horizontal
+            .par_chunks_mut(3 * width)
+            .enumerate()
+            .for_each(|(y, row)| {
+                for x in 0..width {
+                    for (i, weight) in BLUR_WEIGHTS.iter().enumerate() {
+                        let sx = (x as i32 + i as i32 - radius).clamp(0, width as i32 - 1) as usize;
+                        for c in 0..3 {
+                            row[3 * x + c] += weight * self.data[3 * (sx + y * width) + c];
+                        }
+                    }
+                }
+            });
+        self.data
+            .par_chunks_mut(3 * width)
+            .enumerate()
+            .for_each(|(y, row)| {
+                row.fill(0.0);
+                for (i, weight) in BLUR_WEIGHTS.iter().enumerate() {
+                    let sy = (y as i32 + i as i32 - radius).clamp(0, height as i32 - 1) as usize;
+                    for x in 0..width {
+                        for c in 0..3 {
+                            row[3 * x + c] += weight * horizontal[3 * (x + sy * width) + c];
+                        }
+                    }
+                }
+            });
+    }
+
+    /// Bilinearly interpolated color at continuous coordinates in [0, 1], matching pixel centers of all levels.
+    fn sample(&self, u: f32, v: f32) -> [f32; 3] {
+        let x = (u * self.width as f32 - 0.5).clamp(0.0, (self.width - 1) as f32);
+        let y = (v * self.height as f32 - 0.5).clamp(0.0, (self.height - 1) as f32);
+        let (x0, y0) = (x as usize, y as usize);
+        let (x1, y1) = ((x0 + 1).min(self.width - 1), (y0 + 1).min(self.height - 1));
+        let (tx, ty) = (x - x0 as f32, y - y0 as f32);
+        let texel = |x: usize, y: usize, c: usize| self.data[3 * (x + y * self.width) + c];
+        let mut color = [0.0; 3];
+        for c in 0..3 {
+            let top = texel(x0, y0, c) * (1.0 - tx) + texel(x1, y0, c) * tx;
+            let bottom = texel(x0, y1, c) * (1.0 - tx) + texel(x1, y1, c) * tx;
+            color[c] = top * (1.0 - ty) + bottom * ty;
+        }
+        return color;
+    }
+}
+
+/// Adds glow around the pixels of the high dynamic range frame buffer, which are brighter than the threshold.
+/// Bright part of the frame is blurred over several downsampled levels, so wide glow stays cheap.
+pub fn apply_bloom(settings: BloomSettings, frame_buffer: &mut [f32], width: usize, height: usize) {
+    // Only the excess above the threshold glows, colors are in [0, 255] for white.
+    let threshold = settings.threshold * 255.0;
+    let bright = Level {
+        data: frame_buffer
+            .par_iter()
+            .map(|&value| (value - threshold).max(0.0))
+            .collect(),
+        width,
+        height,
+    };
+
+    let mut levels: Vec<Level> = Vec::new();
+    for _ in 0..settings.levels {
+        let previous = levels.last().unwrap_or(&bright);
+        if previous.width == 1 && previous.height == 1 {
+            break;
+        }
+        let mut level = previous.downsample();
+        level.blur();
+        levels.push(level);
+    }
+    if levels.is_empty() {
+        return;
+    }
+
+    let scale = settings.intensity / levels.len() as f32;
+    frame_buffer
+        .par_chunks_mut(3 * width)
+        .enumerate()
+        .for_each(|(y, row)| {
+            let v = (y as f32 + 0.5) / height as f32;
+            for x in 0..width {
+                let u = (x as f32 + 0.5) / width as f32;
+                for level in &levels {
+                    let glow = level.sample(u, v);
+                    for c in 0..3 {
+                        row[3 * x + c] += scale * glow[c];
+                    }
+                }
+            }
+        });
+}
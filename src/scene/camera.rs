@@ -0,0 +1,80 @@
+use na::{UnitQuaternion, Vector3};
+use nalgebra as na;
+
+/// Pose of the camera - position, orientation and field of view, which can be blended with another pose, so
+/// that the camera flies smoothly between viewpoints over time instead of snapping from one to another.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+    pub position: Vector3<f32>,
+    // Rotation from view coordinates, where the camera looks along -z with y going up, to model coordinates.
+    pub orientation: UnitQuaternion<f32>,
+    pub fov_y: f32, // Vertical field of view in radians, only used by perspective projection.
+    // Distance to the point, which the camera looks at, kept so that the point ends up where it was.
+    pub focus_distance: f32,
+}
+
+impl Camera {
+    /// Camera, placed the same way as by `Scene::set_camera`. Up doesn't need to be orthogonal to the view
+    /// direction, only not parallel to it.
+    pub fn look_at(
+        look_from: Vector3<f32>,
+        look_at: Vector3<f32>,
+        up: Vector3<f32>,
+        fov_y: f32,
+    ) -> Self {
+        return Self {
+            position: look_from,
+            orientation: UnitQuaternion::face_towards(&(look_from - look_at), &up),
+            fov_y,
+            focus_distance: (look_at - look_from).norm(),
+        };
+    }
+
+    /// Unit vector, which the camera looks along.
+    pub fn get_forward(&self) -> Vector3<f32> {
+        return self.orientation * -Vector3::z();
+    }
+
+    /// Unit vector, going up in the frame.
+    pub fn get_up(&self) -> Vector3<f32> {
+        return self.orientation * Vector3::y();
+    }
+
+    /// Point, which the camera looks at.
+    pub fn get_look_at(&self) -> Vector3<f32> {
+        return self.position + self.get_forward() * self.focus_distance;
+    }
+
+    /// Pose at t in [0, 1] between the camera and the other one, blending the orientations linearly and
+    /// normalizing the result. It is cheaper than slerp and close to it for nearby orientations, but turns
+    /// faster in the middle of wide turns. Position, field of view and focus distance are blended linearly.
+    pub fn lerp(&self, other: &Camera, t: f32) -> Self {
+        // Quaternions of the opposite sign are the same rotation, the closer one takes the shorter way.
+        let mut to = other.orientation;
+        if self.orientation.coords.dot(&to.coords) < 0.0 {
+            to = UnitQuaternion::new_unchecked(-to.into_inner());
+        }
+        return Self {
+            orientation: self.orientation.nlerp(&to, t),
+            ..self.blend_linear(other, t)
+        };
+    }
+
+    /// Pose at t in [0, 1] between the camera and the other one, turning the orientation at a constant angular
+    /// speed along the shortest arc. Position, field of view and focus distance are blended linearly.
+    pub fn slerp(&self, other: &Camera, t: f32) -> Self {
+        return Self {
+            orientation: self.orientation.slerp(&other.orientation, t),
+            ..self.blend_linear(other, t)
+        };
+    }
+
+    fn blend_linear(&self, other: &Camera, t: f32) -> Self {
+        return Self {
+            position: self.position.lerp(&other.position, t),
+            orientation: self.orientation,
+            fov_y: self.fov_y + (other.fov_y - self.fov_y) * t,
+            focus_distance: self.focus_distance + (other.focus_distance - self.focus_distance) * t,
+        };
+    }
+}
@@ -0,0 +1,62 @@
+use rayon::prelude::*;
+
+/// Buffer, shown in place of the shaded frame, for inspecting intermediate results of the render.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum DebugView {
+    #[default]
+    Color,
+    Depth,     // Z-buffer, normalized to the depth range of the visible geometry.
+    ShadowMap, // Shadow buffer of the main light, normalized the same way.
+    Normals,   // World space normals, mapped from [-1, 1] to [0, 1].
+    Uvs,       // Texture coordinates as red and green.
+}
+
+impl DebugView {
+    /// Every view, in the order of the viewer's function keys.
+    pub const ALL: [DebugView; 5] = [
+        DebugView::Color,
+        DebugView::Depth,
+        DebugView::ShadowMap,
+        DebugView::Normals,
+        DebugView::Uvs,
+    ];
+
+    /// Name of the built-in pipeline, which renders this view in place of the scene's pipeline, if any.
+    pub fn get_pipeline_name(&self) -> Option<&'static str> {
+        match self {
+            DebugView::Normals => return Some("debug_normals"),
+            DebugView::Uvs => return Some("debug_uvs"),
+            _ => return None,
+        }
+    }
+}
+
+/// Maps the first value of every stride of the depth buffer to gray rgb values in [0, 255], closest geometry
+/// being white and the farthest one dark gray. Only covered values count towards the range, uncovered ones
+/// are black.
+pub fn normalize_depth(depth_buffer: &[f32], stride: usize) -> Vec<f32> {
+    let (min_depth, max_depth) = depth_buffer
+        .par_iter()
+        .step_by(stride)
+        .filter(|&&value| value != f32::MIN)
+        .fold(
+            || (f32::MAX, f32::MIN),
+            |(min, max), &value| (min.min(value), max.max(value)),
+        )
+        .reduce(
+            || (f32::MAX, f32::MIN),
+            |(min_1, max_1), (min_2, max_2)| (min_1.min(min_2), max_1.max(max_2)),
+        );
+    // Keeping flat geometry, covering a single depth, visible.
+    let range = (max_depth - min_depth).max(f32::EPSILON);
+    return depth_buffer
+        .par_iter()
+        .step_by(stride)
+        .flat_map_iter(|&value| {
+            if value == f32::MIN {
+                return [0.0; 3];
+            }
+            return [255.0 * (0.1 + 0.9 * (value - min_depth) / range); 3];
+        })
+        .collect();
+}
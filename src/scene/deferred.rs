@@ -0,0 +1,73 @@
+use na::{Point3, Vector3};
+use nalgebra as na;
+use rayon::prelude::*;
+
+use super::material::Material;
+use super::shader::{add_lights_diffuse, get_lights_diffuse, Uniforms};
+
+/// Surface attributes of the closest fragment of a single pixel, written by the geometry pass of the deferred
+/// path. Depth of the texel is the first z-buffer sample of the pixel.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GBufferTexel {
+    pub albedo: Vector3<f32>,   // Linear color in [0, 255].
+    pub t_normal: Vector3<f32>, // Normal, transformed the same way as the light direction.
+    pub specular: f32, // Exponent of the specular highlight, from the material or the specular map.
+    pub material: Material, // Lighting coefficients of the object, the fragment belongs to.
+}
+
+/// Shades every covered sample of the color buffer from the G-buffer texel of its pixel, using the same
+/// lighting model as the specular pipeline. Positions are reconstructed from the z-buffer, so lighting is
+/// only evaluated once per pixel, however many polygons were drawn over it.
+pub fn apply_deferred_lighting(
+    uniforms: &Uniforms,
+    gbuffer: &[GBufferTexel],
+    z_buffer: &[f32],
+    samples: usize,
+    color_buffer: &mut [f32],
+) {
+    let width = uniforms.width as usize;
+    // From raster coordinates back to the model, then into the frame of the transformed light direction.
+    let raster_to_t_matrix = uniforms.m_matrix * uniforms.vpmv_matrix.try_inverse().unwrap();
+    color_buffer
+        .par_chunks_mut(3 * samples)
+        .zip(gbuffer.par_iter())
+        .enumerate()
+        .for_each(|(pixel_index, (pixel, texel))| {
+            let z_value = z_buffer[pixel_index * samples];
+            if z_value == f32::MIN {
+                return;
+            }
+            let raster_position = Point3::new(
+                (pixel_index % width) as f32,
+                (pixel_index / width) as f32,
+                z_value,
+            );
+            let t_position =
+                Point3::from_homogeneous(raster_to_t_matrix * raster_position.to_homogeneous())
+                    .unwrap()
+                    .coords;
+
+            let reflected_t_light_direction = (2.0
+                * (texel.t_normal * uniforms.t_light_direction.dot(&texel.t_normal))
+                - uniforms.t_light_direction)
+                .normalize();
+            let diff_coef = uniforms.t_light_direction.dot(&texel.t_normal);
+            let material = &texel.material;
+            let spec_coef =
+                material.specular * reflected_t_light_direction.z.max(0.0).powf(texel.specular);
+            let color = add_lights_diffuse(
+                texel.albedo
+                    * (material.diffuse * diff_coef + spec_coef).max(0.0)
+                    * uniforms.light_intensity
+                    + texel.albedo * material.ambient,
+                texel.albedo,
+                get_lights_diffuse(uniforms, t_position, texel.t_normal) * material.diffuse,
+            );
+            for sample in 0..samples {
+                if z_buffer[pixel_index * samples + sample] == f32::MIN {
+                    continue;
+                }
+                pixel[3 * sample..3 * sample + 3].copy_from_slice(color.as_slice());
+            }
+        });
+}
@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+
+use na::{Vector2, Vector3};
+use nalgebra as na;
+use obj::raw::object::Polygon;
+use obj::raw::RawObj;
+
+/// Uv, at which the displacement map is read for a position of the model, and the direction it's moved along.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DisplacementVertex {
+    pub uv: Vector2<f32>,
+    pub normal: Vector3<f32>,
+}
+
+/// Displacement of every position of the model - uv of the first corner at the position and the average of
+/// the normals of all the corners there. Corners, sharing a position along uv seams and hard edges, move
+/// together, so the surface doesn't crack.
+pub fn get_displacement_vertices(obj: &RawObj) -> Vec<DisplacementVertex> {
+    let mut vertices = vec![DisplacementVertex::default(); obj.positions.len()];
+    let mut has_uv = vec![false; obj.positions.len()];
+    for polygon in &obj.polygons {
+        let indices = match polygon {
+            Polygon::PTN(indices) => indices,
+            _ => continue,
+        };
+        for &(p, t, n) in indices {
+            if !has_uv[p] {
+                let (u, v, _) = obj.tex_coords[t];
+                vertices[p].uv = Vector2::new(u, v);
+                has_uv[p] = true;
+            }
+            let (x, y, z) = obj.normals[n];
+            vertices[p].normal += Vector3::new(x, y, z).normalize();
+        }
+    }
+    for vertex in vertices.iter_mut() {
+        vertex.normal = vertex
+            .normal
+            .try_normalize(0.0)
+            .unwrap_or_else(Vector3::zeros);
+    }
+    return vertices;
+}
+
+/// Splits every polygon into 4 triangles at the midpoints of the edges of its first triangle, which is the one
+/// being rendered, giving displacement more vertices to move. Midpoints of the edges are shared by the
+/// polygons on both sides, positions and uvs are averaged, while normals are averaged and normalized.
+/// Expects polygons with positions, uvs and normals, see `generate_normals`, others are kept as they are.
+pub fn subdivide(obj: &mut RawObj) {
+    let mut position_midpoints: HashMap<(usize, usize), usize> = HashMap::new();
+    let mut uv_midpoints: HashMap<(usize, usize), usize> = HashMap::new();
+    let mut normal_midpoints: HashMap<(usize, usize), usize> = HashMap::new();
+    let polygons = std::mem::take(&mut obj.polygons);
+    for polygon in polygons {
+        let indices = match &polygon {
+            Polygon::PTN(indices) if indices.len() >= 3 => indices,
+            _ => {
+                obj.polygons.push(polygon);
+                continue;
+            }
+        };
+        let midpoints = [0, 1, 2].map(|k| {
+            let (from, to) = (indices[k], indices[(k + 1) % 3]);
+            let p = *position_midpoints
+                .entry((from.0.min(to.0), from.0.max(to.0)))
+                .or_insert_with(|| {
+                    let (a, b) = (obj.positions[from.0], obj.positions[to.0]);
+                    obj.positions.push((
+                        (a.0 + b.0) / 2.0,
+                        (a.1 + b.1) / 2.0,
+                        (a.2 + b.2) / 2.0,
+                        (a.3 + b.3) / 2.0,
+                    ));
+                    return obj.positions.len() - 1;
+                });
+            let t = *uv_midpoints
+                .entry((from.1.min(to.1), from.1.max(to.1)))
+                .or_insert_with(|| {
+                    let (a, b) = (obj.tex_coords[from.1], obj.tex_coords[to.1]);
+                    obj.tex_coords
+                        .push(((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0, (a.2 + b.2) / 2.0));
+                    return obj.tex_coords.len() - 1;
+                });
+            let n = *normal_midpoints
+                .entry((from.2.min(to.2), from.2.max(to.2)))
+                .or_insert_with(|| {
+                    let (a, b) = (obj.normals[from.2], obj.normals[to.2]);
+                    let normal = (Vector3::new(a.0, a.1, a.2) + Vector3::new(b.0, b.1, b.2))
+                        .try_normalize(0.0)
+                        .unwrap_or_else(|| Vector3::new(a.0, a.1, a.2));
+                    obj.normals.push((normal.x, normal.y, normal.z));
+                    return obj.normals.len() - 1;
+                });
+            return (p, t, n);
+        });
+        // Corner triangles keep the winding of the polygon, the middle one is made of the midpoints.
+        for k in 0..3 {
+            obj.polygons.push(Polygon::PTN(vec![
+                indices[k],
+                midpoints[k],
+                midpoints[(k + 2) % 3],
+            ]));
+        }
+        obj.polygons.push(Polygon::PTN(midpoints.to_vec()));
+    }
+}
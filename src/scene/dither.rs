@@ -0,0 +1,258 @@
+use image::{Rgb, RgbImage};
+use na::{vector, Vector3};
+use nalgebra as na;
+use rayon::prelude::*;
+
+/// The 16 colors of the EGA and of the first entries of the default VGA palette.
+const EGA_COLORS: [u32; 16] = [
+    0x000000, 0x0000aa, 0x00aa00, 0x00aaaa, 0xaa0000, 0xaa00aa, 0xaa5500, 0xaaaaaa, 0x555555,
+    0x5555ff, 0x55ff55, 0x55ffff, 0xff5555, 0xff55ff, 0xffff55, 0xffffff,
+];
+/// Black, cyan, magenta and white of the high intensity CGA palette 1.
+const CGA_COLORS: [u32; 4] = [0x000000, 0x55ffff, 0xff55ff, 0xffffff];
+/// Four shades of green of the original Game Boy screen.
+const GAMEBOY_COLORS: [u32; 4] = [0x0f380f, 0x306230, 0x8bac0f, 0x9bbc0f];
+/// Gray ramp of the default VGA palette in 6-bit DAC values.
+const VGA_GRAYS: [u8; 16] = [0, 5, 8, 11, 14, 17, 20, 24, 28, 32, 36, 40, 45, 50, 56, 63];
+/// Channel levels of the hue wheels of the default VGA palette in 6-bit DAC values, from the lowest to the
+/// highest, for high, medium and low intensity, each with high, medium and low saturation.
+const VGA_WHEEL_LEVELS: [[u8; 5]; 9] = [
+    [0, 16, 31, 47, 63],
+    [31, 39, 47, 55, 63],
+    [45, 49, 54, 58, 63],
+    [0, 7, 14, 21, 28],
+    [14, 17, 21, 24, 28],
+    [20, 22, 24, 26, 28],
+    [0, 4, 8, 12, 16],
+    [8, 10, 12, 14, 16],
+    [11, 12, 13, 15, 16],
+];
+
+/// Size of the side of the Bayer threshold matrix.
+const BAYER_SIZE: usize = 8;
+
+/// How the error of mapping a color to the closest color of the palette is spread over the neighbours.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Dithering {
+    #[default]
+    None, // Every pixel gets the closest color, leaving flat bands.
+    Bayer,          // Ordered dithering with a fixed threshold pattern, stable between frames.
+    FloydSteinberg, // Error diffusion to the right and bottom neighbours, noisier but finer.
+}
+
+impl Dithering {
+    /// Parses dithering from its command line name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "none" => return Some(Dithering::None),
+            "bayer" => return Some(Dithering::Bayer),
+            "floyd-steinberg" => return Some(Dithering::FloydSteinberg),
+            _ => return None,
+        }
+    }
+}
+
+/// Limited set of colors, which the output frame is quantized to, for retro looks or e-ink and terminal
+/// displays.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+    colors: Vec<Vector3<f32>>, // Distinct colors in [0, 255], as they are displayed.
+}
+
+impl Palette {
+    /// Palette of the colors, dropping repeated ones.
+    pub fn new(colors: &[Rgb<u8>]) -> Self {
+        assert!(
+            !colors.is_empty(),
+            "Palette should have at least one color!"
+        );
+        let mut distinct: Vec<Vector3<f32>> = Vec::new();
+        for color in colors {
+            let color = Vector3::from(color.0).map(|value| value as f32);
+            if !distinct.contains(&color) {
+                distinct.push(color);
+            }
+        }
+        return Self { colors: distinct };
+    }
+
+    /// Parses a built-in palette from its command line name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let from_hex = |colors: &[u32]| {
+            let colors: Vec<Rgb<u8>> = colors
+                .iter()
+                .map(|&color| Rgb([(color >> 16) as u8, (color >> 8) as u8, color as u8]))
+                .collect();
+            return Palette::new(&colors);
+        };
+        match name {
+            "1bit" => return Some(from_hex(&[0x000000, 0xffffff])),
+            "gray4" => return Some(from_hex(&[0x000000, 0x555555, 0xaaaaaa, 0xffffff])),
+            "cga" => return Some(from_hex(&CGA_COLORS)),
+            "ega" => return Some(from_hex(&EGA_COLORS)),
+            "gameboy" => return Some(from_hex(&GAMEBOY_COLORS)),
+            "vga" => return Some(Palette::new(&get_vga_colors())),
+            "web" => {
+                let levels = [0x00, 0x33, 0x66, 0x99, 0xcc, 0xff];
+                let mut colors = Vec::new();
+                for r in levels {
+                    for g in levels {
+                        for b in levels {
+                            colors.push(Rgb([r, g, b]));
+                        }
+                    }
+                }
+                return Some(Palette::new(&colors));
+            }
+            _ => return None,
+        }
+    }
+
+    /// Color of the palette, closest to the color in [0, 255].
+    fn get_nearest(&self, color: Vector3<f32>) -> Vector3<f32> {
+        let mut nearest = self.colors[0];
+        let mut nearest_distance = f32::MAX;
+        for &candidate in &self.colors {
+            let distance = (candidate - color).norm_squared();
+            if distance < nearest_distance {
+                nearest = candidate;
+                nearest_distance = distance;
+            }
+        }
+        return nearest;
+    }
+
+    /// Typical step between the colors of the palette along a single channel - the average over the colors of
+    /// the largest channel difference to the closest other color. Ordered dithering offsets colors by up to
+    /// half of it, so any color between two neighbours of the palette gets a mix of them.
+    fn get_spread(&self) -> f32 {
+        if self.colors.len() < 2 {
+            return 0.0;
+        }
+        let mut sum = 0.0;
+        for (i, color) in self.colors.iter().enumerate() {
+            let mut closest = f32::MAX;
+            for (j, other) in self.colors.iter().enumerate() {
+                if i != j {
+                    closest = closest.min((other - color).abs().max());
+                }
+            }
+            sum += closest;
+        }
+        return sum / self.colors.len() as f32;
+    }
+
+    /// Maps every pixel of the displayed image to a color of the palette.
+    pub fn apply(&self, dithering: Dithering, image: &mut RgbImage) {
+        let to_vector =
+            |pixel: &[u8]| vector![pixel[0], pixel[1], pixel[2]].map(|value| value as f32);
+        let width = image.width() as usize;
+        match dithering {
+            Dithering::None | Dithering::Bayer => {
+                let thresholds = get_bayer_matrix();
+                let spread = match dithering {
+                    Dithering::Bayer => self.get_spread(),
+                    _ => 0.0,
+                };
+                image
+                    .par_chunks_mut(3)
+                    .enumerate()
+                    .for_each(|(index, pixel)| {
+                        let (x, y) = (index % width % BAYER_SIZE, index / width % BAYER_SIZE);
+                        let offset = thresholds[x + y * BAYER_SIZE] * spread;
+                        let nearest = self.get_nearest(to_vector(pixel).add_scalar(offset));
+                        for c in 0..3 {
+                            pixel[c] = nearest[c] as u8;
+                        }
+                    });
+            }
+            Dithering::FloydSteinberg => {
+                let height = image.height() as usize;
+                // Errors, carried over to the current and the next row.
+                let mut errors = vec![Vector3::zeros(); width];
+                let mut next_errors = vec![Vector3::zeros(); width];
+                for y in 0..height {
+                    for x in 0..width {
+                        let index = 3 * (x + y * width);
+                        let pixel = &mut image.as_mut()[index..index + 3];
+                        let color = to_vector(pixel) + errors[x];
+                        let nearest = self.get_nearest(color);
+                        for c in 0..3 {
+                            pixel[c] = nearest[c] as u8;
+                        }
+                        let error = color - nearest;
+                        if x + 1 < width {
+                            errors[x + 1] += error * 7.0 / 16.0;
+                            next_errors[x + 1] += error / 16.0;
+                        }
+                        if x > 0 {
+                            next_errors[x - 1] += error * 3.0 / 16.0;
+                        }
+                        next_errors[x] += error * 5.0 / 16.0;
+                    }
+                    errors = std::mem::replace(&mut next_errors, vec![Vector3::zeros(); width]);
+                }
+            }
+        }
+    }
+}
+
+/// Thresholds of the ordered dithering in [-0.5, 0.5), built recursively, so that consecutive thresholds are
+/// as far apart as possible.
+fn get_bayer_matrix() -> Vec<f32> {
+    let mut matrix = vec![0u32];
+    let mut size = 1;
+    while size < BAYER_SIZE {
+        let mut next = vec![0; 4 * size * size];
+        for y in 0..size {
+            for x in 0..size {
+                let value = 4 * matrix[x + y * size];
+                next[x + y * 2 * size] = value;
+                next[x + size + y * 2 * size] = value + 2;
+                next[x + (y + size) * 2 * size] = value + 3;
+                next[x + size + (y + size) * 2 * size] = value + 1;
+            }
+        }
+        matrix = next;
+        size *= 2;
+    }
+    let n = (size * size) as f32;
+    return matrix
+        .into_iter()
+        .map(|value| (value as f32 + 0.5) / n - 0.5)
+        .collect();
+}
+
+/// The 248 colors of the default VGA palette: the EGA colors, a gray ramp and nine wheels of 24 hues, going
+/// from blue over red and green back to blue, of decreasing intensity and saturation.
+fn get_vga_colors() -> Vec<Rgb<u8>> {
+    // 6-bit DAC values are scaled to 8 bits the way VGA emulators do.
+    let to_u8 = |value: u8| (value << 2) | (value >> 4);
+    let mut colors: Vec<Rgb<u8>> = EGA_COLORS
+        .iter()
+        .map(|&color| Rgb([(color >> 16) as u8, (color >> 8) as u8, color as u8]))
+        .collect();
+    colors.extend(VGA_GRAYS.iter().map(|&gray| Rgb([to_u8(gray); 3])));
+    for levels in VGA_WHEEL_LEVELS {
+        let (low, high) = (levels[0], levels[4]);
+        // A channel rises from low to high or falls back over 4 steps between the 6 corners of the wheel.
+        let mut color = vector![low, low, high];
+        for (channel, rising) in [
+            (0, true),
+            (2, false),
+            (1, true),
+            (0, false),
+            (2, true),
+            (1, false),
+        ] {
+            for step in 0..4 {
+                colors.push(Rgb(color.map(to_u8).into()));
+                color[channel] = match rising {
+                    true => levels[step + 1],
+                    false => levels[3 - step],
+                };
+            }
+        }
+    }
+    return colors;
+}
@@ -0,0 +1,110 @@
+use na::{vector, Vector2};
+use nalgebra as na;
+use rayon::prelude::*;
+
+use super::fog::get_view_depth;
+use super::projection::Projection;
+
+/// Parameters of the depth of field, blurring everything in front of and behind the focal plane.
+#[derive(Clone, Copy, Debug)]
+pub struct DofSettings {
+    pub focal_distance: f32, // Distance in front of the camera in view space, which is in focus.
+    // Radius of the circle of confusion in pixels of the rendered frame per unit of distance from the focal
+    // plane, and the largest radius, which the background, where nothing was drawn, gets.
+    pub aperture: f32,
+    pub max_radius: f32,
+    pub samples: u32, // Number of color lookups per pixel, spread over the disk of the largest circle.
+}
+
+impl Default for DofSettings {
+    fn default() -> Self {
+        return DofSettings {
+            focal_distance: 1.0,
+            aperture: 8.0,
+            max_radius: 8.0,
+            samples: 48,
+        };
+    }
+}
+
+/// Offsets inside of the disk of the radius, spread evenly with the golden angle.
+fn get_disk_kernel(samples: u32, radius: f32) -> Vec<Vector2<f32>> {
+    let golden_angle = std::f32::consts::PI * (3.0 - 5.0f32.sqrt());
+    return (0..samples)
+        .map(|i| {
+            let r = radius * ((i as f32 + 0.5) / samples as f32).sqrt();
+            let angle = golden_angle * i as f32;
+            return vector![r * angle.cos(), r * angle.sin()];
+        })
+        .collect();
+}
+
+/// Blurs every pixel of the frame buffer by its circle of confusion, found from the depth buffer, which holds
+/// depth_stride values per pixel, of which only the first one is used. Gathers neighbours, whose circles
+/// reach the pixel, so blurry foreground spreads over the focused background, while neighbours behind the
+/// pixel are limited by its own circle, so focused foreground doesn't pick up the blurry background.
+pub fn apply_dof(
+    settings: DofSettings,
+    projection: &Projection,
+    frame_buffer: &mut [f32],
+    depth_buffer: &[f32],
+    depth_stride: usize,
+    width: usize,
+    height: usize,
+) {
+    // Depth and circle of confusion of every pixel, the background being infinitely far away.
+    let pixels: Vec<(f32, f32)> = (0..width * height)
+        .into_par_iter()
+        .map(|index| {
+            let z_value = depth_buffer[depth_stride * index];
+            if z_value == f32::MIN {
+                return (f32::MAX, settings.max_radius);
+            }
+            let depth = get_view_depth(projection, z_value);
+            let radius = settings.aperture * (depth - settings.focal_distance).abs();
+            return (depth, radius.min(settings.max_radius));
+        })
+        .collect();
+    let kernel = get_disk_kernel(settings.samples, settings.max_radius);
+    let source = frame_buffer.to_vec();
+
+    frame_buffer
+        .par_chunks_mut(3 * width)
+        .enumerate()
+        .for_each(|(y, row)| {
+            for x in 0..width {
+                // Focused pixels are gathered as well, since blurry foreground may spread over them.
+                let (depth, radius) = pixels[x + y * width];
+                let mut color_sum = [0.0; 3];
+                let mut weight_sum = 0.0;
+                // Pixel itself always counts, so the sum is never empty.
+                for offset in std::iter::once(Vector2::zeros()).chain(kernel.iter().copied()) {
+                    let sx = (x as f32 + offset.x).round() as i32;
+                    let sy = (y as f32 + offset.y).round() as i32;
+                    if sx < 0 || sy < 0 || sx >= width as i32 || sy >= height as i32 {
+                        continue;
+                    }
+                    let index = sx as usize + sy as usize * width;
+                    let (sample_depth, sample_radius) = pixels[index];
+                    let reach = match sample_depth > depth {
+                        true => sample_radius.min(radius),
+                        false => sample_radius,
+                    };
+                    // Smooth edge of the circle, one pixel wide.
+                    let weight = (reach - offset.norm() + 0.5).clamp(0.0, 1.0);
+                    if weight == 0.0 {
+                        continue;
+                    }
+                    for c in 0..3 {
+                        color_sum[c] += source[3 * index + c] * weight;
+                    }
+                    weight_sum += weight;
+                }
+                if weight_sum > 0.0 {
+                    for c in 0..3 {
+                        row[3 * x + c] = color_sum[c] / weight_sum;
+                    }
+                }
+            }
+        });
+}
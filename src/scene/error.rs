@@ -0,0 +1,34 @@
+use std::io;
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+/// Failure of loading an asset or setting up the scene, which callers can match on, e.g. to fall back to a
+/// default texture, when the asset doesn't have one.
+#[derive(Debug, Error)]
+pub enum RendererError {
+    #[error("asset not found: {0}")]
+    AssetNotFound(PathBuf),
+    #[error("failed to read {path}: {source}")]
+    Io { path: PathBuf, source: io::Error },
+    #[error("unsupported texture format of {path}: {source}")]
+    UnsupportedTextureFormat {
+        path: PathBuf,
+        source: image::ImageError,
+    },
+    #[error("invalid OBJ model {path}: {source}")]
+    InvalidObj {
+        path: PathBuf,
+        source: obj::ObjError,
+    },
+    #[error("invalid glTF model {path}: {message}")]
+    InvalidGltf { path: PathBuf, message: String },
+    #[error("invalid PLY model {path}: {message}")]
+    InvalidPly { path: PathBuf, message: String },
+    #[error("invalid STL model {path}: {message}")]
+    InvalidStl { path: PathBuf, message: String },
+    #[error("invalid palette {path}: {message}")]
+    InvalidPalette { path: PathBuf, message: String },
+    #[error("unknown shader pipeline '{0}'")]
+    UnknownPipelineName(String),
+}
@@ -0,0 +1,90 @@
+use na::{vector, Vector3};
+use nalgebra as na;
+use rayon::prelude::*;
+
+use super::projection::Projection;
+
+/// How fog thickens with the distance in front of the camera.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FogMode {
+    Linear { start: f32, end: f32 }, // No fog before start, only fog after end.
+    Exponential { density: f32 },    // Fraction of the color left is exp(-density * depth).
+}
+
+/// Fog, blending fragments towards the fog color by their view space depth.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FogSettings {
+    pub mode: FogMode,
+    pub color: Vector3<f32>, // Color in [0, 1].
+}
+
+impl FogSettings {
+    /// Parses fog from a spec like `linear:0.5,2.5:0.6,0.6,0.7`, returning None for malformed specs. Color is
+    /// optional and is gray by default. Supported forms:
+    /// - linear:start,end:color
+    /// - exp:density:color
+    pub fn from_spec(spec: &str) -> Option<Self> {
+        let parts: Vec<&str> = spec.split(':').collect();
+        let values = |part: &str| -> Option<Vec<f32>> {
+            return part.split(',').map(|value| value.parse().ok()).collect();
+        };
+        let mode = match (parts[0], parts.len()) {
+            ("linear", 2 | 3) => match values(parts[1])?.as_slice() {
+                &[start, end] => FogMode::Linear { start, end },
+                _ => return None,
+            },
+            ("exp", 2 | 3) => FogMode::Exponential {
+                density: parts[1].parse().ok()?,
+            },
+            _ => return None,
+        };
+        let color = match parts.get(2) {
+            Some(part) => match values(part)?.as_slice() {
+                &[r, g, b] => vector![r, g, b],
+                _ => return None,
+            },
+            None => vector![0.5, 0.5, 0.5],
+        };
+        return Some(FogSettings { mode, color });
+    }
+
+    /// Amount of fog in [0, 1] at the depth in front of the camera.
+    fn get_factor(&self, depth: f32) -> f32 {
+        match self.mode {
+            FogMode::Linear { start, end } => {
+                return ((depth - start) / (end - start)).clamp(0.0, 1.0);
+            }
+            FogMode::Exponential { density } => return 1.0 - (-density * depth.max(0.0)).exp(),
+        }
+    }
+}
+
+/// Inverts viewport and projection of the z-buffer value, getting distance in front of the camera in view
+/// space.
+pub(super) fn get_view_depth(projection: &Projection, z_value: f32) -> f32 {
+    let ndc_z = 2.0 * z_value / 255.0 - 1.0;
+    return projection.get_view_depth(ndc_z);
+}
+
+/// Blends every covered sample of the color buffer towards the fog color. Depth buffer holds a value for
+/// every color sample, f32::MIN for uncovered ones.
+pub fn apply_fog(
+    settings: FogSettings,
+    projection: &Projection,
+    color_buffer: &mut [f32],
+    depth_buffer: &[f32],
+) {
+    let fog_color = settings.color * 255.0;
+    color_buffer
+        .par_chunks_mut(3)
+        .zip(depth_buffer.par_iter())
+        .for_each(|(sample, &z_value)| {
+            if z_value == f32::MIN {
+                return;
+            }
+            let factor = settings.get_factor(get_view_depth(projection, z_value));
+            for c in 0..3 {
+                sample[c] = sample[c] * (1.0 - factor) + fog_color[c] * factor;
+            }
+        });
+}
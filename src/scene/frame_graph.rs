@@ -0,0 +1,368 @@
+use std::collections::{HashMap, HashSet};
+
+/// Buffer or state of the frame, which the passes read and write.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FrameResource {
+    ShadowMaps,    // Shadow maps of the additional lights and the cascades of the main light.
+    RenderTargets, // Offscreen targets, sampled by the shaders.
+    Camera,        // Jitter and the previous transform of the camera.
+    Samples,       // Sample buffer of multisampling, which the passes before resolving draw into.
+    Color, // Frame buffer, which holds a frame with multisampling only once the samples are resolved.
+    Depth,
+    GBuffer,
+    Velocity,
+}
+
+/// Step of rendering the frame, declaring the resources it reads and writes, from which the frame graph
+/// finds what it depends on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FramePass {
+    Shadows,       // Cube shadow maps of the lights and the cascaded shadow map.
+    RenderTargets, // Model, rendered into the offscreen targets.
+    Camera,        // Jitter of temporal anti-aliasing and the velocity buffer transform.
+    Background,    // Skybox or background behind the model.
+    Geometry,      // Shader pipeline or the geometry pass of the deferred path.
+    DeferredLighting,
+    DebugView, // Pipeline of the debug view, replacing the geometry and the effects.
+    Fog,
+    Resolve, // Averaging the samples of multisampling into pixels.
+    Taa,
+    Ssao,
+    Dof,
+    MotionBlur,
+    Bloom,
+    PostPasses,    // Custom post-processing passes, in the order they were added.
+    ReferenceDiff, // Difference to the ray traced reference.
+}
+
+impl FramePass {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "shadows" => return Some(FramePass::Shadows),
+            "render_targets" => return Some(FramePass::RenderTargets),
+            "camera" => return Some(FramePass::Camera),
+            "background" => return Some(FramePass::Background),
+            "geometry" => return Some(FramePass::Geometry),
+            "deferred_lighting" => return Some(FramePass::DeferredLighting),
+            "debug_view" => return Some(FramePass::DebugView),
+            "fog" => return Some(FramePass::Fog),
+            "resolve" => return Some(FramePass::Resolve),
+            "taa" => return Some(FramePass::Taa),
+            "ssao" => return Some(FramePass::Ssao),
+            "dof" => return Some(FramePass::Dof),
+            "motion_blur" => return Some(FramePass::MotionBlur),
+            "bloom" => return Some(FramePass::Bloom),
+            "post" => return Some(FramePass::PostPasses),
+            "reference_diff" => return Some(FramePass::ReferenceDiff),
+            _ => return None,
+        }
+    }
+
+    /// Resources, which the pass reads, when the frame is multisampled or not.
+    pub fn reads(self, multisampled: bool) -> Vec<FrameResource> {
+        use FrameResource::*;
+        let color = get_color_target(multisampled);
+        match self {
+            FramePass::Shadows | FramePass::Camera | FramePass::Background => return vec![],
+            FramePass::RenderTargets | FramePass::DebugView => return vec![ShadowMaps],
+            // Translucent polygons blend over the background.
+            FramePass::Geometry => return vec![ShadowMaps, RenderTargets, Camera, color],
+            FramePass::DeferredLighting => return vec![GBuffer, Depth, color],
+            FramePass::Fog => return vec![color, Depth],
+            FramePass::Resolve => return vec![Samples],
+            FramePass::Ssao | FramePass::Dof => return vec![Color, Depth],
+            FramePass::Taa => return vec![Camera, Color, Velocity],
+            FramePass::MotionBlur => return vec![Color, Velocity],
+            FramePass::PostPasses => return vec![Color, Depth, Velocity],
+            FramePass::Bloom | FramePass::ReferenceDiff => return vec![Color],
+        }
+    }
+
+    /// Resources, which the pass writes, when the frame is multisampled or not.
+    pub fn writes(self, multisampled: bool) -> Vec<FrameResource> {
+        use FrameResource::*;
+        let color = get_color_target(multisampled);
+        match self {
+            FramePass::Shadows => return vec![ShadowMaps],
+            FramePass::RenderTargets => return vec![RenderTargets],
+            FramePass::Camera => return vec![Camera],
+            FramePass::Geometry => return vec![color, Depth, GBuffer, Velocity],
+            FramePass::DebugView => return vec![color, Depth],
+            FramePass::Background | FramePass::DeferredLighting | FramePass::Fog => {
+                return vec![color]
+            }
+            _ => return vec![Color],
+        }
+    }
+}
+
+/// Buffer, which the passes before resolving draw the color into.
+fn get_color_target(multisampled: bool) -> FrameResource {
+    match multisampled {
+        true => return FrameResource::Samples,
+        false => return FrameResource::Color,
+    }
+}
+
+/// Passes of the frame, ordered by the resources they declare: a pass runs after the passes, which write a
+/// resource it reads, and passes, which write a resource without reading it, run before the ones, which update
+/// it in place. Order of the in-place updates of the same resource, e.g. of the effects, isn't implied by the
+/// resources, so it's given by explicit orderings. Every read sees what the last pass, which wrote the resource
+/// before it, left there - like a new version of the resource for every write, so a skipped pass hands the
+/// version before it to the passes after it.
+pub struct FrameGraph {
+    multisampled: bool,
+    passes: Vec<FramePass>,
+    skipped: HashSet<FramePass>, // Passes, which only run, if a kept pass can't do without them.
+    orderings: Vec<Vec<FramePass>>,
+}
+
+impl FrameGraph {
+    pub fn new(multisampled: bool) -> Self {
+        return FrameGraph {
+            multisampled,
+            passes: Vec::new(),
+            skipped: HashSet::new(),
+            orderings: Vec::new(),
+        };
+    }
+
+    pub fn add_pass(&mut self, pass: FramePass) {
+        self.passes.push(pass);
+    }
+
+    /// Skipping the added pass, unless a pass after it reads a resource, which has no version without it, e.g.
+    /// resolve with multisampling, without which the frame buffer never holds the frame.
+    pub fn skip_pass(&mut self, pass: FramePass) {
+        self.skipped.insert(pass);
+    }
+
+    /// Passes of the list, which are in the graph, run in the order of the list.
+    pub fn add_ordering(&mut self, passes: &[FramePass]) {
+        self.orderings.push(passes.to_vec());
+    }
+
+    /// Passes, which contribute to the color of the frame, in dependency order. Passes, whose results no pass
+    /// after them reads, are culled, e.g. shadows, when the geometry is skipped. Error is the passes, which
+    /// can't be ordered, as they depend on each other in a cycle, or run after such passes.
+    pub fn schedule(&self) -> Result<Vec<FramePass>, Vec<FramePass>> {
+        let order = self.sort()?;
+        let mut kept: Vec<bool> = order
+            .iter()
+            .map(|pass| !self.skipped.contains(pass))
+            .collect();
+        // Bringing back skipped passes, which kept passes depend on, until every read has a version.
+        let (dependencies, output) = loop {
+            match self.find_dependencies(&order, &kept) {
+                Ok(dependencies) => break dependencies,
+                Err(index) => kept[index] = true,
+            }
+        };
+        // Walking back from the pass, which writes the final color, marking every pass it depends on.
+        let mut used = vec![false; order.len()];
+        if let Some(output) = output {
+            used[output] = true;
+        }
+        for index in (0..order.len()).rev() {
+            if used[index] {
+                for &dependency in &dependencies[index] {
+                    used[dependency] = true;
+                }
+            }
+        }
+        return Ok(order
+            .into_iter()
+            .zip(used)
+            .filter(|(_, used)| *used)
+            .map(|(pass, _)| pass)
+            .collect());
+    }
+
+    /// Topological order of the added passes, where independent passes keep the order of adding. Error is the
+    /// passes, which are left unordered by a cycle.
+    fn sort(&self) -> Result<Vec<FramePass>, Vec<FramePass>> {
+        let count = self.passes.len();
+        let mut successors = vec![Vec::new(); count];
+        let mut add_edge = |before: usize, after: usize| {
+            if before != after && !successors[before].contains(&after) {
+                successors[before].push(after);
+            }
+        };
+        for (before, pass) in self.passes.iter().enumerate() {
+            let writes = pass.writes(self.multisampled);
+            let reads = pass.reads(self.multisampled);
+            for (after, other) in self.passes.iter().enumerate() {
+                let other_reads = other.reads(self.multisampled);
+                let other_writes = other.writes(self.multisampled);
+                for resource in &writes {
+                    let produces = !reads.contains(resource);
+                    let other_only_reads = !other_writes.contains(resource);
+                    // Producing before updating or reading, updating before only reading.
+                    if other_reads.contains(resource) && (produces || other_only_reads) {
+                        add_edge(before, after);
+                    }
+                }
+            }
+        }
+        for ordering in &self.orderings {
+            let indices: Vec<usize> = ordering
+                .iter()
+                .filter_map(|pass| self.passes.iter().position(|added| added == pass))
+                .collect();
+            for pair in indices.windows(2) {
+                add_edge(pair[0], pair[1]);
+            }
+        }
+        let mut predecessor_counts = vec![0; count];
+        for after in successors.iter().flatten() {
+            predecessor_counts[*after] += 1;
+        }
+        let mut order = Vec::with_capacity(count);
+        let mut done = vec![false; count];
+        while order.len() < count {
+            let index =
+                match (0..count).find(|&index| !done[index] && predecessor_counts[index] == 0) {
+                    Some(index) => index,
+                    None => {
+                        return Err((0..count)
+                            .filter(|&index| !done[index])
+                            .map(|index| self.passes[index])
+                            .collect())
+                    }
+                };
+            done[index] = true;
+            for &after in &successors[index] {
+                predecessor_counts[after] -= 1;
+            }
+            order.push(self.passes[index]);
+        }
+        return Ok(order);
+    }
+
+    /// Indices of the kept passes in the order, whose versions of the read resources every kept pass sees, and
+    /// the index of the pass, which writes the final color, if any. Resources, other than the frame buffer with
+    /// multisampling, have a version before any pass, either cleared or left from the previous frame. Error is
+    /// the index of a skipped pass, which a kept pass depends on.
+    fn find_dependencies(
+        &self,
+        order: &[FramePass],
+        kept: &[bool],
+    ) -> Result<(Vec<Vec<usize>>, Option<usize>), usize> {
+        let mut last_writers = HashMap::new();
+        let mut skipped_writers = HashMap::new();
+        let mut dependencies = Vec::with_capacity(order.len());
+        for (index, pass) in order.iter().enumerate() {
+            if !kept[index] {
+                for resource in pass.writes(self.multisampled) {
+                    skipped_writers.insert(resource, index);
+                }
+                dependencies.push(Vec::new());
+                continue;
+            }
+            let mut pass_dependencies = Vec::new();
+            for resource in pass.reads(self.multisampled) {
+                match last_writers.get(&resource) {
+                    Some(&writer) => pass_dependencies.push(writer),
+                    None => self.check_initial(resource, &skipped_writers)?,
+                }
+            }
+            dependencies.push(pass_dependencies);
+            for resource in pass.writes(self.multisampled) {
+                last_writers.insert(resource, index);
+                skipped_writers.remove(&resource);
+            }
+        }
+        // Presenting the frame is the last read of the color.
+        let output = last_writers.get(&FrameResource::Color).copied();
+        if output.is_none() {
+            self.check_initial(FrameResource::Color, &skipped_writers)?;
+        }
+        return Ok((dependencies, output));
+    }
+
+    /// Index of the last skipped writer of the resource as the error, when the resource has no version before
+    /// any pass.
+    fn check_initial(
+        &self,
+        resource: FrameResource,
+        skipped_writers: &HashMap<FrameResource, usize>,
+    ) -> Result<(), usize> {
+        let has_initial = !(self.multisampled && resource == FrameResource::Color);
+        match (has_initial, skipped_writers.get(&resource)) {
+            (false, Some(&writer)) => return Err(writer),
+            _ => return Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn orders_passes_by_resources() {
+        let mut graph = FrameGraph::new(true);
+        for pass in [
+            FramePass::Bloom,
+            FramePass::Resolve,
+            FramePass::Fog,
+            FramePass::Geometry,
+            FramePass::Background,
+            FramePass::Shadows,
+        ] {
+            graph.add_pass(pass);
+        }
+        assert_eq!(
+            graph.schedule(),
+            Ok(vec![
+                FramePass::Background,
+                FramePass::Shadows,
+                FramePass::Geometry,
+                FramePass::Fog,
+                FramePass::Resolve,
+                FramePass::Bloom,
+            ])
+        );
+    }
+
+    #[test]
+    fn culls_passes_only_the_skipped_one_reads() {
+        let mut graph = FrameGraph::new(true);
+        for pass in [
+            FramePass::Shadows,
+            FramePass::RenderTargets,
+            FramePass::Camera,
+            FramePass::Background,
+            FramePass::Geometry,
+            FramePass::Resolve,
+            FramePass::Bloom,
+        ] {
+            graph.add_pass(pass);
+        }
+        graph.skip_pass(FramePass::Geometry);
+        graph.skip_pass(FramePass::Resolve);
+        // Frame buffer has no version without resolving the samples, so resolve runs anyway.
+        assert_eq!(
+            graph.schedule(),
+            Ok(vec![
+                FramePass::Background,
+                FramePass::Resolve,
+                FramePass::Bloom,
+            ])
+        );
+    }
+
+    #[test]
+    fn reports_cycle() {
+        let mut graph = FrameGraph::new(false);
+        for pass in [FramePass::Shadows, FramePass::Geometry, FramePass::Fog] {
+            graph.add_pass(pass);
+        }
+        // Fog reads the color, which the geometry writes, so it can't also run before it.
+        graph.add_ordering(&[FramePass::Fog, FramePass::Geometry]);
+        assert_eq!(
+            graph.schedule(),
+            Err(vec![FramePass::Geometry, FramePass::Fog])
+        );
+    }
+}
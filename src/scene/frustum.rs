@@ -0,0 +1,113 @@
+use na::{Matrix4, Point3, RowVector4, Vector4};
+use nalgebra as na;
+
+/// Sphere, enclosing all the vertices of a mesh, in model coordinates.
+#[derive(Clone, Copy, Debug)]
+pub struct BoundingSphere {
+    pub center: Point3<f32>,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    /// Sphere around the center of the axis aligned bounding box of the points, not the tightest one, but
+    /// cheap and close enough for culling.
+    pub fn from_points(points: impl Iterator<Item = Point3<f32>> + Clone) -> Self {
+        let (min, max) = points.clone().fold(
+            (Point3::from([f32::MAX; 3]), Point3::from([f32::MIN; 3])),
+            |(min, max), point| (min.inf(&point), max.sup(&point)),
+        );
+        let center = na::center(&min, &max);
+        let radius = points
+            .map(|point| (point - center).norm())
+            .fold(0.0, f32::max);
+        return BoundingSphere { center, radius };
+    }
+
+    /// Sphere around the average of the centers of the spheres, enclosing all of them.
+    pub fn from_spheres(spheres: &[BoundingSphere]) -> Self {
+        let center = Point3::from(
+            spheres
+                .iter()
+                .map(|sphere| sphere.center.coords)
+                .sum::<na::Vector3<f32>>()
+                / spheres.len() as f32,
+        );
+        let radius = spheres
+            .iter()
+            .map(|sphere| (sphere.center - center).norm() + sphere.radius)
+            .fold(0.0, f32::max);
+        return BoundingSphere { center, radius };
+    }
+}
+
+/// Where a bounding volume is relative to the frustum.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Containment {
+    Outside,
+    Intersecting,
+    Inside,
+}
+
+/// Visible volume of a pass - the part of model space, which ends up inside of the frame buffer in front of the
+/// center of projection. Planes are extracted from the pass transform, so it works for any camera.
+pub struct Frustum {
+    // Left, right, bottom, top and center of projection planes, followed by far and near planes, when depth
+    // is clipped, positive on the inner side.
+    planes: Vec<RowVector4<f32>>,
+}
+
+impl Frustum {
+    /// Frustum of the transform from model coordinates to raster coordinates of a width x height frame buffer.
+    /// With depth clipped, it's also bounded by the z-buffer range [0, 255] of the viewport.
+    pub fn from_matrix(
+        vpmv_matrix: &Matrix4<f32>,
+        width: u32,
+        height: u32,
+        is_depth_clipped: bool,
+    ) -> Self {
+        let (x, y, z, w) = (
+            vpmv_matrix.row(0),
+            vpmv_matrix.row(1),
+            vpmv_matrix.row(2),
+            vpmv_matrix.row(3),
+        );
+        let x_max = (width - 1) as f32;
+        let y_max = (height - 1) as f32;
+        let mut planes = vec![
+            x.into_owned(),
+            w * x_max - x,
+            y.into_owned(),
+            w * y_max - y,
+            w.into_owned(),
+        ];
+        if is_depth_clipped {
+            planes.push(z.into_owned());
+            planes.push(w * 255.0 - z);
+        }
+        return Frustum { planes };
+    }
+
+    pub fn classify_sphere(&self, sphere: &BoundingSphere) -> Containment {
+        let mut containment = Containment::Inside;
+        for plane in &self.planes {
+            let normal_length = plane.fixed_columns::<3>(0).norm();
+            let distance = (plane * sphere.center.to_homogeneous()).x / normal_length;
+            if distance < -sphere.radius {
+                return Containment::Outside;
+            }
+            if distance < sphere.radius {
+                containment = Containment::Intersecting;
+            }
+        }
+        return containment;
+    }
+
+    /// Whether all the vertices in model coordinates are outside of the same plane, so the polygon can't be
+    /// visible. Polygons crossing the corners of the frustum are conservatively kept.
+    pub fn is_polygon_outside(&self, vertices: [Point3<f32>; 3]) -> bool {
+        let vertices: [Vector4<f32>; 3] = vertices.map(|vertex| vertex.to_homogeneous());
+        return self.planes.iter().any(|plane| {
+            return vertices.iter().all(|vertex| (plane * vertex).x < 0.0);
+        });
+    }
+}
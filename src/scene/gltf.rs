@@ -0,0 +1,530 @@
+use std::error::Error;
+use std::path::Path;
+
+use na::{Matrix3, Matrix4, Point3, Quaternion, UnitQuaternion, Vector3};
+use nalgebra as na;
+use obj::raw::object::Polygon;
+use obj::raw::RawObj;
+
+use super::asset::new_raw_obj;
+use super::error::RendererError;
+use super::json::Json;
+use super::skin::{get_global_transforms, Channel, ChannelTarget, Interpolation, Node, Skin};
+use super::texture::encode_srgb;
+
+/// Magic number at the start of the binary glTF container.
+const GLB_MAGIC: &[u8; 4] = b"glTF";
+const GLB_JSON_CHUNK: u32 = 0x4E4F534A;
+const GLB_BIN_CHUNK: u32 = 0x004E4942;
+
+/// Mesh, its skin and vertex colors, loaded from a glTF file.
+pub type GltfModel = (RawObj, Option<Skin>, Vec<Vector3<f32>>);
+
+/// Loads triangles of the first mesh from .gltf or .glb file, together with its skin and the first animation
+/// of the skin, if it has one, and vertex colors, sRGB encoded per position, empty if no primitive has them.
+/// Materials and embedded images are ignored, textures still come from the asset folder. Meshes without a
+/// skin are baked into their place in the node hierarchy.
+pub fn load_gltf(path: &str) -> Result<GltfModel, RendererError> {
+    if !Path::new(path).exists() {
+        return Err(RendererError::AssetNotFound(path.into()));
+    }
+    return read_gltf(path).map_err(|error| RendererError::InvalidGltf {
+        path: path.into(),
+        message: error.to_string(),
+    });
+}
+
+fn read_gltf(path: &str) -> Result<GltfModel, Box<dyn Error>> {
+    let bytes = std::fs::read(path)?;
+    let (document, glb_buffer) = match bytes.starts_with(GLB_MAGIC) {
+        true => parse_glb(&bytes)?,
+        false => (std::str::from_utf8(&bytes)?.to_string(), Vec::new()),
+    };
+    let document = Json::parse(&document).ok_or("Malformed glTF JSON")?;
+    let folder = Path::new(path).parent().unwrap_or(Path::new("."));
+    let mut buffers = Vec::new();
+    for buffer in document.get("buffers").map_or(&[][..], Json::elements) {
+        let data = match buffer.get("uri").and_then(Json::as_str) {
+            None if glb_buffer.is_empty() => {
+                return Err("glTF buffer without uri outside of .glb".into())
+            }
+            None => glb_buffer.clone(),
+            Some(uri) if uri.starts_with("data:") => {
+                let (_, encoded) = uri.split_once(',').ok_or("Malformed glTF data uri")?;
+                decode_base64(encoded).ok_or("Malformed base64 in glTF data uri")?
+            }
+            Some(uri) => std::fs::read(folder.join(uri))?,
+        };
+        buffers.push(data);
+    }
+    let gltf = Gltf { document, buffers };
+    let nodes = gltf.read_nodes()?;
+
+    // Node, instancing the mesh, decides its skin and its transform.
+    let mesh_node = (0..nodes.len())
+        .filter(|&node| gltf.node(node).get("mesh").is_some())
+        .min_by_key(|&node| gltf.node(node).get("skin").is_none());
+    let mesh_index = match mesh_node {
+        Some(node) => gltf.node(node).get("mesh").and_then(Json::as_usize),
+        None => Some(0),
+    }
+    .ok_or("Malformed glTF mesh index")?;
+    let mesh = gltf
+        .document
+        .get("meshes")
+        .map_or(&[][..], Json::elements)
+        .get(mesh_index)
+        .ok_or("glTF file has no meshes")?;
+
+    let mut obj = new_raw_obj();
+    let mut vertex_joints = Vec::new();
+    let mut vertex_weights = Vec::new();
+    let mut vertex_colors = Vec::new();
+    let mut has_colors = false;
+    for primitive in mesh.get("primitives").map_or(&[][..], Json::elements) {
+        // Only triangle lists are supported.
+        if primitive.get("mode").and_then(Json::as_usize).unwrap_or(4) != 4 {
+            continue;
+        }
+        let attributes = primitive
+            .get("attributes")
+            .ok_or("glTF primitive without attributes")?;
+        let attribute = |name: &str| attributes.get(name).and_then(Json::as_usize);
+        let positions =
+            gltf.read_accessor(attribute("POSITION").ok_or("glTF primitive without positions")?)?;
+        let n_vertices = positions.len() / 3;
+        // Normals are optional, primitives without them are left for generate_normals.
+        let normals = match attribute("NORMAL") {
+            Some(accessor) => Some(gltf.read_accessor(accessor)?),
+            None => None,
+        };
+        let tex_coords = match attribute("TEXCOORD_0") {
+            Some(accessor) => gltf.read_accessor(accessor)?,
+            None => vec![0.0; 2 * n_vertices],
+        };
+        let offset = obj.positions.len();
+        for i in 0..n_vertices {
+            obj.positions.push((
+                positions[3 * i],
+                positions[3 * i + 1],
+                positions[3 * i + 2],
+                1.0,
+            ));
+            // Keeping normals aligned with positions, so indices of the other primitives stay shared.
+            obj.normals.push(match &normals {
+                Some(normals) => (normals[3 * i], normals[3 * i + 1], normals[3 * i + 2]),
+                None => (0.0, 0.0, 0.0),
+            });
+            // glTF puts the origin of the texture in the top left corner, obj - in the bottom left one.
+            obj.tex_coords
+                .push((tex_coords[2 * i], 1.0 - tex_coords[2 * i + 1], 0.0));
+        }
+        match (attribute("JOINTS_0"), attribute("WEIGHTS_0")) {
+            (Some(joints), Some(weights)) => {
+                let joints = gltf.read_accessor(joints)?;
+                let weights = gltf.read_accessor(weights)?;
+                for i in 0..n_vertices {
+                    vertex_joints.push([0, 1, 2, 3].map(|k| joints[4 * i + k] as usize));
+                    vertex_weights.push([0, 1, 2, 3].map(|k| weights[4 * i + k]));
+                }
+            }
+            _ => {
+                vertex_joints.resize(obj.positions.len(), [0; 4]);
+                vertex_weights.resize(obj.positions.len(), [0.0; 4]);
+            }
+        }
+        // Colors are linear RGB or RGBA, primitives without them are white.
+        match attribute("COLOR_0") {
+            Some(accessor) => {
+                let colors = gltf.read_accessor(accessor)?;
+                let n_components = colors.len() / n_vertices.max(1);
+                if n_components < 3 {
+                    return Err("Malformed glTF vertex colors".into());
+                }
+                for i in 0..n_vertices {
+                    let color = &colors[n_components * i..n_components * i + 3];
+                    vertex_colors.push(Vector3::from_fn(|c, _| encode_srgb(color[c])));
+                }
+                has_colors = true;
+            }
+            None => vertex_colors.resize(obj.positions.len(), Vector3::repeat(1.0)),
+        }
+        let indices: Vec<usize> = match primitive.get("indices").and_then(Json::as_usize) {
+            Some(accessor) => gltf
+                .read_accessor(accessor)?
+                .iter()
+                .map(|&index| offset + index as usize)
+                .collect(),
+            None => (offset..offset + n_vertices).collect(),
+        };
+        for triangle in indices.chunks_exact(3) {
+            obj.polygons.push(match normals {
+                Some(_) => Polygon::PTN(
+                    triangle
+                        .iter()
+                        .map(|&index| (index, index, index))
+                        .collect(),
+                ),
+                None => Polygon::PT(triangle.iter().map(|&index| (index, index)).collect()),
+            });
+        }
+    }
+    if obj.polygons.is_empty() {
+        return Err("glTF mesh has no triangles".into());
+    }
+
+    let skin_index =
+        mesh_node.and_then(|node| gltf.node(node).get("skin").and_then(Json::as_usize));
+    let skin = match skin_index {
+        Some(skin_index) => {
+            Some(gltf.read_skin(skin_index, nodes, vertex_joints, vertex_weights)?)
+        }
+        None => {
+            // Static mesh is moved into its place in the scene once and for all.
+            if let Some(node) = mesh_node {
+                let transform = get_global_transforms(&nodes)[node];
+                let normal_transform = transform
+                    .fixed_slice::<3, 3>(0, 0)
+                    .into_owned()
+                    .try_inverse()
+                    .unwrap_or_else(Matrix3::identity)
+                    .transpose();
+                for position in obj.positions.iter_mut() {
+                    let moved =
+                        transform.transform_point(&Point3::new(position.0, position.1, position.2));
+                    *position = (moved.x, moved.y, moved.z, 1.0);
+                }
+                for normal in obj.normals.iter_mut() {
+                    let turned = normal_transform * Vector3::new(normal.0, normal.1, normal.2);
+                    // Placeholders of the primitives without normals stay zero.
+                    if let Some(turned) = turned.try_normalize(f32::EPSILON) {
+                        *normal = (turned.x, turned.y, turned.z);
+                    }
+                }
+            }
+            None
+        }
+    };
+    if !has_colors {
+        vertex_colors.clear();
+    }
+    return Ok((obj, skin, vertex_colors));
+}
+
+/// Splits binary glTF container into its JSON document and its binary buffer, empty if there's none.
+fn parse_glb(bytes: &[u8]) -> Result<(String, Vec<u8>), Box<dyn Error>> {
+    let read_u32 = |offset: usize| -> Result<u32, Box<dyn Error>> {
+        let word = bytes.get(offset..offset + 4).ok_or("Truncated .glb file")?;
+        return Ok(u32::from_le_bytes(word.try_into()?));
+    };
+    let mut document = None;
+    let mut buffer = Vec::new();
+    // Skipping the header of magic, version and length.
+    let mut offset = 12;
+    while offset < bytes.len() {
+        let length = read_u32(offset)? as usize;
+        let kind = read_u32(offset + 4)?;
+        let data = bytes
+            .get(offset + 8..offset + 8 + length)
+            .ok_or("Truncated .glb chunk")?;
+        match kind {
+            GLB_JSON_CHUNK => document = Some(std::str::from_utf8(data)?.to_string()),
+            GLB_BIN_CHUNK => buffer = data.to_vec(),
+            _ => (),
+        }
+        offset += 8 + length;
+    }
+    return Ok((document.ok_or(".glb file without JSON chunk")?, buffer));
+}
+
+/// Decodes standard base64 with optional padding.
+fn decode_base64(encoded: &str) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(encoded.len() * 3 / 4);
+    let mut bits: u32 = 0;
+    let mut n_bits = 0;
+    for byte in encoded.bytes().filter(|&byte| byte != b'=') {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        bits = (bits << 6) | value as u32;
+        n_bits += 6;
+        if n_bits >= 8 {
+            n_bits -= 8;
+            decoded.push((bits >> n_bits) as u8);
+        }
+    }
+    return Some(decoded);
+}
+
+/// Parsed document together with the contents of its buffers.
+struct Gltf {
+    document: Json,
+    buffers: Vec<Vec<u8>>,
+}
+
+impl Gltf {
+    fn node(&self, index: usize) -> &Json {
+        return &self.document.get("nodes").map_or(&[][..], Json::elements)[index];
+    }
+
+    /// Every node of the document with its parent and its rest transform.
+    fn read_nodes(&self) -> Result<Vec<Node>, Box<dyn Error>> {
+        let json_nodes = self.document.get("nodes").map_or(&[][..], Json::elements);
+        let mut nodes: Vec<Node> = json_nodes
+            .iter()
+            .map(|node| {
+                let vector = |key: &str, default: Vec<f32>| {
+                    return node.get(key).and_then(Json::as_f32_vec).unwrap_or(default);
+                };
+                let translation = vector("translation", vec![0.0; 3]);
+                let rotation = vector("rotation", vec![0.0, 0.0, 0.0, 1.0]);
+                let scale = vector("scale", vec![1.0; 3]);
+                let matrix = node.get("matrix").and_then(Json::as_f32_vec);
+                return Node {
+                    parent: None,
+                    translation: Vector3::from_column_slice(&translation[..3]),
+                    rotation: UnitQuaternion::from_quaternion(Quaternion::new(
+                        rotation[3],
+                        rotation[0],
+                        rotation[1],
+                        rotation[2],
+                    )),
+                    scale: Vector3::from_column_slice(&scale[..3]),
+                    matrix: matrix
+                        .filter(|matrix| matrix.len() == 16)
+                        .map(|matrix| Matrix4::from_column_slice(&matrix)),
+                };
+            })
+            .collect();
+        for (parent, node) in json_nodes.iter().enumerate() {
+            for child in node.get("children").map_or(&[][..], Json::elements) {
+                let child = child
+                    .as_usize()
+                    .filter(|&child| child < nodes.len())
+                    .ok_or("Malformed glTF node child")?;
+                nodes[child].parent = Some(parent);
+            }
+        }
+        return Ok(nodes);
+    }
+
+    /// Joints and inverse bind matrices of the skin, animated by the first animation of the document, which
+    /// targets any of the nodes.
+    fn read_skin(
+        &self,
+        index: usize,
+        nodes: Vec<Node>,
+        vertex_joints: Vec<[usize; 4]>,
+        vertex_weights: Vec<[f32; 4]>,
+    ) -> Result<Skin, Box<dyn Error>> {
+        let skin = self
+            .document
+            .get("skins")
+            .map_or(&[][..], Json::elements)
+            .get(index)
+            .ok_or("Malformed glTF skin index")?;
+        let joints: Vec<usize> = skin
+            .get("joints")
+            .map_or(&[][..], Json::elements)
+            .iter()
+            .map(|joint| joint.as_usize().filter(|&joint| joint < nodes.len()))
+            .collect::<Option<_>>()
+            .ok_or("Malformed glTF skin joints")?;
+        let inverse_bind_matrices = match skin.get("inverseBindMatrices").and_then(Json::as_usize) {
+            Some(accessor) => self
+                .read_accessor(accessor)?
+                .chunks_exact(16)
+                .map(Matrix4::from_column_slice)
+                .collect(),
+            None => vec![Matrix4::identity(); joints.len()],
+        };
+        if vertex_joints
+            .iter()
+            .flatten()
+            .any(|&joint| joint >= joints.len())
+        {
+            return Err("glTF vertex refers to a missing joint".into());
+        }
+
+        let mut channels = Vec::new();
+        if let Some(animation) = self
+            .document
+            .get("animations")
+            .map_or(&[][..], Json::elements)
+            .first()
+        {
+            let samplers = animation.get("samplers").map_or(&[][..], Json::elements);
+            for channel in animation.get("channels").map_or(&[][..], Json::elements) {
+                let target = channel
+                    .get("target")
+                    .ok_or("glTF animation channel without target")?;
+                let node = match target.get("node").and_then(Json::as_usize) {
+                    Some(node) if node < nodes.len() => node,
+                    _ => continue,
+                };
+                let (target, n_components) = match target.get("path").and_then(Json::as_str) {
+                    Some("translation") => (ChannelTarget::Translation, 3),
+                    Some("rotation") => (ChannelTarget::Rotation, 4),
+                    Some("scale") => (ChannelTarget::Scale, 3),
+                    // Morph target weights aren't supported.
+                    _ => continue,
+                };
+                let sampler = channel
+                    .get("sampler")
+                    .and_then(Json::as_usize)
+                    .and_then(|sampler| samplers.get(sampler))
+                    .ok_or("Malformed glTF animation sampler")?;
+                let times = self.read_accessor(
+                    sampler
+                        .get("input")
+                        .and_then(Json::as_usize)
+                        .ok_or("glTF sampler without input")?,
+                )?;
+                let outputs = self.read_accessor(
+                    sampler
+                        .get("output")
+                        .and_then(Json::as_usize)
+                        .ok_or("glTF sampler without output")?,
+                )?;
+                // Cubic splines store in and out tangents around every value, only the values are kept and
+                // interpolated linearly.
+                let (interpolation, stride, skip) = match sampler
+                    .get("interpolation")
+                    .and_then(Json::as_str)
+                {
+                    Some("STEP") => (Interpolation::Step, n_components, 0),
+                    Some("CUBICSPLINE") => (Interpolation::Linear, 3 * n_components, n_components),
+                    _ => (Interpolation::Linear, n_components, 0),
+                };
+                let values: Vec<[f32; 4]> = outputs
+                    .chunks_exact(stride)
+                    .map(|output| {
+                        [0, 1, 2, 3].map(|c| {
+                            *output
+                                .get(skip + c)
+                                .filter(|_| c < n_components)
+                                .unwrap_or(&0.0)
+                        })
+                    })
+                    .collect();
+                if times.is_empty() || values.len() != times.len() {
+                    return Err("glTF animation sampler input and output don't match".into());
+                }
+                channels.push(Channel {
+                    node,
+                    target,
+                    interpolation,
+                    times,
+                    values,
+                });
+            }
+        }
+        return Ok(Skin::new(
+            nodes,
+            joints,
+            inverse_bind_matrices,
+            vertex_joints,
+            vertex_weights,
+            channels,
+        ));
+    }
+
+    /// Flat components of every element of the accessor, integers are converted to floats and normalized if
+    /// the accessor says so.
+    fn read_accessor(&self, index: usize) -> Result<Vec<f32>, Box<dyn Error>> {
+        let accessor = self
+            .document
+            .get("accessors")
+            .map_or(&[][..], Json::elements)
+            .get(index)
+            .ok_or("Malformed glTF accessor index")?;
+        if accessor.get("sparse").is_some() {
+            return Err("Sparse glTF accessors aren't supported".into());
+        }
+        let count = accessor
+            .get("count")
+            .and_then(Json::as_usize)
+            .ok_or("glTF accessor without count")?;
+        let n_components = match accessor.get("type").and_then(Json::as_str) {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4") | Some("MAT2") => 4,
+            Some("MAT3") => 9,
+            Some("MAT4") => 16,
+            _ => return Err("Unknown glTF accessor type".into()),
+        };
+        let component_type = accessor
+            .get("componentType")
+            .and_then(Json::as_usize)
+            .ok_or("glTF accessor without component type")?;
+        let component_size = match component_type {
+            5120 | 5121 => 1,
+            5122 | 5123 => 2,
+            5125 | 5126 => 4,
+            _ => return Err("Unknown glTF accessor component type".into()),
+        };
+        let normalized = accessor
+            .get("normalized")
+            .and_then(Json::as_bool)
+            .unwrap_or(false);
+        // Accessors without a buffer view are all zeros.
+        let view = match accessor.get("bufferView").and_then(Json::as_usize) {
+            Some(view) => self
+                .document
+                .get("bufferViews")
+                .map_or(&[][..], Json::elements)
+                .get(view)
+                .ok_or("Malformed glTF buffer view index")?,
+            None => return Ok(vec![0.0; count * n_components]),
+        };
+        let buffer = view
+            .get("buffer")
+            .and_then(Json::as_usize)
+            .and_then(|buffer| self.buffers.get(buffer))
+            .ok_or("Malformed glTF buffer index")?;
+        let offset = view.get("byteOffset").and_then(Json::as_usize).unwrap_or(0)
+            + accessor
+                .get("byteOffset")
+                .and_then(Json::as_usize)
+                .unwrap_or(0);
+        let stride = view
+            .get("byteStride")
+            .and_then(Json::as_usize)
+            .unwrap_or(n_components * component_size);
+
+        let mut values = Vec::with_capacity(count * n_components);
+        for element in 0..count {
+            for component in 0..n_components {
+                let start = offset + element * stride + component * component_size;
+                let bytes = buffer
+                    .get(start..start + component_size)
+                    .ok_or("glTF accessor is out of its buffer")?;
+                let value = match component_type {
+                    5120 => (bytes[0] as i8 as f32, 127.0),
+                    5121 => (bytes[0] as f32, 255.0),
+                    5122 => (i16::from_le_bytes([bytes[0], bytes[1]]) as f32, 32767.0),
+                    5123 => (u16::from_le_bytes([bytes[0], bytes[1]]) as f32, 65535.0),
+                    5125 => (
+                        u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32,
+                        u32::MAX as f32,
+                    ),
+                    _ => (
+                        f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+                        1.0,
+                    ),
+                };
+                values.push(match normalized {
+                    true => (value.0 / value.1).max(-1.0),
+                    false => value.0,
+                });
+            }
+        }
+        return Ok(values);
+    }
+}
@@ -0,0 +1,185 @@
+use na::{Matrix4, Rotation3, Unit, Vector3};
+use nalgebra as na;
+
+use super::instance::Instance;
+use super::json::Json;
+
+/// Node of the scene graph, placed relative to its parent, so moving the parent carries its children along.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SceneNode {
+    pub name: String,
+    pub parent: Option<usize>, // Index of the parent node in the graph, None for the nodes at the root.
+    pub transform: Matrix4<f32>, // Local transform relative to the parent.
+    // Rotation around the axis in radians per second of animation time, applied inside of the local transform,
+    // so the node turns in place and its children orbit it, e.g. a moon around its planet.
+    pub spin_axis: Vector3<f32>,
+    pub spin_speed: f32,
+    pub tint: Vector3<f32>, // Multiplier of the shaded color of the copy of the model at the node in [0, 1].
+    pub draws_model: bool, // Whether a copy of the model is drawn at the node, or the node only groups others.
+}
+
+impl SceneNode {
+    /// Node at the root, drawing the model without any transform.
+    pub fn new(name: &str) -> Self {
+        return Self {
+            name: name.to_string(),
+            parent: None,
+            transform: Matrix4::identity(),
+            spin_axis: Vector3::y(),
+            spin_speed: 0.0,
+            tint: Vector3::repeat(1.0),
+            draws_model: true,
+        };
+    }
+
+    fn get_local_transform(&self, time: f32) -> Matrix4<f32> {
+        if self.spin_speed == 0.0 {
+            return self.transform;
+        }
+        let spin = Rotation3::from_axis_angle(
+            &Unit::new_normalize(self.spin_axis),
+            self.spin_speed * time,
+        );
+        return self.transform * spin.to_homogeneous();
+    }
+}
+
+/// Hierarchy of nodes, at which copies of the model are drawn. Parents always come before their children, so
+/// transforms are composed in a single walk from the root.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SceneGraph {
+    nodes: Vec<SceneNode>,
+    time: f32, // Animation time in seconds, which spinning nodes are turned to.
+}
+
+impl SceneGraph {
+    /// Flat graph, drawing a copy of the model at every instance.
+    pub fn from_instances(instances: &[Instance]) -> Self {
+        let mut graph = SceneGraph::default();
+        for (index, instance) in instances.iter().enumerate() {
+            graph.add_node(SceneNode {
+                transform: instance.transform,
+                tint: instance.tint,
+                ..SceneNode::new(&format!("instance_{}", index))
+            });
+        }
+        return graph;
+    }
+
+    /// Parses graph from a JSON document like
+    /// `{"nodes": [{"name": "planet", "spin": [0, 1, 0, 20]}, {"name": "moon", "parent": "planet",
+    /// "translation": [2, 0, 0], "scale": 0.3}]}`, returning None for malformed documents. Parents are referred
+    /// to by name and should come before their children. Optional fields of a node are translation, rotation
+    /// and spin as an axis with an angle in degrees or speed in degrees per second, uniform or per axis scale,
+    /// tint and whether the node draws the model, true by default.
+    pub fn parse(text: &str) -> Option<Self> {
+        fn parse_vector(value: &Json, len: usize) -> Option<Vec<f32>> {
+            let values = value.as_f32_vec()?;
+            if values.len() != len {
+                return None;
+            }
+            return Some(values);
+        }
+
+        let document = Json::parse(text)?;
+        let mut graph = SceneGraph::default();
+        for json_node in document.get("nodes")?.elements() {
+            let mut node = SceneNode::new(json_node.get("name")?.as_str()?);
+            if let Some(parent) = json_node.get("parent") {
+                node.parent = Some(graph.find_node(parent.as_str()?)?);
+            }
+            let mut transform = Matrix4::identity();
+            if let Some(translation) = json_node.get("translation") {
+                let values = parse_vector(translation, 3)?;
+                transform *= Matrix4::new_translation(&Vector3::from_column_slice(&values));
+            }
+            if let Some(rotation) = json_node.get("rotation") {
+                let values = parse_vector(rotation, 4)?;
+                let axis = Unit::new_normalize(Vector3::from_column_slice(&values[..3]));
+                transform *=
+                    Rotation3::from_axis_angle(&axis, values[3].to_radians()).to_homogeneous();
+            }
+            if let Some(scale) = json_node.get("scale") {
+                let scale = match scale.as_f64() {
+                    Some(scale) => Vector3::repeat(scale as f32),
+                    None => Vector3::from_column_slice(&parse_vector(scale, 3)?),
+                };
+                transform *= Matrix4::new_nonuniform_scaling(&scale);
+            }
+            node.transform = transform;
+            if let Some(spin) = json_node.get("spin") {
+                let values = parse_vector(spin, 4)?;
+                node.spin_axis = Vector3::from_column_slice(&values[..3]);
+                node.spin_speed = values[3].to_radians();
+            }
+            if let Some(tint) = json_node.get("tint") {
+                node.tint = Vector3::from_column_slice(&parse_vector(tint, 3)?);
+            }
+            if let Some(draws_model) = json_node.get("model") {
+                node.draws_model = draws_model.as_bool()?;
+            }
+            graph.add_node(node);
+        }
+        return Some(graph);
+    }
+
+    /// Adds the node, returning its index. Parent of the node should already be in the graph.
+    pub fn add_node(&mut self, node: SceneNode) -> usize {
+        if let Some(parent) = node.parent {
+            assert!(
+                parent < self.nodes.len(),
+                "Parent should be added before its children!"
+            );
+        }
+        self.nodes.push(node);
+        return self.nodes.len() - 1;
+    }
+
+    /// Index of the first node with the name.
+    pub fn find_node(&self, name: &str) -> Option<usize> {
+        return self.nodes.iter().position(|node| node.name == name);
+    }
+
+    pub fn get_nodes(&self) -> &[SceneNode] {
+        return &self.nodes;
+    }
+
+    /// Mutable access to a node, e.g. for moving it. Parent can only be changed to one of the nodes before it.
+    pub fn get_node_mut(&mut self, index: usize) -> &mut SceneNode {
+        return &mut self.nodes[index];
+    }
+
+    /// Turning spinning nodes to the animation time in seconds.
+    pub fn set_time(&mut self, time: f32) {
+        self.time = time;
+    }
+
+    /// Transforms of every node from its own coordinates to model coordinates of the scene, composed from the
+    /// root down.
+    pub fn get_world_transforms(&self) -> Vec<Matrix4<f32>> {
+        let mut transforms: Vec<Matrix4<f32>> = Vec::with_capacity(self.nodes.len());
+        for node in &self.nodes {
+            let local_transform = node.get_local_transform(self.time);
+            let transform = match node.parent {
+                Some(parent) => transforms[parent] * local_transform,
+                None => local_transform,
+            };
+            transforms.push(transform);
+        }
+        return transforms;
+    }
+
+    /// Copies of the model at the nodes, which draw it, in the order of the nodes.
+    pub fn get_instances(&self) -> Vec<Instance> {
+        return self
+            .nodes
+            .iter()
+            .zip(self.get_world_transforms())
+            .filter(|(node, _)| node.draws_model)
+            .map(|(node, transform)| Instance {
+                transform,
+                tint: node.tint,
+            })
+            .collect();
+    }
+}
@@ -0,0 +1,66 @@
+use tiny_renderer_core::raster::BLOCK_SIZE;
+
+/// Side of the square blocks of pixels, hierarchical depth is kept for, same as the blocks of the rasterizer.
+pub const HIZ_BLOCK_SIZE: usize = BLOCK_SIZE as usize;
+
+/// Coarse level of the depth target of a single tile, holding the farthest depth of every block of pixels.
+/// Polygon, which is closer nowhere than the farthest depth of the block, is hidden in the whole block, so
+/// the block can be skipped without rasterizing it. Blocks are refreshed lazily from the depth target after
+/// polygons have been drawn over them.
+pub struct HiZ {
+    width: usize,
+    height: usize,
+    samples: usize,
+    blocks_x: usize,
+    farthest: Vec<f32>,
+    dirty: Vec<bool>,
+}
+
+impl HiZ {
+    /// Hierarchical depth of the tile of width x height pixels with several depth samples per pixel.
+    pub fn new(width: usize, height: usize, samples: usize) -> Self {
+        let blocks_x = width.div_ceil(HIZ_BLOCK_SIZE);
+        let n_blocks = blocks_x * height.div_ceil(HIZ_BLOCK_SIZE);
+        return HiZ {
+            width,
+            height,
+            samples,
+            blocks_x,
+            farthest: vec![f32::MIN; n_blocks],
+            dirty: vec![true; n_blocks],
+        };
+    }
+
+    /// Whether the polygon with the closest depth max_depth is hidden everywhere in the block, containing
+    /// the pixel at x, y of the tile.
+    pub fn is_occluded(&mut self, depth: &[f32], x: usize, y: usize, max_depth: f32) -> bool {
+        let (block_x, block_y) = (x / HIZ_BLOCK_SIZE, y / HIZ_BLOCK_SIZE);
+        let block = block_x + block_y * self.blocks_x;
+        if self.dirty[block] {
+            self.dirty[block] = false;
+            let x_range =
+                block_x * HIZ_BLOCK_SIZE..((block_x + 1) * HIZ_BLOCK_SIZE).min(self.width);
+            let y_range =
+                block_y * HIZ_BLOCK_SIZE..((block_y + 1) * HIZ_BLOCK_SIZE).min(self.height);
+            let mut farthest = f32::MAX;
+            for j in y_range {
+                let row_start = self.samples * (x_range.start + j * self.width);
+                let row_end = self.samples * (x_range.end + j * self.width);
+                farthest = depth[row_start..row_end]
+                    .iter()
+                    .fold(farthest, |farthest, &value| farthest.min(value));
+            }
+            self.farthest[block] = farthest;
+        }
+        return max_depth <= self.farthest[block];
+    }
+
+    /// Marks blocks, overlapping the rectangle of pixels of the tile, as possibly written to.
+    pub fn invalidate(&mut self, x_min: usize, x_max: usize, y_min: usize, y_max: usize) {
+        for block_y in y_min / HIZ_BLOCK_SIZE..=y_max / HIZ_BLOCK_SIZE {
+            for block_x in x_min / HIZ_BLOCK_SIZE..=x_max / HIZ_BLOCK_SIZE {
+                self.dirty[block_x + block_y * self.blocks_x] = true;
+            }
+        }
+    }
+}
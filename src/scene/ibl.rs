@@ -0,0 +1,252 @@
+use na::{vector, Vector2, Vector3};
+use nalgebra as na;
+
+use super::texture::{get_cube_face_direction, CubeMap};
+
+/// Number of the prefiltered specular levels, going from roughness 0 to 1 in even steps.
+const SPECULAR_LEVELS: usize = 5;
+/// Face size of the sharpest specular level, smaller environments keep the size of their faces.
+const SPECULAR_SIZE: u32 = 64;
+/// Environment samples per texel of the prefiltered specular levels.
+const SPECULAR_SAMPLES: u32 = 32;
+/// Face size of the irradiance map, which is smooth enough to be stored this small.
+const IRRADIANCE_SIZE: u32 = 16;
+/// Face size of the downsampled environment, which every texel of the irradiance map integrates over.
+const IRRADIANCE_SOURCE_SIZE: u32 = 8;
+/// Side of the square table of the split-sum BRDF, indexed by the view angle and roughness.
+const BRDF_LUT_SIZE: usize = 32;
+const BRDF_SAMPLES: u32 = 64;
+
+/// Lighting of the surfaces by the environment, precomputed for the split-sum approximation: irradiance map
+/// for the diffuse part, specular maps, prefiltered with the GGX distribution for increasing roughness, and
+/// the table of the scale and bias, which the BRDF applies to the reflectance at normal incidence. Maps are in
+/// model coordinates with linear channels in [0, 255], going past 255 for high dynamic range environments.
+pub struct ImageBasedLighting {
+    irradiance: CubeMap,
+    specular: Vec<CubeMap>,
+    brdf_lut: Vec<Vector2<f32>>, // Rows of increasing roughness, columns of increasing cosine of view angle.
+}
+
+impl ImageBasedLighting {
+    /// Precomputes the maps of the environment, decoded from sRGB, if its faces are.
+    pub fn new(environment: &CubeMap) -> Self {
+        let sources = get_downsampled_chain(environment);
+        let irradiance_source = sources
+            .iter()
+            .find(|source| source.face_size() <= IRRADIANCE_SOURCE_SIZE)
+            .unwrap();
+        let specular = (0..SPECULAR_LEVELS)
+            .map(|level| {
+                let size = (sources[0].face_size() >> level).max(1);
+                let roughness = level as f32 / (SPECULAR_LEVELS - 1) as f32;
+                if level == 0 {
+                    return downsample(&sources[0], size);
+                }
+                return CubeMap::from_fn(size, |face, x, y| {
+                    let direction = get_texel_direction(face, x, y, size).normalize();
+                    return prefilter_specular(&sources, direction, roughness);
+                });
+            })
+            .collect();
+        return Self {
+            irradiance: get_irradiance_map(irradiance_source),
+            specular,
+            brdf_lut: get_brdf_lut(),
+        };
+    }
+
+    /// Diffuse light, reaching a surface with the normal, as the cosine weighted environment over its
+    /// hemisphere, divided by pi, so it's the color of a white lambertian surface.
+    pub fn sample_irradiance(&self, normal: Vector3<f32>) -> Vector3<f32> {
+        return self.irradiance.sample_direction(normal);
+    }
+
+    /// Light, reflected along the direction by a surface of the roughness, blending the two closest levels.
+    pub fn sample_specular(&self, direction: Vector3<f32>, roughness: f32) -> Vector3<f32> {
+        let lod = roughness.clamp(0.0, 1.0) * (SPECULAR_LEVELS - 1) as f32;
+        return sample_chain(&self.specular, direction, lod);
+    }
+
+    /// Scale and bias of the reflectance at normal incidence, which give the reflectance of the whole
+    /// specular lobe at the view angle, bilinearly interpolated in the table.
+    pub fn get_brdf(&self, n_dot_v: f32, roughness: f32) -> Vector2<f32> {
+        let size = BRDF_LUT_SIZE as f32;
+        let x = (n_dot_v * size - 0.5).clamp(0.0, size - 1.0);
+        let y = (roughness * size - 0.5).clamp(0.0, size - 1.0);
+        let (x_0, y_0) = (x.floor() as usize, y.floor() as usize);
+        let (x_1, y_1) = (
+            (x_0 + 1).min(BRDF_LUT_SIZE - 1),
+            (y_0 + 1).min(BRDF_LUT_SIZE - 1),
+        );
+        let (t_x, t_y) = (x - x_0 as f32, y - y_0 as f32);
+        let get = |x: usize, y: usize| self.brdf_lut[y * BRDF_LUT_SIZE + x];
+        let bottom = get(x_0, y_0) * (1.0 - t_x) + get(x_1, y_0) * t_x;
+        let top = get(x_0, y_1) * (1.0 - t_x) + get(x_1, y_1) * t_x;
+        return bottom * (1.0 - t_y) + top * t_y;
+    }
+}
+
+/// Environment, resampled to faces of at most SPECULAR_SIZE, followed by its halves down to 1x1 faces, which
+/// the prefiltering samples from, so a few samples cover the whole lobe without noise.
+fn get_downsampled_chain(environment: &CubeMap) -> Vec<CubeMap> {
+    let mut chain = vec![downsample(
+        environment,
+        environment.face_size().min(SPECULAR_SIZE),
+    )];
+    while chain.last().unwrap().face_size() > 1 {
+        let previous = chain.last().unwrap();
+        chain.push(downsample(previous, previous.face_size() / 2));
+    }
+    return chain;
+}
+
+/// Cube map with faces of the size, averaging all source texels, which fall into every texel.
+fn downsample(source: &CubeMap, size: u32) -> CubeMap {
+    let n = (source.face_size() / size).max(1);
+    return CubeMap::from_fn(size, |face, x, y| {
+        let mut sum = Vector3::zeros();
+        for j in 0..n {
+            for i in 0..n {
+                let u = (x as f32 + (i as f32 + 0.5) / n as f32) / size as f32;
+                let v = (y as f32 + (j as f32 + 0.5) / n as f32) / size as f32;
+                sum += source.sample_direction(get_cube_face_direction(face, u, v));
+            }
+        }
+        return sum / (n * n) as f32;
+    });
+}
+
+/// Not normalized direction to the center of the texel.
+fn get_texel_direction(face: usize, x: u32, y: u32, size: u32) -> Vector3<f32> {
+    let u = (x as f32 + 0.5) / size as f32;
+    let v = (y as f32 + 0.5) / size as f32;
+    return get_cube_face_direction(face, u, v);
+}
+
+/// Color of the chain of maps at the fractional level, blending the two closest ones.
+fn sample_chain(chain: &[CubeMap], direction: Vector3<f32>, lod: f32) -> Vector3<f32> {
+    let lod = lod.clamp(0.0, (chain.len() - 1) as f32);
+    let level = lod.floor() as usize;
+    let t = lod - level as f32;
+    let fine = chain[level].sample_direction(direction);
+    if t == 0.0 {
+        return fine;
+    }
+    return fine * (1.0 - t) + chain[level + 1].sample_direction(direction) * t;
+}
+
+/// Integrates every texel of the source against the cosine lobe around the normal of every texel of the
+/// map, weighting it by its solid angle, which shrinks towards the edges of the faces.
+fn get_irradiance_map(source: &CubeMap) -> CubeMap {
+    let size = source.face_size();
+    let texel_area = (2.0 / size as f32).powi(2);
+    let mut texels = Vec::with_capacity((6 * size * size) as usize);
+    for face in 0..6 {
+        for y in 0..size {
+            for x in 0..size {
+                let direction = get_texel_direction(face, x, y, size);
+                let solid_angle = texel_area / direction.norm_squared().powf(1.5);
+                let color = source.sample_direction(direction);
+                texels.push((direction.normalize(), color * solid_angle));
+            }
+        }
+    }
+    return CubeMap::from_fn(IRRADIANCE_SIZE, |face, x, y| {
+        let normal = get_texel_direction(face, x, y, IRRADIANCE_SIZE).normalize();
+        let mut sum = Vector3::zeros();
+        for (direction, color) in texels.iter() {
+            sum += color * normal.dot(direction).max(0.0);
+        }
+        return sum / std::f32::consts::PI;
+    });
+}
+
+/// Environment around the direction, weighted by the GGX lobe of the roughness, assuming the view and the
+/// normal are both along the direction. Samples are importance sampled and read from the level of the chain,
+/// whose texels are about as large as the solid angle every sample stands for.
+fn prefilter_specular(chain: &[CubeMap], direction: Vector3<f32>, roughness: f32) -> Vector3<f32> {
+    let alpha = roughness * roughness;
+    let texel_solid_angle = 4.0 * std::f32::consts::PI / (6 * chain[0].face_size().pow(2)) as f32;
+    let mut sum = Vector3::zeros();
+    let mut weight = 0.0;
+    for i in 0..SPECULAR_SAMPLES {
+        let half = sample_ggx(get_hammersley_point(i, SPECULAR_SAMPLES), direction, alpha);
+        let n_dot_h = direction.dot(&half);
+        let light = 2.0 * n_dot_h * half - direction;
+        let n_dot_l = direction.dot(&light);
+        if n_dot_l <= 0.0 {
+            continue;
+        }
+        // With the view along the normal, pdf of the light direction is a quarter of the distribution.
+        let pdf = get_ggx_distribution(n_dot_h, alpha) / 4.0;
+        let sample_solid_angle = 1.0 / (SPECULAR_SAMPLES as f32 * pdf).max(1e-6);
+        let lod = 0.5 * (sample_solid_angle / texel_solid_angle).log2();
+        sum += sample_chain(chain, light, lod) * n_dot_l;
+        weight += n_dot_l;
+    }
+    return sum / weight;
+}
+
+/// Table of the scale and bias of the reflectance at normal incidence, integrating the Cook-Torrance BRDF
+/// with the Schlick fresnel over the hemisphere for every view angle and roughness.
+fn get_brdf_lut() -> Vec<Vector2<f32>> {
+    let mut lut = Vec::with_capacity(BRDF_LUT_SIZE * BRDF_LUT_SIZE);
+    for y in 0..BRDF_LUT_SIZE {
+        let roughness = (y as f32 + 0.5) / BRDF_LUT_SIZE as f32;
+        let alpha = roughness * roughness;
+        // Geometry term of the environment lighting is remapped differently from the one of the lights.
+        let k = alpha / 2.0;
+        for x in 0..BRDF_LUT_SIZE {
+            let n_dot_v = (x as f32 + 0.5) / BRDF_LUT_SIZE as f32;
+            let view = vector![(1.0 - n_dot_v * n_dot_v).sqrt(), 0.0, n_dot_v];
+            let mut sum = Vector2::zeros();
+            for i in 0..BRDF_SAMPLES {
+                let half = sample_ggx(get_hammersley_point(i, BRDF_SAMPLES), Vector3::z(), alpha);
+                let v_dot_h = view.dot(&half).max(0.0);
+                let light = 2.0 * v_dot_h * half - view;
+                let n_dot_l = light.z;
+                if n_dot_l <= 0.0 {
+                    continue;
+                }
+                let geometry =
+                    (n_dot_l / (n_dot_l * (1.0 - k) + k)) * (n_dot_v / (n_dot_v * (1.0 - k) + k));
+                let visibility = geometry * v_dot_h / (half.z * n_dot_v).max(1e-6);
+                let fresnel = (1.0 - v_dot_h).powi(5);
+                sum += vector![(1.0 - fresnel) * visibility, fresnel * visibility];
+            }
+            lut.push(sum / BRDF_SAMPLES as f32);
+        }
+    }
+    return lut;
+}
+
+/// Point of the Hammersley set of n points in the unit square, spreading samples more evenly than random ones.
+fn get_hammersley_point(i: u32, n: u32) -> Vector2<f32> {
+    return vector![
+        i as f32 / n as f32,
+        i.reverse_bits() as f32 / 4_294_967_296.0
+    ];
+}
+
+/// Half vector around the normal, distributed by the GGX distribution of the alpha, from the point in the
+/// unit square.
+fn sample_ggx(point: Vector2<f32>, normal: Vector3<f32>, alpha: f32) -> Vector3<f32> {
+    let phi = 2.0 * std::f32::consts::PI * point.x;
+    let cos_theta = ((1.0 - point.y) / (1.0 + (alpha * alpha - 1.0) * point.y)).sqrt();
+    let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+    let up = match normal.z.abs() < 0.999 {
+        true => Vector3::z(),
+        false => Vector3::x(),
+    };
+    let tangent = up.cross(&normal).normalize();
+    let bitangent = normal.cross(&tangent);
+    return tangent * phi.cos() * sin_theta
+        + bitangent * phi.sin() * sin_theta
+        + normal * cos_theta;
+}
+
+fn get_ggx_distribution(n_dot_h: f32, alpha: f32) -> f32 {
+    let alpha_2 = alpha * alpha;
+    let denominator = n_dot_h * n_dot_h * (alpha_2 - 1.0) + 1.0;
+    return alpha_2 / (std::f32::consts::PI * denominator * denominator);
+}
@@ -0,0 +1,100 @@
+use na::{Matrix3, Matrix4, Point3, Vector3};
+use nalgebra as na;
+
+use super::frustum::BoundingSphere;
+
+/// Copy of the model, drawn with its own transform and tint, while the geometry is stored once.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Instance {
+    pub transform: Matrix4<f32>, // Model space of the copy relative to the mesh.
+    pub tint: Vector3<f32>,      // Multiplier of the shaded color in [0, 1].
+}
+
+impl Instance {
+    pub fn new(transform: Matrix4<f32>) -> Self {
+        return Self {
+            transform,
+            tint: Vector3::repeat(1.0),
+        };
+    }
+
+    pub fn transform_position(&self, position: Point3<f32>) -> Point3<f32> {
+        return self.transform.transform_point(&position);
+    }
+
+    /// Normals follow the rotation and scale of the transform, assuming scaling is close to uniform.
+    pub fn transform_normal(&self, normal: Vector3<f32>) -> Vector3<f32> {
+        let linear: Matrix3<f32> = self.transform.fixed_slice::<3, 3>(0, 0).into_owned();
+        return (linear * normal).normalize();
+    }
+
+    /// Sphere, enclosing the copy, when the mesh is enclosed by the given one.
+    pub fn transform_bounding_sphere(&self, bounding_sphere: &BoundingSphere) -> BoundingSphere {
+        let scale = (0..3)
+            .map(|column| self.transform.fixed_slice::<3, 1>(0, column).norm())
+            .fold(0.0, f32::max);
+        return BoundingSphere {
+            center: self.transform_position(bounding_sphere.center),
+            radius: bounding_sphere.radius * scale,
+        };
+    }
+}
+
+/// Transforms, the model is drawn with - a single None for the model as it is, when there are no instances.
+pub fn get_drawn_instances(instances: &[Instance]) -> Vec<Option<Instance>> {
+    if instances.is_empty() {
+        return vec![None];
+    }
+    return instances.iter().copied().map(Some).collect();
+}
+
+/// Regular grid of copies of the model, filling the space the model takes on its own.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InstanceGrid {
+    pub counts: [u32; 3], // Number of copies along x, y and z.
+}
+
+impl InstanceGrid {
+    /// Parses grid from a spec like `10x10x10`, returning None for malformed specs or empty grids. Missing
+    /// counts are 1, so `10x10` is a single layer.
+    pub fn from_spec(spec: &str) -> Option<Self> {
+        let values: Vec<u32> = spec
+            .split('x')
+            .map(|value| value.parse().ok())
+            .collect::<Option<_>>()?;
+        if values.is_empty() || values.len() > 3 || values.contains(&0) {
+            return None;
+        }
+        let mut counts = [1; 3];
+        counts[..values.len()].copy_from_slice(&values);
+        return Some(InstanceGrid { counts });
+    }
+
+    /// Copies, scaled down around their own centers and spaced a bounding sphere apart, so the grid fits the
+    /// bounding sphere of the mesh. Tint goes from gray to white along each axis to tell the copies apart.
+    pub fn get_instances(&self, bounding_sphere: &BoundingSphere) -> Vec<Instance> {
+        let max_count = *self.counts.iter().max().unwrap() as f32;
+        let scale = 1.0 / max_count;
+        let spacing = 2.0 * bounding_sphere.radius * scale;
+        let center = bounding_sphere.center.coords;
+        let mut instances = Vec::new();
+        for z in 0..self.counts[2] {
+            for y in 0..self.counts[1] {
+                for x in 0..self.counts[0] {
+                    let cell = Vector3::new(x as f32, y as f32, z as f32);
+                    let counts = Vector3::from(self.counts.map(|count| count as f32));
+                    let offset = (cell - (counts - Vector3::repeat(1.0)) / 2.0) * spacing;
+                    let transform = Matrix4::new_translation(&(center + offset))
+                        * Matrix4::new_scaling(scale)
+                        * Matrix4::new_translation(&-center);
+                    let fraction = cell.component_div(&counts.map(|count| (count - 1.0).max(1.0)));
+                    instances.push(Instance {
+                        transform,
+                        tint: fraction.map(|t| 0.6 + 0.4 * t),
+                    });
+                }
+            }
+        }
+        return instances;
+    }
+}
@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+
+/// Parsed JSON document, just enough of it for reading glTF.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(HashMap<String, Json>),
+}
+
+impl Json {
+    /// Parses the whole text as a single value, returning None for malformed documents.
+    pub fn parse(text: &str) -> Option<Json> {
+        let mut parser = Parser {
+            bytes: text.as_bytes(),
+            position: 0,
+        };
+        let value = parser.parse_value()?;
+        parser.skip_whitespace();
+        if parser.position != parser.bytes.len() {
+            return None;
+        }
+        return Some(value);
+    }
+
+    /// Member of the object, None for missing members and for values other than objects.
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => return members.get(key),
+            _ => return None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Json::Number(number) => return Some(*number),
+            _ => return None,
+        }
+    }
+
+    pub fn as_usize(&self) -> Option<usize> {
+        return self
+            .as_f64()
+            .filter(|number| *number >= 0.0 && number.fract() == 0.0)
+            .map(|number| number as usize);
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(string) => return Some(string),
+            _ => return None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Json::Bool(value) => return Some(*value),
+            _ => return None,
+        }
+    }
+
+    /// Elements of the array, empty for values other than arrays, so missing arrays read as empty ones.
+    pub fn elements(&self) -> &[Json] {
+        match self {
+            Json::Array(elements) => return elements,
+            _ => return &[],
+        }
+    }
+
+    /// Array of numbers as f32, None if any of the elements isn't a number.
+    pub fn as_f32_vec(&self) -> Option<Vec<f32>> {
+        return self
+            .elements()
+            .iter()
+            .map(|element| element.as_f64().map(|number| number as f32))
+            .collect();
+    }
+}
+
+/// Recursive descent over the bytes of the document.
+struct Parser<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.position < self.bytes.len() && self.bytes[self.position].is_ascii_whitespace() {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        return self.bytes.get(self.position).copied();
+    }
+
+    /// Consumes the literal, if the document continues with it.
+    fn consume(&mut self, literal: &str) -> bool {
+        self.skip_whitespace();
+        if self.bytes[self.position..].starts_with(literal.as_bytes()) {
+            self.position += literal.len();
+            return true;
+        }
+        return false;
+    }
+
+    fn parse_value(&mut self) -> Option<Json> {
+        match self.peek()? {
+            b'{' => return self.parse_object(),
+            b'[' => return self.parse_array(),
+            b'"' => return self.parse_string().map(Json::String),
+            b't' if self.consume("true") => return Some(Json::Bool(true)),
+            b'f' if self.consume("false") => return Some(Json::Bool(false)),
+            b'n' if self.consume("null") => return Some(Json::Null),
+            _ => return self.parse_number(),
+        }
+    }
+
+    fn parse_object(&mut self) -> Option<Json> {
+        self.consume("{");
+        let mut members = HashMap::new();
+        if self.consume("}") {
+            return Some(Json::Object(members));
+        }
+        loop {
+            if self.peek()? != b'"' {
+                return None;
+            }
+            let key = self.parse_string()?;
+            if !self.consume(":") {
+                return None;
+            }
+            members.insert(key, self.parse_value()?);
+            if self.consume("}") {
+                return Some(Json::Object(members));
+            }
+            if !self.consume(",") {
+                return None;
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Option<Json> {
+        self.consume("[");
+        let mut elements = Vec::new();
+        if self.consume("]") {
+            return Some(Json::Array(elements));
+        }
+        loop {
+            elements.push(self.parse_value()?);
+            if self.consume("]") {
+                return Some(Json::Array(elements));
+            }
+            if !self.consume(",") {
+                return None;
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> Option<String> {
+        self.consume("\"");
+        let mut string = Vec::new();
+        loop {
+            let byte = *self.bytes.get(self.position)?;
+            self.position += 1;
+            match byte {
+                b'"' => return String::from_utf8(string).ok(),
+                b'\\' => {
+                    let escaped = *self.bytes.get(self.position)?;
+                    self.position += 1;
+                    let character = match escaped {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        // Surrogate pairs aren't combined, which only matters for names outside of the BMP.
+                        b'u' => {
+                            let hex = std::str::from_utf8(
+                                self.bytes.get(self.position..self.position + 4)?,
+                            )
+                            .ok()?;
+                            self.position += 4;
+                            char::from_u32(u32::from_str_radix(hex, 16).ok()?)
+                                .unwrap_or(char::REPLACEMENT_CHARACTER)
+                        }
+                        _ => return None,
+                    };
+                    let mut buffer = [0; 4];
+                    string.extend_from_slice(character.encode_utf8(&mut buffer).as_bytes());
+                }
+                _ => string.push(byte),
+            }
+        }
+    }
+
+    fn parse_number(&mut self) -> Option<Json> {
+        let start = self.position;
+        while self.position < self.bytes.len()
+            && matches!(
+                self.bytes[self.position],
+                b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9'
+            )
+        {
+            self.position += 1;
+        }
+        let text = std::str::from_utf8(&self.bytes[start..self.position]).ok()?;
+        return text.parse().ok().map(Json::Number);
+    }
+}
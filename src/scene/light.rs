@@ -0,0 +1,294 @@
+use image::RgbImage;
+use na::{vector, Matrix4, Point3, Vector3};
+use nalgebra as na;
+
+use super::texture::{Filter, Sampler, TexCoord, Texture, Wrap};
+
+/// Falloff of the light intensity with distance d as 1 / (constant + linear * d + quadratic * d^2).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Attenuation {
+    pub constant: f32,
+    pub linear: f32,
+    pub quadratic: f32,
+}
+
+impl Default for Attenuation {
+    fn default() -> Self {
+        return Attenuation {
+            constant: 1.0,
+            linear: 0.0,
+            quadratic: 1.0,
+        };
+    }
+}
+
+impl Attenuation {
+    fn get_factor(&self, distance: f32) -> f32 {
+        return 1.0
+            / (self.constant + self.linear * distance + self.quadratic * distance * distance);
+    }
+}
+
+/// Light source of the scene. Colors are in [0, 1], positions are in model coordinates.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Light {
+    Directional {
+        direction: Vector3<f32>, // Direction FROM surface TO source, same as the main light direction.
+        color: Vector3<f32>,
+        intensity: f32,
+    },
+    Point {
+        position: Vector3<f32>,
+        color: Vector3<f32>,
+        intensity: f32,
+        attenuation: Attenuation,
+    },
+    Spot {
+        position: Vector3<f32>,
+        direction: Vector3<f32>, // Direction the spot is pointing at, FROM source.
+        inner_angle: f32,        // Half angle of the fully lit cone in radians.
+        outer_angle: f32, // Half angle of the cone in radians, light fades out between the two.
+        color: Vector3<f32>,
+        intensity: f32,
+        attenuation: Attenuation,
+    },
+}
+
+impl Light {
+    /// Parses light from a spec like `point:0,1,1:1,0.5,0:2`, returning None for malformed specs. Supported forms:
+    /// - directional:direction:color:intensity
+    /// - point:position:color:intensity:attenuation
+    /// - spot:position:direction:angles_in_degrees:color:intensity:attenuation
+    ///
+    /// Attenuation is optional and is given as constant,linear,quadratic coefficients. Spot angles are either
+    /// inner,outer half angles or just the outer one, with the inner one being 0.9 of it.
+    pub fn from_spec(spec: &str) -> Option<Self> {
+        fn parse_vector(part: &str) -> Option<Vector3<f32>> {
+            let values: Vec<f32> = part
+                .split(',')
+                .map(|value| value.parse().ok())
+                .collect::<Option<Vec<f32>>>()?;
+            if values.len() != 3 {
+                return None;
+            }
+            return Some(Vector3::new(values[0], values[1], values[2]));
+        }
+
+        fn parse_attenuation(part: Option<&&str>) -> Option<Attenuation> {
+            match part {
+                Some(part) => {
+                    let coefficients = parse_vector(part)?;
+                    return Some(Attenuation {
+                        constant: coefficients.x,
+                        linear: coefficients.y,
+                        quadratic: coefficients.z,
+                    });
+                }
+                None => return Some(Attenuation::default()),
+            }
+        }
+
+        fn parse_angles(part: &str) -> Option<(f32, f32)> {
+            let values: Vec<f32> = part
+                .split(',')
+                .map(|value| value.parse().ok())
+                .collect::<Option<Vec<f32>>>()?;
+            match *values.as_slice() {
+                [outer] => return Some((0.9 * outer.to_radians(), outer.to_radians())),
+                [inner, outer] if inner < outer => {
+                    return Some((inner.to_radians(), outer.to_radians()))
+                }
+                _ => return None,
+            }
+        }
+
+        let parts: Vec<&str> = spec.split(':').collect();
+        match (parts[0], parts.len()) {
+            ("directional", 4) => {
+                return Some(Light::Directional {
+                    direction: parse_vector(parts[1])?.normalize(),
+                    color: parse_vector(parts[2])?,
+                    intensity: parts[3].parse().ok()?,
+                })
+            }
+            ("point", 4 | 5) => {
+                return Some(Light::Point {
+                    position: parse_vector(parts[1])?,
+                    color: parse_vector(parts[2])?,
+                    intensity: parts[3].parse().ok()?,
+                    attenuation: parse_attenuation(parts.get(4))?,
+                })
+            }
+            ("spot", 6 | 7) => {
+                let (inner_angle, outer_angle) = parse_angles(parts[3])?;
+                return Some(Light::Spot {
+                    position: parse_vector(parts[1])?,
+                    direction: parse_vector(parts[2])?.normalize(),
+                    inner_angle,
+                    outer_angle,
+                    color: parse_vector(parts[4])?,
+                    intensity: parts[5].parse().ok()?,
+                    attenuation: parse_attenuation(parts.get(6))?,
+                });
+            }
+            _ => return None,
+        }
+    }
+
+    /// Position of the light, if it has one.
+    pub fn get_position(&self) -> Option<Vector3<f32>> {
+        match *self {
+            Light::Directional { .. } => return None,
+            Light::Point { position, .. } => return Some(position),
+            Light::Spot { position, .. } => return Some(position),
+        }
+    }
+
+    /// Same light with positions and directions transformed into the camera frame with the model matrix.
+    pub fn transformed(&self, m_matrix: &Matrix4<f32>) -> Self {
+        let transform_direction = |direction: Vector3<f32>| {
+            return Vector3::from_homogeneous(m_matrix * direction.to_homogeneous())
+                .unwrap()
+                .normalize();
+        };
+        let transform_position = |position: Vector3<f32>| {
+            return Point3::from_homogeneous(m_matrix * Point3::from(position).to_homogeneous())
+                .unwrap()
+                .coords;
+        };
+        let mut light = *self;
+        match &mut light {
+            Light::Directional { direction, .. } => {
+                *direction = transform_direction(*direction);
+            }
+            Light::Point { position, .. } => {
+                *position = transform_position(*position);
+            }
+            Light::Spot {
+                position,
+                direction,
+                ..
+            } => {
+                *position = transform_position(*position);
+                *direction = transform_direction(*direction);
+            }
+        }
+        return light;
+    }
+
+    /// Direction from the surface point to the light and light color, scaled by intensity and falloff, that
+    /// reaches the point. Point and light should be in the same coordinates.
+    pub fn get_incident_light(&self, point: Vector3<f32>) -> (Vector3<f32>, Vector3<f32>) {
+        match *self {
+            Light::Directional {
+                direction,
+                color,
+                intensity,
+            } => return (direction, color * intensity),
+            Light::Point {
+                position,
+                color,
+                intensity,
+                attenuation,
+            } => {
+                let to_light = position - point;
+                let distance = to_light.norm();
+                return (
+                    to_light / distance,
+                    color * intensity * attenuation.get_factor(distance),
+                );
+            }
+            Light::Spot {
+                position,
+                direction,
+                inner_angle,
+                outer_angle,
+                color,
+                intensity,
+                attenuation,
+            } => {
+                let to_light = position - point;
+                let distance = to_light.norm();
+                let to_light = to_light / distance;
+                // Fading out between the cones, so the edge of the spot isn't aliased.
+                let cos_outer = outer_angle.cos();
+                let cos_inner = inner_angle.cos();
+                let t = ((-to_light).dot(&direction) - cos_outer) / (cos_inner - cos_outer);
+                let cone_factor = t.clamp(0.0, 1.0);
+                return (
+                    to_light,
+                    color * intensity * attenuation.get_factor(distance) * cone_factor,
+                );
+            }
+        }
+    }
+
+    /// Transform from model coordinates to the clip space of the spot light, looking along its direction with
+    /// the field of view of its outer cone, None for the other lights. Up of the light is the y axis, unless
+    /// the light points along it.
+    pub fn get_spot_matrix(&self) -> Option<Matrix4<f32>> {
+        let (position, direction, outer_angle) = match *self {
+            Light::Spot {
+                position,
+                direction,
+                outer_angle,
+                ..
+            } => (position, direction, outer_angle),
+            _ => return None,
+        };
+        let up = match direction.y.abs() > 0.99 {
+            true => Vector3::z(),
+            false => Vector3::y(),
+        };
+        let view = Matrix4::look_at_rh(
+            &Point3::from(position),
+            &Point3::from(position + direction),
+            &up,
+        );
+        let fov_y = (2.0 * outer_angle).min(179f32.to_radians());
+        return Some(Matrix4::new_perspective(1.0, fov_y, 0.01, 100.0) * view);
+    }
+}
+
+/// Image, projected by a spot light like a slide, filtering the color of the light it casts. Transform of the
+/// light follows it, as the scene updates it every frame.
+pub struct Gobo {
+    texture: Texture,
+    spot_matrix: Matrix4<f32>,
+}
+
+impl Gobo {
+    pub fn new(image: RgbImage) -> Self {
+        let mut texture = Texture::new(image);
+        texture.sampler = Sampler {
+            filter: Filter::Bilinear,
+            wrap: Wrap::Clamp,
+            ..Default::default()
+        };
+        return Self {
+            texture,
+            spot_matrix: Matrix4::identity(),
+        };
+    }
+
+    /// Following the light, the gobo belongs to. Gobos of lights other than spot lights stay where they are.
+    pub fn update(&mut self, light: &Light) {
+        if let Some(spot_matrix) = light.get_spot_matrix() {
+            self.spot_matrix = spot_matrix;
+        }
+    }
+
+    /// Filter in [0, 1] per channel, applied to the light reaching the position in model coordinates. Image is
+    /// stretched over the square, enclosing the outer cone, with its top towards the up of the light.
+    pub fn get_filter(&self, position: Vector3<f32>) -> Vector3<f32> {
+        let clip = self.spot_matrix * Point3::from(position).to_homogeneous();
+        if clip.w <= 0.0 {
+            return Vector3::zeros();
+        }
+        let uv = vector![(clip.x / clip.w + 1.0) / 2.0, (1.0 - clip.y / clip.w) / 2.0];
+        return self.texture.sample(TexCoord {
+            uv,
+            ..Default::default()
+        }) / 255.0;
+    }
+}
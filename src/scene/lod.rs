@@ -0,0 +1,253 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap};
+
+use na::{Matrix4, Point3, Vector3, Vector4};
+use nalgebra as na;
+use obj::raw::object::Polygon;
+use obj::raw::RawObj;
+
+use super::frustum::BoundingSphere;
+
+/// Fraction of the polygons of the previous level, every coarser level of detail is simplified to.
+const LOD_REDUCTION: f32 = 0.25;
+
+/// Radius on the screen in pixels, below which the first coarser level of detail is drawn. Every next level
+/// takes over at half of the radius of the previous one, keeping about the same number of polygons per pixel.
+const LOD_RADIUS: f32 = 128.0;
+
+/// Weight of the planes, keeping open borders of the mesh in place, relative to the planes of the polygons.
+const BORDER_WEIGHT: f64 = 100.0;
+
+/// Corners of a triangle as indices of their positions, uvs and normals.
+type Triangle = [(usize, usize, usize); 3];
+
+/// Collapse of the removed position into the kept one, ordered by the error it introduces. Versions of both
+/// positions tell if the collapse is still up to date, when it's taken from the queue.
+struct Collapse {
+    error: f64,
+    keep: usize,
+    remove: usize,
+    versions: (u32, u32),
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        return self.cmp(other) == Ordering::Equal;
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        return Some(self.cmp(other));
+    }
+}
+
+impl Ord for Collapse {
+    fn cmp(&self, other: &Self) -> Ordering {
+        return self.error.total_cmp(&other.error);
+    }
+}
+
+/// Sum of the squared distances to the planes of the polygons around a position, which it's kept close to.
+fn get_plane_quadric(normal: Vector3<f64>, point: Vector3<f64>, weight: f64) -> Matrix4<f64> {
+    let plane = Vector4::new(normal.x, normal.y, normal.z, -normal.dot(&point));
+    return plane * plane.transpose() * weight;
+}
+
+fn get_error(quadric: &Matrix4<f64>, position: Vector3<f64>) -> f64 {
+    let position = position.push(1.0);
+    return (position.transpose() * quadric * position)[0];
+}
+
+/// Simplifies the polygons down to about the target number of triangles by collapsing the edges, which move the
+/// surface the least, as measured by the quadric error metric. Edges collapse into one of their positions, so
+/// the result shares the vertices of the model, keeping skins, displacement and tangents valid, while uvs and
+/// normals stay with the corners. Collapses, which would flip a triangle, are skipped.
+pub fn simplify(obj: &RawObj, polygons: &[Polygon], target_count: usize) -> Vec<Polygon> {
+    let position = |index: usize| -> Vector3<f64> {
+        let (x, y, z, _) = obj.positions[index];
+        return Vector3::new(x as f64, y as f64, z as f64);
+    };
+    // Scene triangulates the model, so only the first 3 corners of a polygon are ever there to simplify.
+    let mut triangles: Vec<Triangle> = polygons
+        .iter()
+        .filter_map(|polygon| match polygon {
+            Polygon::PTN(indices) if indices.len() >= 3 => {
+                Some([indices[0], indices[1], indices[2]])
+            }
+            _ => None,
+        })
+        .collect();
+    let mut alive = vec![true; triangles.len()];
+    let mut triangle_count = triangles.len();
+    let mut position_triangles: Vec<Vec<usize>> = vec![Vec::new(); obj.positions.len()];
+    let mut quadrics = vec![Matrix4::<f64>::zeros(); obj.positions.len()];
+    // Counting the triangles on both sides of every edge to find the open borders.
+    let mut edges: HashMap<(usize, usize), (usize, usize)> = HashMap::new();
+    for (index, triangle) in triangles.iter().enumerate() {
+        let points = triangle.map(|corner| position(corner.0));
+        let cross = (points[1] - points[0]).cross(&(points[2] - points[0]));
+        let area = cross.norm() / 2.0;
+        let quadric = match cross.try_normalize(0.0) {
+            Some(normal) => get_plane_quadric(normal, points[0], area),
+            None => Matrix4::zeros(),
+        };
+        for k in 0..3 {
+            position_triangles[triangle[k].0].push(index);
+            quadrics[triangle[k].0] += quadric;
+            let (from, to) = (triangle[k].0, triangle[(k + 1) % 3].0);
+            edges
+                .entry((from.min(to), from.max(to)))
+                .or_insert((index, 0))
+                .1 += 1;
+        }
+    }
+    // Borders are kept by planes through the border edges, perpendicular to their triangles.
+    for (&(from, to), &(index, count)) in &edges {
+        if count != 1 {
+            continue;
+        }
+        let points = triangles[index].map(|corner| position(corner.0));
+        let face_normal = (points[1] - points[0]).cross(&(points[2] - points[0]));
+        let edge = position(to) - position(from);
+        if let Some(normal) = edge.cross(&face_normal).try_normalize(0.0) {
+            let quadric =
+                get_plane_quadric(normal, position(from), BORDER_WEIGHT * edge.norm_squared());
+            quadrics[from] += quadric;
+            quadrics[to] += quadric;
+        }
+    }
+
+    let mut versions = vec![0u32; obj.positions.len()];
+    let mut removed = vec![false; obj.positions.len()];
+    let get_collapse = |quadrics: &[Matrix4<f64>], versions: &[u32], a: usize, b: usize| {
+        let quadric = quadrics[a] + quadrics[b];
+        let (error_a, error_b) = (
+            get_error(&quadric, position(a)),
+            get_error(&quadric, position(b)),
+        );
+        let (keep, remove, error) = match error_a <= error_b {
+            true => (a, b, error_a),
+            false => (b, a, error_b),
+        };
+        return Reverse(Collapse {
+            error,
+            keep,
+            remove,
+            versions: (versions[keep], versions[remove]),
+        });
+    };
+    let mut queue: BinaryHeap<Reverse<Collapse>> = edges
+        .keys()
+        .map(|&(a, b)| get_collapse(&quadrics, &versions, a, b))
+        .collect();
+
+    while triangle_count > target_count {
+        let collapse = match queue.pop() {
+            Some(Reverse(collapse)) => collapse,
+            None => break,
+        };
+        let (keep, remove) = (collapse.keep, collapse.remove);
+        if removed[keep]
+            || removed[remove]
+            || collapse.versions != (versions[keep], versions[remove])
+        {
+            continue;
+        }
+        // Triangles, which don't have the edge, move with the removed position and mustn't turn over.
+        let flips = position_triangles[remove].iter().any(|&index| {
+            let triangle = &triangles[index];
+            if !alive[index] || triangle.iter().any(|corner| corner.0 == keep) {
+                return false;
+            }
+            let before = triangle.map(|corner| position(corner.0));
+            let after = triangle.map(|corner| match corner.0 == remove {
+                true => position(keep),
+                false => position(corner.0),
+            });
+            let normal_before = (before[1] - before[0]).cross(&(before[2] - before[0]));
+            let normal_after = (after[1] - after[0]).cross(&(after[2] - after[0]));
+            return normal_before.dot(&normal_after) <= 0.0;
+        });
+        if flips {
+            continue;
+        }
+
+        for index in std::mem::take(&mut position_triangles[remove]) {
+            if !alive[index] {
+                continue;
+            }
+            if triangles[index].iter().any(|corner| corner.0 == keep) {
+                alive[index] = false;
+                triangle_count -= 1;
+                continue;
+            }
+            for corner in triangles[index].iter_mut() {
+                if corner.0 == remove {
+                    corner.0 = keep;
+                }
+            }
+            position_triangles[keep].push(index);
+        }
+        removed[remove] = true;
+        quadrics[keep] = quadrics[keep] + quadrics[remove];
+        versions[keep] += 1;
+        position_triangles[keep].retain(|&index| alive[index]);
+        let mut neighbors: Vec<usize> = position_triangles[keep]
+            .iter()
+            .flat_map(|&index| triangles[index].map(|corner| corner.0))
+            .filter(|&neighbor| neighbor != keep)
+            .collect();
+        neighbors.sort_unstable();
+        neighbors.dedup();
+        for neighbor in neighbors {
+            queue.push(get_collapse(&quadrics, &versions, keep, neighbor));
+        }
+    }
+
+    return triangles
+        .into_iter()
+        .zip(alive)
+        .filter(|(_, alive)| *alive)
+        .map(|(triangle, _)| Polygon::PTN(triangle.to_vec()))
+        .collect();
+}
+
+/// Coarser levels of detail of the model, each one simplified from the previous one to a quarter of its
+/// polygons.
+pub fn generate_lods(obj: &RawObj, levels: u32) -> Vec<Vec<Polygon>> {
+    let mut lods: Vec<Vec<Polygon>> = Vec::new();
+    for _ in 0..levels {
+        let polygons = lods.last().unwrap_or(&obj.polygons);
+        let target_count = (polygons.len() as f32 * LOD_REDUCTION) as usize;
+        lods.push(simplify(obj, polygons, target_count));
+    }
+    return lods;
+}
+
+/// Level of detail for the sphere, projected by the pass transform, with right being the direction in model
+/// space along the x axis of the screen. 0 is the full mesh, drawn as well when the sphere is behind the camera.
+pub fn select_lod(
+    vpmv_matrix: &Matrix4<f32>,
+    right: Vector3<f32>,
+    bounding_sphere: &BoundingSphere,
+    levels: usize,
+) -> usize {
+    let center = vpmv_matrix * bounding_sphere.center.to_homogeneous();
+    let side =
+        vpmv_matrix * (bounding_sphere.center + right * bounding_sphere.radius).to_homogeneous();
+    if center.w <= 0.0 || side.w <= 0.0 {
+        return 0;
+    }
+    let radius = (Point3::from_homogeneous(side).unwrap()
+        - Point3::from_homogeneous(center).unwrap())
+    .xy()
+    .norm();
+    if radius >= LOD_RADIUS {
+        return 0;
+    }
+    let level = (LOD_RADIUS / radius.max(f32::EPSILON)).log2().floor() as usize + 1;
+    return level.min(levels);
+}
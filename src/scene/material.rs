@@ -0,0 +1,46 @@
+/// Lighting coefficients of an object, shared by the built-in pipelines, so the same pipeline renders matte
+/// and glossy objects side by side. Defaults reproduce the pipelines as they are without any materials, the
+/// physically based pipeline has metallic and roughness maps instead and ignores them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Material {
+    pub ambient: f32,  // Fraction of the texture color, shown regardless of the lights.
+    pub diffuse: f32,  // Scale of the Lambertian term of the main light and the additional lights.
+    pub specular: f32, // Scale of the highlight of the main light, in the pipelines which have one.
+    pub shininess: Option<f32>, // Exponent of the highlight, None reads it from the specular map.
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        return Material {
+            ambient: 0.0,
+            diffuse: 1.0,
+            specular: 0.6,
+            shininess: None,
+        };
+    }
+}
+
+impl Material {
+    /// Parses material from a spec like `0.1:0.8:1:32` of ambient, diffuse, specular and shininess, returning
+    /// None for malformed specs. Trailing values are optional and keep their defaults, so `0.1:0.8` is a matte
+    /// material with the highlight exponent read from the specular map.
+    pub fn from_spec(spec: &str) -> Option<Self> {
+        let values: Vec<f32> = spec
+            .split(':')
+            .map(|value| value.parse().ok())
+            .collect::<Option<_>>()?;
+        if values.len() > 4 || values.iter().any(|value| *value < 0.0) {
+            return None;
+        }
+        let mut material = Material::default();
+        for (index, value) in values.into_iter().enumerate() {
+            match index {
+                0 => material.ambient = value,
+                1 => material.diffuse = value,
+                2 => material.specular = value,
+                _ => material.shininess = Some(value),
+            }
+        }
+        return Some(material);
+    }
+}
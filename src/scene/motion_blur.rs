@@ -0,0 +1,63 @@
+use na::Vector2;
+use nalgebra as na;
+use rayon::prelude::*;
+
+/// Parameters of the motion blur, smearing every pixel along its motion on the screen since the previous frame.
+#[derive(Clone, Copy, Debug)]
+pub struct MotionBlurSettings {
+    pub strength: f32, // Fraction of the motion since the previous frame, which is blurred over, like a shutter.
+    pub samples: u32,  // Number of color lookups per pixel along its motion.
+}
+
+impl Default for MotionBlurSettings {
+    fn default() -> Self {
+        return MotionBlurSettings {
+            strength: 1.0,
+            samples: 12,
+        };
+    }
+}
+
+/// Averages colors of the frame buffer along the velocity of every pixel, centered at the pixel, leaving
+/// pixels, which didn't move, as they are. Velocity buffer holds a single value per pixel in pixels of the
+/// rendered frame, background never moves.
+pub fn apply_motion_blur(
+    settings: MotionBlurSettings,
+    frame_buffer: &mut [f32],
+    velocity_buffer: &[Vector2<f32>],
+    width: usize,
+    height: usize,
+) {
+    let source = frame_buffer.to_vec();
+    frame_buffer
+        .par_chunks_mut(3 * width)
+        .enumerate()
+        .for_each(|(y, row)| {
+            for x in 0..width {
+                let velocity = velocity_buffer[x + y * width] * settings.strength;
+                if velocity.norm() < 0.5 {
+                    continue;
+                }
+                let mut color_sum = [0.0; 3];
+                let mut count = 0;
+                for sample in 0..settings.samples {
+                    let t = (sample as f32 + 0.5) / settings.samples as f32 - 0.5;
+                    let sx = (x as f32 - velocity.x * t).round() as i32;
+                    let sy = (y as f32 - velocity.y * t).round() as i32;
+                    if sx < 0 || sy < 0 || sx >= width as i32 || sy >= height as i32 {
+                        continue;
+                    }
+                    let index = sx as usize + sy as usize * width;
+                    for c in 0..3 {
+                        color_sum[c] += source[3 * index + c];
+                    }
+                    count += 1;
+                }
+                if count > 0 {
+                    for c in 0..3 {
+                        row[3 * x + c] = color_sum[c] / count as f32;
+                    }
+                }
+            }
+        });
+}
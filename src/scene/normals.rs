@@ -0,0 +1,142 @@
+use na::{Point3, Vector3};
+use nalgebra as na;
+use obj::raw::object::Polygon;
+use obj::raw::RawObj;
+
+/// Corner of a polygon as indices of position, optional uv and optional normal.
+type Corner = (usize, Option<usize>, Option<usize>);
+
+/// Where vertex normals of the model come from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NormalGeneration {
+    #[default]
+    Auto, // Normals of the file are kept, only polygons without them get smooth ones.
+    Smooth, // Every normal is replaced by the average of the face normals around the vertex.
+    Flat,   // Every normal is replaced by the face normal, so polygons are shaded as flat facets.
+}
+
+impl NormalGeneration {
+    /// Parses normal generation mode from its command line name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "auto" => return Some(NormalGeneration::Auto),
+            "smooth" => return Some(NormalGeneration::Smooth),
+            "flat" => return Some(NormalGeneration::Flat),
+            _ => return None,
+        }
+    }
+}
+
+/// Brings every polygon of the model to positions, uvs and normals, generating the normals, which are missing
+/// or are to be replaced. Smooth normals average the face normals of the polygons, sharing the position,
+/// weighted by the angles of their corners, so the result doesn't depend on how the surface is tessellated.
+/// Polygons without uvs get a single uv at the origin. Returns the number of polygons with generated normals.
+pub fn generate_normals(obj: &mut RawObj, generation: NormalGeneration) -> usize {
+    let mut polygons: Vec<Vec<Corner>> = obj
+        .polygons
+        .iter()
+        .map(|polygon| match polygon {
+            Polygon::P(indices) => return indices.iter().map(|&p| (p, None, None)).collect(),
+            Polygon::PT(indices) => {
+                return indices.iter().map(|&(p, t)| (p, Some(t), None)).collect()
+            }
+            Polygon::PN(indices) => {
+                return indices.iter().map(|&(p, n)| (p, None, Some(n))).collect()
+            }
+            Polygon::PTN(indices) => {
+                return indices
+                    .iter()
+                    .map(|&(p, t, n)| (p, Some(t), Some(n)))
+                    .collect()
+            }
+        })
+        .collect();
+    let is_generated = |corners: &Vec<Corner>| {
+        return generation != NormalGeneration::Auto
+            || corners.iter().any(|corner| corner.2.is_none());
+    };
+    let n_generated = polygons
+        .iter()
+        .filter(|corners| is_generated(corners))
+        .count();
+    let is_missing_uvs = polygons.iter().flatten().any(|corner| corner.1.is_none());
+    // Polygons without missing attributes are already all PTN.
+    if n_generated == 0 && !is_missing_uvs {
+        return 0;
+    }
+
+    let positions: Vec<Point3<f32>> = obj
+        .positions
+        .iter()
+        .map(|&(x, y, z, _)| Point3::new(x, y, z))
+        .collect();
+    let position = |index: usize| positions[index];
+    // Newell's method, which works for any planar polygon, not only triangles.
+    let get_face_normal = |corners: &[Corner]| -> Vector3<f32> {
+        let mut normal = Vector3::zeros();
+        for k in 0..corners.len() {
+            let current = position(corners[k].0);
+            let next = position(corners[(k + 1) % corners.len()].0);
+            normal += current.coords.cross(&next.coords);
+        }
+        return normal
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_else(Vector3::z);
+    };
+
+    if generation != NormalGeneration::Auto {
+        obj.normals.clear();
+    }
+    match generation {
+        NormalGeneration::Flat => {
+            for corners in polygons.iter_mut() {
+                let normal = get_face_normal(corners);
+                obj.normals.push((normal.x, normal.y, normal.z));
+                for corner in corners.iter_mut() {
+                    corner.2 = Some(obj.normals.len() - 1);
+                }
+            }
+        }
+        NormalGeneration::Auto | NormalGeneration::Smooth => {
+            let mut sums = vec![Vector3::<f32>::zeros(); obj.positions.len()];
+            for corners in polygons.iter().filter(|corners| is_generated(corners)) {
+                let normal = get_face_normal(corners);
+                for k in 0..corners.len() {
+                    let current = position(corners[k].0);
+                    let to_next = position(corners[(k + 1) % corners.len()].0) - current;
+                    let to_previous =
+                        position(corners[(k + corners.len() - 1) % corners.len()].0) - current;
+                    sums[corners[k].0] += normal * to_next.angle(&to_previous);
+                }
+            }
+            // Smooth normals are indexed the same as positions, following the normals of the file.
+            let offset = obj.normals.len();
+            for sum in sums {
+                let normal = sum.try_normalize(f32::EPSILON).unwrap_or_else(Vector3::z);
+                obj.normals.push((normal.x, normal.y, normal.z));
+            }
+            for corners in polygons.iter_mut().filter(|corners| is_generated(corners)) {
+                for corner in corners.iter_mut() {
+                    corner.2 = Some(offset + corner.0);
+                }
+            }
+        }
+    }
+
+    let missing_uv_index = obj.tex_coords.len();
+    if is_missing_uvs {
+        obj.tex_coords.push((0.0, 0.0, 0.0));
+    }
+    obj.polygons = polygons
+        .into_iter()
+        .map(|corners| {
+            return Polygon::PTN(
+                corners
+                    .into_iter()
+                    .map(|(p, t, n)| (p, t.unwrap_or(missing_uv_index), n.unwrap()))
+                    .collect(),
+            );
+        })
+        .collect();
+    return n_generated;
+}
@@ -0,0 +1,21 @@
+use na::{Point3, Vector3};
+use nalgebra as na;
+
+/// Closest opaque polygon at a single pixel of the ID buffer, written together with the depth, so it always
+/// matches the z-buffer.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct IdTexel {
+    pub instance: u32, // Index of the instance, 0 when the model isn't instanced.
+    pub polygon: Option<u32>, // Index of the polygon in its level of detail, None where nothing is drawn.
+    pub bar_coord: Vector3<f32>, // Barycentric coordinates of the pixel inside of the polygon.
+}
+
+/// What the frame shows at a pixel, found with Scene::pick.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PickHit {
+    pub object: u32, // Index of the instance of the model, 0 when the model isn't instanced.
+    pub lod: u32, // Level of detail the instance was drawn with, 0 being the polygons of the model.
+    pub polygon: u32, // Index of the polygon in that level of detail.
+    pub bar_coord: Vector3<f32>, // Barycentric coordinates of the pixel inside of the polygon.
+    pub position: Point3<f32>, // Point of the polygon under the pixel in model space, moved with the instance.
+}
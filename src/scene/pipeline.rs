@@ -0,0 +1,113 @@
+use super::shader::{default_prepare, DepthTarget, StencilState, Targets, Uniforms};
+use super::util::Model;
+
+use na::{Matrix2x3, Vector2, Vector3};
+use nalgebra as na;
+
+/// Values passed from the vertex stage to the fragment stage of a pipeline for a single polygon. Layout is up
+/// to the pipeline, rasterizer only needs to know where the polygon ended up on the screen.
+pub trait ShaderVaryings: Default + Send + Sync {
+    /// x, y coordinates of the polygon vertices after all transformations, including viewport, as columns,
+    /// in fixed point with `SUBPIXEL_BITS` of the fraction, see `to_raster_coord`.
+    fn get_raster_coords(&self) -> Matrix2x3<i32>;
+
+    /// Called by the rasterizer after the vertex stage with screen space derivatives of the barycentric
+    /// coordinates, which are constant over the polygon. Ignored by default.
+    fn set_bar_coord_derivatives(&mut self, _dx: Vector3<f32>, _dy: Vector3<f32>) {}
+
+    /// Whether polygon is blended over the opaque ones, which defers it until all the opaque polygons of the
+    /// pass are drawn. Translucent polygons don't write depth and are drawn from back to front.
+    fn is_translucent(&self) -> bool {
+        return false;
+    }
+
+    /// Depth of the polygon, used to sort translucent polygons, larger is closer to the camera.
+    fn get_depth(&self) -> f32 {
+        return 0.0;
+    }
+
+    /// Depth of every vertex in the units of the depth target, if fragment stage tests the depth interpolated
+    /// from them. Lets the rasterizer reject hidden fragments before running the fragment stage.
+    fn get_z_values(&self) -> Option<Vector3<f32>> {
+        return None;
+    }
+
+    /// Motion of every vertex on the screen in pixels since the previous frame as columns, written into the
+    /// velocity buffer for motion blur. Vertices are still by default.
+    fn get_velocities(&self) -> Matrix2x3<f32> {
+        return Matrix2x3::zeros();
+    }
+}
+
+/// Shader pipeline, consisting of one or several passes with a vertex and a fragment stage each. Implement
+/// it and register with Scene::set_shader_pipeline to render the model with custom shading.
+pub trait ShaderPipeline: Send + Sync {
+    /// Output of the vertex stage, shared by all fragment stage invocations of the polygon.
+    type Varyings: ShaderVaryings;
+
+    /// Number of passes, which are run one after another every frame.
+    fn get_pass_count(&self) -> usize {
+        return 1;
+    }
+
+    /// Depth buffer, which fragment stage of the pass is testing against and writing to.
+    fn get_depth_target(&self, _pass: usize) -> DepthTarget {
+        return DepthTarget::ZBuffer;
+    }
+
+    /// Stencil test and write of the pass, none by default.
+    fn get_stencil_state(&self, _pass: usize) -> StencilState {
+        return StencilState::default();
+    }
+
+    /// Fills uniforms with the pass constants before the vertex stage. Sets up transforms relative to the
+    /// camera by default.
+    fn prepare(
+        &self,
+        _pass: usize,
+        uniforms: &mut Uniforms,
+        width: u32,
+        height: u32,
+        light_direction: Vector3<f32>,
+        look_from: Vector3<f32>,
+        look_at: Vector3<f32>,
+        up: Vector3<f32>,
+    ) {
+        default_prepare(
+            uniforms,
+            width,
+            height,
+            light_direction,
+            look_from,
+            look_at,
+            up,
+        );
+    }
+
+    /// Vertex stage, taking position, uv and normal indices of the polygon vertices. Returns false if the
+    /// polygon shouldn't be rendered.
+    fn vertex(
+        &self,
+        pass: usize,
+        uniforms: &Uniforms,
+        varyings: &mut Self::Varyings,
+        model: &Model,
+        position_indices: Vector3<usize>,
+        uv_indices: Vector3<usize>,
+        normal_indices: Vector3<usize>,
+    ) -> bool;
+
+    /// Fragment stage for the pixel at coord with given barycentric coordinates, returning None if fragment
+    /// shouldn't be drawn. Color channels are in [0, 255] for white, brighter values are kept until tone
+    /// mapping.
+    fn fragment(
+        &self,
+        pass: usize,
+        uniforms: &Uniforms,
+        varyings: &Self::Varyings,
+        targets: &mut Targets,
+        model: &Model,
+        coord: Vector2<u32>,
+        bar_coord: Vector3<f32>,
+    ) -> Option<Vector3<f32>>;
+}
@@ -0,0 +1,312 @@
+use std::error::Error;
+use std::path::Path;
+
+use na::Vector3;
+use nalgebra as na;
+use obj::raw::object::Polygon;
+
+use super::asset::{new_raw_obj, ColoredMesh};
+use super::error::RendererError;
+
+/// Encoding of the body of the file, which follows the text header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+/// Type of a property value or of the length of a list property.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ScalarType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl ScalarType {
+    /// Parses type from its name in the header, both the old and the sized names.
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "char" | "int8" => return Some(ScalarType::I8),
+            "uchar" | "uint8" => return Some(ScalarType::U8),
+            "short" | "int16" => return Some(ScalarType::I16),
+            "ushort" | "uint16" => return Some(ScalarType::U16),
+            "int" | "int32" => return Some(ScalarType::I32),
+            "uint" | "uint32" => return Some(ScalarType::U32),
+            "float" | "float32" => return Some(ScalarType::F32),
+            "double" | "float64" => return Some(ScalarType::F64),
+            _ => return None,
+        }
+    }
+
+    fn get_size(&self) -> usize {
+        match self {
+            ScalarType::I8 | ScalarType::U8 => return 1,
+            ScalarType::I16 | ScalarType::U16 => return 2,
+            ScalarType::I32 | ScalarType::U32 | ScalarType::F32 => return 4,
+            ScalarType::F64 => return 8,
+        }
+    }
+
+    /// Value, which integer colors are divided by to get into [0, 1], floating point colors are already there.
+    fn get_color_scale(&self) -> f64 {
+        match self {
+            ScalarType::U8 => return u8::MAX as f64,
+            ScalarType::U16 => return u16::MAX as f64,
+            ScalarType::U32 => return u32::MAX as f64,
+            ScalarType::I8 => return i8::MAX as f64,
+            ScalarType::I16 => return i16::MAX as f64,
+            ScalarType::I32 => return i32::MAX as f64,
+            ScalarType::F32 | ScalarType::F64 => return 1.0,
+        }
+    }
+}
+
+/// Property of an element, a list of values, when it has the type of its length.
+#[derive(Clone, Debug)]
+struct Property {
+    name: String,
+    value_type: ScalarType,
+    length_type: Option<ScalarType>,
+}
+
+/// Element of the header, e.g. vertex or face, with the number of its instances in the body.
+#[derive(Clone, Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+/// Values of the body, read one at a time in the order, which the header declares them in.
+struct BodyReader<'a> {
+    format: Format,
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl BodyReader<'_> {
+    fn read(&mut self, value_type: ScalarType) -> Result<f64, Box<dyn Error>> {
+        if self.format == Format::Ascii {
+            let rest = &self.bytes[self.position..];
+            let start = rest
+                .iter()
+                .position(|byte| !byte.is_ascii_whitespace())
+                .ok_or("Truncated PLY body")?;
+            let length = rest[start..]
+                .iter()
+                .position(|byte| byte.is_ascii_whitespace())
+                .unwrap_or(rest.len() - start);
+            self.position += start + length;
+            return Ok(std::str::from_utf8(&rest[start..start + length])?.parse()?);
+        }
+        let size = value_type.get_size();
+        let mut bytes = [0; 8];
+        bytes[..size].copy_from_slice(
+            self.bytes
+                .get(self.position..self.position + size)
+                .ok_or("Truncated PLY body")?,
+        );
+        self.position += size;
+        if self.format == Format::BinaryBigEndian {
+            bytes[..size].reverse();
+        }
+        let value = match value_type {
+            ScalarType::I8 => bytes[0] as i8 as f64,
+            ScalarType::U8 => bytes[0] as f64,
+            ScalarType::I16 => i16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            ScalarType::U16 => u16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            ScalarType::I32 => i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+            ScalarType::U32 => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+            ScalarType::F32 => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+            ScalarType::F64 => f64::from_le_bytes(bytes),
+        };
+        return Ok(value);
+    }
+}
+
+/// Loads the mesh from an ASCII or binary PLY file, as written by 3D scanners, together with its vertex colors,
+/// sRGB encoded in [0, 1] per position, empty if the vertices don't have them. Normals and uvs are taken, when
+/// the vertices have them, faces are polygons of indices into the vertices and other elements are skipped.
+pub fn load_ply(path: &str) -> Result<ColoredMesh, RendererError> {
+    if !Path::new(path).exists() {
+        return Err(RendererError::AssetNotFound(path.into()));
+    }
+    return read_ply(path).map_err(|error| RendererError::InvalidPly {
+        path: path.into(),
+        message: error.to_string(),
+    });
+}
+
+fn read_ply(path: &str) -> Result<ColoredMesh, Box<dyn Error>> {
+    let bytes = std::fs::read(path)?;
+    let (format, elements, body_start) = parse_header(&bytes)?;
+    let mut reader = BodyReader {
+        format,
+        bytes: &bytes[body_start..],
+        position: 0,
+    };
+
+    let mut obj = new_raw_obj();
+    let mut colors = Vec::new();
+    let (mut has_normals, mut has_uvs) = (false, false);
+    for element in &elements {
+        let find = |names: &[&str]| {
+            return element
+                .properties
+                .iter()
+                .position(|property| names.contains(&property.name.as_str()));
+        };
+        let position = [find(&["x"]), find(&["y"]), find(&["z"])];
+        let normal = [find(&["nx"]), find(&["ny"]), find(&["nz"])];
+        let uv = [
+            find(&["u", "s", "texture_u", "texture_s"]),
+            find(&["v", "t", "texture_v", "texture_t"]),
+        ];
+        let color = [
+            find(&["red", "r", "diffuse_red"]),
+            find(&["green", "g", "diffuse_green"]),
+            find(&["blue", "b", "diffuse_blue"]),
+        ];
+        let indices = find(&["vertex_indices", "vertex_index"]);
+        has_normals |= element.name == "vertex" && normal.iter().all(Option::is_some);
+        has_uvs |= element.name == "vertex" && uv.iter().all(Option::is_some);
+
+        let mut values = vec![0.0; element.properties.len()];
+        for _ in 0..element.count {
+            let mut face = Vec::new();
+            for (k, property) in element.properties.iter().enumerate() {
+                match property.length_type {
+                    Some(length_type) => {
+                        let length = reader.read(length_type)? as usize;
+                        for _ in 0..length {
+                            let value = reader.read(property.value_type)?;
+                            if element.name == "face" && Some(k) == indices {
+                                face.push(value as usize);
+                            }
+                        }
+                    }
+                    None => values[k] = reader.read(property.value_type)?,
+                }
+            }
+            match element.name.as_str() {
+                "vertex" => {
+                    let value = |k: Option<usize>| k.map_or(0.0, |k| values[k] as f32);
+                    let [x, y, z] = position.map(value);
+                    obj.positions.push((x, y, z, 1.0));
+                    if has_normals {
+                        let [x, y, z] = normal.map(value);
+                        obj.normals.push((x, y, z));
+                    }
+                    if has_uvs {
+                        let [u, v] = uv.map(value);
+                        obj.tex_coords.push((u, v, 0.0));
+                    }
+                    if color.iter().all(Option::is_some) {
+                        colors.push(Vector3::from_fn(|c, _| {
+                            let k = color[c].unwrap();
+                            let scale = element.properties[k].value_type.get_color_scale();
+                            return (values[k] / scale) as f32;
+                        }));
+                    }
+                }
+                "face" if face.len() >= 3 => {
+                    if let Some(&index) = face.iter().find(|&&index| index >= obj.positions.len()) {
+                        return Err(format!("PLY face refers to missing vertex {}", index).into());
+                    }
+                    obj.polygons.push(match (has_uvs, has_normals) {
+                        (true, true) => Polygon::PTN(face.iter().map(|&i| (i, i, i)).collect()),
+                        (true, false) => Polygon::PT(face.iter().map(|&i| (i, i)).collect()),
+                        (false, true) => Polygon::PN(face.iter().map(|&i| (i, i)).collect()),
+                        (false, false) => Polygon::P(face),
+                    });
+                }
+                _ => (),
+            }
+        }
+    }
+    if obj.polygons.is_empty() {
+        return Err("PLY file has no faces".into());
+    }
+    return Ok((obj, colors));
+}
+
+/// Format and elements of the header and the offset of the body, which follows it.
+fn parse_header(bytes: &[u8]) -> Result<(Format, Vec<Element>, usize), Box<dyn Error>> {
+    const END: &[u8] = b"end_header";
+    let end = bytes
+        .windows(END.len())
+        .position(|window| window == END)
+        .ok_or("PLY file without end of header")?;
+    // Body starts on the line after the end of the header.
+    let body_start = bytes[end..]
+        .iter()
+        .position(|&byte| byte == b'\n')
+        .map_or(bytes.len(), |offset| end + offset + 1);
+    let header = std::str::from_utf8(&bytes[..end])?;
+    let mut lines = header.lines();
+    if lines.next().map(str::trim) != Some("ply") {
+        return Err("File isn't a PLY file".into());
+    }
+
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    for line in lines {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["format", name, _] => {
+                format = match *name {
+                    "ascii" => Some(Format::Ascii),
+                    "binary_little_endian" => Some(Format::BinaryLittleEndian),
+                    "binary_big_endian" => Some(Format::BinaryBigEndian),
+                    _ => return Err("Unknown PLY format".into()),
+                };
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count.parse()?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", length_type, value_type, name] => {
+                elements
+                    .last_mut()
+                    .ok_or("PLY property outside of an element")?
+                    .properties
+                    .push(Property {
+                        name: name.to_string(),
+                        value_type: ScalarType::from_name(value_type)
+                            .ok_or("Unknown PLY property type")?,
+                        length_type: Some(
+                            ScalarType::from_name(length_type)
+                                .ok_or("Unknown PLY property type")?,
+                        ),
+                    });
+            }
+            ["property", value_type, name] => {
+                elements
+                    .last_mut()
+                    .ok_or("PLY property outside of an element")?
+                    .properties
+                    .push(Property {
+                        name: name.to_string(),
+                        value_type: ScalarType::from_name(value_type)
+                            .ok_or("Unknown PLY property type")?,
+                        length_type: None,
+                    });
+            }
+            ["comment", ..] | ["obj_info", ..] | [] => (),
+            _ => return Err(format!("Malformed PLY header line '{}'", line).into()),
+        }
+    }
+    return Ok((
+        format.ok_or("PLY header without format")?,
+        elements,
+        body_start,
+    ));
+}
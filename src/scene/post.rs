@@ -0,0 +1,128 @@
+use na::Vector2;
+use nalgebra as na;
+use rayon::prelude::*;
+
+use super::bloom::{apply_bloom, BloomSettings};
+use super::dof::{apply_dof, DofSettings};
+use super::motion_blur::{apply_motion_blur, MotionBlurSettings};
+use super::projection::Projection;
+use super::ssao::{apply_ssao, SsaoSettings};
+
+/// Buffers of the rendered frame, handed to post-processing passes. Everything is in the render resolution,
+/// before downsampling and tone mapping.
+pub struct PostFrame<'a> {
+    pub color: &'a mut [f32], // Rgb colors, where 255 is white and brighter values are allowed.
+    pub depth: &'a [f32],     // Depth values, larger is closer, f32::MIN where nothing was drawn.
+    pub depth_stride: usize, // Number of depth values per pixel, of which the first one is the pixel's.
+    pub width: usize,
+    pub height: usize,
+    pub projection: Projection, // Projection of the camera, mapping depth values back to view space.
+    // Motion of every pixel on the screen since the previous frame, empty when motion blur is disabled.
+    pub velocity: &'a [Vector2<f32>],
+}
+
+/// Effect, applied to the whole frame after all the passes of the shader pipeline. Register with
+/// Scene::add_post_pass, passes run in the order they were added.
+pub trait PostPass: Send + Sync {
+    fn apply(&self, frame: &mut PostFrame);
+}
+
+impl PostPass for SsaoSettings {
+    fn apply(&self, frame: &mut PostFrame) {
+        apply_ssao(
+            *self,
+            frame.color,
+            frame.depth,
+            frame.depth_stride,
+            frame.width,
+            frame.height,
+        );
+    }
+}
+
+impl PostPass for DofSettings {
+    fn apply(&self, frame: &mut PostFrame) {
+        apply_dof(
+            *self,
+            &frame.projection,
+            frame.color,
+            frame.depth,
+            frame.depth_stride,
+            frame.width,
+            frame.height,
+        );
+    }
+}
+
+impl PostPass for MotionBlurSettings {
+    fn apply(&self, frame: &mut PostFrame) {
+        if frame.velocity.is_empty() {
+            return;
+        }
+        apply_motion_blur(
+            *self,
+            frame.color,
+            frame.velocity,
+            frame.width,
+            frame.height,
+        );
+    }
+}
+
+impl PostPass for BloomSettings {
+    fn apply(&self, frame: &mut PostFrame) {
+        apply_bloom(*self, frame.color, frame.width, frame.height);
+    }
+}
+
+/// Simple effects, built into the crate and chosen by name.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BuiltinPostPass {
+    Vignette { strength: f32 }, // Darkening towards the corners, fully black there for strength 1.
+    Grayscale,
+    Invert,
+}
+
+impl BuiltinPostPass {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "vignette" => return Some(BuiltinPostPass::Vignette { strength: 0.6 }),
+            "grayscale" => return Some(BuiltinPostPass::Grayscale),
+            "invert" => return Some(BuiltinPostPass::Invert),
+            _ => return None,
+        }
+    }
+}
+
+impl PostPass for BuiltinPostPass {
+    fn apply(&self, frame: &mut PostFrame) {
+        let (width, height) = (frame.width, frame.height);
+        frame
+            .color
+            .par_chunks_mut(3 * width)
+            .enumerate()
+            .for_each(|(y, row)| {
+                for x in 0..width {
+                    let pixel = &mut row[3 * x..3 * x + 3];
+                    match *self {
+                        BuiltinPostPass::Vignette { strength } => {
+                            // Squared distance from the center, normalized to 1 in the corners.
+                            let u = 2.0 * (x as f32 + 0.5) / width as f32 - 1.0;
+                            let v = 2.0 * (y as f32 + 0.5) / height as f32 - 1.0;
+                            let factor = 1.0 - strength * (u * u + v * v) / 2.0;
+                            pixel.iter_mut().for_each(|c| *c *= factor);
+                        }
+                        BuiltinPostPass::Grayscale => {
+                            // Rec. 709 luminance of the linear color.
+                            let luminance =
+                                0.2126 * pixel[0] + 0.7152 * pixel[1] + 0.0722 * pixel[2];
+                            pixel.fill(luminance);
+                        }
+                        BuiltinPostPass::Invert => {
+                            pixel.iter_mut().for_each(|c| *c = (255.0 - *c).max(0.0));
+                        }
+                    }
+                }
+            });
+    }
+}
@@ -0,0 +1,164 @@
+use na::{matrix, vector, Matrix4, Vector3};
+use nalgebra as na;
+
+/// Distance from the center of projection to the camera position in the fixed projection, the larger it is,
+/// the narrower the field of view is.
+pub const PROJECTION_DISTANCE: f32 = 5.0;
+
+/// Transform from view space, where the camera looks along -z, to clip space.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Projection {
+    // Projection the renderer started with - center of projection is PROJECTION_DISTANCE behind the camera
+    // position, frame spans [-1, 1] around it whatever the aspect is, and depth isn't clipped.
+    #[default]
+    Fixed,
+    Perspective {
+        fov_y: f32, // Vertical field of view in radians, horizontal one follows the aspect of the frame.
+        near: f32,
+        far: f32,
+    },
+    // Parallel projection of a width x height view space rectangle, near may be negative to see behind the
+    // camera position.
+    Orthographic {
+        width: f32,
+        height: f32,
+        near: f32,
+        far: f32,
+    },
+}
+
+impl Projection {
+    /// Parses projection from a spec like `perspective:45,0.1,10`, returning None for malformed specs. Field
+    /// of view is in degrees. Supported forms:
+    /// - fixed
+    /// - perspective:fov_y,near,far
+    /// - orthographic:width,height,near,far
+    pub fn from_spec(spec: &str) -> Option<Self> {
+        let parts: Vec<&str> = spec.split(':').collect();
+        let values = |part: &str| -> Option<Vec<f32>> {
+            return part.split(',').map(|value| value.parse().ok()).collect();
+        };
+        let projection = match (parts[0], parts.len()) {
+            ("fixed", 1) => Projection::Fixed,
+            ("perspective", 2) => match values(parts[1])?.as_slice() {
+                &[fov_y, near, far] => Projection::Perspective {
+                    fov_y: fov_y.to_radians(),
+                    near,
+                    far,
+                },
+                _ => return None,
+            },
+            ("orthographic", 2) => match values(parts[1])?.as_slice() {
+                &[width, height, near, far] => Projection::Orthographic {
+                    width,
+                    height,
+                    near,
+                    far,
+                },
+                _ => return None,
+            },
+            _ => return None,
+        };
+        return Some(projection);
+    }
+
+    /// Projection matrix for the frame of the aspect (width / height). Depth in normalized device coordinates
+    /// goes from -1 at the far plane to 1 at the near plane, so closer fragments get larger z-buffer values.
+    pub fn get_matrix(&self, aspect: f32) -> Matrix4<f32> {
+        match *self {
+            Projection::Fixed => {
+                let coef = -1.0 / PROJECTION_DISTANCE;
+                return matrix![1.0, 0.0, 0.0,  0.0;
+                               0.0, 1.0, 0.0,  0.0;
+                               0.0, 0.0, 1.0,  0.0;
+                               0.0, 0.0, coef, 1.0];
+            }
+            Projection::Perspective { fov_y, near, far } => {
+                let focal = 1.0 / (fov_y / 2.0).tan();
+                let a = (far + near) / (far - near);
+                let b = 2.0 * far * near / (far - near);
+                return matrix![focal / aspect, 0.0,   0.0,  0.0;
+                               0.0,            focal, 0.0,  0.0;
+                               0.0,            0.0,   a,    b;
+                               0.0,            0.0,   -1.0, 0.0];
+            }
+            Projection::Orthographic {
+                width,
+                height,
+                near,
+                far,
+            } => {
+                let a = 2.0 / (far - near);
+                let b = (far + near) / (far - near);
+                return matrix![2.0 / width, 0.0,          0.0, 0.0;
+                               0.0,         2.0 / height, 0.0, 0.0;
+                               0.0,         0.0,          a,   b;
+                               0.0,         0.0,          0.0, 1.0];
+            }
+        }
+    }
+
+    /// Whether geometry in front of the near plane or behind the far plane is culled.
+    pub fn is_depth_clipped(&self) -> bool {
+        return *self != Projection::Fixed;
+    }
+
+    /// Inverts the depth mapping, getting distance in front of the camera in view space from the depth in
+    /// normalized device coordinates.
+    pub fn get_view_depth(&self, ndc_z: f32) -> f32 {
+        match *self {
+            Projection::Fixed => return -ndc_z / (1.0 + ndc_z / PROJECTION_DISTANCE),
+            Projection::Perspective { near, far, .. } => {
+                return 2.0 * far * near / (far + near + ndc_z * (far - near));
+            }
+            Projection::Orthographic { near, far, .. } => {
+                return (far + near - ndc_z * (far - near)) / 2.0;
+            }
+        }
+    }
+
+    /// Direction of the view ray in view space through the point of the frame in normalized device
+    /// coordinates, not normalized.
+    pub fn get_view_ray(&self, u: f32, v: f32, aspect: f32) -> Vector3<f32> {
+        match *self {
+            Projection::Fixed => return vector![u, v, -PROJECTION_DISTANCE],
+            Projection::Perspective { fov_y, .. } => {
+                let half_height = (fov_y / 2.0).tan();
+                return vector![u * half_height * aspect, v * half_height, -1.0];
+            }
+            Projection::Orthographic { .. } => return vector![0.0, 0.0, -1.0],
+        }
+    }
+
+    /// Origin of the view ray in view space through the point of the frame in normalized device coordinates -
+    /// center of projection, or the point on the camera plane for the parallel projection.
+    pub fn get_view_ray_origin(&self, u: f32, v: f32) -> Vector3<f32> {
+        match *self {
+            Projection::Fixed => return vector![0.0, 0.0, PROJECTION_DISTANCE],
+            Projection::Perspective { .. } => return Vector3::zeros(),
+            Projection::Orthographic { width, height, .. } => {
+                return vector![u * width / 2.0, v * height / 2.0, 0.0];
+            }
+        }
+    }
+
+    /// Distances in front of the camera, between which geometry is seen, None if depth isn't clipped.
+    pub fn get_depth_range(&self) -> Option<(f32, f32)> {
+        match *self {
+            Projection::Fixed => return None,
+            Projection::Perspective { near, far, .. } => return Some((near, far)),
+            Projection::Orthographic { near, far, .. } => return Some((near, far)),
+        }
+    }
+
+    /// Distance from the center of a unit sphere, at which the camera sees all of it.
+    pub fn get_framing_distance(&self) -> f32 {
+        match *self {
+            Projection::Fixed => return 1.0,
+            Projection::Perspective { fov_y, near, .. } => {
+                return (1.0 / (fov_y / 2.0).sin()).max(near + 1.0);
+            }
+            Projection::Orthographic { near, .. } => return near + 1.0,
+        }
+    }
+}
@@ -0,0 +1,318 @@
+use na::{Point3, Vector2, Vector3};
+use nalgebra as na;
+
+use super::light::{Gobo, Light};
+use super::material::Material;
+use super::texture::{CubeMap, TexCoord};
+use super::util::{color_blend, Model};
+
+/// Number of triangles, below which a node of the BVH isn't split any further.
+const BVH_LEAF_SIZE: usize = 4;
+
+/// Distance along the shadow rays, which they start at, so they don't hit the surface they leave.
+const SHADOW_RAY_OFFSET: f32 = 1e-3;
+
+/// Scale of the absolute difference between the rasterized and ray traced frames, making small errors visible.
+pub const REFERENCE_DIFF_SCALE: f32 = 4.0;
+
+/// Whether the frame is rasterized as usual, ray traced instead, or shows how far the rasterized frame is
+/// from the ray traced one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReferenceMode {
+    #[default]
+    Off,
+    Trace,
+    Diff,
+}
+
+impl ReferenceMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "off" => return Some(ReferenceMode::Off),
+            "trace" => return Some(ReferenceMode::Trace),
+            "diff" => return Some(ReferenceMode::Diff),
+            _ => return None,
+        }
+    }
+}
+
+/// Triangle of the model as it's drawn - moved to its instance and deformed by the skin, with everything
+/// needed to shade it.
+#[derive(Clone, Copy, Debug)]
+pub struct BvhTriangle {
+    pub positions: [Point3<f32>; 3],
+    pub normals: [Vector3<f32>; 3],
+    pub uvs: [Vector2<f32>; 3],
+    pub colors: [Vector3<f32>; 3], // Vertex colors in [0, 1], white when the mesh has none.
+    pub tint: Vector3<f32>,
+    pub material: Material,
+}
+
+impl BvhTriangle {
+    fn get_centroid(&self) -> Point3<f32> {
+        return Point3::from(
+            (self.positions[0].coords + self.positions[1].coords + self.positions[2].coords) / 3.0,
+        );
+    }
+}
+
+/// Closest intersection along a ray.
+#[derive(Clone, Copy, Debug)]
+struct RayHit {
+    t: f32,
+    triangle: usize,
+    bar_coord: Vector3<f32>,
+}
+
+/// Node of the BVH, either with two children, the second one right after the first, or a leaf with a range
+/// of the triangles.
+#[derive(Clone, Copy, Debug)]
+struct BvhNode {
+    min: Point3<f32>,
+    max: Point3<f32>,
+    first: usize, // Index of the first child or of the first triangle of a leaf.
+    count: usize, // Number of triangles of a leaf, 0 for the inner nodes.
+}
+
+/// Bounding volume hierarchy over the triangles, split at the median of the centroids along the longest axis.
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    triangles: Vec<BvhTriangle>,
+}
+
+impl Bvh {
+    pub fn new(triangles: Vec<BvhTriangle>) -> Self {
+        let mut bvh = Bvh {
+            nodes: Vec::new(),
+            triangles,
+        };
+        bvh.nodes.push(BvhNode {
+            min: Point3::origin(),
+            max: Point3::origin(),
+            first: 0,
+            count: bvh.triangles.len(),
+        });
+        bvh.split(0);
+        return bvh;
+    }
+
+    /// Fits the node around its triangles and splits it in two, until it's small enough.
+    fn split(&mut self, node_index: usize) {
+        let BvhNode { first, count, .. } = self.nodes[node_index];
+        let triangles = &mut self.triangles[first..first + count];
+        let (min, max) = triangles
+            .iter()
+            .flat_map(|triangle| triangle.positions)
+            .fold(
+                (Point3::from([f32::MAX; 3]), Point3::from([f32::MIN; 3])),
+                |(min, max), point| (min.inf(&point), max.sup(&point)),
+            );
+        self.nodes[node_index].min = min;
+        self.nodes[node_index].max = max;
+        if count <= BVH_LEAF_SIZE {
+            return;
+        }
+        let axis = (max - min).imax();
+        let middle = count / 2;
+        triangles.select_nth_unstable_by(middle, |a, b| {
+            a.get_centroid()[axis].total_cmp(&b.get_centroid()[axis])
+        });
+        let children = self.nodes.len();
+        self.nodes[node_index].first = children;
+        self.nodes[node_index].count = 0;
+        for (first, count) in [(first, middle), (first + middle, count - middle)] {
+            self.nodes.push(BvhNode {
+                min,
+                max,
+                first,
+                count,
+            });
+        }
+        self.split(children);
+        self.split(children + 1);
+    }
+
+    /// Closest triangle along the ray between t_min and t_max, if any.
+    fn intersect(
+        &self,
+        origin: Point3<f32>,
+        direction: Vector3<f32>,
+        t_min: f32,
+        t_max: f32,
+        any_hit: bool,
+    ) -> Option<RayHit> {
+        if self.triangles.is_empty() {
+            return None;
+        }
+        let inverse_direction = direction.map(|d| 1.0 / d);
+        let mut closest: Option<RayHit> = None;
+        let mut t_max = t_max;
+        let mut stack = vec![0];
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            if !intersects_box(node, origin, inverse_direction, t_min, t_max) {
+                continue;
+            }
+            if node.count == 0 {
+                stack.push(node.first);
+                stack.push(node.first + 1);
+                continue;
+            }
+            for index in node.first..node.first + node.count {
+                if let Some((t, bar_coord)) =
+                    intersect_triangle(&self.triangles[index], origin, direction)
+                {
+                    if t > t_min && t < t_max {
+                        t_max = t;
+                        closest = Some(RayHit {
+                            t,
+                            triangle: index,
+                            bar_coord,
+                        });
+                        if any_hit {
+                            return closest;
+                        }
+                    }
+                }
+            }
+        }
+        return closest;
+    }
+}
+
+/// Slab test of the ray against the bounds of the node.
+fn intersects_box(
+    node: &BvhNode,
+    origin: Point3<f32>,
+    inverse_direction: Vector3<f32>,
+    t_min: f32,
+    t_max: f32,
+) -> bool {
+    let (mut t_enter, mut t_exit) = (t_min, t_max);
+    for axis in 0..3 {
+        let t_1 = (node.min[axis] - origin[axis]) * inverse_direction[axis];
+        let t_2 = (node.max[axis] - origin[axis]) * inverse_direction[axis];
+        t_enter = t_enter.max(t_1.min(t_2));
+        t_exit = t_exit.min(t_1.max(t_2));
+    }
+    return t_enter <= t_exit;
+}
+
+/// Möller-Trumbore intersection, giving distance along the ray and barycentric coordinates of the hit, both
+/// sides of the triangle are hit.
+fn intersect_triangle(
+    triangle: &BvhTriangle,
+    origin: Point3<f32>,
+    direction: Vector3<f32>,
+) -> Option<(f32, Vector3<f32>)> {
+    let [a, b, c] = triangle.positions;
+    let (edge_1, edge_2) = (b - a, c - a);
+    let p = direction.cross(&edge_2);
+    let determinant = edge_1.dot(&p);
+    if determinant.abs() < f32::EPSILON * edge_1.norm() * edge_2.norm() * direction.norm() {
+        return None;
+    }
+    let to_origin = origin - a;
+    let u = to_origin.dot(&p) / determinant;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = to_origin.cross(&edge_1);
+    let v = direction.dot(&q) / determinant;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    return Some((
+        edge_2.dot(&q) / determinant,
+        Vector3::new(1.0 - u - v, u, v),
+    ));
+}
+
+/// Ray traced shading of the model, used as the ground truth for the rasterizer. Surfaces are shaded as by
+/// the shadow pipeline - diffuse texture lit by the main light, dimmed where shadowed, and by the rest of the
+/// lights, only where they are visible, with exact shadows from rays towards the lights. Everything is in
+/// model coordinates and surfaces are opaque.
+pub struct ReferenceTracer<'a> {
+    pub bvh: &'a Bvh,
+    pub model: &'a Model,
+    pub light_direction: Vector3<f32>,
+    pub light_intensity: f32,
+    pub lights: &'a [Light],
+    pub gobos: &'a [Option<Gobo>], // Images, projected by the lights with the same indices.
+    pub environment: Option<&'a CubeMap>,
+}
+
+impl ReferenceTracer<'_> {
+    /// Color seen along the view ray between t_min and t_max, environment or the background color, where
+    /// nothing is hit.
+    pub fn trace(
+        &self,
+        origin: Point3<f32>,
+        direction: Vector3<f32>,
+        t_min: f32,
+        t_max: f32,
+        background: Vector3<f32>,
+    ) -> Vector3<f32> {
+        let hit = match self.bvh.intersect(origin, direction, t_min, t_max, false) {
+            Some(hit) => hit,
+            None => {
+                return self.environment.map_or(background, |environment| {
+                    environment.sample_direction(direction)
+                });
+            }
+        };
+        let triangle = &self.bvh.triangles[hit.triangle];
+        let bar_coord = hit.bar_coord;
+        let position = origin + direction * hit.t;
+        let normal = (0..3)
+            .map(|k| triangle.normals[k] * bar_coord[k])
+            .sum::<Vector3<f32>>()
+            .normalize();
+        let uv = (0..3).map(|k| triangle.uvs[k] * bar_coord[k]).sum();
+        let mut color = self.model.get_color_at_uv(TexCoord {
+            uv,
+            ..Default::default()
+        });
+        if !self.model.vertex_colors.is_empty() {
+            color.component_mul_assign(&(0..3).map(|k| triangle.colors[k] * bar_coord[k]).sum());
+        }
+
+        let light_direction = self.light_direction.normalize();
+        let diff_coef = light_direction.dot(&normal);
+        // Fully shadowed points are still dimly lit, as in the shadow pipeline.
+        let shadow_coef = match self.is_occluded(position, light_direction, f32::MAX) {
+            true => 0.3,
+            false => 1.0,
+        };
+        let mut lights_diffuse = Vector3::zeros();
+        for (index, light) in self.lights.iter().enumerate() {
+            let (to_light, mut radiance) = light.get_incident_light(position.coords);
+            if let Some(Some(gobo)) = self.gobos.get(index) {
+                radiance.component_mul_assign(&gobo.get_filter(position.coords));
+            }
+            let distance = match light.get_position() {
+                Some(light_position) => (light_position - position.coords).norm(),
+                None => f32::MAX,
+            };
+            if !self.is_occluded(position, to_light, distance) {
+                lights_diffuse += radiance * to_light.dot(&normal).max(0.0);
+            }
+        }
+        let material = &triangle.material;
+        let shaded_color = color_blend(
+            color,
+            Vector3::zeros(),
+            material.diffuse * diff_coef * shadow_coef * self.light_intensity,
+        ) + color * material.ambient
+            + (lights_diffuse * material.diffuse).component_mul(&color);
+        return shaded_color.component_mul(&triangle.tint);
+    }
+
+    /// Whether anything is between the point and the light in the direction, closer than the distance.
+    fn is_occluded(&self, point: Point3<f32>, direction: Vector3<f32>, distance: f32) -> bool {
+        return self
+            .bvh
+            .intersect(point, direction, SHADOW_RAY_OFFSET, distance, true)
+            .is_some();
+    }
+}
@@ -1,83 +1,318 @@
 // @TODO this crate is hot garbage - I wanted to have the ability to have different shaders with variable
 // requirements to the buffer contents and different number of passes which created bloat in the buffer,
-// vector of closures in the ShaderPipeline struct, some questonable separation of data and not so pretty
+// vector of closures in the BuiltinPipeline struct, some questonable separation of data and not so pretty
 // function signatures. Improvements surely can be made here.
 
+use super::deferred::GBufferTexel;
+use super::ibl::ImageBasedLighting;
+use super::light::{Gobo, Light};
+use super::material::Material;
+use super::picking::IdTexel;
+use super::pipeline::{ShaderPipeline, ShaderVaryings};
+use super::projection::Projection;
+use super::shadow::{CascadedShadowMap, CubeShadowMap};
+use super::target::RenderTarget;
+use super::texture::{CubeMap, TexCoord};
 use super::util::{color_blend, Model};
 
+use std::collections::HashMap;
+
 use na::{matrix, point, vector, Matrix2x3, Matrix3, Matrix4, Point3, Rotation3, Vector2, Vector3};
 use nalgebra as na;
+use tiny_renderer_core::raster::{interpolate_depth, to_raster_coord, MAX_SAMPLES};
 
-/// Buffer for passing values between different stages of a pipeline and setting up frame constants
-/// like light direction and transform matrices.
+/// Frame constants like light direction and transform matrices, set up by the preparation step of a pass
+/// and shared by all vertex and fragment shader invocations of that pass.
 #[derive(Default)]
-pub struct Buffer {
+pub struct Uniforms {
     pub width: u32,  // Width of the frame buffer.
     pub height: u32, // Height of the frame buffer.
-    // Pointers to some fat buffers like z-buffer and shadow buffer.
-    pub z_buffer: Vec<f32>,
-    pub shadow_buffer: Vec<f32>,
-    // A collection of various uniforms.
     pub camera_direction: Vector3<f32>,
+    pub projection: Projection, // Projection of the pass, set by the scene before the preparation step.
     pub t_light_direction: Vector3<f32>, // Light direction with model and view transformations applied.
+    pub light_intensity: f32,            // Scale of the light, coming from light direction.
     pub vpmv_matrix: Matrix4<f32>,       // Applied to vertices to get final screen coodrdinates.
     pub i_vpmv_matrix: Matrix4<f32>,     // Needed for the shadow shader.
     pub m_matrix: Matrix4<f32>,          // Applied to light direction.
     pub i_m_matrix: Matrix4<f32>,        // Applied to transformed light direction.
     pub it_m_matrix: Matrix4<f32>,       // Applied to model normals.
     pub shadow_matrix: Matrix4<f32>,     // Transform from frame-buffer to shadow buffer coords.
-    // Local buffer for passing values between vertex and fragment parts of the pipeline.
+    // Transform of the camera in the previous frame, set while the passes of the frame run with the velocity
+    // buffer enabled, so the vertex stage finds how far the vertices moved on the screen.
+    pub previous_vpmv_matrix: Option<Matrix4<f32>>,
+    // Subpixel offset of the frame in pixels, moving the viewport of the passes of the frame for temporal
+    // anti-aliasing.
+    pub jitter: Vector2<f32>,
+    pub cull_mode: CullMode, // Which faces are skipped by the vertex shaders.
+    pub t_lights: Vec<Light>, // Additional scene lights, transformed the same way as light direction.
+    pub shadow: ShadowSettings,
+    pub alpha: AlphaSettings,
+    // Omnidirectional shadow maps of the additional lights with the same indices, if they have one.
+    pub light_shadow_maps: Vec<Option<CubeShadowMap>>,
+    // Shadow maps of the main light over the view frustum, used by the shadow pipeline instead of the shadow
+    // buffer, when there are cascades.
+    pub cascaded_shadow_map: Option<CascadedShadowMap>,
+    // Images, projected by the additional lights with the same indices, if they have one.
+    pub light_gobos: Vec<Option<Gobo>>,
+    pub environment: Option<CubeMap>, // Surroundings of the model in model coordinates, seen in reflections.
+    // Prefiltered lighting of the environment, replacing the constant ambient term of the pbr pipeline.
+    pub ibl: Option<ImageBasedLighting>,
+    pub materials: Vec<Material>, // Lighting coefficients of the objects by instance, default for the rest.
+    // Targets by name, rendered before the frame, while the target being rendered is taken out of the map.
+    pub render_targets: HashMap<String, RenderTarget>,
+}
+
+/// Filtering and bias, used when looking up the shadow buffer.
+#[derive(Clone, Copy, Debug)]
+pub struct ShadowSettings {
+    pub pcf_radius: u32, // Shadow buffer texels in each direction, averaged by percentage-closer filtering.
+    pub bias: f32,       // Constant depth offset in shadow buffer z units, fighting shadow acne.
+    pub slope_bias: f32, // Additional offset, scaled by the tangent of the angle between normal and light.
+    // Face size of the cube shadow maps of the positional lights, 0 disables their shadows.
+    pub cube_resolution: u32,
+    pub cube_bias: f32, // Distance offset in model units for the cube shadow map lookups.
+    // Number of cascades of the main light, splitting the view frustum, 0 keeps the single shadow buffer.
+    pub cascades: u32,
+    pub cascade_resolution: u32, // Side of the square shadow map of every cascade.
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        return ShadowSettings {
+            pcf_radius: 0,
+            bias: 1.0,
+            slope_bias: 0.0,
+            cube_resolution: 0,
+            cube_bias: 0.02,
+            cascades: 0,
+            cascade_resolution: 1024,
+        };
+    }
+}
+
+/// How opacity of the material affects rendering of its fragments.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum AlphaMode {
+    #[default]
+    Opaque, // Opacity is ignored.
+    Mask {
+        cutoff: f32,
+    }, // Fragments less opaque than the cutoff are discarded, the rest are opaque.
+    Blend, // Translucent polygons are drawn after the opaque ones, back to front, blending over them.
+}
+
+impl AlphaMode {
+    /// Parses alpha mode from a spec like `mask:0.5`, where cutoff is optional and is 0.5 by default.
+    pub fn from_spec(spec: &str) -> Option<Self> {
+        let parts: Vec<&str> = spec.split(':').collect();
+        match (parts[0], parts.len()) {
+            ("opaque", 1) => return Some(AlphaMode::Opaque),
+            ("mask", 1) => return Some(AlphaMode::Mask { cutoff: 0.5 }),
+            ("mask", 2) => {
+                return Some(AlphaMode::Mask {
+                    cutoff: parts[1].parse().ok()?,
+                })
+            }
+            ("blend", 1) => return Some(AlphaMode::Blend),
+            _ => return None,
+        }
+    }
+}
+
+/// Alpha mode and opacity of the material, which is multiplied by the alpha map.
+#[derive(Clone, Copy, Debug)]
+pub struct AlphaSettings {
+    pub mode: AlphaMode,
+    pub opacity: f32,
+}
+
+impl Default for AlphaSettings {
+    fn default() -> Self {
+        return AlphaSettings {
+            mode: AlphaMode::default(),
+            opacity: 1.0,
+        };
+    }
+}
+
+/// Values passed from the vertex shader to the fragment shader for a single polygon.
+#[derive(Clone, Copy, Default)]
+pub struct Varyings {
     vertex_intensities: Vector3<f32>, // Light intensity in each vertex of a polygon.
     vertex_t_positions: Matrix3<f32>, // Transformed vertex positions as columns.
     vertex_t_normals: Matrix3<f32>,   // Transformed vertex normals at each vertex as columns.
+    vertex_t_tangents: Matrix3<f32>,  // Transformed vertex tangents at each vertex as columns.
+    vertex_tangent_signs: Vector3<f32>, // Handedness of the tangent basis at each vertex.
     vertex_uvs: Matrix2x3<f32>, // UV coordinates, defining where to look for a color of a vertex as columns.
-    pub vertex_t_raster: Matrix2x3<i32>, // x, y coordinates after all transformation, including viewport as columns.
+    pub vertex_t_raster: Matrix2x3<i32>, // Fixed-point x, y coordinates after all transformation, including viewport as columns.
     pub vertex_z_values: Vector3<f32>,   // Value used for comparison with existing z-buffer values.
-    // Access to color after application of fragment shader.
-    pub fragment_color: Vector3<u8>, // Final output for a fragment.
+    pub vertex_velocities: Matrix2x3<f32>, // Screen space motion of the vertices since the previous frame.
+    vertex_colors: Matrix3<f32>, // Colors of the vertices in [0, 1] as columns, white when the mesh has none.
+    pub bar_coord_dx: Vector3<f32>, // Screen space derivatives of barycentric coordinates, set by rasterizer.
+    pub bar_coord_dy: Vector3<f32>,
+    pub translucent: bool, // Whether polygon is blended, so it is drawn after the opaque ones.
 }
 
-impl Buffer {
-    fn new(width: u32, height: u32) -> Self {
-        let frame_buffer_size = (width * height) as usize;
-        return Self {
-            width,
-            height,
-            z_buffer: vec![0.0; frame_buffer_size],
-            shadow_buffer: vec![0.0; frame_buffer_size],
-            ..Default::default()
-        };
+/// Fat buffer, which is written to by the fragment shaders of a pass.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DepthTarget {
+    ZBuffer,
+    ShadowBuffer,
+}
+
+/// Comparison of the stencil buffer against a reference value, which fragments have to pass to be drawn.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StencilTest {
+    #[default]
+    Always,
+    Equal(u8),
+    NotEqual(u8),
+}
+
+impl StencilTest {
+    /// Whether fragment over the pixel with the stencil value is drawn.
+    pub fn passes(&self, value: u8) -> bool {
+        match self {
+            StencilTest::Always => return true,
+            StencilTest::Equal(reference) => return value == *reference,
+            StencilTest::NotEqual(reference) => return value != *reference,
+        }
+    }
+}
+
+/// Stencil setting of a pass - the test, which runs before the depth test, and the value, written into the
+/// stencil buffer wherever a fragment is drawn. Disabled by default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StencilState {
+    pub test: StencilTest,
+    pub write: Option<u8>,
+}
+
+/// View of the fat buffers, given to the fragment shaders working on a single tile of the frame.
+/// Depth slice covers only the rows of the tile, while shadow buffer is whole and read only - it is left
+/// empty, when shadow buffer itself is the depth target of the pass.
+pub struct Targets<'a> {
+    pub width: u32,               // Width of the frame buffer.
+    pub offset: usize,            // Index of the first pixel of the tile in the whole frame.
+    pub depth: &'a mut [f32],     // Slice of the depth target, belonging to the tile.
+    pub shadow_buffer: &'a [f32], // Shadow buffer from the previous passes.
+    // Multisampling state of the current pixel, filled by the rasterizer.
+    pub samples: usize, // Number of depth samples per pixel in the depth target.
+    pub sample_bar_coords: [Vector3<f32>; MAX_SAMPLES], // Barycentric coordinates of each sample.
+    pub sample_mask: u32, // Covered samples, narrowed down to the ones passing the depth test.
+    // Blending state of the current fragment - translucent polygons are only tested against the depth target,
+    // fragment shader sets opacity of its color.
+    pub depth_write: bool,
+    pub alpha: f32,
+    // Slice of the G-buffer, belonging to the tile, when rendering the geometry pass of the deferred path.
+    pub gbuffer: Option<&'a mut [GBufferTexel]>,
+    // Slice of the stencil buffer, belonging to the tile, with a single value per pixel.
+    pub stencil: &'a mut [u8],
+    // Slice of the ID buffer, belonging to the tile, when picking is enabled, and the instance and polygon
+    // being drawn.
+    pub id_buffer: Option<&'a mut [IdTexel]>,
+    pub instance: u32,
+    pub polygon: u32,
+    // Slice of the velocity buffer, belonging to the tile, when motion blur is enabled, and the motion of the
+    // vertices of the polygon being drawn.
+    pub velocity: Option<&'a mut [Vector2<f32>]>,
+    pub vertex_velocities: Matrix2x3<f32>,
+}
+
+impl Targets<'_> {
+    /// Index of the pixel inside of the tile depth slice.
+    fn depth_index(&self, coord: Vector2<u32>) -> usize {
+        return coord.x as usize + (coord.y * self.width) as usize - self.offset;
+    }
+
+    /// Marks the pixel as covered by the current polygon, which has just written its depth, together with its
+    /// motion on the screen.
+    fn write_id(&mut self, index: usize, bar_coord: Vector3<f32>) {
+        let (instance, polygon) = (self.instance, self.polygon);
+        if let Some(id_buffer) = self.id_buffer.as_mut() {
+            id_buffer[index] = IdTexel {
+                instance,
+                polygon: Some(polygon),
+                bar_coord,
+            };
+        }
+        let velocity = self.vertex_velocities * bar_coord;
+        if let Some(velocity_buffer) = self.velocity.as_mut() {
+            velocity_buffer[index] = velocity;
+        }
+    }
+}
+
+/// Order of the polygon vertices, as seen from the camera, which makes the polygon front facing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Winding {
+    Clockwise,
+    CounterClockwise,
+}
+
+/// Backface culling setting - either faces with opposite winding are skipped, or every face is drawn,
+/// which is what double-sided materials need.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CullMode {
+    Off,
+    Back(Winding),
+}
+
+impl Default for CullMode {
+    fn default() -> Self {
+        // Obj files store polygons in counter-clockwise order.
+        return CullMode::Back(Winding::CounterClockwise);
+    }
+}
+
+impl CullMode {
+    /// Parses cull mode from its name, returning None for unknown names.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "ccw" => Some(CullMode::Back(Winding::CounterClockwise)),
+            "cw" => Some(CullMode::Back(Winding::Clockwise)),
+            "off" => Some(CullMode::Off),
+            _ => None,
+        }
     }
 }
 
 /// Type representing a function, which is called in order to prepare pipeline for application of vertex
 /// and fragment shaders.
 type Prepare = dyn Fn(
-    &mut Buffer,  // Buffer.
-    u32,          // Screen width.
-    u32,          // Screen height.
-    Vector3<f32>, // Light direction.
-    Vector3<f32>, // Camera placement.
-    Vector3<f32>, // Camera direction.
-    Vector3<f32>, // Camera up direction.
-);
+        &mut Uniforms, // Uniforms.
+        u32,           // Screen width.
+        u32,           // Screen height.
+        Vector3<f32>,  // Light direction.
+        Vector3<f32>,  // Camera placement.
+        Vector3<f32>,  // Camera direction.
+        Vector3<f32>,  // Camera up direction.
+    ) + Send
+    + Sync;
 
 /// Type representing vertex shader.
 type VertexShader = dyn Fn(
-    &mut Buffer,    // Buffer.
-    &Model,         // Model info.
-    Vector3<usize>, // Position indices.
-    Vector3<usize>, // Diffuse texture indices.
-    Vector3<usize>, // Vertex normal indices.
-) -> bool;
-
-/// Type representing fragment shader.
+        &Uniforms,      // Uniforms.
+        &mut Varyings,  // Output for the fragment shader.
+        &Model,         // Model info.
+        Vector3<usize>, // Position indices.
+        Vector3<usize>, // Diffuse texture indices.
+        Vector3<usize>, // Vertex normal indices.
+    ) -> bool
+    + Send
+    + Sync;
+
+/// Type representing fragment shader, returning None if fragment shouldn't be drawn.
 type FragmentShader = dyn Fn(
-    &mut Buffer,  // Buffer
-    &Model,       // Model info.
-    Vector2<u32>, // Coordinates of the fragment in the frame buffer.
-    Vector3<f32>, // Barycentric coordinates.
-) -> bool;
+        &Uniforms,    // Uniforms.
+        &Varyings,    // Output of the vertex shader.
+        &mut Targets, // Depth and shadow buffers.
+        &Model,       // Model info.
+        Vector2<u32>, // Coordinates of the fragment in the frame buffer.
+        Vector3<f32>, // Barycentric coordinates.
+    ) -> Option<Vector3<f32>>
+    + Send
+    + Sync;
 
 /// Representation of one pass in the shader pipeline storing closures, representing a 3 steps -
 /// preparation of the pipeline buffer, vertex shader application and fragment shader application.
@@ -85,46 +320,194 @@ pub struct ShaderPass {
     pub prepare: Box<Prepare>,
     pub vertex: Box<VertexShader>,
     pub fragment: Box<FragmentShader>,
+    pub depth_target: DepthTarget,
+    pub stencil: StencilState,
 }
 
-/// Simple struct to organize several passes of the pipeline and provide a reference to the buffer.
-pub struct ShaderPipeline {
-    pub buffer: Buffer,
+/// Names of the pipelines, built into the crate, in the order the viewer cycles through them.
+pub const BUILTIN_PIPELINE_NAMES: &[&str] = &[
+    "default",
+    "phong",
+    "normal_map",
+    "specular",
+    "darboux",
+    "shadow",
+    "occlusion",
+    "pbr",
+    "toon",
+    "reflection",
+    "outline",
+    "vertex_color",
+];
+
+/// Pipeline, built into the crate and chosen by name, storing its passes as closures.
+pub struct BuiltinPipeline {
     pub passes: Vec<ShaderPass>,
 }
 
-impl ShaderPipeline {
-    pub fn new(pipeline_name: String, width: u32, height: u32) -> Self {
-        let buffer = Buffer::new(width, height);
-        let passes: Vec<ShaderPass>;
-        match pipeline_name.as_str() {
-            "default" => passes = get_default_pipeline_passes(),
-            "phong" => passes = get_phong_pipeline_passes(),
-            "normal_map" => passes = get_normal_map_pipeline_passes(),
-            "specular" => passes = get_specular_pipeline_passes(),
-            "darboux" => passes = get_darboux_pipeline_passes(),
-            "shadow" => passes = get_shadow_pipeline_passes(),
-            "occlusion" => passes = get_occlusion_pipeline_passes(),
+impl BuiltinPipeline {
+    pub fn new(pipeline_name: String) -> Self {
+        let passes = match pipeline_name.as_str() {
+            "default" => get_default_pipeline_passes(),
+            "phong" => get_phong_pipeline_passes(),
+            "vertex_color" => get_vertex_color_pipeline_passes(),
+            "normal_map" => get_normal_map_pipeline_passes(),
+            "specular" => get_specular_pipeline_passes(),
+            "darboux" => get_darboux_pipeline_passes(),
+            "shadow" => get_shadow_pipeline_passes(),
+            "occlusion" => get_occlusion_pipeline_passes(),
+            "pbr" => get_pbr_pipeline_passes(),
+            "toon" => get_toon_pipeline_passes(),
+            "reflection" => get_reflection_pipeline_passes(),
+            "outline" => get_outline_pipeline_passes(),
+            // Debug views, not listed among the pipelines to cycle through.
+            "debug_normals" => get_debug_normals_pipeline_passes(),
+            "debug_uvs" => get_debug_uvs_pipeline_passes(),
+            // Geometry pass of the deferred path, shaded by the scene afterwards.
+            "deferred_geometry" => get_deferred_geometry_pipeline_passes(),
             _ => panic!("Provided pipeline name is not supported!"),
+        };
+
+        return Self { passes };
+    }
+}
+
+impl ShaderVaryings for Varyings {
+    fn get_raster_coords(&self) -> Matrix2x3<i32> {
+        return self.vertex_t_raster;
+    }
+
+    fn set_bar_coord_derivatives(&mut self, dx: Vector3<f32>, dy: Vector3<f32>) {
+        self.bar_coord_dx = dx;
+        self.bar_coord_dy = dy;
+    }
+
+    fn is_translucent(&self) -> bool {
+        return self.translucent;
+    }
+
+    fn get_depth(&self) -> f32 {
+        return self.vertex_z_values.sum() / 3.0;
+    }
+
+    fn get_z_values(&self) -> Option<Vector3<f32>> {
+        return Some(self.vertex_z_values);
+    }
+
+    fn get_velocities(&self) -> Matrix2x3<f32> {
+        return self.vertex_velocities;
+    }
+}
+
+impl ShaderPipeline for BuiltinPipeline {
+    type Varyings = Varyings;
+
+    fn get_pass_count(&self) -> usize {
+        return self.passes.len();
+    }
+
+    fn get_depth_target(&self, pass: usize) -> DepthTarget {
+        return self.passes[pass].depth_target;
+    }
+
+    fn get_stencil_state(&self, pass: usize) -> StencilState {
+        return self.passes[pass].stencil;
+    }
+
+    fn prepare(
+        &self,
+        pass: usize,
+        uniforms: &mut Uniforms,
+        width: u32,
+        height: u32,
+        light_direction: Vector3<f32>,
+        look_from: Vector3<f32>,
+        look_at: Vector3<f32>,
+        up: Vector3<f32>,
+    ) {
+        (self.passes[pass].prepare)(
+            uniforms,
+            width,
+            height,
+            light_direction,
+            look_from,
+            look_at,
+            up,
+        );
+    }
+
+    fn vertex(
+        &self,
+        pass: usize,
+        uniforms: &Uniforms,
+        varyings: &mut Varyings,
+        model: &Model,
+        position_indices: Vector3<usize>,
+        uv_indices: Vector3<usize>,
+        normal_indices: Vector3<usize>,
+    ) -> bool {
+        if !(self.passes[pass].vertex)(
+            uniforms,
+            varyings,
+            model,
+            position_indices,
+            uv_indices,
+            normal_indices,
+        ) {
+            return false;
+        }
+        store_vertex_colors(&mut varyings.vertex_colors, model, position_indices);
+        if let Some(previous_vpmv_matrix) = uniforms.previous_vpmv_matrix {
+            store_vertex_velocities(
+                get_vertex_positions(model, position_indices),
+                uniforms.vpmv_matrix,
+                previous_vpmv_matrix,
+                &mut varyings.vertex_velocities,
+            );
         }
+        return true;
+    }
 
-        return Self { buffer, passes };
+    fn fragment(
+        &self,
+        pass: usize,
+        uniforms: &Uniforms,
+        varyings: &Varyings,
+        targets: &mut Targets,
+        model: &Model,
+        coord: Vector2<u32>,
+        bar_coord: Vector3<f32>,
+    ) -> Option<Vector3<f32>> {
+        return (self.passes[pass].fragment)(uniforms, varyings, targets, model, coord, bar_coord);
     }
 }
 
-/// Simple backface culling.
-fn should_cull_face(vertex_positions: [Point3<f32>; 3], camera_direction: Vector3<f32>) -> bool {
+/// Whether polygon is facing the camera according to the winding of the cull mode, treating polygons as
+/// counter-clockwise, when culling is off.
+pub fn is_front_facing(vertex_positions: [Point3<f32>; 3], uniforms: &Uniforms) -> bool {
     let face_normal = (vertex_positions[1] - vertex_positions[0])
         .cross(&(vertex_positions[2] - vertex_positions[0]));
-    if camera_direction.dot(&face_normal) <= 0.0 {
-        return true;
-    } else {
-        return false;
+    let facing = uniforms.camera_direction.dot(&face_normal);
+    match uniforms.cull_mode {
+        CullMode::Back(Winding::Clockwise) => return facing < 0.0,
+        _ => return facing > 0.0,
+    }
+}
+
+/// Simple backface culling, respecting the cull mode stored in the uniforms.
+pub fn should_cull_face(vertex_positions: [Point3<f32>; 3], uniforms: &Uniforms) -> bool {
+    let face_normal = (vertex_positions[1] - vertex_positions[0])
+        .cross(&(vertex_positions[2] - vertex_positions[0]));
+    let facing = uniforms.camera_direction.dot(&face_normal);
+    match uniforms.cull_mode {
+        CullMode::Off => return false,
+        CullMode::Back(Winding::CounterClockwise) => return facing <= 0.0,
+        CullMode::Back(Winding::Clockwise) => return facing >= 0.0,
     }
 }
 
 /// Boilerplate for accessing vertex positions from model vertex list.
-fn get_vertex_positions(model: &Model, indices: Vector3<usize>) -> [Point3<f32>; 3] {
+pub fn get_vertex_positions(model: &Model, indices: Vector3<usize>) -> [Point3<f32>; 3] {
     let mut vertex_positions = [point![0.0, 0.0, 0.0]; 3];
     for i in 0..3 {
         vertex_positions[i] = model.get_vertex_position_at_index(indices[i]);
@@ -133,9 +516,9 @@ fn get_vertex_positions(model: &Model, indices: Vector3<usize>) -> [Point3<f32>;
 }
 
 /// Boilerplate for moving uvs to a 2x3 matrix buffer.
-fn store_vertex_uvs(
+pub fn store_vertex_uvs(
     uvs_buffer: &mut Matrix2x3<f32>,
-    tex_coords: &Vec<(f32, f32, f32)>,
+    tex_coords: &[(f32, f32, f32)],
     indices: Vector3<usize>,
 ) {
     for i in 0..3 {
@@ -147,7 +530,7 @@ fn store_vertex_uvs(
 }
 
 /// Boilerplate for transforming and moving vertex information, namely screen coords and z-values into buffers.
-fn store_vertex_transformation_results(
+pub fn store_vertex_transformation_results(
     vertex_positions: [Point3<f32>; 3],
     vpmv_matrix: Matrix4<f32>,
     t_coords_buffer: &mut Matrix2x3<i32>,
@@ -158,30 +541,396 @@ fn store_vertex_transformation_results(
             Point3::from_homogeneous(vpmv_matrix * vertex_positions[i].to_homogeneous()).unwrap();
         t_coords_buffer.set_column(
             i,
-            &vector![vertex_t_position.x as i32, vertex_t_position.y as i32],
+            &to_raster_coord(vertex_t_position.x, vertex_t_position.y),
         );
         z_values_buffer[i] = vertex_t_position.z;
     }
 }
 
-/// Boilerplate for checking z-value of the fragment against the z-buffer.
+/// Boilerplate for storing colors of the vertices as columns.
+pub fn store_vertex_colors(
+    colors_buffer: &mut Matrix3<f32>,
+    model: &Model,
+    indices: Vector3<usize>,
+) {
+    for i in 0..3 {
+        colors_buffer.set_column(i, &model.get_vertex_color_at_index(indices[i]));
+    }
+}
+
+/// Boilerplate for storing motion of the vertices on the screen in pixels between the previous and the current
+/// transform of the camera.
+pub fn store_vertex_velocities(
+    vertex_positions: [Point3<f32>; 3],
+    vpmv_matrix: Matrix4<f32>,
+    previous_vpmv_matrix: Matrix4<f32>,
+    velocities_buffer: &mut Matrix2x3<f32>,
+) {
+    for i in 0..3 {
+        let position = vertex_positions[i].to_homogeneous();
+        let current = Point3::from_homogeneous(vpmv_matrix * position).unwrap();
+        let previous = Point3::from_homogeneous(previous_vpmv_matrix * position).unwrap();
+        velocities_buffer.set_column(i, &(current - previous).xy());
+    }
+}
+
+/// Boilerplate for storing vertex positions, transformed the same way as light direction, for lighting.
+pub fn store_vertex_t_positions(
+    t_positions_buffer: &mut Matrix3<f32>,
+    m_matrix: Matrix4<f32>,
+    vertex_positions: [Point3<f32>; 3],
+) {
+    for i in 0..3 {
+        t_positions_buffer.set_column(
+            i,
+            &Point3::from_homogeneous(m_matrix * vertex_positions[i].to_homogeneous())
+                .unwrap()
+                .coords,
+        );
+    }
+}
+
+/// Boilerplate for storing model vertex normals, transformed the same way as light direction.
+pub fn store_vertex_t_normals(
+    t_normals_buffer: &mut Matrix3<f32>,
+    it_m_matrix: Matrix4<f32>,
+    model: &Model,
+    pos_indices: Vector3<usize>,
+    normal_indices: Vector3<usize>,
+) {
+    let vertex_normals = model.get_polygon_normals(pos_indices, normal_indices);
+    for i in 0..3 {
+        let vertex_normal = vertex_normals[i];
+        let vertex_t_normal =
+            Vector3::from_homogeneous(it_m_matrix * vertex_normal.to_homogeneous())
+                .unwrap()
+                .normalize();
+        t_normals_buffer.set_column(i, &vertex_t_normal);
+    }
+}
+
+/// Stores transformed vertex normals for pipelines, which take normals from the normal map, but have to
+/// interpolate them, when model has no normal map, see `get_fragment_t_normal`.
+fn store_fallback_vertex_t_normals(
+    varyings: &mut Varyings,
+    uniforms: &Uniforms,
+    model: &Model,
+    pos_indices: Vector3<usize>,
+    normal_indices: Vector3<usize>,
+) {
+    if !model.has_normal_map {
+        store_vertex_t_normals(
+            &mut varyings.vertex_t_normals,
+            uniforms.it_m_matrix,
+            model,
+            pos_indices,
+            normal_indices,
+        );
+    }
+}
+
+/// Transformed normal of the fragment from the normal map, or interpolated from the vertex normals, when
+/// model has no normal map, so normal mapping pipelines still shade it as the Phong pipeline would.
+fn get_fragment_t_normal(
+    uniforms: &Uniforms,
+    varyings: &Varyings,
+    model: &Model,
+    uv: TexCoord,
+    bar_coord: Vector3<f32>,
+) -> Vector3<f32> {
+    if !model.has_normal_map {
+        return (varyings.vertex_t_normals * bar_coord).normalize();
+    }
+    let fragment_normal = model.get_normal_at_uv(uv);
+    return Vector3::from_homogeneous(uniforms.it_m_matrix * fragment_normal.to_homogeneous())
+        .unwrap()
+        .normalize();
+}
+
+/// Stores transformed tangents and their handedness of the polygon corners. Tangents lie in the surface, so
+/// they are transformed the same way as positions.
+pub fn store_vertex_t_tangents(
+    varyings: &mut Varyings,
+    m_matrix: Matrix4<f32>,
+    model: &Model,
+    pos_indices: Vector3<usize>,
+    tex_indices: Vector3<usize>,
+    normal_indices: Vector3<usize>,
+) {
+    for i in 0..3 {
+        let tangent = model.get_vertex_tangent((pos_indices[i], tex_indices[i], normal_indices[i]));
+        let t_tangent =
+            Vector3::from_homogeneous(m_matrix * tangent.xyz().to_homogeneous()).unwrap();
+        varyings.vertex_t_tangents.set_column(i, &t_tangent);
+        varyings.vertex_tangent_signs[i] = tangent.w;
+    }
+}
+
+/// Whether the additional light with the index reaches the transformed position, according to the shadow map
+/// of the light. Lights without shadow maps reach everything.
+pub fn is_lit_by_light(uniforms: &Uniforms, index: usize, t_position: Vector3<f32>) -> bool {
+    match uniforms.light_shadow_maps.get(index) {
+        Some(Some(shadow_map)) => {
+            let position = to_model_position(uniforms, t_position);
+            return shadow_map.is_lit(position, uniforms.shadow.cube_bias);
+        }
+        _ => return true,
+    }
+}
+
+/// Transformed position back in model coordinates.
+fn to_model_position(uniforms: &Uniforms, t_position: Vector3<f32>) -> Vector3<f32> {
+    // Model matrix is a pure rotation, so transposing it is enough to get back to model coordinates.
+    return Point3::from_homogeneous(
+        uniforms.m_matrix.transpose() * Point3::from(t_position).to_homogeneous(),
+    )
+    .unwrap()
+    .coords;
+}
+
+/// Lambertian lighting per color channel from all the additional scene lights at the transformed fragment
+/// position with the transformed normal.
+pub fn get_lights_diffuse(
+    uniforms: &Uniforms,
+    t_position: Vector3<f32>,
+    t_normal: Vector3<f32>,
+) -> Vector3<f32> {
+    let mut diffuse = Vector3::zeros();
+    for (index, light) in uniforms.t_lights.iter().enumerate() {
+        if !is_lit_by_light(uniforms, index, t_position) {
+            continue;
+        }
+        let (direction, mut radiance) = light.get_incident_light(t_position);
+        if let Some(Some(gobo)) = uniforms.light_gobos.get(index) {
+            radiance
+                .component_mul_assign(&gobo.get_filter(to_model_position(uniforms, t_position)));
+        }
+        diffuse += radiance * direction.dot(&t_normal).max(0.0);
+    }
+    return diffuse;
+}
+
+/// Material of the object, which the fragment being shaded belongs to.
+pub fn get_material(uniforms: &Uniforms, targets: &Targets) -> Material {
+    return uniforms
+        .materials
+        .get(targets.instance as usize)
+        .copied()
+        .unwrap_or_default();
+}
+
+/// Color of the fragment, lit with the coefficients of its material - ambient term, diffuse term of the main
+/// light, where light_coef is the cosine to the normal, scaled by the light intensity and shadowing, and the
+/// diffuse term of the additional lights.
+pub fn apply_material(
+    material: &Material,
+    color: Vector3<f32>,
+    light_coef: f32,
+    lights_diffuse: Vector3<f32>,
+) -> Vector3<f32> {
+    return add_lights_diffuse(
+        color_blend(color, Vector3::zeros(), material.diffuse * light_coef)
+            + color * material.ambient,
+        color,
+        lights_diffuse * material.diffuse,
+    );
+}
+
+/// Adds color lit by the additional lights on top of the already shaded fragment color.
+pub fn add_lights_diffuse(
+    shaded_color: Vector3<f32>,
+    color: Vector3<f32>,
+    diffuse: Vector3<f32>,
+) -> Vector3<f32> {
+    return shaded_color + diffuse.component_mul(&color);
+}
+
+/// Fraction of the shadow buffer texels around the fragment in shadow buffer coordinates, which are not
+/// occluding it, where diff_coef is the cosine between the normal and the light direction.
+pub fn get_shadow_visibility(
+    uniforms: &Uniforms,
+    shadow_buffer: &[f32],
+    shadow_coord: Point3<f32>,
+    diff_coef: f32,
+) -> f32 {
+    // Surfaces at grazing angles to the light need a larger offset, tangent is clamped since it goes to
+    // infinity there.
+    let cos = diff_coef.clamp(1e-3, 1.0);
+    let tan = ((1.0 - cos * cos).sqrt() / cos).min(10.0);
+    let bias = uniforms.shadow.bias + uniforms.shadow.slope_bias * tan;
+
+    let radius = uniforms.shadow.pcf_radius as i32;
+    let mut lit = 0;
+    for dy in -radius..=radius {
+        for dx in -radius..=radius {
+            // Very importnat to cast shadow_coord to u32 as opposed to uniforms.width to f32!
+            let x = (shadow_coord.x.round() as i32 + dx).clamp(0, uniforms.width as i32 - 1);
+            let y = (shadow_coord.y.round() as i32 + dy).clamp(0, uniforms.height as i32 - 1);
+            let shadow_index = (x as u32 + y as u32 * uniforms.width) as usize;
+            if shadow_coord.z + bias >= shadow_buffer[shadow_index] {
+                lit += 1;
+            }
+        }
+    }
+    return lit as f32 / ((2 * radius + 1) * (2 * radius + 1)) as f32;
+}
+
+/// Color of the diffuse texture at the fragment, multiplied by the interpolated vertex colors, if the mesh
+/// has them.
+fn get_fragment_color(
+    model: &Model,
+    varyings: &Varyings,
+    uv: TexCoord,
+    bar_coord: Vector3<f32>,
+) -> Vector3<f32> {
+    let color = model.get_color_at_uv(uv);
+    if model.vertex_colors.is_empty() {
+        return color;
+    }
+    return color.component_mul(&(varyings.vertex_colors * bar_coord));
+}
+
+/// Boilerplate for interpolating uv at the fragment together with its screen space derivatives.
+fn get_fragment_tex_coord(varyings: &Varyings, bar_coord: Vector3<f32>) -> TexCoord {
+    return TexCoord {
+        uv: varyings.vertex_uvs * bar_coord,
+        duv_dx: varyings.vertex_uvs * varyings.bar_coord_dx,
+        duv_dy: varyings.vertex_uvs * varyings.bar_coord_dy,
+    };
+}
+
+/// Whether polygon with stored uvs should be blended over the opaque ones - either the whole material is
+/// translucent, or the alpha map is at some vertex or at the center of the polygon.
+pub fn is_polygon_translucent(
+    uniforms: &Uniforms,
+    model: &Model,
+    vertex_uvs: Matrix2x3<f32>,
+) -> bool {
+    if uniforms.alpha.mode != AlphaMode::Blend {
+        return false;
+    }
+    if uniforms.alpha.opacity < 1.0 {
+        return true;
+    }
+    let center = vector![1.0, 1.0, 1.0] / 3.0;
+    for bar_coord in [Vector3::x(), Vector3::y(), Vector3::z(), center] {
+        let uv = TexCoord {
+            uv: vertex_uvs * bar_coord,
+            duv_dx: Vector2::zeros(),
+            duv_dy: Vector2::zeros(),
+        };
+        if model.get_alpha_value_at_uv(uv) < 1.0 {
+            return true;
+        }
+    }
+    return false;
+}
+
+/// Boilerplate for applying the alpha mode to the fragment at uv before the depth test. Returns false if
+/// fragment is cut out, otherwise stores its opacity in targets for blending.
+pub fn process_alpha_value(
+    uniforms: &Uniforms,
+    targets: &mut Targets,
+    model: &Model,
+    uv: TexCoord,
+) -> bool {
+    if uniforms.alpha.mode == AlphaMode::Opaque {
+        return true;
+    }
+    let alpha = uniforms.alpha.opacity * model.get_alpha_value_at_uv(uv);
+    match uniforms.alpha.mode {
+        AlphaMode::Mask { cutoff } => return alpha >= cutoff,
+        _ => {
+            targets.alpha = alpha;
+            return alpha > 0.0;
+        }
+    }
+}
+
+/// Depth test of the fragment before the fragment stage, same as the one of process_z_value, but without
+/// writing depth. Leaves only the passing samples in the mask, returns false if there are none.
+pub fn early_depth_test(
+    targets: &mut Targets,
+    z_values: Vector3<f32>,
+    bar_coord: Vector3<f32>,
+    coord: Vector2<u32>,
+) -> bool {
+    let index = targets.depth_index(coord);
+    if targets.samples == 1 {
+        return interpolate_depth(z_values, bar_coord) > targets.depth[index];
+    }
+    for sample in 0..targets.samples {
+        let sample_index = index * targets.samples + sample;
+        if interpolate_depth(z_values, targets.sample_bar_coords[sample])
+            <= targets.depth[sample_index]
+        {
+            targets.sample_mask &= !(1 << sample);
+        }
+    }
+    return targets.sample_mask != 0;
+}
+
+/// Boilerplate for checking z-value of the fragment, interpolated from the polygon vertex z-values, against
+/// the depth target.
 /// Returns false if there is no need to update the frame-buffer.
-fn process_z_value(buffer: &mut Buffer, bar_coord: Vector3<f32>, coord: Vector2<u32>) -> bool {
+pub fn process_z_value(
+    targets: &mut Targets,
+    z_values: Vector3<f32>,
+    bar_coord: Vector3<f32>,
+    coord: Vector2<u32>,
+) -> bool {
     // Checking fragment z-value in the pipeline buffer and comparing it to the value in the
     // buffer, on failure returning false, signifying that no further fragment processing
     // should be done
-    let index = coord.x as usize + (coord.y * buffer.width) as usize;
-    let z_value = bar_coord.dot(&buffer.vertex_z_values);
-    if z_value <= buffer.z_buffer[index] {
-        return false;
+    let index = targets.depth_index(coord);
+    if targets.samples == 1 {
+        let z_value = interpolate_depth(z_values, bar_coord);
+        if z_value <= targets.depth[index] {
+            return false;
+        }
+        if targets.depth_write {
+            targets.depth[index] = z_value;
+            targets.write_id(index, bar_coord);
+        }
+        return true;
     }
-    buffer.z_buffer[index] = z_value;
-    return true;
+
+    // With multisampling every covered sample is tested separately, leaving only passed ones in the mask.
+    for sample in 0..targets.samples {
+        if targets.sample_mask & (1 << sample) == 0 {
+            continue;
+        }
+        let sample_index = index * targets.samples + sample;
+        let z_value = interpolate_depth(z_values, targets.sample_bar_coords[sample]);
+        if z_value <= targets.depth[sample_index] {
+            targets.sample_mask &= !(1 << sample);
+            continue;
+        }
+        if targets.depth_write {
+            targets.depth[sample_index] = z_value;
+        }
+    }
+    if targets.depth_write && targets.sample_mask != 0 {
+        targets.write_id(index, bar_coord);
+    }
+    return targets.sample_mask != 0;
+}
+
+/// New coordinate system x, y, z around camera position, where camera looks along -z.
+pub fn get_camera_basis(
+    look_from: Vector3<f32>,
+    look_at: Vector3<f32>,
+    up: Vector3<f32>,
+) -> (Vector3<f32>, Vector3<f32>, Vector3<f32>) {
+    let new_z = (look_from - look_at).normalize();
+    let new_y = (up - new_z.dot(&up) * new_z).normalize();
+    let new_x = new_y.cross(&new_z).normalize();
+    return (new_x, new_y, new_z);
 }
 
 /// Standard setup which prepares transforms to the basis relative to the camera.
-fn default_prepare(
-    buffer: &mut Buffer,
+pub fn default_prepare(
+    uniforms: &mut Uniforms,
     width: u32,
     height: u32,
     light_direction: Vector3<f32>,
@@ -189,10 +938,7 @@ fn default_prepare(
     look_at: Vector3<f32>,
     up: Vector3<f32>,
 ) {
-    // New coordinate system a, b, c around camera position.
-    let new_z = (look_from - look_at).normalize();
-    let new_y = (up - new_z.dot(&up) * new_z).normalize();
-    let new_x = new_y.cross(&new_z).normalize();
+    let (new_x, new_y, new_z) = get_camera_basis(look_from, look_at, up);
     let model_matrix = matrix![new_x.x, new_x.y, new_x.z, 0.0;
                                new_y.x, new_y.y, new_y.z, 0.0;
                                new_z.x, new_z.y, new_z.z, 0.0;
@@ -201,30 +947,29 @@ fn default_prepare(
                               0.0, 1.0, 0.0, -look_from.y;
                               0.0, 0.0, 1.0, -look_from.z;
                               0.0, 0.0, 0.0, 1.0];
-    let coef = -1.0 / 5.0;
-    let projection_matrix = matrix![1.0, 0.0, 0.0,  0.0;
-                                    0.0, 1.0, 0.0,  0.0;
-                                    0.0, 0.0, 1.0,  0.0;
-                                    0.0, 0.0, coef, 1.0];
+    let projection_matrix = uniforms.projection.get_matrix(width as f32 / height as f32);
     // Viewport matrix depends only on constants.
     // Setting z-buffer resolution to 255.
     // Redef for convenience.
     let w = (width - 1) as f32;
     let h = (height - 1) as f32;
     let d = 255.;
-    let viewport_matrix = matrix![w / 2.0, 0.0,     0.0,     w / 2.0;
-                                  0.0,     h / 2.0, 0.0,     h / 2.0;
+    let (j_x, j_y) = (uniforms.jitter.x, uniforms.jitter.y);
+    let viewport_matrix = matrix![w / 2.0, 0.0,     0.0,     w / 2.0 + j_x;
+                                  0.0,     h / 2.0, 0.0,     h / 2.0 + j_y;
                                   0.0,     0.0,     d / 2.0, d / 2.0;
                                   0.0,     0.0,     0.0,     1.0];
 
     // Preparing shader pipeline for the render pass.
-    buffer.vpmv_matrix = viewport_matrix * projection_matrix * model_matrix * view_matrix;
+    uniforms.width = width;
+    uniforms.height = height;
+    uniforms.vpmv_matrix = viewport_matrix * projection_matrix * model_matrix * view_matrix;
     // Not interested in translation, projection and rasterization, when transformaing light direction and normals.
-    buffer.m_matrix = model_matrix;
-    buffer.it_m_matrix = (model_matrix).transpose().try_inverse().unwrap();
-    buffer.camera_direction = new_z;
-    buffer.t_light_direction =
-        Vector3::from_homogeneous(buffer.m_matrix * light_direction.to_homogeneous())
+    uniforms.m_matrix = model_matrix;
+    uniforms.it_m_matrix = (model_matrix).transpose().try_inverse().unwrap();
+    uniforms.camera_direction = new_z;
+    uniforms.t_light_direction =
+        Vector3::from_homogeneous(uniforms.m_matrix * light_direction.to_homogeneous())
             .unwrap()
             .normalize();
 }
@@ -232,7 +977,7 @@ fn default_prepare(
 /// Pipeline preparation for the pass, where we want to get depth information as if our camera was placed at
 /// the light source.
 fn shadow_pass_prepare_1(
-    buffer: &mut Buffer,
+    uniforms: &mut Uniforms,
     width: u32,
     height: u32,
     light_direction: Vector3<f32>,
@@ -240,8 +985,10 @@ fn shadow_pass_prepare_1(
     look_at: Vector3<f32>,
     up: Vector3<f32>,
 ) {
+    // Shadow buffer keeps the fixed projection, whatever the camera uses.
+    uniforms.projection = Projection::Fixed;
     default_prepare(
-        buffer,
+        uniforms,
         width,
         height,
         light_direction,
@@ -251,13 +998,13 @@ fn shadow_pass_prepare_1(
     );
     // After default application with changed camera position storing the resulting vpmv transform in a
     // separate matrix buffer for future use.
-    buffer.shadow_matrix = buffer.vpmv_matrix;
+    uniforms.shadow_matrix = uniforms.vpmv_matrix;
 }
 
 /// Pipeline preparation for the pass, where we want to get depth information as if our camera was placed at
 /// the light source.
 fn shadow_pass_prepare_2(
-    buffer: &mut Buffer,
+    uniforms: &mut Uniforms,
     width: u32,
     height: u32,
     light_direction: Vector3<f32>,
@@ -266,7 +1013,7 @@ fn shadow_pass_prepare_2(
     up: Vector3<f32>,
 ) {
     default_prepare(
-        buffer,
+        uniforms,
         width,
         height,
         light_direction,
@@ -274,8 +1021,8 @@ fn shadow_pass_prepare_2(
         look_at,
         up,
     );
-    buffer.i_vpmv_matrix = buffer.vpmv_matrix.try_inverse().unwrap();
-    buffer.i_m_matrix = buffer.m_matrix.try_inverse().unwrap();
+    uniforms.i_vpmv_matrix = uniforms.vpmv_matrix.try_inverse().unwrap();
+    uniforms.i_m_matrix = uniforms.m_matrix.try_inverse().unwrap();
 }
 
 /// Calculating diffuse coefficient based on the face normal and light direction.
@@ -283,14 +1030,15 @@ fn get_default_pipeline_passes() -> Vec<ShaderPass> {
     let mut passes = Vec::<ShaderPass>::new();
 
     fn vertex_pass_1(
-        buffer: &mut Buffer,
+        uniforms: &Uniforms,
+        varyings: &mut Varyings,
         model: &Model,
         pos_indices: Vector3<usize>,
         tex_indices: Vector3<usize>,
-        normal_indices: Vector3<usize>,
+        _normal_indices: Vector3<usize>,
     ) -> bool {
         let vertex_positions = get_vertex_positions(model, pos_indices);
-        if should_cull_face(vertex_positions, buffer.camera_direction) {
+        if should_cull_face(vertex_positions, uniforms) {
             return false;
         }
 
@@ -298,44 +1046,67 @@ fn get_default_pipeline_passes() -> Vec<ShaderPass> {
         let face_normal = (vertex_positions[1] - vertex_positions[0])
             .cross(&(vertex_positions[2] - vertex_positions[0]));
         let t_face_normal =
-            Vector3::from_homogeneous(buffer.it_m_matrix * face_normal.to_homogeneous())
+            Vector3::from_homogeneous(uniforms.it_m_matrix * face_normal.to_homogeneous())
                 .unwrap()
                 .normalize();
-        let diff_coef = buffer.t_light_direction.dot(&t_face_normal);
-        buffer.vertex_intensities = vector![diff_coef, diff_coef, diff_coef];
+        let diff_coef = uniforms.t_light_direction.dot(&t_face_normal);
+        varyings.vertex_intensities = vector![diff_coef, diff_coef, diff_coef];
+        varyings.vertex_t_normals =
+            Matrix3::from_columns(&[t_face_normal, t_face_normal, t_face_normal]);
 
         store_vertex_transformation_results(
             vertex_positions,
-            buffer.vpmv_matrix,
-            &mut buffer.vertex_t_raster,
-            &mut buffer.vertex_z_values,
+            uniforms.vpmv_matrix,
+            &mut varyings.vertex_t_raster,
+            &mut varyings.vertex_z_values,
+        );
+        store_vertex_uvs(&mut varyings.vertex_uvs, &model.obj.tex_coords, tex_indices);
+        varyings.translucent = is_polygon_translucent(uniforms, model, varyings.vertex_uvs);
+        store_vertex_t_positions(
+            &mut varyings.vertex_t_positions,
+            uniforms.m_matrix,
+            vertex_positions,
         );
-        store_vertex_uvs(&mut buffer.vertex_uvs, &model.obj.tex_coords, tex_indices);
 
         return true;
     }
 
     fn fragment_pass_1(
-        buffer: &mut Buffer,
+        uniforms: &Uniforms,
+        varyings: &Varyings,
+        targets: &mut Targets,
         model: &Model,
         coord: Vector2<u32>,
         bar_coord: Vector3<f32>,
-    ) -> bool {
-        if !process_z_value(buffer, bar_coord, coord) {
-            return false;
+    ) -> Option<Vector3<f32>> {
+        let uv = get_fragment_tex_coord(varyings, bar_coord);
+        if !process_alpha_value(uniforms, targets, model, uv) {
+            return None;
         }
-        let uv = buffer.vertex_uvs * bar_coord;
-        let color = model.get_color_at_uv(uv);
-        let diff_coef = buffer.vertex_intensities[0];
-        buffer.fragment_color = color_blend(color, vector![0, 0, 0], diff_coef);
-
-        return true;
+        if !process_z_value(targets, varyings.vertex_z_values, bar_coord, coord) {
+            return None;
+        }
+        let color = get_fragment_color(model, varyings, uv, bar_coord);
+        let diff_coef = varyings.vertex_intensities[0];
+        let lights_diffuse = get_lights_diffuse(
+            uniforms,
+            varyings.vertex_t_positions * bar_coord,
+            varyings.vertex_t_normals.column(0).into(),
+        );
+        return Some(apply_material(
+            &get_material(uniforms, targets),
+            color,
+            diff_coef * uniforms.light_intensity,
+            lights_diffuse,
+        ));
     }
 
     passes.push(ShaderPass {
         prepare: Box::new(default_prepare),
         vertex: Box::new(vertex_pass_1),
         fragment: Box::new(fragment_pass_1),
+        depth_target: DepthTarget::ZBuffer,
+        stencil: StencilState::default(),
     });
 
     return passes;
@@ -347,196 +1118,352 @@ fn get_phong_pipeline_passes() -> Vec<ShaderPass> {
     let mut passes = Vec::<ShaderPass>::new();
 
     fn vertex_pass_1(
-        buffer: &mut Buffer,
+        uniforms: &Uniforms,
+        varyings: &mut Varyings,
         model: &Model,
         pos_indices: Vector3<usize>,
         tex_indices: Vector3<usize>,
         normal_indices: Vector3<usize>,
     ) -> bool {
         let vertex_positions = get_vertex_positions(model, pos_indices);
-        if should_cull_face(vertex_positions, buffer.camera_direction) {
+        if should_cull_face(vertex_positions, uniforms) {
             return false;
         }
 
         // Calculating light intensities at each vertex to then interpolate them in fragment shader.
+        let vertex_normals = model.get_polygon_normals(pos_indices, normal_indices);
         for i in 0..3 {
-            let vertex_normal = vector![
-                model.obj.normals[normal_indices[i]].0,
-                model.obj.normals[normal_indices[i]].1,
-                model.obj.normals[normal_indices[i]].2
-            ];
+            let vertex_normal = vertex_normals[i];
             let vertex_t_normal =
-                Vector3::from_homogeneous(buffer.it_m_matrix * vertex_normal.to_homogeneous())
+                Vector3::from_homogeneous(uniforms.it_m_matrix * vertex_normal.to_homogeneous())
                     .unwrap()
                     .normalize();
-            buffer.vertex_intensities[i] = buffer.t_light_direction.dot(&vertex_t_normal);
+            varyings.vertex_intensities[i] = uniforms.t_light_direction.dot(&vertex_t_normal);
         }
 
         store_vertex_transformation_results(
             vertex_positions,
-            buffer.vpmv_matrix,
-            &mut buffer.vertex_t_raster,
-            &mut buffer.vertex_z_values,
+            uniforms.vpmv_matrix,
+            &mut varyings.vertex_t_raster,
+            &mut varyings.vertex_z_values,
+        );
+        store_vertex_uvs(&mut varyings.vertex_uvs, &model.obj.tex_coords, tex_indices);
+        varyings.translucent = is_polygon_translucent(uniforms, model, varyings.vertex_uvs);
+        store_vertex_t_positions(
+            &mut varyings.vertex_t_positions,
+            uniforms.m_matrix,
+            vertex_positions,
+        );
+        store_vertex_t_normals(
+            &mut varyings.vertex_t_normals,
+            uniforms.it_m_matrix,
+            model,
+            pos_indices,
+            normal_indices,
         );
-        store_vertex_uvs(&mut buffer.vertex_uvs, &model.obj.tex_coords, tex_indices);
 
         return true;
     }
 
     fn fragment_pass_1(
-        buffer: &mut Buffer,
+        uniforms: &Uniforms,
+        varyings: &Varyings,
+        targets: &mut Targets,
         model: &Model,
         coord: Vector2<u32>,
         bar_coord: Vector3<f32>,
-    ) -> bool {
-        if !process_z_value(buffer, bar_coord, coord) {
-            return false;
+    ) -> Option<Vector3<f32>> {
+        let uv = get_fragment_tex_coord(varyings, bar_coord);
+        if !process_alpha_value(uniforms, targets, model, uv) {
+            return None;
         }
-        let uv = buffer.vertex_uvs * bar_coord;
-        let color = model.get_color_at_uv(uv);
-        let diff_coef = bar_coord.dot(&buffer.vertex_intensities);
-        buffer.fragment_color = color_blend(color, vector![0, 0, 0], diff_coef);
-
-        return true;
+        if !process_z_value(targets, varyings.vertex_z_values, bar_coord, coord) {
+            return None;
+        }
+        let color = get_fragment_color(model, varyings, uv, bar_coord);
+        let diff_coef = bar_coord.dot(&varyings.vertex_intensities);
+        let lights_diffuse = get_lights_diffuse(
+            uniforms,
+            varyings.vertex_t_positions * bar_coord,
+            (varyings.vertex_t_normals * bar_coord).normalize(),
+        );
+        return Some(apply_material(
+            &get_material(uniforms, targets),
+            color,
+            diff_coef * uniforms.light_intensity,
+            lights_diffuse,
+        ));
     }
 
     passes.push(ShaderPass {
         prepare: Box::new(default_prepare),
         vertex: Box::new(vertex_pass_1),
         fragment: Box::new(fragment_pass_1),
+        depth_target: DepthTarget::ZBuffer,
+        stencil: StencilState::default(),
     });
 
     return passes;
 }
 
-/// Calculating diffuse coefficient by looking up normal from normal map at the fragment uv.
-fn get_normal_map_pipeline_passes() -> Vec<ShaderPass> {
+/// Phong shading of the interpolated vertex colors alone, without any textures, e.g. for untextured scans.
+fn get_vertex_color_pipeline_passes() -> Vec<ShaderPass> {
     let mut passes = Vec::<ShaderPass>::new();
 
     fn vertex_pass_1(
-        buffer: &mut Buffer,
+        uniforms: &Uniforms,
+        varyings: &mut Varyings,
         model: &Model,
         pos_indices: Vector3<usize>,
         tex_indices: Vector3<usize>,
         normal_indices: Vector3<usize>,
     ) -> bool {
         let vertex_positions = get_vertex_positions(model, pos_indices);
-        if should_cull_face(vertex_positions, buffer.camera_direction) {
+        if should_cull_face(vertex_positions, uniforms) {
             return false;
         }
 
+        // Calculating light intensities at each vertex to then interpolate them in fragment shader.
+        let vertex_normals = model.get_polygon_normals(pos_indices, normal_indices);
+        for i in 0..3 {
+            let vertex_normal = vertex_normals[i];
+            let vertex_t_normal =
+                Vector3::from_homogeneous(uniforms.it_m_matrix * vertex_normal.to_homogeneous())
+                    .unwrap()
+                    .normalize();
+            varyings.vertex_intensities[i] = uniforms.t_light_direction.dot(&vertex_t_normal);
+        }
+
         store_vertex_transformation_results(
             vertex_positions,
-            buffer.vpmv_matrix,
-            &mut buffer.vertex_t_raster,
-            &mut buffer.vertex_z_values,
+            uniforms.vpmv_matrix,
+            &mut varyings.vertex_t_raster,
+            &mut varyings.vertex_z_values,
+        );
+        store_vertex_uvs(&mut varyings.vertex_uvs, &model.obj.tex_coords, tex_indices);
+        varyings.translucent = is_polygon_translucent(uniforms, model, varyings.vertex_uvs);
+        store_vertex_t_positions(
+            &mut varyings.vertex_t_positions,
+            uniforms.m_matrix,
+            vertex_positions,
+        );
+        store_vertex_t_normals(
+            &mut varyings.vertex_t_normals,
+            uniforms.it_m_matrix,
+            model,
+            pos_indices,
+            normal_indices,
         );
-        store_vertex_uvs(&mut buffer.vertex_uvs, &model.obj.tex_coords, tex_indices);
 
         return true;
     }
 
     fn fragment_pass_1(
-        buffer: &mut Buffer,
+        uniforms: &Uniforms,
+        varyings: &Varyings,
+        targets: &mut Targets,
         model: &Model,
         coord: Vector2<u32>,
         bar_coord: Vector3<f32>,
-    ) -> bool {
-        if !process_z_value(buffer, bar_coord, coord) {
-            return false;
+    ) -> Option<Vector3<f32>> {
+        let uv = get_fragment_tex_coord(varyings, bar_coord);
+        if !process_alpha_value(uniforms, targets, model, uv) {
+            return None;
         }
-        let uv = buffer.vertex_uvs * bar_coord;
-        let color = model.get_color_at_uv(uv);
-        let fragment_normal = model.get_normal_at_uv(uv);
-        let t_fragment_normal =
-            Vector3::from_homogeneous(buffer.it_m_matrix * fragment_normal.to_homogeneous())
-                .unwrap()
-                .normalize();
-        let diff_coef = buffer.t_light_direction.dot(&t_fragment_normal);
-        buffer.fragment_color = color_blend(color, vector![0, 0, 0], diff_coef);
-
-        return true;
+        if !process_z_value(targets, varyings.vertex_z_values, bar_coord, coord) {
+            return None;
+        }
+        let color = varyings.vertex_colors * bar_coord * 255.0;
+        let diff_coef = bar_coord.dot(&varyings.vertex_intensities);
+        let lights_diffuse = get_lights_diffuse(
+            uniforms,
+            varyings.vertex_t_positions * bar_coord,
+            (varyings.vertex_t_normals * bar_coord).normalize(),
+        );
+        return Some(apply_material(
+            &get_material(uniforms, targets),
+            color,
+            diff_coef * uniforms.light_intensity,
+            lights_diffuse,
+        ));
     }
 
     passes.push(ShaderPass {
         prepare: Box::new(default_prepare),
         vertex: Box::new(vertex_pass_1),
         fragment: Box::new(fragment_pass_1),
+        depth_target: DepthTarget::ZBuffer,
+        stencil: StencilState::default(),
     });
 
     return passes;
 }
 
-/// Calculating diffuse coefficient as in the Phong pipeline, but also accounting for a specular component
-/// by using specular map texture.
-fn get_specular_pipeline_passes() -> Vec<ShaderPass> {
-    let mut passes = Vec::<ShaderPass>::new();
+/// Calculating diffuse coefficient by looking up normal from normal map at the fragment uv.
+fn get_normal_map_pipeline_passes() -> Vec<ShaderPass> {
+    let mut passes = Vec::<ShaderPass>::new();
 
     fn vertex_pass_1(
-        buffer: &mut Buffer,
+        uniforms: &Uniforms,
+        varyings: &mut Varyings,
         model: &Model,
         pos_indices: Vector3<usize>,
         tex_indices: Vector3<usize>,
         normal_indices: Vector3<usize>,
     ) -> bool {
         let vertex_positions = get_vertex_positions(model, pos_indices);
-        if should_cull_face(vertex_positions, buffer.camera_direction) {
+        if should_cull_face(vertex_positions, uniforms) {
             return false;
         }
 
         store_vertex_transformation_results(
             vertex_positions,
-            buffer.vpmv_matrix,
-            &mut buffer.vertex_t_raster,
-            &mut buffer.vertex_z_values,
+            uniforms.vpmv_matrix,
+            &mut varyings.vertex_t_raster,
+            &mut varyings.vertex_z_values,
         );
-        store_vertex_uvs(&mut buffer.vertex_uvs, &model.obj.tex_coords, tex_indices);
+        store_vertex_uvs(&mut varyings.vertex_uvs, &model.obj.tex_coords, tex_indices);
+        varyings.translucent = is_polygon_translucent(uniforms, model, varyings.vertex_uvs);
+        store_vertex_t_positions(
+            &mut varyings.vertex_t_positions,
+            uniforms.m_matrix,
+            vertex_positions,
+        );
+
+        store_fallback_vertex_t_normals(varyings, uniforms, model, pos_indices, normal_indices);
 
         return true;
     }
 
     fn fragment_pass_1(
-        buffer: &mut Buffer,
+        uniforms: &Uniforms,
+        varyings: &Varyings,
+        targets: &mut Targets,
         model: &Model,
         coord: Vector2<u32>,
         bar_coord: Vector3<f32>,
+    ) -> Option<Vector3<f32>> {
+        let uv = get_fragment_tex_coord(varyings, bar_coord);
+        if !process_alpha_value(uniforms, targets, model, uv) {
+            return None;
+        }
+        if !process_z_value(targets, varyings.vertex_z_values, bar_coord, coord) {
+            return None;
+        }
+        let color = get_fragment_color(model, varyings, uv, bar_coord);
+        let t_fragment_normal = get_fragment_t_normal(uniforms, varyings, model, uv, bar_coord);
+        let diff_coef = uniforms.t_light_direction.dot(&t_fragment_normal);
+        let lights_diffuse = get_lights_diffuse(
+            uniforms,
+            varyings.vertex_t_positions * bar_coord,
+            t_fragment_normal,
+        );
+        return Some(apply_material(
+            &get_material(uniforms, targets),
+            color,
+            diff_coef * uniforms.light_intensity,
+            lights_diffuse,
+        ));
+    }
+
+    passes.push(ShaderPass {
+        prepare: Box::new(default_prepare),
+        vertex: Box::new(vertex_pass_1),
+        fragment: Box::new(fragment_pass_1),
+        depth_target: DepthTarget::ZBuffer,
+        stencil: StencilState::default(),
+    });
+
+    return passes;
+}
+
+/// Calculating diffuse coefficient as in the Phong pipeline, but also accounting for a specular component
+/// by using specular map texture.
+fn get_specular_pipeline_passes() -> Vec<ShaderPass> {
+    let mut passes = Vec::<ShaderPass>::new();
+
+    fn vertex_pass_1(
+        uniforms: &Uniforms,
+        varyings: &mut Varyings,
+        model: &Model,
+        pos_indices: Vector3<usize>,
+        tex_indices: Vector3<usize>,
+        normal_indices: Vector3<usize>,
     ) -> bool {
-        if !process_z_value(buffer, bar_coord, coord) {
+        let vertex_positions = get_vertex_positions(model, pos_indices);
+        if should_cull_face(vertex_positions, uniforms) {
             return false;
         }
-        let uv = buffer.vertex_uvs * bar_coord;
-        let color = model.get_color_at_uv(uv);
-        let fragment_normal = model.get_normal_at_uv(uv);
-        let t_fragment_normal =
-            Vector3::from_homogeneous(buffer.it_m_matrix * fragment_normal.to_homogeneous())
-                .unwrap()
-                .normalize();
+
+        store_vertex_transformation_results(
+            vertex_positions,
+            uniforms.vpmv_matrix,
+            &mut varyings.vertex_t_raster,
+            &mut varyings.vertex_z_values,
+        );
+        store_vertex_uvs(&mut varyings.vertex_uvs, &model.obj.tex_coords, tex_indices);
+        varyings.translucent = is_polygon_translucent(uniforms, model, varyings.vertex_uvs);
+        store_vertex_t_positions(
+            &mut varyings.vertex_t_positions,
+            uniforms.m_matrix,
+            vertex_positions,
+        );
+
+        store_fallback_vertex_t_normals(varyings, uniforms, model, pos_indices, normal_indices);
+
+        return true;
+    }
+
+    fn fragment_pass_1(
+        uniforms: &Uniforms,
+        varyings: &Varyings,
+        targets: &mut Targets,
+        model: &Model,
+        coord: Vector2<u32>,
+        bar_coord: Vector3<f32>,
+    ) -> Option<Vector3<f32>> {
+        let uv = get_fragment_tex_coord(varyings, bar_coord);
+        if !process_alpha_value(uniforms, targets, model, uv) {
+            return None;
+        }
+        if !process_z_value(targets, varyings.vertex_z_values, bar_coord, coord) {
+            return None;
+        }
+        let color = get_fragment_color(model, varyings, uv, bar_coord);
+        let t_fragment_normal = get_fragment_t_normal(uniforms, varyings, model, uv, bar_coord);
         // Calculated reflection direction, immediately in a new camera frame.
         let reflected_t_light_direction = (2.0
-            * (t_fragment_normal * buffer.t_light_direction.dot(&t_fragment_normal))
-            - buffer.t_light_direction)
+            * (t_fragment_normal * uniforms.t_light_direction.dot(&t_fragment_normal))
+            - uniforms.t_light_direction)
             .normalize();
-        let diff_coef = buffer.t_light_direction.dot(&t_fragment_normal);
+        let diff_coef = uniforms.t_light_direction.dot(&t_fragment_normal);
+        let material = get_material(uniforms, targets);
+        let shininess = material
+            .shininess
+            .unwrap_or_else(|| model.get_specular_value_at_uv(uv));
         // Accesing only .z, since in the new frame camera direction is always [0.0, 0.0, -1.0].
-        let spec_coef = 0.6
-            * reflected_t_light_direction
-                .z
-                .max(0.0)
-                .powf(model.get_specular_value_at_uv(uv));
-        let corrected_color = vector![
-            ((diff_coef + spec_coef) * color[0] as f32).min(255.0) as u8,
-            ((diff_coef + spec_coef) * color[1] as f32).min(255.0) as u8,
-            ((diff_coef + spec_coef) * color[2] as f32).min(255.0) as u8
-        ];
-        buffer.fragment_color = corrected_color;
-
-        return true;
+        let spec_coef = material.specular * reflected_t_light_direction.z.max(0.0).powf(shininess);
+        let corrected_color =
+            color * (material.diffuse * diff_coef + spec_coef).max(0.0) * uniforms.light_intensity
+                + color * material.ambient;
+        let lights_diffuse = get_lights_diffuse(
+            uniforms,
+            varyings.vertex_t_positions * bar_coord,
+            t_fragment_normal,
+        );
+        return Some(add_lights_diffuse(
+            corrected_color,
+            color,
+            lights_diffuse * material.diffuse,
+        ));
     }
 
     passes.push(ShaderPass {
         prepare: Box::new(default_prepare),
         vertex: Box::new(vertex_pass_1),
         fragment: Box::new(fragment_pass_1),
+        depth_target: DepthTarget::ZBuffer,
+        stencil: StencilState::default(),
     });
 
     return passes;
@@ -547,117 +1474,104 @@ fn get_darboux_pipeline_passes() -> Vec<ShaderPass> {
     let mut passes = Vec::<ShaderPass>::new();
 
     fn vertex_pass_1(
-        buffer: &mut Buffer,
+        uniforms: &Uniforms,
+        varyings: &mut Varyings,
         model: &Model,
         pos_indices: Vector3<usize>,
         tex_indices: Vector3<usize>,
         normal_indices: Vector3<usize>,
     ) -> bool {
         let vertex_positions = get_vertex_positions(model, pos_indices);
-        if should_cull_face(vertex_positions, buffer.camera_direction) {
+        if should_cull_face(vertex_positions, uniforms) {
             return false;
         }
 
         // Collecting transformed vertex positions in a buffer to use in local basis calculation.
-        for i in 0..3 {
-            buffer.vertex_t_positions.set_column(
-                i,
-                &Point3::from_homogeneous(buffer.m_matrix * vertex_positions[i].to_homogeneous())
-                    .unwrap()
-                    .coords,
-            );
-        }
+        store_vertex_t_positions(
+            &mut varyings.vertex_t_positions,
+            uniforms.m_matrix,
+            vertex_positions,
+        );
 
-        // Collecting transformed normals at each vertex into a single matrix for subsequent interpolation
-        // in a fragment shader.
-        for i in 0..3 {
-            let vertex_normal = vector![
-                model.obj.normals[normal_indices[i]].0,
-                model.obj.normals[normal_indices[i]].1,
-                model.obj.normals[normal_indices[i]].2
-            ];
-            let vertex_t_normal =
-                Vector3::from_homogeneous(buffer.it_m_matrix * vertex_normal.to_homogeneous())
-                    .unwrap()
-                    .normalize();
-            buffer.vertex_t_normals.set_column(i, &vertex_t_normal);
-        }
+        // Collecting transformed normals and tangents at each vertex into matrices for subsequent
+        // interpolation in a fragment shader.
+        store_vertex_t_normals(
+            &mut varyings.vertex_t_normals,
+            uniforms.it_m_matrix,
+            model,
+            pos_indices,
+            normal_indices,
+        );
+        store_vertex_t_tangents(
+            varyings,
+            uniforms.m_matrix,
+            model,
+            pos_indices,
+            tex_indices,
+            normal_indices,
+        );
 
         store_vertex_transformation_results(
             vertex_positions,
-            buffer.vpmv_matrix,
-            &mut buffer.vertex_t_raster,
-            &mut buffer.vertex_z_values,
+            uniforms.vpmv_matrix,
+            &mut varyings.vertex_t_raster,
+            &mut varyings.vertex_z_values,
         );
-        store_vertex_uvs(&mut buffer.vertex_uvs, &model.obj.tex_coords, tex_indices);
+        store_vertex_uvs(&mut varyings.vertex_uvs, &model.obj.tex_coords, tex_indices);
+        varyings.translucent = is_polygon_translucent(uniforms, model, varyings.vertex_uvs);
 
         return true;
     }
 
     fn fragment_pass_1(
-        buffer: &mut Buffer,
+        uniforms: &Uniforms,
+        varyings: &Varyings,
+        targets: &mut Targets,
         model: &Model,
         coord: Vector2<u32>,
         bar_coord: Vector3<f32>,
-    ) -> bool {
-        if !process_z_value(buffer, bar_coord, coord) {
-            return false;
+    ) -> Option<Vector3<f32>> {
+        let uv = get_fragment_tex_coord(varyings, bar_coord);
+        if !process_alpha_value(uniforms, targets, model, uv) {
+            return None;
+        }
+        if !process_z_value(targets, varyings.vertex_z_values, bar_coord, coord) {
+            return None;
         }
-        let uv = buffer.vertex_uvs * bar_coord;
-        let color = model.get_color_at_uv(uv);
+        let color = get_fragment_color(model, varyings, uv, bar_coord);
         let fragment_normal_tangent = model.get_normal_tangent_at_uv(uv);
-        // Calculating the matrix, giving required transformation from Darboux basis to the global one.
-        let mut local_basis_matrix: Matrix3<f32> = Default::default();
-        let local_z = buffer.vertex_t_normals * bar_coord;
-        local_basis_matrix.set_row(
-            0,
-            &(buffer.vertex_t_positions * vector![-1.0, 1.0, 0.0])
-                .normalize()
-                .transpose(),
-        );
-        local_basis_matrix.set_row(
-            1,
-            &(buffer.vertex_t_positions * vector![-1.0, 0.0, 1.0])
-                .normalize()
-                .transpose(),
-        );
-        local_basis_matrix.set_row(
-            2,
-            &(buffer.vertex_t_normals * bar_coord)
-                .normalize()
-                .transpose(),
-        );
-
-        let i_local_basis_matrix = local_basis_matrix.try_inverse().unwrap();
-        let local_x = i_local_basis_matrix
-            * vector![
-                buffer.vertex_uvs.m12 - buffer.vertex_uvs.m11,
-                buffer.vertex_uvs.m13 - buffer.vertex_uvs.m11,
-                0.0
-            ];
-        let local_y = i_local_basis_matrix
-            * vector![
-                buffer.vertex_uvs.m22 - buffer.vertex_uvs.m21,
-                buffer.vertex_uvs.m23 - buffer.vertex_uvs.m21,
-                0.0
-            ];
-
-        let mut local_transform_matrix: Matrix3<f32> = Default::default();
-        local_transform_matrix.set_column(0, &local_x.normalize());
-        local_transform_matrix.set_column(1, &local_y.normalize());
-        local_transform_matrix.set_column(2, &local_z.normalize());
+        // Darboux basis of the fragment from the interpolated vertex tangents and normals, tangent being made
+        // orthogonal to the normal again after interpolation.
+        let local_z = (varyings.vertex_t_normals * bar_coord).normalize();
+        let tangent = varyings.vertex_t_tangents * bar_coord;
+        let local_x = (tangent - local_z * local_z.dot(&tangent))
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_else(Vector3::zeros);
+        let handedness = bar_coord.dot(&varyings.vertex_tangent_signs).signum();
+        let local_y = local_z.cross(&local_x) * handedness;
+        let local_transform_matrix = Matrix3::from_columns(&[local_x, local_y, local_z]);
         let t_fragment_normal = (local_transform_matrix * fragment_normal_tangent).normalize();
 
-        let diff_coef = buffer.t_light_direction.dot(&t_fragment_normal);
-        buffer.fragment_color = color_blend(color, vector![0, 0, 0], diff_coef);
-
-        return true;
+        let diff_coef = uniforms.t_light_direction.dot(&t_fragment_normal);
+        let lights_diffuse = get_lights_diffuse(
+            uniforms,
+            varyings.vertex_t_positions * bar_coord,
+            t_fragment_normal,
+        );
+        return Some(apply_material(
+            &get_material(uniforms, targets),
+            color,
+            diff_coef * uniforms.light_intensity,
+            lights_diffuse,
+        ));
     }
 
     passes.push(ShaderPass {
         prepare: Box::new(default_prepare),
         vertex: Box::new(vertex_pass_1),
         fragment: Box::new(fragment_pass_1),
+        depth_target: DepthTarget::ZBuffer,
+        stencil: StencilState::default(),
     });
 
     return passes;
@@ -669,11 +1583,12 @@ fn get_shadow_pipeline_passes() -> Vec<ShaderPass> {
     let mut passes = Vec::<ShaderPass>::new();
 
     fn vertex_pass_1(
-        buffer: &mut Buffer,
+        uniforms: &Uniforms,
+        varyings: &mut Varyings,
         model: &Model,
         pos_indices: Vector3<usize>,
         tex_indices: Vector3<usize>,
-        normal_indices: Vector3<usize>,
+        _normal_indices: Vector3<usize>,
     ) -> bool {
         let vertex_positions = get_vertex_positions(model, pos_indices);
         // No culling on this pass, since cull decisions for the real camera can be different.
@@ -681,35 +1596,38 @@ fn get_shadow_pipeline_passes() -> Vec<ShaderPass> {
         // need only info, that will help us to calculate the shadow buffer in the fragment shader.
         store_vertex_transformation_results(
             vertex_positions,
-            buffer.shadow_matrix,
-            &mut buffer.vertex_t_raster,
-            &mut buffer.vertex_z_values,
+            uniforms.shadow_matrix,
+            &mut varyings.vertex_t_raster,
+            &mut varyings.vertex_z_values,
         );
-        store_vertex_uvs(&mut buffer.vertex_uvs, &model.obj.tex_coords, tex_indices);
+        store_vertex_uvs(&mut varyings.vertex_uvs, &model.obj.tex_coords, tex_indices);
 
         // Returning true so shadow buffer is updated with z-values from all fragments.
         return true;
     }
 
     fn fragment_pass_1(
-        buffer: &mut Buffer,
-        model: &Model,
+        _uniforms: &Uniforms,
+        varyings: &Varyings,
+        targets: &mut Targets,
+        _model: &Model,
         coord: Vector2<u32>,
         bar_coord: Vector3<f32>,
-    ) -> bool {
-        // Filling shadow buffer.
-        let index = (coord.x + coord.y * buffer.width) as usize;
-        let z_value = bar_coord.dot(&buffer.vertex_z_values);
-        if z_value >= buffer.shadow_buffer[index] {
-            buffer.shadow_buffer[index] = z_value;
+    ) -> Option<Vector3<f32>> {
+        // Filling shadow buffer, which is the depth target of this pass.
+        let index = targets.depth_index(coord);
+        let z_value = interpolate_depth(varyings.vertex_z_values, bar_coord);
+        if z_value >= targets.depth[index] {
+            targets.depth[index] = z_value;
         }
 
-        // Don't need to draw anything to the final frame buffer on this pass, so just returning false.
-        return false;
+        // Don't need to draw anything to the final frame buffer on this pass, so just returning None.
+        return None;
     }
 
     fn vertex_pass_2(
-        buffer: &mut Buffer,
+        uniforms: &Uniforms,
+        varyings: &mut Varyings,
         model: &Model,
         pos_indices: Vector3<usize>,
         tex_indices: Vector3<usize>,
@@ -717,85 +1635,114 @@ fn get_shadow_pipeline_passes() -> Vec<ShaderPass> {
     ) -> bool {
         // Phong vertex shader.
         let vertex_positions = get_vertex_positions(model, pos_indices);
-        if should_cull_face(vertex_positions, buffer.camera_direction) {
+        if should_cull_face(vertex_positions, uniforms) {
             return false;
         }
 
         // Calculating light intensities at each vertex to then interpolate them in fragment shader.
+        let vertex_normals = model.get_polygon_normals(pos_indices, normal_indices);
         for i in 0..3 {
-            let vertex_normal = vector![
-                model.obj.normals[normal_indices[i]].0,
-                model.obj.normals[normal_indices[i]].1,
-                model.obj.normals[normal_indices[i]].2
-            ];
+            let vertex_normal = vertex_normals[i];
             let vertex_t_normal =
-                Vector3::from_homogeneous(buffer.it_m_matrix * vertex_normal.to_homogeneous())
+                Vector3::from_homogeneous(uniforms.it_m_matrix * vertex_normal.to_homogeneous())
                     .unwrap()
                     .normalize();
-            buffer.vertex_intensities[i] = buffer.t_light_direction.dot(&vertex_t_normal);
+            varyings.vertex_intensities[i] = uniforms.t_light_direction.dot(&vertex_t_normal);
         }
 
         store_vertex_transformation_results(
             vertex_positions,
-            buffer.vpmv_matrix,
-            &mut buffer.vertex_t_raster,
-            &mut buffer.vertex_z_values,
+            uniforms.vpmv_matrix,
+            &mut varyings.vertex_t_raster,
+            &mut varyings.vertex_z_values,
+        );
+        store_vertex_uvs(&mut varyings.vertex_uvs, &model.obj.tex_coords, tex_indices);
+        varyings.translucent = is_polygon_translucent(uniforms, model, varyings.vertex_uvs);
+        store_vertex_t_positions(
+            &mut varyings.vertex_t_positions,
+            uniforms.m_matrix,
+            vertex_positions,
+        );
+        store_vertex_t_normals(
+            &mut varyings.vertex_t_normals,
+            uniforms.it_m_matrix,
+            model,
+            pos_indices,
+            normal_indices,
         );
-        store_vertex_uvs(&mut buffer.vertex_uvs, &model.obj.tex_coords, tex_indices);
 
         return true;
     }
 
     fn fragment_pass_2(
-        buffer: &mut Buffer,
+        uniforms: &Uniforms,
+        varyings: &Varyings,
+        targets: &mut Targets,
         model: &Model,
         coord: Vector2<u32>,
         bar_coord: Vector3<f32>,
-    ) -> bool {
-        if !process_z_value(buffer, bar_coord, coord) {
-            return false;
+    ) -> Option<Vector3<f32>> {
+        let uv = get_fragment_tex_coord(varyings, bar_coord);
+        if !process_alpha_value(uniforms, targets, model, uv) {
+            return None;
+        }
+        if !process_z_value(targets, varyings.vertex_z_values, bar_coord, coord) {
+            return None;
         }
 
         // Accounting for the shadow - finding the current fragment in the shadow buffer and looking at its
         // z-value there - if it is larger than the z-value, that we got from current transform, it means,
         // that our fragment is in the shadow, so we need to dim the color.
         let shadow_coord = Point3::from_homogeneous(
-            buffer.shadow_matrix
-                * buffer.i_vpmv_matrix
+            uniforms.shadow_matrix
+                * uniforms.i_vpmv_matrix
                 * point![
                     coord.x as f32,
                     coord.y as f32,
-                    bar_coord.dot(&buffer.vertex_z_values)
+                    bar_coord.dot(&varyings.vertex_z_values)
                 ]
                 .to_homogeneous(),
         )
         .unwrap();
-        // Very importnat to cast shadow_coord to u32 as opposed to buffer.width to f32!
-        let shadow_index = (shadow_coord.x.round() as u32
-            + (shadow_coord.y.round() as u32) * buffer.width) as usize;
-        let mut shadow_coef = 1.0;
-        // +1.0 to combat z-fighting.
-        if shadow_coord.z + 1.0 < buffer.shadow_buffer[shadow_index] {
-            shadow_coef = 0.3;
-        }
-
-        let uv = buffer.vertex_uvs * bar_coord;
-        let color = model.get_color_at_uv(uv);
-        let diff_coef = bar_coord.dot(&buffer.vertex_intensities);
-        buffer.fragment_color = color_blend(color, vector![0, 0, 0], diff_coef * shadow_coef);
-
-        return true;
+        let diff_coef = bar_coord.dot(&varyings.vertex_intensities);
+        let visibility = match &uniforms.cascaded_shadow_map {
+            Some(cascaded_shadow_map) => cascaded_shadow_map.get_visibility(
+                to_model_position(uniforms, varyings.vertex_t_positions * bar_coord),
+                diff_coef,
+                uniforms.shadow.pcf_radius,
+            ),
+            None => get_shadow_visibility(uniforms, targets.shadow_buffer, shadow_coord, diff_coef),
+        };
+        // Fully shadowed fragments are still dimly lit.
+        let shadow_coef = 0.3 + 0.7 * visibility;
+
+        let color = get_fragment_color(model, varyings, uv, bar_coord);
+        let lights_diffuse = get_lights_diffuse(
+            uniforms,
+            varyings.vertex_t_positions * bar_coord,
+            (varyings.vertex_t_normals * bar_coord).normalize(),
+        );
+        return Some(apply_material(
+            &get_material(uniforms, targets),
+            color,
+            diff_coef * shadow_coef * uniforms.light_intensity,
+            lights_diffuse,
+        ));
     }
 
     passes.push(ShaderPass {
         prepare: Box::new(shadow_pass_prepare_1),
         vertex: Box::new(vertex_pass_1),
         fragment: Box::new(fragment_pass_1),
+        depth_target: DepthTarget::ShadowBuffer,
+        stencil: StencilState::default(),
     });
     passes.push(ShaderPass {
         prepare: Box::new(shadow_pass_prepare_2),
         vertex: Box::new(vertex_pass_2),
         fragment: Box::new(fragment_pass_2),
+        depth_target: DepthTarget::ZBuffer,
+        stencil: StencilState::default(),
     });
 
     return passes;
@@ -807,11 +1754,12 @@ fn get_occlusion_pipeline_passes() -> Vec<ShaderPass> {
     let mut passes = Vec::<ShaderPass>::new();
 
     fn vertex_pass_1(
-        buffer: &mut Buffer,
+        uniforms: &Uniforms,
+        varyings: &mut Varyings,
         model: &Model,
         pos_indices: Vector3<usize>,
         tex_indices: Vector3<usize>,
-        normal_indices: Vector3<usize>,
+        _normal_indices: Vector3<usize>,
     ) -> bool {
         let vertex_positions = get_vertex_positions(model, pos_indices);
         // No culling on this pass, since cull decisions for the real camera can be different.
@@ -819,97 +1767,102 @@ fn get_occlusion_pipeline_passes() -> Vec<ShaderPass> {
         // need only info, that will help us to calculate the shadow buffer in the fragment shader.
         store_vertex_transformation_results(
             vertex_positions,
-            buffer.shadow_matrix,
-            &mut buffer.vertex_t_raster,
-            &mut buffer.vertex_z_values,
+            uniforms.shadow_matrix,
+            &mut varyings.vertex_t_raster,
+            &mut varyings.vertex_z_values,
         );
-        store_vertex_uvs(&mut buffer.vertex_uvs, &model.obj.tex_coords, tex_indices);
+        store_vertex_uvs(&mut varyings.vertex_uvs, &model.obj.tex_coords, tex_indices);
 
         // Returning true so shadow buffer is updated with z-values from all fragments.
         return true;
     }
 
     fn fragment_pass_1(
-        buffer: &mut Buffer,
-        model: &Model,
+        _uniforms: &Uniforms,
+        varyings: &Varyings,
+        targets: &mut Targets,
+        _model: &Model,
         coord: Vector2<u32>,
         bar_coord: Vector3<f32>,
-    ) -> bool {
-        // Filling shadow buffer.
-        let index = (coord.x + coord.y * buffer.width) as usize;
-        let z_value = bar_coord.dot(&buffer.vertex_z_values);
-        if z_value >= buffer.shadow_buffer[index] {
-            buffer.shadow_buffer[index] = z_value;
+    ) -> Option<Vector3<f32>> {
+        // Filling shadow buffer, which is the depth target of this pass.
+        let index = targets.depth_index(coord);
+        let z_value = interpolate_depth(varyings.vertex_z_values, bar_coord);
+        if z_value >= targets.depth[index] {
+            targets.depth[index] = z_value;
         }
 
-        // Don't need to draw anything to the final frame buffer on this pass, so just returning false.
-        return false;
+        // Don't need to draw anything to the final frame buffer on this pass, so just returning None.
+        return None;
     }
 
     fn vertex_pass_2(
-        buffer: &mut Buffer,
+        uniforms: &Uniforms,
+        varyings: &mut Varyings,
         model: &Model,
         pos_indices: Vector3<usize>,
         tex_indices: Vector3<usize>,
-        normal_indices: Vector3<usize>,
+        _normal_indices: Vector3<usize>,
     ) -> bool {
         // Phong vertex shader.
         let vertex_positions = get_vertex_positions(model, pos_indices);
-        if should_cull_face(vertex_positions, buffer.camera_direction) {
+        if should_cull_face(vertex_positions, uniforms) {
             return false;
         }
         store_vertex_transformation_results(
             vertex_positions,
-            buffer.vpmv_matrix,
-            &mut buffer.vertex_t_raster,
-            &mut buffer.vertex_z_values,
+            uniforms.vpmv_matrix,
+            &mut varyings.vertex_t_raster,
+            &mut varyings.vertex_z_values,
         );
-        store_vertex_uvs(&mut buffer.vertex_uvs, &model.obj.tex_coords, tex_indices);
+        store_vertex_uvs(&mut varyings.vertex_uvs, &model.obj.tex_coords, tex_indices);
 
         return true;
     }
 
     fn fragment_pass_2(
-        buffer: &mut Buffer,
-        model: &Model,
+        uniforms: &Uniforms,
+        varyings: &Varyings,
+        targets: &mut Targets,
+        _model: &Model,
         coord: Vector2<u32>,
         bar_coord: Vector3<f32>,
-    ) -> bool {
-        if !process_z_value(buffer, bar_coord, coord) {
-            return false;
+    ) -> Option<Vector3<f32>> {
+        if !process_z_value(targets, varyings.vertex_z_values, bar_coord, coord) {
+            return None;
         }
 
         let light_direction = Vector3::from_homogeneous(
-            buffer.i_m_matrix * buffer.t_light_direction.to_homogeneous(),
+            uniforms.i_m_matrix * uniforms.t_light_direction.to_homogeneous(),
         )
         .unwrap();
         // Finding position of the fragment in the global coordinates first.
         let fragment_world_position = Point3::from_homogeneous(
-            buffer.i_vpmv_matrix
+            uniforms.i_vpmv_matrix
                 * point![
                     coord.x as f32,
                     coord.y as f32,
-                    bar_coord.dot(&buffer.vertex_z_values)
+                    bar_coord.dot(&varyings.vertex_z_values)
                 ]
                 .to_homogeneous(),
         )
         .unwrap();
         // Finding a point in the shadow buffer, which corresponds to the current fragment.
         let fragment_shadow_coord = Point3::from_homogeneous(
-            buffer.shadow_matrix
-                * buffer.i_vpmv_matrix
+            uniforms.shadow_matrix
+                * uniforms.i_vpmv_matrix
                 * point![
                     coord.x as f32,
                     coord.y as f32,
-                    bar_coord.dot(&buffer.vertex_z_values)
+                    bar_coord.dot(&varyings.vertex_z_values)
                 ]
                 .to_homogeneous(),
         )
         .unwrap();
         let fragment_shadow_index = (fragment_shadow_coord.x.round() as u32
-            + (fragment_shadow_coord.y.round() as u32) * buffer.width)
+            + (fragment_shadow_coord.y.round() as u32) * uniforms.width)
             as usize;
-        let fragment_shadow_value = buffer.shadow_buffer[fragment_shadow_index];
+        let fragment_shadow_value = targets.shadow_buffer[fragment_shadow_index];
 
         // Sampling 16 points around the fragment uniformly in the plane perpendicular to the light direction.
         // Each sample is transformed to shadow buffer coordinates in order to check occlusion.
@@ -928,22 +1881,23 @@ fn get_occlusion_pipeline_passes() -> Vec<ShaderPass> {
             let step_dir = rot * global_step_dir;
             let sample = fragment_world_position + step_dir * step_size;
             let sample_shadow_coord =
-                Point3::from_homogeneous(buffer.shadow_matrix * sample.to_homogeneous()).unwrap();
+                Point3::from_homogeneous(uniforms.shadow_matrix * sample.to_homogeneous()).unwrap();
             let sample_shadow_index = (sample_shadow_coord.x.round() as u32
-                + (sample_shadow_coord.y.round() as u32) * buffer.width)
+                + (sample_shadow_coord.y.round() as u32) * uniforms.width)
                 as usize;
-            if buffer.shadow_buffer[sample_shadow_index] - threshold > fragment_shadow_value {
+            if targets.shadow_buffer[sample_shadow_index] - threshold > fragment_shadow_value {
                 let mut occlusion_strength =
-                    (buffer.shadow_buffer[sample_shadow_index] - fragment_shadow_value) / 20.0;
+                    (targets.shadow_buffer[sample_shadow_index] - fragment_shadow_value) / 20.0;
                 occlusion_strength = occlusion_strength.min(1.0);
                 occlusion_coef -= (1.0 / (number_of_samples as f32)) * occlusion_strength;
             }
         }
 
-        buffer.fragment_color =
-            color_blend(vector![255, 255, 255], vector![0, 0, 0], occlusion_coef);
-
-        return true;
+        return Some(color_blend(
+            vector![255.0, 255.0, 255.0],
+            Vector3::zeros(),
+            occlusion_coef,
+        ));
     }
 
     // Preparation for the occlusion passes is the same as for shadow passes, since we need the same
@@ -952,12 +1906,677 @@ fn get_occlusion_pipeline_passes() -> Vec<ShaderPass> {
         prepare: Box::new(shadow_pass_prepare_1),
         vertex: Box::new(vertex_pass_1),
         fragment: Box::new(fragment_pass_1),
+        depth_target: DepthTarget::ShadowBuffer,
+        stencil: StencilState::default(),
     });
     passes.push(ShaderPass {
         prepare: Box::new(shadow_pass_prepare_2),
         vertex: Box::new(vertex_pass_2),
         fragment: Box::new(fragment_pass_2),
+        depth_target: DepthTarget::ZBuffer,
+        stencil: StencilState::default(),
+    });
+
+    return passes;
+}
+
+/// Cook-Torrance BRDF with GGX normal distribution, Smith geometry term and Schlick Fresnel approximation,
+/// returning reflected radiance for a unit light intensity. All the directions are normalized and point
+/// away from the surface, albedo is in [0, 1].
+fn get_cook_torrance_radiance(
+    normal: Vector3<f32>,
+    light_direction: Vector3<f32>,
+    view_direction: Vector3<f32>,
+    albedo: Vector3<f32>,
+    metallic: f32,
+    roughness: f32,
+) -> Vector3<f32> {
+    let n_dot_l = normal.dot(&light_direction);
+    let n_dot_v = normal.dot(&view_direction);
+    if n_dot_l <= 0.0 || n_dot_v <= 0.0 {
+        return Vector3::zeros();
+    }
+    let half_vector = (light_direction + view_direction).normalize();
+    let n_dot_h = normal.dot(&half_vector).max(0.0);
+    let h_dot_v = half_vector.dot(&view_direction).max(0.0);
+
+    // GGX distribution with alpha being squared perceptual roughness.
+    let alpha_2 = (roughness * roughness).powi(2);
+    let denominator = n_dot_h * n_dot_h * (alpha_2 - 1.0) + 1.0;
+    let distribution = alpha_2 / (std::f32::consts::PI * denominator * denominator);
+
+    // Smith geometry term, combining Schlick-GGX masking for light and view directions.
+    let k = (roughness + 1.0).powi(2) / 8.0;
+    let geometry = (n_dot_l / (n_dot_l * (1.0 - k) + k)) * (n_dot_v / (n_dot_v * (1.0 - k) + k));
+
+    // Dielectrics reflect about 4% at normal incidence, metals reflect with their albedo.
+    let f_0 = vector![0.04, 0.04, 0.04] * (1.0 - metallic) + albedo * metallic;
+    let fresnel = f_0 + (vector![1.0, 1.0, 1.0] - f_0) * (1.0 - h_dot_v).powi(5);
+
+    let specular = fresnel * distribution * geometry / (4.0 * n_dot_l * n_dot_v).max(1e-4);
+    // Metals have no diffuse reflection, the rest of the energy not reflected specularly is diffused.
+    let diffuse = (vector![1.0, 1.0, 1.0] - fresnel)
+        .component_mul(&albedo)
+        .scale((1.0 - metallic) / std::f32::consts::PI);
+    // Light intensity of pi makes lambertian white surface lit head on exactly white.
+    return (diffuse + specular) * std::f32::consts::PI * n_dot_l;
+}
+
+/// Light of the environment, reflected to the camera by the fragment, by the split-sum approximation -
+/// diffuse part from the irradiance map and specular part from the prefiltered map, sampled along the
+/// reflected view direction, scaled by the BRDF table. Normal is in the camera frame, maps are looked up in
+/// the model coordinates.
+fn get_ibl_radiance(
+    ibl: &ImageBasedLighting,
+    uniforms: &Uniforms,
+    t_normal: Vector3<f32>,
+    albedo: Vector3<f32>,
+    metallic: f32,
+    roughness: f32,
+) -> Vector3<f32> {
+    let to_model = |t_direction: Vector3<f32>| {
+        return Vector3::from_homogeneous(
+            uniforms.m_matrix.transpose() * t_direction.to_homogeneous(),
+        )
+        .unwrap();
+    };
+    let n_dot_v = t_normal.z.max(1e-4);
+    let t_reflected_direction = 2.0 * t_normal.z * t_normal - vector![0.0, 0.0, 1.0];
+    let f_0 = vector![0.04, 0.04, 0.04] * (1.0 - metallic) + albedo * metallic;
+    // Schlick fresnel, rising less at grazing angles for rough surfaces, which reflect over the whole lobe.
+    let fresnel = f_0 + (f_0.map(|c| c.max(1.0 - roughness)) - f_0) * (1.0 - n_dot_v).powi(5);
+    let irradiance = ibl.sample_irradiance(to_model(t_normal)) / 255.0;
+    let diffuse = (vector![1.0, 1.0, 1.0] - fresnel)
+        .component_mul(&albedo)
+        .component_mul(&irradiance)
+        .scale(1.0 - metallic);
+    let brdf = ibl.get_brdf(n_dot_v, roughness);
+    let prefiltered = ibl.sample_specular(to_model(t_reflected_direction), roughness) / 255.0;
+    let specular = prefiltered.component_mul(&(f_0 * brdf.x).add_scalar(brdf.y));
+    return diffuse + specular;
+}
+
+/// Physically based shading with metallic-roughness material, where normals come from the normal map.
+fn get_pbr_pipeline_passes() -> Vec<ShaderPass> {
+    let mut passes = Vec::<ShaderPass>::new();
+
+    fn vertex_pass_1(
+        uniforms: &Uniforms,
+        varyings: &mut Varyings,
+        model: &Model,
+        pos_indices: Vector3<usize>,
+        tex_indices: Vector3<usize>,
+        normal_indices: Vector3<usize>,
+    ) -> bool {
+        let vertex_positions = get_vertex_positions(model, pos_indices);
+        if should_cull_face(vertex_positions, uniforms) {
+            return false;
+        }
+
+        store_vertex_transformation_results(
+            vertex_positions,
+            uniforms.vpmv_matrix,
+            &mut varyings.vertex_t_raster,
+            &mut varyings.vertex_z_values,
+        );
+        store_vertex_uvs(&mut varyings.vertex_uvs, &model.obj.tex_coords, tex_indices);
+        varyings.translucent = is_polygon_translucent(uniforms, model, varyings.vertex_uvs);
+        store_vertex_t_positions(
+            &mut varyings.vertex_t_positions,
+            uniforms.m_matrix,
+            vertex_positions,
+        );
+
+        store_fallback_vertex_t_normals(varyings, uniforms, model, pos_indices, normal_indices);
+
+        return true;
+    }
+
+    fn fragment_pass_1(
+        uniforms: &Uniforms,
+        varyings: &Varyings,
+        targets: &mut Targets,
+        model: &Model,
+        coord: Vector2<u32>,
+        bar_coord: Vector3<f32>,
+    ) -> Option<Vector3<f32>> {
+        let uv = get_fragment_tex_coord(varyings, bar_coord);
+        if !process_alpha_value(uniforms, targets, model, uv) {
+            return None;
+        }
+        if !process_z_value(targets, varyings.vertex_z_values, bar_coord, coord) {
+            return None;
+        }
+        let albedo = get_fragment_color(model, varyings, uv, bar_coord) / 255.0;
+        let metallic = model.get_metallic_value_at_uv(uv);
+        // Clamping roughness from below, since perfectly smooth surface turns the highlight into a single point.
+        let roughness = model.get_roughness_value_at_uv(uv).max(0.05);
+        let ao = model.get_ao_value_at_uv(uv);
+        let t_fragment_normal = get_fragment_t_normal(uniforms, varyings, model, uv, bar_coord);
+
+        // In the new camera frame direction to the camera is always [0.0, 0.0, 1.0].
+        let radiance = get_cook_torrance_radiance(
+            t_fragment_normal,
+            uniforms.t_light_direction,
+            vector![0.0, 0.0, 1.0],
+            albedo,
+            metallic,
+            roughness,
+        );
+        let t_fragment_position = varyings.vertex_t_positions * bar_coord;
+        let mut radiance = radiance * uniforms.light_intensity;
+        for (index, light) in uniforms.t_lights.iter().enumerate() {
+            if !is_lit_by_light(uniforms, index, t_fragment_position) {
+                continue;
+            }
+            let (direction, light_radiance) = light.get_incident_light(t_fragment_position);
+            radiance += get_cook_torrance_radiance(
+                t_fragment_normal,
+                direction,
+                vector![0.0, 0.0, 1.0],
+                albedo,
+                metallic,
+                roughness,
+            )
+            .component_mul(&light_radiance);
+        }
+        let ambient = match &uniforms.ibl {
+            Some(ibl) => {
+                get_ibl_radiance(
+                    ibl,
+                    uniforms,
+                    t_fragment_normal,
+                    albedo,
+                    metallic,
+                    roughness,
+                ) * ao
+            }
+            None => albedo * 0.03 * ao,
+        };
+        return Some((radiance + ambient) * 255.0);
+    }
+
+    passes.push(ShaderPass {
+        prepare: Box::new(default_prepare),
+        vertex: Box::new(vertex_pass_1),
+        fragment: Box::new(fragment_pass_1),
+        depth_target: DepthTarget::ZBuffer,
+        stencil: StencilState::default(),
+    });
+
+    return passes;
+}
+
+/// Number of discrete lighting levels in the toon pipeline.
+const TOON_BANDS: f32 = 4.0;
+
+/// Distance, by which the outline hull is pushed out along the vertex normals in model space.
+const TOON_OUTLINE_WIDTH: f32 = 0.008;
+
+/// Cel shading with lighting quantized into bands, outlined by drawing back faces of the model, inflated
+/// along the vertex normals, in the first pass.
+fn get_toon_pipeline_passes() -> Vec<ShaderPass> {
+    let mut passes = Vec::<ShaderPass>::new();
+
+    fn vertex_pass_1(
+        uniforms: &Uniforms,
+        varyings: &mut Varyings,
+        model: &Model,
+        pos_indices: Vector3<usize>,
+        _tex_indices: Vector3<usize>,
+        normal_indices: Vector3<usize>,
+    ) -> bool {
+        let mut vertex_positions = get_vertex_positions(model, pos_indices);
+        // Only back faces of the hull are drawn, so that it shows up just around the silhouette.
+        if is_front_facing(vertex_positions, uniforms) {
+            return false;
+        }
+
+        for i in 0..3 {
+            let vertex_normal = model.get_vertex_normal_at_index(normal_indices[i]);
+            vertex_positions[i] += vertex_normal.normalize() * TOON_OUTLINE_WIDTH;
+        }
+
+        store_vertex_transformation_results(
+            vertex_positions,
+            uniforms.vpmv_matrix,
+            &mut varyings.vertex_t_raster,
+            &mut varyings.vertex_z_values,
+        );
+
+        return true;
+    }
+
+    fn fragment_pass_1(
+        _uniforms: &Uniforms,
+        varyings: &Varyings,
+        targets: &mut Targets,
+        _model: &Model,
+        coord: Vector2<u32>,
+        bar_coord: Vector3<f32>,
+    ) -> Option<Vector3<f32>> {
+        if !process_z_value(targets, varyings.vertex_z_values, bar_coord, coord) {
+            return None;
+        }
+        return Some(Vector3::zeros());
+    }
+
+    fn vertex_pass_2(
+        uniforms: &Uniforms,
+        varyings: &mut Varyings,
+        model: &Model,
+        pos_indices: Vector3<usize>,
+        tex_indices: Vector3<usize>,
+        normal_indices: Vector3<usize>,
+    ) -> bool {
+        let vertex_positions = get_vertex_positions(model, pos_indices);
+        if should_cull_face(vertex_positions, uniforms) {
+            return false;
+        }
+
+        // Calculating light intensities at each vertex to then interpolate them in fragment shader.
+        let vertex_normals = model.get_polygon_normals(pos_indices, normal_indices);
+        for i in 0..3 {
+            let vertex_normal = vertex_normals[i];
+            let vertex_t_normal =
+                Vector3::from_homogeneous(uniforms.it_m_matrix * vertex_normal.to_homogeneous())
+                    .unwrap()
+                    .normalize();
+            varyings.vertex_intensities[i] = uniforms.t_light_direction.dot(&vertex_t_normal);
+        }
+
+        store_vertex_transformation_results(
+            vertex_positions,
+            uniforms.vpmv_matrix,
+            &mut varyings.vertex_t_raster,
+            &mut varyings.vertex_z_values,
+        );
+        store_vertex_uvs(&mut varyings.vertex_uvs, &model.obj.tex_coords, tex_indices);
+        varyings.translucent = is_polygon_translucent(uniforms, model, varyings.vertex_uvs);
+        store_vertex_t_positions(
+            &mut varyings.vertex_t_positions,
+            uniforms.m_matrix,
+            vertex_positions,
+        );
+        store_vertex_t_normals(
+            &mut varyings.vertex_t_normals,
+            uniforms.it_m_matrix,
+            model,
+            pos_indices,
+            normal_indices,
+        );
+
+        return true;
+    }
+
+    fn fragment_pass_2(
+        uniforms: &Uniforms,
+        varyings: &Varyings,
+        targets: &mut Targets,
+        model: &Model,
+        coord: Vector2<u32>,
+        bar_coord: Vector3<f32>,
+    ) -> Option<Vector3<f32>> {
+        let uv = get_fragment_tex_coord(varyings, bar_coord);
+        if !process_alpha_value(uniforms, targets, model, uv) {
+            return None;
+        }
+        if !process_z_value(targets, varyings.vertex_z_values, bar_coord, coord) {
+            return None;
+        }
+        let color = get_fragment_color(model, varyings, uv, bar_coord);
+        // Snapping intensity to the top of its band, leaving some ambient light in the darkest one.
+        let diff_coef = bar_coord.dot(&varyings.vertex_intensities).clamp(0.0, 1.0);
+        let band = (diff_coef * TOON_BANDS).ceil().max(1.0);
+        let toon_coef = 0.15 + 0.85 * band / TOON_BANDS;
+        let lights_diffuse = get_lights_diffuse(
+            uniforms,
+            varyings.vertex_t_positions * bar_coord,
+            (varyings.vertex_t_normals * bar_coord).normalize(),
+        );
+        return Some(apply_material(
+            &get_material(uniforms, targets),
+            color,
+            toon_coef * uniforms.light_intensity,
+            lights_diffuse,
+        ));
+    }
+
+    passes.push(ShaderPass {
+        prepare: Box::new(default_prepare),
+        vertex: Box::new(vertex_pass_1),
+        fragment: Box::new(fragment_pass_1),
+        depth_target: DepthTarget::ZBuffer,
+        stencil: StencilState::default(),
+    });
+    passes.push(ShaderPass {
+        prepare: Box::new(default_prepare),
+        vertex: Box::new(vertex_pass_2),
+        fragment: Box::new(fragment_pass_2),
+        depth_target: DepthTarget::ZBuffer,
+        stencil: StencilState::default(),
+    });
+
+    return passes;
+}
+
+/// Stencil value, marking the pixels covered by the selected model.
+const OUTLINE_STENCIL: u8 = 1;
+
+/// Distance, by which the selection hull is pushed out along the vertex normals in model space.
+const OUTLINE_WIDTH: f32 = 0.012;
+
+/// Color of the selection outline.
+const OUTLINE_COLOR: Vector3<f32> = Vector3::new(255.0, 160.0, 0.0);
+
+/// Phong shading of the model as the selected object - the shading pass marks every pixel it draws in the
+/// stencil buffer, then front faces of the model, inflated along the vertex normals, are drawn in a flat
+/// color only where the stencil isn't marked, leaving an outline around the silhouette.
+fn get_outline_pipeline_passes() -> Vec<ShaderPass> {
+    let mut passes = get_phong_pipeline_passes();
+    for pass in passes.iter_mut() {
+        pass.stencil.write = Some(OUTLINE_STENCIL);
+    }
+
+    fn vertex_pass_2(
+        uniforms: &Uniforms,
+        varyings: &mut Varyings,
+        model: &Model,
+        pos_indices: Vector3<usize>,
+        _tex_indices: Vector3<usize>,
+        normal_indices: Vector3<usize>,
+    ) -> bool {
+        let mut vertex_positions = get_vertex_positions(model, pos_indices);
+        if should_cull_face(vertex_positions, uniforms) {
+            return false;
+        }
+
+        for i in 0..3 {
+            let vertex_normal = model.get_vertex_normal_at_index(normal_indices[i]);
+            vertex_positions[i] += vertex_normal.normalize() * OUTLINE_WIDTH;
+        }
+
+        store_vertex_transformation_results(
+            vertex_positions,
+            uniforms.vpmv_matrix,
+            &mut varyings.vertex_t_raster,
+            &mut varyings.vertex_z_values,
+        );
+
+        return true;
+    }
+
+    // Stencil test has already kept the hull off the model, so every fragment, which gets here, is outline.
+    fn fragment_pass_2(
+        _uniforms: &Uniforms,
+        _varyings: &Varyings,
+        _targets: &mut Targets,
+        _model: &Model,
+        _coord: Vector2<u32>,
+        _bar_coord: Vector3<f32>,
+    ) -> Option<Vector3<f32>> {
+        return Some(OUTLINE_COLOR);
+    }
+
+    passes.push(ShaderPass {
+        prepare: Box::new(default_prepare),
+        vertex: Box::new(vertex_pass_2),
+        fragment: Box::new(fragment_pass_2),
+        depth_target: DepthTarget::ZBuffer,
+        stencil: StencilState {
+            test: StencilTest::NotEqual(OUTLINE_STENCIL),
+            write: None,
+        },
     });
 
     return passes;
 }
+
+/// Normal mapped diffuse shading, mixed with the environment reflected along the view direction, where
+/// specular map controls how shiny the surface is.
+fn get_reflection_pipeline_passes() -> Vec<ShaderPass> {
+    let mut passes = Vec::<ShaderPass>::new();
+
+    fn vertex_pass_1(
+        uniforms: &Uniforms,
+        varyings: &mut Varyings,
+        model: &Model,
+        pos_indices: Vector3<usize>,
+        tex_indices: Vector3<usize>,
+        normal_indices: Vector3<usize>,
+    ) -> bool {
+        let vertex_positions = get_vertex_positions(model, pos_indices);
+        if should_cull_face(vertex_positions, uniforms) {
+            return false;
+        }
+
+        store_vertex_transformation_results(
+            vertex_positions,
+            uniforms.vpmv_matrix,
+            &mut varyings.vertex_t_raster,
+            &mut varyings.vertex_z_values,
+        );
+        store_vertex_uvs(&mut varyings.vertex_uvs, &model.obj.tex_coords, tex_indices);
+        varyings.translucent = is_polygon_translucent(uniforms, model, varyings.vertex_uvs);
+        store_vertex_t_positions(
+            &mut varyings.vertex_t_positions,
+            uniforms.m_matrix,
+            vertex_positions,
+        );
+
+        store_fallback_vertex_t_normals(varyings, uniforms, model, pos_indices, normal_indices);
+
+        return true;
+    }
+
+    fn fragment_pass_1(
+        uniforms: &Uniforms,
+        varyings: &Varyings,
+        targets: &mut Targets,
+        model: &Model,
+        coord: Vector2<u32>,
+        bar_coord: Vector3<f32>,
+    ) -> Option<Vector3<f32>> {
+        let uv = get_fragment_tex_coord(varyings, bar_coord);
+        if !process_alpha_value(uniforms, targets, model, uv) {
+            return None;
+        }
+        if !process_z_value(targets, varyings.vertex_z_values, bar_coord, coord) {
+            return None;
+        }
+        let color = get_fragment_color(model, varyings, uv, bar_coord);
+        let t_fragment_normal = get_fragment_t_normal(uniforms, varyings, model, uv, bar_coord);
+        let diff_coef = uniforms.t_light_direction.dot(&t_fragment_normal);
+        let lights_diffuse = get_lights_diffuse(
+            uniforms,
+            varyings.vertex_t_positions * bar_coord,
+            t_fragment_normal,
+        );
+        let diffuse_color = apply_material(
+            &get_material(uniforms, targets),
+            color,
+            diff_coef * uniforms.light_intensity,
+            lights_diffuse,
+        );
+
+        let environment = match &uniforms.environment {
+            Some(environment) => environment,
+            None => return Some(diffuse_color),
+        };
+        // In the new camera frame direction to the camera is always [0.0, 0.0, 1.0], reflecting the opposite
+        // direction and rotating the result back to the model coordinates for the lookup.
+        let t_reflected_direction =
+            2.0 * t_fragment_normal.z * t_fragment_normal - vector![0.0, 0.0, 1.0];
+        let reflected_direction = Vector3::from_homogeneous(
+            uniforms.m_matrix.transpose() * t_reflected_direction.to_homogeneous(),
+        )
+        .unwrap();
+        let reflected_color = environment.sample_direction(reflected_direction);
+        let reflectivity = 0.15 + 0.85 * model.get_specular_value_at_uv(uv) / 255.0;
+        return Some(color_blend(reflected_color, diffuse_color, reflectivity));
+    }
+
+    passes.push(ShaderPass {
+        prepare: Box::new(default_prepare),
+        vertex: Box::new(vertex_pass_1),
+        fragment: Box::new(fragment_pass_1),
+        depth_target: DepthTarget::ZBuffer,
+        stencil: StencilState::default(),
+    });
+
+    return passes;
+}
+
+/// Vertex shader of the debug views, storing only what's needed to show normals and uvs.
+fn debug_vertex(
+    uniforms: &Uniforms,
+    varyings: &mut Varyings,
+    model: &Model,
+    pos_indices: Vector3<usize>,
+    tex_indices: Vector3<usize>,
+    normal_indices: Vector3<usize>,
+) -> bool {
+    let vertex_positions = get_vertex_positions(model, pos_indices);
+    if should_cull_face(vertex_positions, uniforms) {
+        return false;
+    }
+
+    store_vertex_transformation_results(
+        vertex_positions,
+        uniforms.vpmv_matrix,
+        &mut varyings.vertex_t_raster,
+        &mut varyings.vertex_z_values,
+    );
+    store_vertex_uvs(&mut varyings.vertex_uvs, &model.obj.tex_coords, tex_indices);
+    store_vertex_t_normals(
+        &mut varyings.vertex_t_normals,
+        uniforms.it_m_matrix,
+        model,
+        pos_indices,
+        normal_indices,
+    );
+
+    return true;
+}
+
+/// Showing interpolated world space normals, mapping each axis from [-1, 1] to [0, 255].
+fn get_debug_normals_pipeline_passes() -> Vec<ShaderPass> {
+    fn fragment_pass_1(
+        _uniforms: &Uniforms,
+        varyings: &Varyings,
+        targets: &mut Targets,
+        _model: &Model,
+        coord: Vector2<u32>,
+        bar_coord: Vector3<f32>,
+    ) -> Option<Vector3<f32>> {
+        if !process_z_value(targets, varyings.vertex_z_values, bar_coord, coord) {
+            return None;
+        }
+        let normal = (varyings.vertex_t_normals * bar_coord).normalize();
+        return Some((normal * 0.5).add_scalar(0.5) * 255.0);
+    }
+
+    return vec![ShaderPass {
+        prepare: Box::new(default_prepare),
+        vertex: Box::new(debug_vertex),
+        fragment: Box::new(fragment_pass_1),
+        depth_target: DepthTarget::ZBuffer,
+        stencil: StencilState::default(),
+    }];
+}
+
+/// Showing texture coordinates, wrapped into [0, 1], as red and green.
+fn get_debug_uvs_pipeline_passes() -> Vec<ShaderPass> {
+    fn fragment_pass_1(
+        _uniforms: &Uniforms,
+        varyings: &Varyings,
+        targets: &mut Targets,
+        _model: &Model,
+        coord: Vector2<u32>,
+        bar_coord: Vector3<f32>,
+    ) -> Option<Vector3<f32>> {
+        if !process_z_value(targets, varyings.vertex_z_values, bar_coord, coord) {
+            return None;
+        }
+        let uv = varyings.vertex_uvs * bar_coord;
+        return Some(vector![uv.x.rem_euclid(1.0), uv.y.rem_euclid(1.0), 0.0] * 255.0);
+    }
+
+    return vec![ShaderPass {
+        prepare: Box::new(default_prepare),
+        vertex: Box::new(debug_vertex),
+        fragment: Box::new(fragment_pass_1),
+        depth_target: DepthTarget::ZBuffer,
+        stencil: StencilState::default(),
+    }];
+}
+
+/// Geometry pass of the deferred path, storing surface attributes of the specular pipeline into the G-buffer
+/// instead of shading. Every polygon is opaque here, since G-buffer holds a single surface per pixel.
+fn get_deferred_geometry_pipeline_passes() -> Vec<ShaderPass> {
+    fn vertex_pass_1(
+        uniforms: &Uniforms,
+        varyings: &mut Varyings,
+        model: &Model,
+        pos_indices: Vector3<usize>,
+        tex_indices: Vector3<usize>,
+        normal_indices: Vector3<usize>,
+    ) -> bool {
+        let vertex_positions = get_vertex_positions(model, pos_indices);
+        if should_cull_face(vertex_positions, uniforms) {
+            return false;
+        }
+
+        store_vertex_transformation_results(
+            vertex_positions,
+            uniforms.vpmv_matrix,
+            &mut varyings.vertex_t_raster,
+            &mut varyings.vertex_z_values,
+        );
+        store_vertex_uvs(&mut varyings.vertex_uvs, &model.obj.tex_coords, tex_indices);
+
+        store_fallback_vertex_t_normals(varyings, uniforms, model, pos_indices, normal_indices);
+
+        return true;
+    }
+
+    fn fragment_pass_1(
+        uniforms: &Uniforms,
+        varyings: &Varyings,
+        targets: &mut Targets,
+        model: &Model,
+        coord: Vector2<u32>,
+        bar_coord: Vector3<f32>,
+    ) -> Option<Vector3<f32>> {
+        let uv = get_fragment_tex_coord(varyings, bar_coord);
+        if !process_alpha_value(uniforms, targets, model, uv) {
+            return None;
+        }
+        if !process_z_value(targets, varyings.vertex_z_values, bar_coord, coord) {
+            return None;
+        }
+        let t_normal = get_fragment_t_normal(uniforms, varyings, model, uv, bar_coord);
+        let index = targets.depth_index(coord);
+        let material = get_material(uniforms, targets);
+        if let Some(gbuffer) = targets.gbuffer.as_mut() {
+            gbuffer[index] = GBufferTexel {
+                albedo: get_fragment_color(model, varyings, uv, bar_coord),
+                t_normal,
+                specular: material
+                    .shininess
+                    .unwrap_or_else(|| model.get_specular_value_at_uv(uv)),
+                material,
+            };
+        }
+        // Color is left to the lighting pass.
+        return None;
+    }
+
+    return vec![ShaderPass {
+        prepare: Box::new(default_prepare),
+        vertex: Box::new(vertex_pass_1),
+        fragment: Box::new(fragment_pass_1),
+        depth_target: DepthTarget::ZBuffer,
+        stencil: StencilState::default(),
+    }];
+}
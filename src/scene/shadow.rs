@@ -0,0 +1,492 @@
+use na::{Matrix2x3, Matrix4, Point3, Vector3};
+use nalgebra as na;
+use obj::raw::object::Polygon;
+use rayon::prelude::*;
+use tiny_renderer_core::raster::{get_triangle_bounding_box, rasterize_row, to_raster_coord};
+
+use super::frustum::BoundingSphere;
+use super::instance::{get_drawn_instances, Instance};
+use super::projection::{Projection, PROJECTION_DISTANCE};
+use super::util::Model;
+
+/// Depth of the near plane of the cube faces, which polygons are clipped against, since points closer to the
+/// light can't be projected onto the cube.
+const NEAR_DISTANCE: f32 = 1e-3;
+
+/// Forward and up directions of the cube faces in the usual +x, -x, +y, -y, +z, -z order.
+const FACE_BASES: [([f32; 3], [f32; 3]); 6] = [
+    ([1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+    ([-1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+    ([0.0, 1.0, 0.0], [0.0, 0.0, -1.0]),
+    ([0.0, -1.0, 0.0], [0.0, 0.0, 1.0]),
+    ([0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+    ([0.0, 0.0, -1.0], [0.0, 1.0, 0.0]),
+];
+
+/// Omnidirectional shadow map of a positional light, storing distance from the light to the closest surface
+/// in every direction as 6 square faces of a cube around the light. Everything is in model coordinates.
+pub struct CubeShadowMap {
+    resolution: u32,
+    light_position: Vector3<f32>,
+    faces: Vec<Vec<f32>>,
+}
+
+impl CubeShadowMap {
+    pub fn new(resolution: u32) -> Self {
+        return Self {
+            resolution,
+            light_position: Vector3::zeros(),
+            faces: vec![vec![f32::MAX; (resolution * resolution) as usize]; 6],
+        };
+    }
+
+    pub fn get_resolution(&self) -> u32 {
+        return self.resolution;
+    }
+
+    /// Right, up and forward directions of the face.
+    fn get_face_basis(face: usize) -> (Vector3<f32>, Vector3<f32>, Vector3<f32>) {
+        let (forward, up) = FACE_BASES[face];
+        let forward = Vector3::from(forward);
+        let up = Vector3::from(up);
+        return (forward.cross(&up), up, forward);
+    }
+
+    /// Continuous texel coordinates of the direction, projected onto the face, together with the depth along
+    /// the face forward direction.
+    fn project(&self, face: usize, direction: Vector3<f32>) -> (f32, f32, f32) {
+        let (right, up, forward) = Self::get_face_basis(face);
+        let depth = direction.dot(&forward);
+        let half = self.resolution as f32 / 2.0;
+        return (
+            (direction.dot(&right) / depth + 1.0) * half,
+            (direction.dot(&up) / depth + 1.0) * half,
+            depth,
+        );
+    }
+
+    /// Renders distances to the closest surfaces of every instance of the model around the light, faces are
+    /// rendered in parallel.
+    pub fn render(
+        &mut self,
+        model: &mut Model,
+        instances: &[Instance],
+        light_position: Vector3<f32>,
+    ) {
+        self.light_position = light_position;
+        let resolution = self.resolution;
+        let mut faces = vec![vec![f32::MAX; (resolution * resolution) as usize]; 6];
+        for instance in get_drawn_instances(instances) {
+            model.instance = instance;
+            let this = &*self;
+            let model = &*model;
+            faces
+                .par_iter_mut()
+                .enumerate()
+                .for_each(|(face, distances)| {
+                    for polygon in &model.obj.polygons {
+                        let indices = match polygon {
+                            Polygon::PTN(indices) => indices,
+                            _ => {
+                                panic!("Encountered some garbage, while looking through polygons.")
+                            }
+                        };
+                        this.rasterize_polygon(
+                            face,
+                            distances,
+                            [
+                                model.get_vertex_position_at_index(indices[0].0),
+                                model.get_vertex_position_at_index(indices[1].0),
+                                model.get_vertex_position_at_index(indices[2].0),
+                            ],
+                        );
+                    }
+                });
+        }
+        model.instance = None;
+        self.faces = faces;
+    }
+
+    /// Clips the triangle against the frustum of the face, so triangles, reaching behind the light, like a floor
+    /// under it, still cover the face up to its near plane, and rasterizes what is left as a fan. Side planes
+    /// lie a texel outside of the face, keeping raster coords of the clipped corners small.
+    fn rasterize_polygon(&self, face: usize, distances: &mut [f32], positions: [Point3<f32>; 3]) {
+        let (right, up, forward) = Self::get_face_basis(face);
+        let slope = 1.0 + 2.0 / self.resolution as f32;
+        let planes: [&dyn Fn(Vector3<f32>) -> f32; 5] = [
+            &|direction| direction.dot(&forward) - NEAR_DISTANCE,
+            &|direction| direction.dot(&forward) * slope - direction.dot(&right),
+            &|direction| direction.dot(&forward) * slope + direction.dot(&right),
+            &|direction| direction.dot(&forward) * slope - direction.dot(&up),
+            &|direction| direction.dot(&forward) * slope + direction.dot(&up),
+        ];
+        let mut corners: Vec<Vector3<f32>> = positions
+            .iter()
+            .map(|position| position.coords - self.light_position)
+            .collect();
+        // Sutherland-Hodgman clipping, keeping the part of the polygon, where the plane function isn't negative.
+        for plane in planes {
+            let n = corners.len();
+            let mut clipped = Vec::with_capacity(n + 1);
+            for i in 0..n {
+                let (a, b) = (corners[i], corners[(i + 1) % n]);
+                let (side_a, side_b) = (plane(a), plane(b));
+                if side_a >= 0.0 {
+                    clipped.push(a);
+                }
+                if (side_a >= 0.0) != (side_b >= 0.0) {
+                    clipped.push(a + (b - a) * (side_a / (side_a - side_b)));
+                }
+            }
+            corners = clipped;
+        }
+        for k in 1..corners.len().saturating_sub(1) {
+            self.rasterize_triangle(face, distances, [corners[0], corners[k], corners[k + 1]]);
+        }
+    }
+
+    /// Rasterizes the triangle, given by directions from the light, which are all in front of the near plane.
+    fn rasterize_triangle(
+        &self,
+        face: usize,
+        distances: &mut [f32],
+        directions: [Vector3<f32>; 3],
+    ) {
+        let mut coords = Matrix2x3::<i32>::zeros();
+        // Distance and inverse depth are divided by depth, so they can be interpolated linearly on the face.
+        let mut distance_over_depth = Vector3::zeros();
+        let mut inverse_depth = Vector3::zeros();
+        for i in 0..3 {
+            let (x, y, depth) = self.project(face, directions[i]);
+            coords.set_column(i, &to_raster_coord(x, y));
+            distance_over_depth[i] = directions[i].norm() / depth;
+            inverse_depth[i] = 1.0 / depth;
+        }
+
+        let size = self.resolution as i32;
+        let bbox = get_triangle_bounding_box(coords);
+        let x_min = bbox.ll.x.max(0);
+        let x_max = bbox.ur.x.min(size - 1);
+        for y in bbox.ll.y.max(0)..=bbox.ur.y.min(size - 1) {
+            rasterize_row(coords, y, x_min, x_max, |x, bar_coord| {
+                let distance = bar_coord.dot(&distance_over_depth) / bar_coord.dot(&inverse_depth);
+                let index = (x + y * size) as usize;
+                if distance < distances[index] {
+                    distances[index] = distance;
+                }
+            });
+        }
+    }
+
+    /// Whether the point is lit by the light, where bias is the distance the point can be behind the closest
+    /// surface, before it is considered to be shadowed.
+    pub fn is_lit(&self, point: Vector3<f32>, bias: f32) -> bool {
+        let direction = point - self.light_position;
+        // Face is picked by the major axis of the direction.
+        let abs = direction.abs();
+        let face = if abs.x >= abs.y && abs.x >= abs.z {
+            if direction.x > 0.0 {
+                0
+            } else {
+                1
+            }
+        } else if abs.y >= abs.z {
+            if direction.y > 0.0 {
+                2
+            } else {
+                3
+            }
+        } else if direction.z > 0.0 {
+            4
+        } else {
+            5
+        };
+        let (x, y, _) = self.project(face, direction);
+        let size = self.resolution as i32;
+        let x = (x as i32).clamp(0, size - 1);
+        let y = (y as i32).clamp(0, size - 1);
+        return direction.norm() - bias <= self.faces[face][(x + y * size) as usize];
+    }
+}
+
+/// Weight of the logarithmic split of the view depth range between the cascades, the rest being the uniform
+/// split. Logarithmic split keeps texels the same size on the screen, but starves the far cascades.
+const CASCADE_SPLIT_LAMBDA: f32 = 0.5;
+
+/// Fraction of every cascade at its far end, where its visibility is blended with the next cascade, so the
+/// change of resolution doesn't show as a seam.
+const CASCADE_BLEND: f32 = 0.1;
+
+/// Depth offset in texels of the cascade, fighting shadow acne, grown with the slope of the surface.
+const CASCADE_BIAS: f32 = 1.5;
+
+/// Camera, whose view frustum is covered by the cascades.
+pub struct CascadeView {
+    pub position: Vector3<f32>,
+    pub basis: (Vector3<f32>, Vector3<f32>, Vector3<f32>), // Right, up and back directions of the camera.
+    pub projection: Projection,
+    pub aspect: f32,
+}
+
+impl CascadeView {
+    /// Corners of the view frustum at the view depth in model coordinates.
+    fn get_corners(&self, depth: f32) -> [Vector3<f32>; 4] {
+        let (right, up, back) = self.basis;
+        return [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)].map(|(u, v)| {
+            let origin = self.projection.get_view_ray_origin(u, v);
+            let ray = self.projection.get_view_ray(u, v, self.aspect);
+            let view = origin + ray * ((-depth - origin.z) / ray.z);
+            return self.position + right * view.x + up * view.y + back * view.z;
+        });
+    }
+}
+
+/// Single cascade, storing height of the closest surface towards the light in a square orthographic map.
+struct Cascade {
+    matrix: Matrix4<f32>, // From model coordinates to texel x, y and height towards the light as z.
+    texel_size: f32,      // Side of a texel in model units.
+    heights: Vec<f32>,
+}
+
+/// Shadow maps of the main light, each one covering a slice of the view frustum along the view depth, so the
+/// cascades close to the camera have small texels, while the far ones still cover the rest of the scene.
+/// Everything is in model coordinates.
+pub struct CascadedShadowMap {
+    resolution: u32,
+    camera_position: Vector3<f32>,
+    camera_direction: Vector3<f32>, // Direction the camera looks along.
+    splits: Vec<f32>, // View depths of the borders between the cascades, including the near and far ones.
+    cascades: Vec<Cascade>,
+}
+
+impl CascadedShadowMap {
+    pub fn new(count: u32, resolution: u32) -> Self {
+        let cascades = (0..count)
+            .map(|_| Cascade {
+                matrix: Matrix4::identity(),
+                texel_size: 0.0,
+                heights: vec![f32::MIN; (resolution * resolution) as usize],
+            })
+            .collect();
+        return Self {
+            resolution,
+            camera_position: Vector3::zeros(),
+            camera_direction: Vector3::zeros(),
+            splits: Vec::new(),
+            cascades,
+        };
+    }
+
+    pub fn get_count(&self) -> u32 {
+        return self.cascades.len() as u32;
+    }
+
+    pub fn get_resolution(&self) -> u32 {
+        return self.resolution;
+    }
+
+    /// Splits the view depth range, where the model is, between the cascades, fits each cascade around its
+    /// slice of the view frustum and renders heights of every instance of the model into them in parallel.
+    /// Bounding sphere encloses all the instances, so casters outside of the view are still found.
+    pub fn render(
+        &mut self,
+        model: &mut Model,
+        instances: &[Instance],
+        bounding_sphere: &BoundingSphere,
+        light_direction: Vector3<f32>,
+        view: &CascadeView,
+    ) {
+        let light_direction = light_direction.normalize();
+        self.camera_position = view.position;
+        self.camera_direction = -view.basis.2;
+        let center_depth =
+            (bounding_sphere.center.coords - view.position).dot(&self.camera_direction);
+        let (mut near, mut far) = (
+            center_depth - bounding_sphere.radius,
+            center_depth + bounding_sphere.radius,
+        );
+        match view.projection.get_depth_range() {
+            Some((projection_near, projection_far)) => {
+                near = near.max(projection_near);
+                far = far.min(projection_far);
+            }
+            // Fixed projection sees everything in front of its center of projection behind the camera.
+            None => near = near.max(1e-3 - PROJECTION_DISTANCE),
+        }
+        far = far.max(near + f32::EPSILON);
+        // Logarithmic split needs positive depths, the fixed projection sees a bit behind the camera as well.
+        let log_near = near.max(far * 1e-3);
+        let count = self.cascades.len();
+        self.splits = (0..=count)
+            .map(|i| {
+                let fraction = i as f32 / count as f32;
+                let uniform = near + (far - near) * fraction;
+                let logarithmic = log_near * (far / log_near).powf(fraction);
+                return CASCADE_SPLIT_LAMBDA * logarithmic + (1.0 - CASCADE_SPLIT_LAMBDA) * uniform;
+            })
+            .collect();
+        self.splits[0] = near;
+        self.splits[count] = far;
+
+        // Light basis, with up being the y axis, unless the light is along it.
+        let up = match light_direction.y.abs() > 0.99 {
+            true => Vector3::z(),
+            false => Vector3::y(),
+        };
+        let light_right = up.cross(&light_direction).normalize();
+        let light_up = light_direction.cross(&light_right);
+        let resolution = self.resolution as f32;
+        for (i, cascade) in self.cascades.iter_mut().enumerate() {
+            let corners: Vec<Vector3<f32>> = [self.splits[i], self.splits[i + 1]]
+                .iter()
+                .flat_map(|&depth| view.get_corners(depth))
+                .collect();
+            let center = corners.iter().sum::<Vector3<f32>>() / corners.len() as f32;
+            let radius = corners
+                .iter()
+                .map(|corner| (corner - center).norm())
+                .fold(0.0, f32::max);
+            // Moving the cascade by whole texels only, so its shadows don't shimmer, as the camera moves.
+            cascade.texel_size = 2.0 * radius / resolution;
+            let snap = |value: f32| (value / cascade.texel_size).round() * cascade.texel_size;
+            let x_0 = snap(center.dot(&light_right)) - radius;
+            let y_0 = snap(center.dot(&light_up)) - radius;
+            let scale = 1.0 / cascade.texel_size;
+            cascade.matrix = Matrix4::new(
+                light_right.x * scale,
+                light_right.y * scale,
+                light_right.z * scale,
+                -x_0 * scale,
+                light_up.x * scale,
+                light_up.y * scale,
+                light_up.z * scale,
+                -y_0 * scale,
+                light_direction.x,
+                light_direction.y,
+                light_direction.z,
+                0.0,
+                0.0,
+                0.0,
+                0.0,
+                1.0,
+            );
+        }
+
+        let resolution = self.resolution;
+        let mut heights: Vec<Vec<f32>> = self
+            .cascades
+            .iter_mut()
+            .map(|cascade| std::mem::take(&mut cascade.heights))
+            .collect();
+        heights
+            .par_iter_mut()
+            .for_each(|heights| heights.fill(f32::MIN));
+        for instance in get_drawn_instances(instances) {
+            model.instance = instance;
+            let model = &*model;
+            let cascades = &self.cascades;
+            heights
+                .par_iter_mut()
+                .zip(cascades.par_iter())
+                .for_each(|(heights, cascade)| {
+                    for polygon in &model.obj.polygons {
+                        let indices = match polygon {
+                            Polygon::PTN(indices) => indices,
+                            _ => {
+                                panic!("Encountered some garbage, while looking through polygons.")
+                            }
+                        };
+                        cascade.rasterize_polygon(
+                            resolution,
+                            heights,
+                            [0, 1, 2].map(|k| model.get_vertex_position_at_index(indices[k].0)),
+                        );
+                    }
+                });
+        }
+        model.instance = None;
+        for (cascade, heights) in self.cascades.iter_mut().zip(heights) {
+            cascade.heights = heights;
+        }
+    }
+
+    /// Fraction of the light reaching the point, filtered over the texels within the radius, where diff_coef
+    /// is the cosine between the normal and the light direction. Points past the last cascade are lit.
+    pub fn get_visibility(&self, point: Vector3<f32>, diff_coef: f32, pcf_radius: u32) -> f32 {
+        let depth = (point - self.camera_position).dot(&self.camera_direction);
+        let count = self.cascades.len();
+        let index = match self.splits[1..].iter().position(|&split| depth <= split) {
+            Some(index) => index,
+            None => return 1.0,
+        };
+        let visibility =
+            self.cascades[index].get_visibility(self.resolution, point, diff_coef, pcf_radius);
+        // Fading into the next cascade towards the far end of this one.
+        let (start, end) = (self.splits[index], self.splits[index + 1]);
+        let blend_start = end - CASCADE_BLEND * (end - start);
+        if index + 1 == count || depth <= blend_start {
+            return visibility;
+        }
+        let next =
+            self.cascades[index + 1].get_visibility(self.resolution, point, diff_coef, pcf_radius);
+        let t = (depth - blend_start) / (end - blend_start);
+        return visibility * (1.0 - t) + next * t;
+    }
+}
+
+impl Cascade {
+    fn rasterize_polygon(&self, resolution: u32, heights: &mut [f32], positions: [Point3<f32>; 3]) {
+        let mut coords = Matrix2x3::<i32>::zeros();
+        let mut vertex_heights = Vector3::zeros();
+        for i in 0..3 {
+            let texel = self.matrix.transform_point(&positions[i]);
+            coords.set_column(i, &to_raster_coord(texel.x, texel.y));
+            vertex_heights[i] = texel.z;
+        }
+
+        let size = resolution as i32;
+        let bbox = get_triangle_bounding_box(coords);
+        if bbox.ur.x < 0 || bbox.ur.y < 0 || bbox.ll.x >= size || bbox.ll.y >= size {
+            return;
+        }
+        let x_min = bbox.ll.x.max(0);
+        let x_max = bbox.ur.x.min(size - 1);
+        for y in bbox.ll.y.max(0)..=bbox.ur.y.min(size - 1) {
+            rasterize_row(coords, y, x_min, x_max, |x, bar_coord| {
+                let height = bar_coord.dot(&vertex_heights);
+                let index = (x + y * size) as usize;
+                if height > heights[index] {
+                    heights[index] = height;
+                }
+            });
+        }
+    }
+
+    fn get_visibility(
+        &self,
+        resolution: u32,
+        point: Vector3<f32>,
+        diff_coef: f32,
+        pcf_radius: u32,
+    ) -> f32 {
+        let texel = self.matrix.transform_point(&Point3::from(point));
+        // Surfaces at grazing angles to the light need a larger offset, as in the shadow buffer lookup.
+        let cos = diff_coef.clamp(1e-3, 1.0);
+        let tan = ((1.0 - cos * cos).sqrt() / cos).min(10.0);
+        let bias = self.texel_size * CASCADE_BIAS * (1.0 + tan);
+
+        let size = resolution as i32;
+        let radius = pcf_radius as i32;
+        let mut lit = 0;
+        for dy in -radius..=radius {
+            for dx in -radius..=radius {
+                let x = (texel.x.round() as i32 + dx).clamp(0, size - 1);
+                let y = (texel.y.round() as i32 + dy).clamp(0, size - 1);
+                if texel.z + bias >= self.heights[(x + y * size) as usize] {
+                    lit += 1;
+                }
+            }
+        }
+        return lit as f32 / ((2 * radius + 1) * (2 * radius + 1)) as f32;
+    }
+}
@@ -0,0 +1,205 @@
+use na::{Matrix3, Matrix4, Point3, Quaternion, UnitQuaternion, Vector3};
+use nalgebra as na;
+
+/// Node of the glTF hierarchy, joints of the skeleton are nodes as well.
+#[derive(Clone, Debug)]
+pub struct Node {
+    pub parent: Option<usize>,
+    // Local transform, either as separate translation, rotation and scale, which animations act upon, or as a
+    // fixed matrix.
+    pub translation: Vector3<f32>,
+    pub rotation: UnitQuaternion<f32>,
+    pub scale: Vector3<f32>,
+    pub matrix: Option<Matrix4<f32>>,
+}
+
+impl Node {
+    fn get_local_transform(&self) -> Matrix4<f32> {
+        if let Some(matrix) = self.matrix {
+            return matrix;
+        }
+        return Matrix4::new_translation(&self.translation)
+            * self.rotation.to_homogeneous()
+            * Matrix4::new_nonuniform_scaling(&self.scale);
+    }
+}
+
+/// Transforms of every node from its own coordinates to the coordinates of the scene.
+pub fn get_global_transforms(nodes: &[Node]) -> Vec<Matrix4<f32>> {
+    // Parents don't necessarily precede their children, so transforms are resolved recursively.
+    fn resolve(
+        nodes: &[Node],
+        transforms: &mut [Option<Matrix4<f32>>],
+        node: usize,
+    ) -> Matrix4<f32> {
+        if let Some(transform) = transforms[node] {
+            return transform;
+        }
+        let local_transform = nodes[node].get_local_transform();
+        let transform = match nodes[node].parent {
+            Some(parent) => resolve(nodes, transforms, parent) * local_transform,
+            None => local_transform,
+        };
+        transforms[node] = Some(transform);
+        return transform;
+    }
+    let mut transforms = vec![None; nodes.len()];
+    return (0..nodes.len())
+        .map(|node| resolve(nodes, &mut transforms, node))
+        .collect();
+}
+
+/// Property of the node, driven by an animation channel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChannelTarget {
+    Translation,
+    Rotation,
+    Scale,
+}
+
+/// Interpolation between the keyframes of a channel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interpolation {
+    Step,
+    Linear,
+}
+
+/// Keyframes of a single property of a single node.
+#[derive(Clone, Debug)]
+pub struct Channel {
+    pub node: usize,
+    pub target: ChannelTarget,
+    pub interpolation: Interpolation,
+    pub times: Vec<f32>,
+    pub values: Vec<[f32; 4]>, // Vectors use first 3 components, rotations are quaternions as x, y, z, w.
+}
+
+impl Channel {
+    /// Value at the time, clamped to the first and the last keyframes.
+    fn sample(&self, time: f32) -> [f32; 4] {
+        let next = self
+            .times
+            .partition_point(|&keyframe_time| keyframe_time <= time);
+        if next == 0 {
+            return self.values[0];
+        }
+        if next == self.times.len() || self.interpolation == Interpolation::Step {
+            return self.values[next - 1];
+        }
+        let (from, to) = (self.values[next - 1], self.values[next]);
+        let t = (time - self.times[next - 1]) / (self.times[next] - self.times[next - 1]);
+        if self.target == ChannelTarget::Rotation {
+            let from = UnitQuaternion::from_quaternion(Quaternion::from(from));
+            let to = UnitQuaternion::from_quaternion(Quaternion::from(to));
+            return from.slerp(&to, t).into_inner().coords.into();
+        }
+        return [0, 1, 2, 3].map(|c| from[c] * (1.0 - t) + to[c] * t);
+    }
+}
+
+/// Skeleton, deforming the mesh, together with its animation. Joints are blended per vertex, so every vertex
+/// follows up to 4 joints with the given weights.
+#[derive(Clone, Debug)]
+pub struct Skin {
+    pub nodes: Vec<Node>,
+    pub joints: Vec<usize>, // Nodes, acting as joints, weights of the vertices refer to indices into it.
+    pub inverse_bind_matrices: Vec<Matrix4<f32>>,
+    // Joints and weights of every vertex, indexed the same as both positions and normals of the model.
+    pub vertex_joints: Vec<[usize; 4]>,
+    pub vertex_weights: Vec<[f32; 4]>,
+    pub channels: Vec<Channel>,
+    // Transform of every joint in the current pose from the bind pose, blended in the vertex stage.
+    joint_matrices: Vec<Matrix4<f32>>,
+}
+
+impl Skin {
+    pub fn new(
+        nodes: Vec<Node>,
+        joints: Vec<usize>,
+        inverse_bind_matrices: Vec<Matrix4<f32>>,
+        vertex_joints: Vec<[usize; 4]>,
+        vertex_weights: Vec<[f32; 4]>,
+        channels: Vec<Channel>,
+    ) -> Self {
+        let mut skin = Skin {
+            nodes,
+            joints,
+            inverse_bind_matrices,
+            vertex_joints,
+            vertex_weights,
+            channels,
+            joint_matrices: Vec::new(),
+        };
+        skin.pose(0.0);
+        return skin;
+    }
+
+    /// Length of the animation in seconds, 0 without one.
+    pub fn get_duration(&self) -> f32 {
+        return self
+            .channels
+            .iter()
+            .filter_map(|channel| channel.times.last())
+            .fold(0.0, |duration, &time| f32::max(duration, time));
+    }
+
+    /// Evaluates the animation at the time, looping it, and updates the joint matrices.
+    pub fn pose(&mut self, time: f32) {
+        let duration = self.get_duration();
+        let looped_time = if duration > 0.0 {
+            time.rem_euclid(duration)
+        } else {
+            0.0
+        };
+        for channel in &self.channels {
+            let value = channel.sample(looped_time);
+            let node = &mut self.nodes[channel.node];
+            match channel.target {
+                ChannelTarget::Translation => {
+                    node.translation = Vector3::new(value[0], value[1], value[2])
+                }
+                ChannelTarget::Rotation => {
+                    node.rotation = UnitQuaternion::from_quaternion(Quaternion::from(value))
+                }
+                ChannelTarget::Scale => node.scale = Vector3::new(value[0], value[1], value[2]),
+            }
+        }
+
+        let global_transforms = get_global_transforms(&self.nodes);
+        self.joint_matrices = self
+            .joints
+            .iter()
+            .zip(&self.inverse_bind_matrices)
+            .map(|(&joint, inverse_bind_matrix)| global_transforms[joint] * inverse_bind_matrix)
+            .collect();
+    }
+
+    /// Blend of the joint matrices of the vertex by its weights, vertices without weights stay in place.
+    fn get_skin_matrix(&self, index: usize) -> Matrix4<f32> {
+        if self.vertex_weights[index] == [0.0; 4] {
+            return Matrix4::identity();
+        }
+        let mut skin_matrix = Matrix4::zeros();
+        for k in 0..4 {
+            let weight = self.vertex_weights[index][k];
+            if weight != 0.0 {
+                skin_matrix += self.joint_matrices[self.vertex_joints[index][k]] * weight;
+            }
+        }
+        return skin_matrix;
+    }
+
+    pub fn transform_position(&self, index: usize, position: Point3<f32>) -> Point3<f32> {
+        return Point3::from_homogeneous(self.get_skin_matrix(index) * position.to_homogeneous())
+            .unwrap();
+    }
+
+    /// Normals follow the rotation and scale of the blended joints, assuming scaling is close to uniform.
+    pub fn transform_normal(&self, index: usize, normal: Vector3<f32>) -> Vector3<f32> {
+        let skin_matrix: Matrix3<f32> = self
+            .get_skin_matrix(index)
+            .fixed_slice::<3, 3>(0, 0)
+            .into_owned();
+        return (skin_matrix * normal).normalize();
+    }
+}
@@ -0,0 +1,154 @@
+use na::{vector, Vector3};
+use nalgebra as na;
+use rayon::prelude::*;
+
+/// Side of the square tile, over which kernel rotations repeat and which is blurred afterwards.
+const NOISE_SIZE: usize = 4;
+
+/// Parameters of the screen-space ambient occlusion.
+#[derive(Clone, Copy, Debug)]
+pub struct SsaoSettings {
+    pub radius: f32,  // Radius of the sampled hemisphere in pixels of the rendered frame.
+    pub samples: u32, // Number of depth buffer lookups per pixel.
+    pub strength: f32, // How dark fully occluded pixel becomes, in [0, 1].
+}
+
+impl Default for SsaoSettings {
+    fn default() -> Self {
+        return SsaoSettings {
+            radius: 16.0,
+            samples: 16,
+            strength: 0.8,
+        };
+    }
+}
+
+/// Points inside of the unit hemisphere around +z, spread with the golden angle and getting denser closer
+/// to the center, since close occluders matter more.
+fn get_hemisphere_kernel(samples: u32) -> Vec<Vector3<f32>> {
+    let golden_angle = std::f32::consts::PI * (3.0 - 5.0f32.sqrt());
+    return (0..samples)
+        .map(|i| {
+            let t = (i as f32 + 0.5) / samples as f32;
+            let z = 1.0 - t;
+            let r = (1.0 - z * z).sqrt();
+            let angle = golden_angle * i as f32;
+            let scale = 0.1 + 0.9 * t * t;
+            return vector![r * angle.cos(), r * angle.sin(), z] * scale;
+        })
+        .collect();
+}
+
+/// Darkens the frame buffer in creases and contact areas, estimated from the depth buffer only. Depth buffer
+/// holds depth_stride values per pixel, of which only the first one is used. Depth is scaled to pixel units
+/// with the same factor viewport uses for x, so the hemisphere is round in all three dimensions.
+pub fn apply_ssao(
+    settings: SsaoSettings,
+    frame_buffer: &mut [f32],
+    depth_buffer: &[f32],
+    depth_stride: usize,
+    width: usize,
+    height: usize,
+) {
+    let depth_scale = width as f32 / 255.0;
+    let get_depth = |x: i32, y: i32| -> Option<f32> {
+        if x < 0 || y < 0 || x >= width as i32 || y >= height as i32 {
+            return None;
+        }
+        let depth = depth_buffer[depth_stride * (x as usize + y as usize * width)];
+        if depth == f32::MIN {
+            return None;
+        }
+        return Some(depth * depth_scale);
+    };
+    let kernel = get_hemisphere_kernel(settings.samples);
+
+    let mut occlusion = vec![0.0f32; width * height];
+    occlusion
+        .par_chunks_mut(width)
+        .enumerate()
+        .for_each(|(y, row)| {
+            for x in 0..width {
+                let (xi, yi) = (x as i32, y as i32);
+                let depth = match get_depth(xi, yi) {
+                    Some(depth) => depth,
+                    None => continue,
+                };
+                // Normal is reconstructed from depth differences with neighbours, falling back to one-sided
+                // differences on the silhouette.
+                let slope = |before: Option<f32>, after: Option<f32>| match (before, after) {
+                    (Some(before), Some(after)) => (after - before) / 2.0,
+                    (None, Some(after)) => after - depth,
+                    (Some(before), None) => depth - before,
+                    (None, None) => 0.0,
+                };
+                let dz_dx = slope(get_depth(xi - 1, yi), get_depth(xi + 1, yi));
+                let dz_dy = slope(get_depth(xi, yi - 1), get_depth(xi, yi + 1));
+                let normal = vector![-dz_dx, -dz_dy, 1.0].normalize();
+
+                // Rotating the kernel per pixel of the noise tile trades banding for noise, removed by the blur.
+                let noise_index = (x % NOISE_SIZE) + (y % NOISE_SIZE) * NOISE_SIZE;
+                let angle = 2.0 * std::f32::consts::PI * noise_index as f32
+                    / (NOISE_SIZE * NOISE_SIZE) as f32;
+                let (sin, cos) = angle.sin_cos();
+
+                let position = vector![x as f32, y as f32, depth];
+                let mut occluded = 0;
+                for sample in &kernel {
+                    let mut offset = vector![
+                        sample.x * cos - sample.y * sin,
+                        sample.x * sin + sample.y * cos,
+                        sample.z
+                    ];
+                    // Flipping samples into the hemisphere around the normal.
+                    if offset.dot(&normal) < 0.0 {
+                        offset = -offset;
+                    }
+                    let point = position + offset * settings.radius;
+                    let occluder_depth =
+                        match get_depth(point.x.round() as i32, point.y.round() as i32) {
+                            Some(depth) => depth,
+                            None => continue,
+                        };
+                    // Larger depth is closer to the camera. Occluders much closer than the radius are
+                    // probably some other object in front, so they are ignored.
+                    if occluder_depth > point.z + 1.0 && occluder_depth - depth < settings.radius {
+                        occluded += 1;
+                    }
+                }
+                row[x] = occluded as f32 / settings.samples as f32;
+            }
+        });
+
+    frame_buffer
+        .par_chunks_mut(3 * width)
+        .enumerate()
+        .for_each(|(y, row)| {
+            for x in 0..width {
+                if depth_buffer[depth_stride * (x + y * width)] == f32::MIN {
+                    continue;
+                }
+                // Averaging over the noise tile, only taking covered pixels into account.
+                let mut sum = 0.0;
+                let mut count = 0;
+                for by in 0..NOISE_SIZE {
+                    for bx in 0..NOISE_SIZE {
+                        let sx = (x + bx).saturating_sub(NOISE_SIZE / 2).min(width - 1);
+                        let sy = (y + by).saturating_sub(NOISE_SIZE / 2).min(height - 1);
+                        if depth_buffer[depth_stride * (sx + sy * width)] == f32::MIN {
+                            continue;
+                        }
+                        sum += occlusion[sx + sy * width];
+                        count += 1;
+                    }
+                }
+                if count == 0 {
+                    continue;
+                }
+                let ambient = 1.0 - settings.strength * sum / count as f32;
+                for c in 0..3 {
+                    row[3 * x + c] *= ambient;
+                }
+            }
+        });
+}
@@ -0,0 +1,139 @@
+use std::ops::{Add, AddAssign};
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+
+use super::shader::DepthTarget;
+
+/// Counters of the work done by the last render, summed over all the passes of the pipeline, and time spent
+/// in its stages.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FrameStats {
+    pub polygons_submitted: usize,
+    // Polygons, rejected by the frustum test or by the vertex stage, e.g. facing away from the camera.
+    pub polygons_culled: usize,
+    pub polygons_rasterized: usize, // Polygons, overlapping the frame buffer after the vertex stage.
+    pub fragments_shaded: usize, // Fragment stage invocations, not counting early rejected fragments.
+    // Fragments, hidden behind the depth buffer before the fragment stage, and the ones, fragment stage
+    // didn't return a color for, e.g. failing its own depth test or the alpha test, or only writing depth.
+    pub fragments_depth_rejected: usize,
+    pub fragments_discarded: usize,
+    // Blocks of a polygon, hidden according to the hierarchical depth buffer and skipped without rasterizing.
+    pub hiz_blocks_culled: usize,
+    pub clear_time: Duration, // Clearing the buffers before the render.
+    // Passes into the shadow buffer and shadow maps of the positional lights, all of their stages.
+    pub shadow_time: Duration,
+    pub vertex_time: Duration, // Vertex stage and frustum tests of the passes, looking from the camera.
+    pub raster_time: Duration, // Setting up and binning polygons into bins.
+    // Rasterizing the tiles together with the fragment stage, which runs as pixels are covered, and shading
+    // of the whole frame, like the skybox and deferred lighting.
+    pub fragment_time: Duration,
+    pub post_time: Duration, // Fog, resolving the samples and post-processing passes.
+}
+
+impl FrameStats {
+    /// Adds time of the stages of a pass, pass into the shadow buffer counting as a whole.
+    pub(crate) fn add_pass_times(
+        &mut self,
+        depth_target: DepthTarget,
+        vertex_time: Duration,
+        raster_time: Duration,
+        fragment_time: Duration,
+    ) {
+        match depth_target {
+            DepthTarget::ShadowBuffer => {
+                self.shadow_time += vertex_time + raster_time + fragment_time
+            }
+            DepthTarget::ZBuffer => {
+                self.vertex_time += vertex_time;
+                self.raster_time += raster_time;
+                self.fragment_time += fragment_time;
+            }
+        }
+    }
+
+    /// Average over the frames of the stats, summed over them.
+    pub fn get_average(&self, n_frames: u32) -> FrameStats {
+        let n = n_frames.max(1);
+        return FrameStats {
+            polygons_submitted: self.polygons_submitted / n as usize,
+            polygons_culled: self.polygons_culled / n as usize,
+            polygons_rasterized: self.polygons_rasterized / n as usize,
+            fragments_shaded: self.fragments_shaded / n as usize,
+            fragments_depth_rejected: self.fragments_depth_rejected / n as usize,
+            fragments_discarded: self.fragments_discarded / n as usize,
+            hiz_blocks_culled: self.hiz_blocks_culled / n as usize,
+            clear_time: self.clear_time / n,
+            shadow_time: self.shadow_time / n,
+            vertex_time: self.vertex_time / n,
+            raster_time: self.raster_time / n,
+            fragment_time: self.fragment_time / n,
+            post_time: self.post_time / n,
+        };
+    }
+
+    /// Time of all the stages of the render and clearing before it.
+    pub fn get_total_time(&self) -> Duration {
+        return self.clear_time
+            + self.shadow_time
+            + self.vertex_time
+            + self.raster_time
+            + self.fragment_time
+            + self.post_time;
+    }
+}
+
+/// Measures time since it was started. There is no clock on WebAssembly without bindings to the browser, so
+/// stages take no time there.
+pub(crate) struct StageTimer {
+    #[cfg(not(target_arch = "wasm32"))]
+    begin: Instant,
+}
+
+impl StageTimer {
+    pub fn start() -> Self {
+        return Self {
+            #[cfg(not(target_arch = "wasm32"))]
+            begin: Instant::now(),
+        };
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn elapsed(&self) -> Duration {
+        return self.begin.elapsed();
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn elapsed(&self) -> Duration {
+        return Duration::ZERO;
+    }
+}
+
+impl Add for FrameStats {
+    type Output = FrameStats;
+
+    fn add(self, other: FrameStats) -> FrameStats {
+        return FrameStats {
+            polygons_submitted: self.polygons_submitted + other.polygons_submitted,
+            polygons_culled: self.polygons_culled + other.polygons_culled,
+            polygons_rasterized: self.polygons_rasterized + other.polygons_rasterized,
+            fragments_shaded: self.fragments_shaded + other.fragments_shaded,
+            fragments_depth_rejected: self.fragments_depth_rejected
+                + other.fragments_depth_rejected,
+            fragments_discarded: self.fragments_discarded + other.fragments_discarded,
+            hiz_blocks_culled: self.hiz_blocks_culled + other.hiz_blocks_culled,
+            clear_time: self.clear_time + other.clear_time,
+            shadow_time: self.shadow_time + other.shadow_time,
+            vertex_time: self.vertex_time + other.vertex_time,
+            raster_time: self.raster_time + other.raster_time,
+            fragment_time: self.fragment_time + other.fragment_time,
+            post_time: self.post_time + other.post_time,
+        };
+    }
+}
+
+impl AddAssign for FrameStats {
+    fn add_assign(&mut self, other: FrameStats) {
+        *self = *self + other;
+    }
+}
@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+
+use obj::raw::object::Polygon;
+use obj::raw::RawObj;
+
+use super::asset::new_raw_obj;
+use super::error::RendererError;
+
+/// Size of the header of binary files, followed by the number of triangles.
+const BINARY_HEADER_SIZE: usize = 80;
+/// Size of a triangle of binary files: normal, 3 corners and 2 bytes of attributes.
+const BINARY_TRIANGLE_SIZE: usize = 50;
+
+/// Loads the triangles from an ASCII or binary STL file, as used for 3D printing. Corners at the same position
+/// are merged into a single vertex, so smooth normals can be generated across triangles. Normals of the file are
+/// ignored and the polygons come without normals and uvs, see `generate_normals`.
+pub fn load_stl(path: &str) -> Result<RawObj, RendererError> {
+    if !Path::new(path).exists() {
+        return Err(RendererError::AssetNotFound(path.into()));
+    }
+    return read_stl(path).map_err(|error| RendererError::InvalidStl {
+        path: path.into(),
+        message: error.to_string(),
+    });
+}
+
+fn read_stl(path: &str) -> Result<RawObj, Box<dyn Error>> {
+    let bytes = std::fs::read(path)?;
+    // Binary files may start with "solid" too, so the size, matching the number of triangles, decides.
+    let binary_count = bytes
+        .get(BINARY_HEADER_SIZE..BINARY_HEADER_SIZE + 4)
+        .map(|count| u32::from_le_bytes(count.try_into().unwrap()) as usize);
+    let corners = match binary_count {
+        Some(count) if bytes.len() == BINARY_HEADER_SIZE + 4 + count * BINARY_TRIANGLE_SIZE => {
+            let mut corners = Vec::with_capacity(3 * count);
+            for triangle in bytes[BINARY_HEADER_SIZE + 4..].chunks_exact(BINARY_TRIANGLE_SIZE) {
+                // Normal comes first, the attributes last.
+                for corner in triangle[12..48].chunks_exact(12) {
+                    let value = |k: usize| {
+                        return f32::from_le_bytes(corner[4 * k..4 * k + 4].try_into().unwrap());
+                    };
+                    corners.push([value(0), value(1), value(2)]);
+                }
+            }
+            corners
+        }
+        _ => {
+            let text = std::str::from_utf8(&bytes)?;
+            if !text.trim_start().starts_with("solid") {
+                return Err("File isn't an STL file".into());
+            }
+            let mut corners = Vec::new();
+            let mut words = text.split_whitespace();
+            while let Some(word) = words.next() {
+                if word == "vertex" {
+                    let mut value = || -> Result<f32, Box<dyn Error>> {
+                        return Ok(words.next().ok_or("Truncated STL vertex")?.parse()?);
+                    };
+                    corners.push([value()?, value()?, value()?]);
+                }
+            }
+            if corners.len() % 3 != 0 {
+                return Err("STL facet without 3 vertices".into());
+            }
+            corners
+        }
+    };
+    if corners.is_empty() {
+        return Err("STL file has no triangles".into());
+    }
+
+    let mut obj = new_raw_obj();
+    let mut vertices: HashMap<[u32; 3], usize> = HashMap::new();
+    for triangle in corners.chunks_exact(3) {
+        let indices = triangle
+            .iter()
+            .map(|corner| {
+                // Adding zero turns negative zeros positive, so they are merged with positive ones.
+                let key = corner.map(|value| (value + 0.0).to_bits());
+                return *vertices.entry(key).or_insert_with(|| {
+                    obj.positions.push((corner[0], corner[1], corner[2], 1.0));
+                    return obj.positions.len() - 1;
+                });
+            })
+            .collect();
+        obj.polygons.push(Polygon::P(indices));
+    }
+    return Ok(obj);
+}
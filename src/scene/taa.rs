@@ -0,0 +1,142 @@
+use na::{vector, Vector2, Vector3};
+use nalgebra as na;
+use rayon::prelude::*;
+
+/// Number of distinct subpixel offsets, which the projection cycles through.
+const JITTER_PERIOD: u32 = 16;
+
+/// Parameters of the temporal anti-aliasing, accumulating frames with jittered projection into a history.
+#[derive(Clone, Copy, Debug)]
+pub struct TaaSettings {
+    // Weight of the new frame, blended over the reprojected history, while the camera moves.
+    pub blend: f32,
+    // Number of frames, which are averaged with equal weights, while the camera stays still. After that every
+    // new frame gets weight of 1 / max_frames, so lights and animation still show up.
+    pub max_frames: u32,
+}
+
+impl Default for TaaSettings {
+    fn default() -> Self {
+        return TaaSettings {
+            blend: 0.1,
+            max_frames: 64,
+        };
+    }
+}
+
+/// Element of the Halton low discrepancy sequence with the base.
+fn get_halton(index: u32, base: u32) -> f32 {
+    let mut result = 0.0;
+    let mut fraction = 1.0;
+    let mut index = index;
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    return result;
+}
+
+/// Subpixel offset of the frame in pixels in [-0.5, 0.5], cycling through the Halton (2, 3) sequence, which
+/// covers the pixel evenly.
+pub fn get_jitter(frame_index: u32) -> Vector2<f32> {
+    let index = frame_index % JITTER_PERIOD + 1;
+    return vector![get_halton(index, 2) - 0.5, get_halton(index, 3) - 0.5];
+}
+
+/// Frames, accumulated so far, and the number of them, while the camera stays still.
+#[derive(Default)]
+pub struct TaaHistory {
+    color: Vec<f32>,
+    frames: u32,
+}
+
+impl TaaHistory {
+    /// Forgets the accumulated frames, e.g. after a change of the resolution.
+    pub fn reset(&mut self) {
+        self.color.clear();
+        self.frames = 0;
+    }
+
+    /// Blends the frame into the history and writes the result back into the frame. While the camera is still,
+    /// frames are averaged, converging to the supersampled image. Once it moves, history is reprojected along
+    /// the velocity of every pixel and clamped to the colors around the pixel in the new frame, so whatever was
+    /// hidden or moved in the history doesn't leave a trail.
+    pub fn accumulate(
+        &mut self,
+        settings: TaaSettings,
+        frame_buffer: &mut [f32],
+        velocity_buffer: &[Vector2<f32>],
+        camera_moved: bool,
+        width: usize,
+        height: usize,
+    ) {
+        if self.color.len() != frame_buffer.len() {
+            self.color = frame_buffer.to_vec();
+            self.frames = 1;
+            return;
+        }
+        if !camera_moved {
+            self.frames = (self.frames + 1).min(settings.max_frames.max(1));
+            let weight = 1.0 / self.frames as f32;
+            self.color
+                .par_iter_mut()
+                .zip(frame_buffer.par_iter_mut())
+                .for_each(|(history, color)| {
+                    *history += (*color - *history) * weight;
+                    *color = *history;
+                });
+            return;
+        }
+
+        self.frames = 1;
+        let history = &self.color;
+        let get_color = |buffer: &[f32], x: usize, y: usize| -> Vector3<f32> {
+            let index = 3 * (x + y * width);
+            return vector![buffer[index], buffer[index + 1], buffer[index + 2]];
+        };
+        let mut blended = vec![0.0; frame_buffer.len()];
+        blended
+            .par_chunks_mut(3 * width)
+            .enumerate()
+            .for_each(|(y, row)| {
+                for x in 0..width {
+                    let color = get_color(frame_buffer, x, y);
+                    // Bilinear lookup of the history, where the pixel was in the previous frame.
+                    let previous = vector![x as f32, y as f32] - velocity_buffer[x + y * width];
+                    let output = &mut row[3 * x..3 * x + 3];
+                    if previous.x < 0.0
+                        || previous.y < 0.0
+                        || previous.x > (width - 1) as f32
+                        || previous.y > (height - 1) as f32
+                    {
+                        output.copy_from_slice(color.as_slice());
+                        continue;
+                    }
+                    let (x_0, y_0) = (previous.x.floor() as usize, previous.y.floor() as usize);
+                    let (x_1, y_1) = ((x_0 + 1).min(width - 1), (y_0 + 1).min(height - 1));
+                    let (t_x, t_y) = (previous.x.fract(), previous.y.fract());
+                    let bottom = get_color(history, x_0, y_0) * (1.0 - t_x)
+                        + get_color(history, x_1, y_0) * t_x;
+                    let top = get_color(history, x_0, y_1) * (1.0 - t_x)
+                        + get_color(history, x_1, y_1) * t_x;
+                    let history_color = bottom * (1.0 - t_y) + top * t_y;
+
+                    let mut min = color;
+                    let mut max = color;
+                    for ny in y.saturating_sub(1)..=(y + 1).min(height - 1) {
+                        for nx in x.saturating_sub(1)..=(x + 1).min(width - 1) {
+                            let neighbour = get_color(frame_buffer, nx, ny);
+                            min = min.inf(&neighbour);
+                            max = max.sup(&neighbour);
+                        }
+                    }
+                    let history_color = history_color.sup(&min).inf(&max);
+                    let result = history_color + (color - history_color) * settings.blend;
+                    output.copy_from_slice(result.as_slice());
+                }
+            });
+        frame_buffer.copy_from_slice(&blended);
+        self.color = blended;
+    }
+}
@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+
+use na::{point, Point3, Vector2, Vector3, Vector4};
+use nalgebra as na;
+use obj::raw::object::Polygon;
+use obj::raw::RawObj;
+
+/// Corner of a polygon as indices of its position, uv and normal. Tangents are shared by the corners with the
+/// same key, so they are split along uv seams and hard edges the same way as the rest of the attributes.
+pub type VertexKey = (usize, usize, usize);
+
+/// Generates tangent basis of every vertex from positions, uvs and normals of the polygons around it. Tangent
+/// points along increasing u, bitangent along increasing v. Tangents of the polygons are weighted by the angles
+/// of their corners at the vertex and made orthogonal to the vertex normal, with w being the handedness, so
+/// bitangent is w * normal x tangent and mirrored uvs work as well. Polygons may be other than the ones of the
+/// model, e.g. of a level of detail, sharing its vertices.
+pub fn generate_tangents(obj: &RawObj, polygons: &[Polygon]) -> HashMap<VertexKey, Vector4<f32>> {
+    let position = |index: usize| -> Point3<f32> {
+        let (x, y, z, _) = obj.positions[index];
+        return point![x, y, z];
+    };
+    let uv = |index: usize| -> Vector2<f32> {
+        let (u, v, _) = obj.tex_coords[index];
+        return Vector2::new(u, v);
+    };
+
+    // Sums of the angle weighted tangents and bitangents of the polygons around every vertex.
+    let mut sums: HashMap<VertexKey, (Vector3<f32>, Vector3<f32>)> = HashMap::new();
+    for polygon in polygons {
+        let indices = match polygon {
+            Polygon::PTN(indices) if indices.len() >= 3 => indices,
+            _ => continue,
+        };
+        let positions = [0, 1, 2].map(|k| position(indices[k].0));
+        let uvs = [0, 1, 2].map(|k| uv(indices[k].1));
+        let (edge_1, edge_2) = (positions[1] - positions[0], positions[2] - positions[0]);
+        let (delta_uv_1, delta_uv_2) = (uvs[1] - uvs[0], uvs[2] - uvs[0]);
+        let determinant = delta_uv_1.x * delta_uv_2.y - delta_uv_2.x * delta_uv_1.y;
+        // Polygons with degenerate uvs don't define any direction.
+        if determinant.abs() < f32::EPSILON {
+            continue;
+        }
+        let tangent = (edge_1 * delta_uv_2.y - edge_2 * delta_uv_1.y) / determinant;
+        let bitangent = (edge_2 * delta_uv_1.x - edge_1 * delta_uv_2.x) / determinant;
+        let (tangent, bitangent) = match (tangent.try_normalize(0.0), bitangent.try_normalize(0.0))
+        {
+            (Some(tangent), Some(bitangent)) => (tangent, bitangent),
+            _ => continue,
+        };
+        for k in 0..3 {
+            let to_next = positions[(k + 1) % 3] - positions[k];
+            let to_previous = positions[(k + 2) % 3] - positions[k];
+            let angle = to_next.angle(&to_previous);
+            let sum = sums
+                .entry(indices[k])
+                .or_insert((Vector3::zeros(), Vector3::zeros()));
+            sum.0 += tangent * angle;
+            sum.1 += bitangent * angle;
+        }
+    }
+
+    return sums
+        .into_iter()
+        .map(|(key, (tangent, bitangent))| {
+            let (x, y, z) = obj.normals[key.2];
+            let normal = Vector3::new(x, y, z).normalize();
+            // Gram-Schmidt, falling back to any direction orthogonal to the normal.
+            let axis = match normal.x.abs() < 0.9 {
+                true => Vector3::x(),
+                false => Vector3::y(),
+            };
+            let tangent = (tangent - normal * normal.dot(&tangent))
+                .try_normalize(f32::EPSILON)
+                .unwrap_or_else(|| normal.cross(&axis).normalize());
+            let handedness = match normal.cross(&tangent).dot(&bitangent) < 0.0 {
+                true => -1.0,
+                false => 1.0,
+            };
+            return (key, tangent.push(handedness));
+        })
+        .collect();
+}
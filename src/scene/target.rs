@@ -0,0 +1,119 @@
+use image::Rgb32FImage;
+use na::{matrix, vector, Matrix4, Point3, Vector2, Vector3};
+use nalgebra as na;
+
+use super::projection::Projection;
+use super::shader::get_camera_basis;
+use super::texture::{sample_float_image, Wrap};
+
+/// Camera, which a render target is rendered from, placed in model coordinates like the camera of the scene.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TargetView {
+    pub look_from: Vector3<f32>,
+    pub look_at: Vector3<f32>,
+    pub up: Vector3<f32>,
+    pub projection: Projection,
+}
+
+/// Color and depth buffers, which the scene renders the model into from the camera of the target before the
+/// frame, with the same pipeline and lights as the frame. Passes of the frame, and of the targets rendered
+/// after it, sample the colors from Uniforms::render_targets, e.g. for planar mirrors, water reflections or
+/// security-camera screens.
+pub struct RenderTarget {
+    pub view: TargetView,
+    width: u32,
+    height: u32,
+    // Rgb colors of the last render, where 255 is white and brighter values are allowed, with rows going from
+    // the bottom, like the frame buffer.
+    color: Rgb32FImage,
+    depth: Vec<f32>, // Depth values, larger is closer, f32::MIN where nothing was drawn.
+    vp_matrix: Matrix4<f32>, // Transform from model coordinates to clip space of the target camera.
+}
+
+impl RenderTarget {
+    pub fn new(width: u32, height: u32, view: TargetView) -> Self {
+        assert!(
+            width > 1 && height > 1,
+            "Render target should be at least 2x2 pixels!"
+        );
+        let mut target = Self {
+            view,
+            width,
+            height,
+            color: Rgb32FImage::new(width, height),
+            depth: vec![f32::MIN; (width * height) as usize],
+            vp_matrix: Matrix4::identity(),
+        };
+        target.update_matrix();
+        return target;
+    }
+
+    pub fn get_width(&self) -> u32 {
+        return self.width;
+    }
+
+    pub fn get_height(&self) -> u32 {
+        return self.height;
+    }
+
+    /// Following the camera of the target, which may have changed since the last render.
+    fn update_matrix(&mut self) {
+        let view = &self.view;
+        let (new_x, new_y, new_z) = get_camera_basis(view.look_from, view.look_at, view.up);
+        let model_matrix = matrix![new_x.x, new_x.y, new_x.z, 0.0;
+                                   new_y.x, new_y.y, new_y.z, 0.0;
+                                   new_z.x, new_z.y, new_z.z, 0.0;
+                                   0.0,     0.0,     0.0,     1.0];
+        let view_matrix = Matrix4::new_translation(&-view.look_from);
+        let aspect = self.width as f32 / self.height as f32;
+        self.vp_matrix = view.projection.get_matrix(aspect) * model_matrix * view_matrix;
+    }
+
+    /// Hands the color and depth buffers of the target over to the scene to render into, in the layout of its
+    /// frame and z-buffers.
+    pub(super) fn take_buffers(&mut self) -> (Vec<f32>, Vec<f32>) {
+        let color = std::mem::take(&mut self.color).into_raw();
+        return (color, std::mem::take(&mut self.depth));
+    }
+
+    /// Takes the buffers back after the render.
+    pub(super) fn set_buffers(&mut self, color: Vec<f32>, depth: Vec<f32>) {
+        self.color = Rgb32FImage::from_raw(self.width, self.height, color)
+            .expect("Color buffer should match the size of the render target!");
+        self.depth = depth;
+        self.update_matrix();
+    }
+
+    /// Color at uv of the target with bilinear filtering, where (0, 0) is the lower left corner.
+    pub fn sample(&self, uv: Vector2<f32>) -> Vector3<f32> {
+        return sample_float_image(&self.color, uv, Wrap::Clamp, |texel| texel);
+    }
+
+    /// Depth of the pixel, larger is closer, f32::MIN where nothing was drawn.
+    pub fn get_depth(&self, x: u32, y: u32) -> f32 {
+        return self.depth[(x + y * self.width) as usize];
+    }
+
+    /// Uv of the target, where its camera sees the position in model coordinates, or None, when the position
+    /// is behind the camera or out of its view. Projecting the fragments of a surface through a mirrored camera
+    /// gives the reflection, projecting them through the camera itself maps the image over the scene.
+    pub fn project(&self, position: Point3<f32>) -> Option<Vector2<f32>> {
+        let clip = self.vp_matrix * position.to_homogeneous();
+        if clip.w <= 0.0 {
+            return None;
+        }
+        let ndc = clip.xy() / clip.w;
+        if ndc.x.abs() > 1.0 || ndc.y.abs() > 1.0 {
+            return None;
+        }
+        // Pixels span [-1, 1] with their centers, as in the viewport transform of the scene.
+        let pixel = vector![
+            (ndc.x + 1.0) / 2.0 * (self.width - 1) as f32,
+            (ndc.y + 1.0) / 2.0 * (self.height - 1) as f32
+        ];
+        return Some(vector![
+            (pixel.x + 0.5) / self.width as f32,
+            (pixel.y + 0.5) / self.height as f32
+        ]);
+    }
+}
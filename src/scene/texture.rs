@@ -0,0 +1,634 @@
+use std::cmp::{max, min};
+use std::sync::OnceLock;
+
+use image::{Rgb, Rgb32FImage, RgbImage};
+use na::{vector, Vector2, Vector3};
+use nalgebra as na;
+use rayon::prelude::*;
+
+/// Way of reconstructing texture value between the texel centers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Filter {
+    #[default]
+    Nearest,
+    Bilinear,
+    // Bilinear lookups in the two closest mip levels, blended together.
+    Trilinear,
+    // Trilinear lookups along the longer axis of the footprint, keeping surfaces at grazing angles sharp.
+    Anisotropic,
+}
+
+impl Filter {
+    /// Parses filter from its name, returning None for unknown names.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "nearest" => Some(Filter::Nearest),
+            "bilinear" => Some(Filter::Bilinear),
+            "trilinear" => Some(Filter::Trilinear),
+            "anisotropic" => Some(Filter::Anisotropic),
+            _ => None,
+        }
+    }
+}
+
+/// Way of handling texel coordinates outside of the texture, e.g. when uv is outside of [0, 1].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Wrap {
+    #[default]
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+impl Wrap {
+    /// Parses wrap mode from its name, returning None for unknown names.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "repeat" => Some(Wrap::Repeat),
+            "clamp" => Some(Wrap::Clamp),
+            "mirror" => Some(Wrap::Mirror),
+            _ => None,
+        }
+    }
+
+    /// Maps arbitrary texel coordinate into [0, size).
+    fn apply(&self, coord: i32, size: u32) -> u32 {
+        let size = size as i32;
+        match self {
+            Wrap::Repeat => return coord.rem_euclid(size) as u32,
+            Wrap::Clamp => return coord.clamp(0, size - 1) as u32,
+            Wrap::Mirror => {
+                let period_coord = coord.rem_euclid(2 * size);
+                if period_coord < size {
+                    return period_coord as u32;
+                }
+                return (2 * size - 1 - period_coord) as u32;
+            }
+        }
+    }
+}
+
+/// Direction of the green channel of tangent space normal maps - OpenGL maps point it along increasing v,
+/// DirectX maps point it the other way, while Auto guesses it from the map itself.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum NormalMapConvention {
+    #[default]
+    OpenGl,
+    DirectX,
+    Auto,
+}
+
+impl NormalMapConvention {
+    /// Parses convention from its name, returning None for unknown names.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "opengl" => Some(NormalMapConvention::OpenGl),
+            "directx" => Some(NormalMapConvention::DirectX),
+            "auto" => Some(NormalMapConvention::Auto),
+            _ => None,
+        }
+    }
+
+    /// Whether green channel of the map has to be flipped to point along increasing v.
+    pub fn is_green_flipped(&self, image: &RgbImage) -> bool {
+        match self {
+            NormalMapConvention::OpenGl => return false,
+            NormalMapConvention::DirectX => return true,
+            NormalMapConvention::Auto => {
+                return detect_normal_map_convention(image) == NormalMapConvention::DirectX;
+            }
+        }
+    }
+}
+
+/// Guesses convention of a tangent space normal map, treating it as slopes of a height field. Height, summed up
+/// from the slopes around a closed loop, comes back to where it started, when the green channel is decoded in
+/// the right direction, so the convention with the smaller error over the loops wins, OpenGL for flat maps.
+/// Loops span 1/64 of the map, since fine detail of baked maps is often far from any height field.
+pub fn detect_normal_map_convention(image: &RgbImage) -> NormalMapConvention {
+    // Height slopes along u and v, None for normals too close to the surface to describe a height field.
+    let get_slopes = |x: u32, y: u32| -> Option<Vector2<f32>> {
+        let normal = image
+            .get_pixel(x, y)
+            .0
+            .map(|c| c as f32 / 255.0 * 2.0 - 1.0);
+        if normal[2] < 0.1 {
+            return None;
+        }
+        return Some(vector![-normal[0] / normal[2], -normal[1] / normal[2]]);
+    };
+    let loop_size = max(1, min(image.width(), image.height()) / 64);
+    let (mut error_opengl, mut error_directx) = (0.0, 0.0);
+    // Image rows go from the top, so the lower side of a loop is the bottom row of it.
+    for y in (loop_size..image.height()).step_by(loop_size as usize) {
+        'loops: for x in (0..image.width() - loop_size).step_by(loop_size as usize) {
+            // Height gained along u on the lower side over the upper one, and along v on the right side over
+            // the left one.
+            let (mut rise_u, mut rise_v) = (0.0, 0.0);
+            for t in 0..loop_size {
+                match (
+                    get_slopes(x + t, y),
+                    get_slopes(x + t, y - loop_size),
+                    get_slopes(x, y - t),
+                    get_slopes(x + loop_size, y - t),
+                ) {
+                    (Some(lower), Some(upper), Some(left), Some(right)) => {
+                        rise_u += lower.x - upper.x;
+                        rise_v += right.y - left.y;
+                    }
+                    _ => continue 'loops,
+                }
+            }
+            error_opengl += f32::abs(rise_u + rise_v);
+            error_directx += f32::abs(rise_u - rise_v);
+        }
+    }
+    if error_directx < error_opengl {
+        return NormalMapConvention::DirectX;
+    }
+    return NormalMapConvention::OpenGl;
+}
+
+/// Settings, describing how texture is looked up at uv.
+#[derive(Clone, Copy, Debug)]
+pub struct Sampler {
+    pub filter: Filter,
+    pub wrap: Wrap,
+    // Largest ratio of the longer to the shorter side of the footprint, which anisotropic filtering resolves
+    // with a lookup per unit of the ratio, blurring longer footprints beyond it.
+    pub max_anisotropy: u32,
+}
+
+impl Default for Sampler {
+    fn default() -> Self {
+        return Self {
+            filter: Filter::default(),
+            wrap: Wrap::default(),
+            max_anisotropy: 16,
+        };
+    }
+}
+
+/// Texture coordinates of a fragment together with their screen space derivatives, which describe the
+/// footprint of the fragment in the texture.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TexCoord {
+    pub uv: Vector2<f32>,
+    pub duv_dx: Vector2<f32>,
+    pub duv_dy: Vector2<f32>,
+}
+
+/// Converts 8-bit sRGB encoded value to linear value in [0, 255].
+pub fn decode_srgb(value: u8) -> f32 {
+    static TABLE: OnceLock<[f32; 256]> = OnceLock::new();
+    let table = TABLE.get_or_init(|| {
+        let mut table = [0.0; 256];
+        for i in 0..256 {
+            let c = i as f32 / 255.0;
+            table[i] = match c <= 0.04045 {
+                true => c / 12.92,
+                false => ((c + 0.055) / 1.055).powf(2.4),
+            } * 255.0;
+        }
+        return table;
+    });
+    return table[value as usize];
+}
+
+/// Converts linear value in [0, 1] to sRGB encoding.
+pub fn encode_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        return value * 12.92;
+    }
+    return 1.055 * value.powf(1.0 / 2.4) - 0.055;
+}
+
+/// Image together with its mip chain and the sampler, used to read it.
+pub struct Texture {
+    pub image: RgbImage,
+    pub sampler: Sampler,
+    // Whether texels are sRGB encoded colors, which are decoded to linear values, when sampled.
+    pub srgb: bool,
+    // Whether green channel of the tangent space normal map points against v and is flipped, when decoded.
+    pub flip_green: bool,
+    // Successively halved versions of the image down to 1x1, not including the image itself.
+    mips: Vec<RgbImage>,
+}
+
+impl Texture {
+    pub fn new(image: RgbImage) -> Self {
+        let mips = generate_mips(&image);
+        return Self {
+            image,
+            sampler: Sampler::default(),
+            srgb: false,
+            flip_green: false,
+            mips,
+        };
+    }
+
+    pub fn width(&self) -> u32 {
+        return self.image.width();
+    }
+
+    pub fn height(&self) -> u32 {
+        return self.image.height();
+    }
+
+    /// Image of the mip level, where level 0 is the original image.
+    fn level(&self, level: usize) -> &RgbImage {
+        match level {
+            0 => return &self.image,
+            _ => return &self.mips[level - 1],
+        }
+    }
+
+    /// Texel value of the mip level with channels in [0, 255], decoded to linear, if texture is sRGB.
+    fn texel(&self, level: usize, x: u32, y: u32) -> Vector3<f32> {
+        let pixel = self.level(level).get_pixel(x, y).0;
+        if self.srgb {
+            return vector![
+                decode_srgb(pixel[0]),
+                decode_srgb(pixel[1]),
+                decode_srgb(pixel[2])
+            ];
+        }
+        return vector![pixel[0] as f32, pixel[1] as f32, pixel[2] as f32];
+    }
+
+    /// Returns texture value at uv with channels in [0, 255], filtered according to the sampler.
+    pub fn sample(&self, tex_coord: TexCoord) -> Vector3<f32> {
+        match self.sampler.filter {
+            Filter::Nearest => return self.sample_nearest(tex_coord.uv),
+            Filter::Bilinear => return self.sample_bilinear(0, tex_coord.uv),
+            Filter::Trilinear => {
+                return self.sample_trilinear(self.get_lod(tex_coord), tex_coord.uv)
+            }
+            Filter::Anisotropic => return self.sample_anisotropic(tex_coord),
+        }
+    }
+
+    fn sample_nearest(&self, uv: Vector2<f32>) -> Vector3<f32> {
+        let wrap = self.sampler.wrap;
+        let coord = vector![
+            wrap.apply((uv.x * self.width() as f32).floor() as i32, self.width()),
+            wrap.apply((uv.y * self.height() as f32).floor() as i32, self.height())
+        ];
+        return self.texel(0, coord.x, coord.y);
+    }
+
+    /// Bilinear lookup in the full resolution image, whatever the filter is, for stages without a footprint
+    /// like the vertex stage.
+    pub fn sample_vertex(&self, uv: Vector2<f32>) -> Vector3<f32> {
+        return self.sample_bilinear(0, uv);
+    }
+
+    /// Blends 4 closest texels of the mip level, treating texel centers as being at half-integer coordinates.
+    fn sample_bilinear(&self, level: usize, uv: Vector2<f32>) -> Vector3<f32> {
+        let width = self.level(level).width();
+        let height = self.level(level).height();
+        let x = uv.x * width as f32 - 0.5;
+        let y = uv.y * height as f32 - 0.5;
+        let x_0 = x.floor();
+        let y_0 = y.floor();
+        let t_x = x - x_0;
+        let t_y = y - y_0;
+
+        // Neighbours past the border are handled according to the wrap mode.
+        let wrap = self.sampler.wrap;
+        let (x_0, x_1) = (
+            wrap.apply(x_0 as i32, width),
+            wrap.apply(x_0 as i32 + 1, width),
+        );
+        let (y_0, y_1) = (
+            wrap.apply(y_0 as i32, height),
+            wrap.apply(y_0 as i32 + 1, height),
+        );
+
+        let bottom = self.texel(level, x_0, y_0) * (1.0 - t_x) + self.texel(level, x_1, y_0) * t_x;
+        let top = self.texel(level, x_0, y_1) * (1.0 - t_x) + self.texel(level, x_1, y_1) * t_x;
+        return bottom * (1.0 - t_y) + top * t_y;
+    }
+
+    /// Level of detail from the longest side of the pixel footprint in texels, fractional part of which is
+    /// used for blending between the mip levels.
+    fn get_lod(&self, tex_coord: TexCoord) -> f32 {
+        let size = vector![self.width() as f32, self.height() as f32];
+        let footprint_x = tex_coord.duv_dx.component_mul(&size).norm();
+        let footprint_y = tex_coord.duv_dy.component_mul(&size).norm();
+        let max_lod = self.mips.len() as f32;
+        return footprint_x.max(footprint_y).max(1.0).log2().min(max_lod);
+    }
+
+    fn sample_trilinear(&self, lod: f32, uv: Vector2<f32>) -> Vector3<f32> {
+        let level = lod.floor() as usize;
+        let t = lod - level as f32;
+        let fine = self.sample_bilinear(level, uv);
+        if level == self.mips.len() || t == 0.0 {
+            return fine;
+        }
+        let coarse = self.sample_bilinear(level + 1, uv);
+        return fine * (1.0 - t) + coarse * t;
+    }
+
+    /// Averages trilinear lookups, spread evenly along the longer axis of the footprint, one per unit of its
+    /// ratio to the shorter axis, up to the max anisotropy of the sampler. Level of detail comes from the
+    /// length, which every lookup covers, so it's as sharp as the shorter axis allows.
+    fn sample_anisotropic(&self, tex_coord: TexCoord) -> Vector3<f32> {
+        let size = vector![self.width() as f32, self.height() as f32];
+        let footprint_x = tex_coord.duv_dx.component_mul(&size).norm();
+        let footprint_y = tex_coord.duv_dy.component_mul(&size).norm();
+        let (major_axis, major, minor) = match footprint_x >= footprint_y {
+            true => (tex_coord.duv_dx, footprint_x, footprint_y),
+            false => (tex_coord.duv_dy, footprint_y, footprint_x),
+        };
+        let max_anisotropy = self.sampler.max_anisotropy.max(1) as f32;
+        let n_probes = (major / minor.max(1e-6)).ceil().clamp(1.0, max_anisotropy);
+        let max_lod = self.mips.len() as f32;
+        let lod = (major / n_probes).max(1.0).log2().min(max_lod);
+        if n_probes == 1.0 {
+            return self.sample_trilinear(lod, tex_coord.uv);
+        }
+        let mut sum = Vector3::zeros();
+        for i in 0..n_probes as u32 {
+            let offset = (i as f32 + 0.5) / n_probes - 0.5;
+            sum += self.sample_trilinear(lod, tex_coord.uv + major_axis * offset);
+        }
+        return sum / n_probes;
+    }
+}
+
+/// Environment, surrounding the scene, stored as 6 square faces of a cube in the usual +x, -x, +y, -y, +z,
+/// -z order and orientation, where image rows go from the top. Faces keep channels in [0, 255], which go past
+/// 255 for high dynamic range environments. These are linear, so they are never decoded from sRGB.
+pub struct CubeMap {
+    faces: Vec<Rgb32FImage>,
+    hdr: bool,
+    srgb: bool,
+}
+
+impl CubeMap {
+    /// Builds cube map from 6 square images of the same size.
+    pub fn from_faces(images: Vec<RgbImage>) -> Self {
+        return Self::from_float_faces(images.iter().map(to_float_image).collect(), false);
+    }
+
+    /// Builds high dynamic range cube map from 6 square images of the same size with linear channels, where
+    /// 1 is white.
+    pub fn from_hdr_faces(images: Vec<Rgb32FImage>) -> Self {
+        let faces = images
+            .into_iter()
+            .map(|mut image| {
+                image
+                    .pixels_mut()
+                    .for_each(|pixel| pixel.0 = pixel.0.map(|c| c * 255.0));
+                return image;
+            })
+            .collect();
+        return Self::from_float_faces(faces, true);
+    }
+
+    fn from_float_faces(faces: Vec<Rgb32FImage>, hdr: bool) -> Self {
+        assert!(faces.len() == 6, "Cube map should have exactly 6 faces!");
+        return Self {
+            faces,
+            hdr,
+            srgb: false,
+        };
+    }
+
+    /// Resamples panorama with longitude along x and latitude along y into a cube map with faces of a quarter
+    /// of the panorama width.
+    pub fn from_equirectangular(panorama: &RgbImage) -> Self {
+        return Self::from_float_panorama(&to_float_image(panorama), false);
+    }
+
+    /// Same as from_equirectangular for high dynamic range panoramas, e.g. loaded from Radiance HDR files,
+    /// with linear channels, where 1 is white.
+    pub fn from_equirectangular_hdr(panorama: &Rgb32FImage) -> Self {
+        let mut panorama = panorama.clone();
+        panorama
+            .pixels_mut()
+            .for_each(|pixel| pixel.0 = pixel.0.map(|c| c * 255.0));
+        return Self::from_float_panorama(&panorama, true);
+    }
+
+    fn from_float_panorama(panorama: &Rgb32FImage, hdr: bool) -> Self {
+        let size = (panorama.width() / 4).max(1);
+        let faces = (0..6)
+            .map(|face| {
+                return Rgb32FImage::from_fn(size, size, |x, y| {
+                    let direction = get_cube_face_direction(
+                        face,
+                        (x as f32 + 0.5) / size as f32,
+                        (y as f32 + 0.5) / size as f32,
+                    );
+                    let uv = get_equirectangular_uv(direction);
+                    let color = sample_float_image(panorama, uv, Wrap::Repeat, |texel| texel);
+                    return Rgb([color.x, color.y, color.z]);
+                });
+            })
+            .collect();
+        return Self::from_float_faces(faces, hdr);
+    }
+
+    /// Resamples the cube map back into a panorama of the width and half of its height with channels as
+    /// stored, where 1 is white, so it round trips through from_equirectangular or from_equirectangular_hdr
+    /// and saves with the image crate.
+    pub fn to_equirectangular(&self, width: u32) -> Rgb32FImage {
+        let height = (width / 2).max(1);
+        return Rgb32FImage::from_fn(width, height, |x, y| {
+            let direction = get_equirectangular_direction(vector![
+                (x as f32 + 0.5) / width as f32,
+                (y as f32 + 0.5) / height as f32
+            ]);
+            let color = self.sample_stored(direction) / 255.0;
+            return Rgb([color.x, color.y, color.z]);
+        });
+    }
+
+    /// Builds high dynamic range cube map with faces of the size from the texel closure, which gets the face
+    /// and the texel coords and returns linear channels, where 255 is white. Faces are filled in parallel.
+    pub(super) fn from_fn(
+        size: u32,
+        texel: impl Fn(usize, u32, u32) -> Vector3<f32> + Sync,
+    ) -> Self {
+        let faces = (0..6)
+            .into_par_iter()
+            .map(|face| {
+                return Rgb32FImage::from_fn(size, size, |x, y| {
+                    let color = texel(face, x, y);
+                    return Rgb([color.x, color.y, color.z]);
+                });
+            })
+            .collect();
+        return Self::from_float_faces(faces, true);
+    }
+
+    pub fn is_hdr(&self) -> bool {
+        return self.hdr;
+    }
+
+    pub fn face_size(&self) -> u32 {
+        return self.faces[0].width();
+    }
+
+    /// Marks faces as sRGB encoded or linear, high dynamic range faces are always linear.
+    pub fn set_srgb(&mut self, srgb: bool) {
+        self.srgb = srgb;
+    }
+
+    /// Returns environment color in the direction with channels in [0, 255], or above for high dynamic range
+    /// environments, decoded to linear, if faces are sRGB.
+    pub fn sample_direction(&self, direction: Vector3<f32>) -> Vector3<f32> {
+        let (face, uv) = get_cube_face_uv(direction);
+        return sample_float_image(&self.faces[face], uv, Wrap::Clamp, |texel| {
+            if self.srgb && !self.hdr {
+                return texel.map(|c| decode_srgb(c as u8));
+            }
+            return texel;
+        });
+    }
+
+    /// Color in the direction as it is stored, without decoding.
+    fn sample_stored(&self, direction: Vector3<f32>) -> Vector3<f32> {
+        let (face, uv) = get_cube_face_uv(direction);
+        return sample_float_image(&self.faces[face], uv, Wrap::Clamp, |texel| texel);
+    }
+}
+
+/// Image with the channels of the 8-bit one, keeping them in [0, 255].
+fn to_float_image(image: &RgbImage) -> Rgb32FImage {
+    return Rgb32FImage::from_fn(image.width(), image.height(), |x, y| {
+        return Rgb(image.get_pixel(x, y).0.map(f32::from));
+    });
+}
+
+/// Bilinear lookup in the float image, same as the one of Texture, where texel closure maps stored texels
+/// before they are blended, e.g. decoding them.
+pub(super) fn sample_float_image(
+    image: &Rgb32FImage,
+    uv: Vector2<f32>,
+    wrap: Wrap,
+    texel: impl Fn(Vector3<f32>) -> Vector3<f32>,
+) -> Vector3<f32> {
+    let (width, height) = image.dimensions();
+    let x = uv.x * width as f32 - 0.5;
+    let y = uv.y * height as f32 - 0.5;
+    let x_0 = x.floor();
+    let y_0 = y.floor();
+    let t_x = x - x_0;
+    let t_y = y - y_0;
+    let (x_0, x_1) = (
+        wrap.apply(x_0 as i32, width),
+        wrap.apply(x_0 as i32 + 1, width),
+    );
+    let (y_0, y_1) = (
+        wrap.apply(y_0 as i32, height),
+        wrap.apply(y_0 as i32 + 1, height),
+    );
+    let get = |x: u32, y: u32| texel(Vector3::from(image.get_pixel(x, y).0));
+
+    let bottom = get(x_0, y_0) * (1.0 - t_x) + get(x_1, y_0) * t_x;
+    let top = get(x_0, y_1) * (1.0 - t_x) + get(x_1, y_1) * t_x;
+    return bottom * (1.0 - t_y) + top * t_y;
+}
+
+/// Face of the cube, which the direction points at, picked by the major axis, and uv on that face.
+fn get_cube_face_uv(direction: Vector3<f32>) -> (usize, Vector2<f32>) {
+    let abs = direction.abs();
+    let (face, major, s, t) = if abs.x >= abs.y && abs.x >= abs.z {
+        if direction.x > 0.0 {
+            (0, abs.x, -direction.z, -direction.y)
+        } else {
+            (1, abs.x, direction.z, -direction.y)
+        }
+    } else if abs.y >= abs.z {
+        if direction.y > 0.0 {
+            (2, abs.y, direction.x, direction.z)
+        } else {
+            (3, abs.y, direction.x, -direction.z)
+        }
+    } else if direction.z > 0.0 {
+        (4, abs.z, direction.x, -direction.y)
+    } else {
+        (5, abs.z, -direction.x, -direction.y)
+    };
+    return (
+        face,
+        vector![(s / major + 1.0) / 2.0, (t / major + 1.0) / 2.0],
+    );
+}
+
+/// Inverse of get_cube_face_uv, giving not normalized direction to the point at uv of the face.
+pub(super) fn get_cube_face_direction(face: usize, u: f32, v: f32) -> Vector3<f32> {
+    let s = 2.0 * u - 1.0;
+    let t = 2.0 * v - 1.0;
+    match face {
+        0 => return vector![1.0, -t, -s],
+        1 => return vector![-1.0, -t, s],
+        2 => return vector![s, 1.0, t],
+        3 => return vector![s, -1.0, -t],
+        4 => return vector![s, -t, 1.0],
+        _ => return vector![-s, -t, -1.0],
+    }
+}
+
+/// Uv in the equirectangular panorama, where -z direction is in the middle and +y is at the top.
+fn get_equirectangular_uv(direction: Vector3<f32>) -> Vector2<f32> {
+    let direction = direction.normalize();
+    let longitude = direction.x.atan2(-direction.z);
+    let latitude = direction.y.clamp(-1.0, 1.0).asin();
+    return vector![
+        0.5 + longitude / (2.0 * std::f32::consts::PI),
+        0.5 - latitude / std::f32::consts::PI
+    ];
+}
+
+/// Inverse of get_equirectangular_uv, giving unit direction to the point at uv of the panorama.
+fn get_equirectangular_direction(uv: Vector2<f32>) -> Vector3<f32> {
+    let longitude = (uv.x - 0.5) * 2.0 * std::f32::consts::PI;
+    let latitude = (0.5 - uv.y) * std::f32::consts::PI;
+    return vector![
+        latitude.cos() * longitude.sin(),
+        latitude.sin(),
+        -latitude.cos() * longitude.cos()
+    ];
+}
+
+/// Builds the mip chain by repeatedly halving the image with a box filter until it is 1x1.
+fn generate_mips(image: &RgbImage) -> Vec<RgbImage> {
+    let mut mips: Vec<RgbImage> = Vec::new();
+    loop {
+        let previous = mips.last().unwrap_or(image);
+        if previous.width() == 1 && previous.height() == 1 {
+            break;
+        }
+        let width = (previous.width() / 2).max(1);
+        let height = (previous.height() / 2).max(1);
+        let mut mip = RgbImage::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                // Clamping source coords for the odd dimensions, which would otherwise read past the edge.
+                let x_0 = (2 * x).min(previous.width() - 1);
+                let x_1 = (2 * x + 1).min(previous.width() - 1);
+                let y_0 = (2 * y).min(previous.height() - 1);
+                let y_1 = (2 * y + 1).min(previous.height() - 1);
+                let mut sum = [0u32; 3];
+                for (sx, sy) in [(x_0, y_0), (x_1, y_0), (x_0, y_1), (x_1, y_1)] {
+                    let pixel = previous.get_pixel(sx, sy).0;
+                    for c in 0..3 {
+                        sum[c] += pixel[c] as u32;
+                    }
+                }
+                mip.put_pixel(
+                    x,
+                    y,
+                    Rgb([(sum[0] / 4) as u8, (sum[1] / 4) as u8, (sum[2] / 4) as u8]),
+                );
+            }
+        }
+        mips.push(mip);
+    }
+    return mips;
+}
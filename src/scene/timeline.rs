@@ -0,0 +1,189 @@
+use na::Vector3;
+use nalgebra as na;
+
+use super::json::Json;
+
+/// Shape of the interpolation from a keyframe to the next one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Easing {
+    Linear,
+    Smoothstep, // Starting and stopping gently, with zero velocity at both keyframes.
+}
+
+impl Easing {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "linear" => return Some(Easing::Linear),
+            "smoothstep" => return Some(Easing::Smoothstep),
+            _ => return None,
+        }
+    }
+
+    fn apply(&self, t: f32) -> f32 {
+        match self {
+            Easing::Linear => return t,
+            Easing::Smoothstep => return t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// Value at the time in seconds, eased towards the next value by the easing of the keyframe.
+#[derive(Clone, Copy, Debug)]
+pub struct Keyframe<T> {
+    pub time: f32,
+    pub value: T,
+    pub easing: Easing,
+}
+
+/// Camera, placed the same way as by `Scene::set_camera`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CameraPose {
+    pub look_from: Vector3<f32>,
+    pub look_at: Vector3<f32>,
+    pub up: Vector3<f32>,
+}
+
+/// Main light, direction being FROM surface TO source, same as in `Scene::set_light_direction`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LightPose {
+    pub direction: Vector3<f32>,
+    pub intensity: f32,
+}
+
+/// Keyframes of the camera and the main light, played over time instead of the interactive controls.
+/// Either track may be empty, leaving that part of the scene to the controls.
+#[derive(Clone, Debug)]
+pub struct Timeline {
+    pub camera: Vec<Keyframe<CameraPose>>,
+    pub light: Vec<Keyframe<LightPose>>,
+    pub looped: bool, // Whether the timeline starts over after the last keyframe or holds it.
+}
+
+impl Timeline {
+    /// Parses timeline from JSON text, returning None for malformed documents. Keyframes look like
+    /// `{"time": 1.5, "look_from": [0, 0, 1], "look_at": [0, 0, 0], "up": [0, 1, 0], "easing": "smoothstep"}`
+    /// in the "camera" array and `{"time": 0, "direction": [1, 0, 1], "intensity": 0.8}` in the "light"
+    /// array. Everything but time and look_from or direction is optional, "loop" defaults to true.
+    pub fn parse(text: &str) -> Option<Self> {
+        fn parse_vector(value: Option<&Json>, default: Vector3<f32>) -> Option<Vector3<f32>> {
+            let values = match value {
+                Some(value) => value.as_f32_vec()?,
+                None => return Some(default),
+            };
+            if values.len() != 3 {
+                return None;
+            }
+            return Some(Vector3::new(values[0], values[1], values[2]));
+        }
+        fn parse_track<T>(
+            document: &Json,
+            name: &str,
+            parse_value: impl Fn(&Json) -> Option<T>,
+        ) -> Option<Vec<Keyframe<T>>> {
+            let mut keyframes = Vec::new();
+            for keyframe in document.get(name).map_or(&[][..], Json::elements) {
+                let easing = match keyframe.get("easing") {
+                    Some(easing) => Easing::from_name(easing.as_str()?)?,
+                    None => Easing::Linear,
+                };
+                keyframes.push(Keyframe {
+                    time: keyframe.get("time")?.as_f64()? as f32,
+                    value: parse_value(keyframe)?,
+                    easing,
+                });
+            }
+            if keyframes
+                .windows(2)
+                .any(|pair| pair[1].time <= pair[0].time)
+            {
+                return None;
+            }
+            return Some(keyframes);
+        }
+
+        let document = Json::parse(text)?;
+        let camera = parse_track(&document, "camera", |keyframe| {
+            return Some(CameraPose {
+                look_from: parse_vector(Some(keyframe.get("look_from")?), Vector3::zeros())?,
+                look_at: parse_vector(keyframe.get("look_at"), Vector3::zeros())?,
+                up: parse_vector(keyframe.get("up"), Vector3::y())?,
+            });
+        })?;
+        let light = parse_track(&document, "light", |keyframe| {
+            let intensity = match keyframe.get("intensity") {
+                Some(intensity) => intensity.as_f64()? as f32,
+                None => 1.0,
+            };
+            return Some(LightPose {
+                direction: parse_vector(Some(keyframe.get("direction")?), Vector3::zeros())?
+                    .normalize(),
+                intensity,
+            });
+        })?;
+        let looped = match document.get("loop") {
+            Some(looped) => looped.as_bool()?,
+            None => true,
+        };
+        return Some(Timeline {
+            camera,
+            light,
+            looped,
+        });
+    }
+
+    /// Time of the last keyframe of both tracks.
+    pub fn get_duration(&self) -> f32 {
+        let camera_end = self.camera.last().map_or(0.0, |keyframe| keyframe.time);
+        let light_end = self.light.last().map_or(0.0, |keyframe| keyframe.time);
+        return camera_end.max(light_end);
+    }
+
+    /// Camera at the time in seconds, None without camera keyframes.
+    pub fn get_camera(&self, time: f32) -> Option<CameraPose> {
+        return sample(&self.camera, self.wrap_time(time), |from, to, t| {
+            return CameraPose {
+                look_from: from.look_from.lerp(&to.look_from, t),
+                look_at: from.look_at.lerp(&to.look_at, t),
+                up: from.up.lerp(&to.up, t),
+            };
+        });
+    }
+
+    /// Main light at the time in seconds, None without light keyframes.
+    pub fn get_light(&self, time: f32) -> Option<LightPose> {
+        return sample(&self.light, self.wrap_time(time), |from, to, t| {
+            return LightPose {
+                direction: from.direction.lerp(&to.direction, t).normalize(),
+                intensity: from.intensity * (1.0 - t) + to.intensity * t,
+            };
+        });
+    }
+
+    fn wrap_time(&self, time: f32) -> f32 {
+        let duration = self.get_duration();
+        if self.looped && duration > 0.0 {
+            return time.rem_euclid(duration);
+        }
+        return time;
+    }
+}
+
+/// Value of the track at the time, holding the first and the last values outside of the keyframes.
+fn sample<T: Copy>(
+    keyframes: &[Keyframe<T>],
+    time: f32,
+    interpolate: impl Fn(&T, &T, f32) -> T,
+) -> Option<T> {
+    let next = keyframes.partition_point(|keyframe| keyframe.time <= time);
+    if next == 0 {
+        return keyframes.first().map(|keyframe| keyframe.value);
+    }
+    if next == keyframes.len() {
+        return Some(keyframes[next - 1].value);
+    }
+    let (from, to) = (&keyframes[next - 1], &keyframes[next]);
+    let t = from
+        .easing
+        .apply((time - from.time) / (to.time - from.time));
+    return Some(interpolate(&from.value, &to.value, t));
+}
@@ -0,0 +1,35 @@
+/// Operator, compressing high dynamic range colors of the frame buffer into the displayable [0, 1] range.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ToneMapping {
+    #[default]
+    Clamp, // Everything brighter than white is clipped.
+    Reinhard, // x / (1 + x), never reaching white.
+    Aces,     // Filmic curve, fitted to the ACES reference transform.
+}
+
+impl ToneMapping {
+    /// Parses tone mapping operator from its command line name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "clamp" => return Some(ToneMapping::Clamp),
+            "reinhard" => return Some(ToneMapping::Reinhard),
+            "aces" => return Some(ToneMapping::Aces),
+            _ => return None,
+        }
+    }
+
+    /// Maps single linear channel value, where 1.0 is white, into [0, 1].
+    pub fn apply(&self, value: f32) -> f32 {
+        let value = value.max(0.0);
+        match self {
+            ToneMapping::Clamp => return value.min(1.0),
+            ToneMapping::Reinhard => return value / (1.0 + value),
+            // Krzysztof Narkowicz's fit of the ACES filmic curve.
+            ToneMapping::Aces => {
+                let mapped =
+                    (value * (2.51 * value + 0.03)) / (value * (2.43 * value + 0.59) + 0.14);
+                return mapped.clamp(0.0, 1.0);
+            }
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use na::{Vector2, Vector3};
+use nalgebra as na;
+use obj::raw::object::{Polygon, Range};
+use obj::raw::RawObj;
+
+/// Splits every polygon with more than 3 corners into triangles by ear clipping, so concave faces are covered
+/// exactly, and drops degenerate polygons - ones with less than 3 distinct corners or without any area.
+/// Triangles take the place of their polygon, and polygon ranges of the groups are moved along. Returns the
+/// number of polygons, which were split, and the number of degenerate ones, which were dropped.
+pub fn triangulate(obj: &mut RawObj) -> (usize, usize) {
+    let mut n_split = 0;
+    let mut n_dropped = 0;
+    let polygons = std::mem::take(&mut obj.polygons);
+    // Index of the first triangle of every polygon, followed by the number of triangles, for moving the ranges.
+    let mut starts = Vec::with_capacity(polygons.len() + 1);
+    for polygon in polygons {
+        starts.push(obj.polygons.len());
+        let positions = get_position_indices(&polygon);
+        let n = positions.len();
+        // Corners, repeating the position of the previous one, only add a zero length edge.
+        let corners: Vec<usize> = (0..n)
+            .filter(|&k| positions[k] != positions[(k + n - 1) % n])
+            .collect();
+        let points: Vec<Vector3<f32>> = corners
+            .iter()
+            .map(|&k| {
+                let (x, y, z, _) = obj.positions[positions[k]];
+                return Vector3::new(x, y, z);
+            })
+            .collect();
+        let normal = get_polygon_normal(&points);
+        let edge_length_sum: f32 = (0..points.len())
+            .map(|k| (points[(k + 1) % points.len()] - points[k]).norm_squared())
+            .sum();
+        if corners.len() < 3 || normal.norm() <= f32::EPSILON * edge_length_sum {
+            n_dropped += 1;
+            continue;
+        }
+        if n == 3 {
+            obj.polygons.push(polygon);
+            continue;
+        }
+        n_split += 1;
+        for triangle in clip_ears(&points, &normal) {
+            obj.polygons
+                .push(select_corners(&polygon, triangle.map(|k| corners[k])));
+        }
+    }
+    starts.push(obj.polygons.len());
+    let groups = obj.groups.values_mut().chain(obj.meshes.values_mut());
+    let groups = groups
+        .chain(obj.smoothing_groups.values_mut())
+        .chain(obj.merging_groups.values_mut());
+    for group in groups {
+        for range in group.polygons.iter_mut() {
+            *range = Range {
+                start: starts[range.start],
+                end: starts[range.end],
+            };
+        }
+    }
+    return (n_split, n_dropped);
+}
+
+fn get_position_indices(polygon: &Polygon) -> Vec<usize> {
+    match polygon {
+        Polygon::P(indices) => return indices.clone(),
+        Polygon::PT(indices) => return indices.iter().map(|index| index.0).collect(),
+        Polygon::PN(indices) => return indices.iter().map(|index| index.0).collect(),
+        Polygon::PTN(indices) => return indices.iter().map(|index| index.0).collect(),
+    }
+}
+
+/// Triangle of the corners of the polygon at the indices, keeping its uvs and normals.
+fn select_corners(polygon: &Polygon, corners: [usize; 3]) -> Polygon {
+    match polygon {
+        Polygon::P(indices) => return Polygon::P(corners.map(|k| indices[k]).to_vec()),
+        Polygon::PT(indices) => return Polygon::PT(corners.map(|k| indices[k]).to_vec()),
+        Polygon::PN(indices) => return Polygon::PN(corners.map(|k| indices[k]).to_vec()),
+        Polygon::PTN(indices) => return Polygon::PTN(corners.map(|k| indices[k]).to_vec()),
+    }
+}
+
+/// Normal by Newell's method, twice as long as the area of the polygon, which also works for polygons, which
+/// aren't quite planar or convex.
+fn get_polygon_normal(points: &[Vector3<f32>]) -> Vector3<f32> {
+    let mut normal = Vector3::zeros();
+    for k in 1..points.len().saturating_sub(1) {
+        normal += (points[k] - points[0]).cross(&(points[k + 1] - points[0]));
+    }
+    return normal;
+}
+
+/// Twice the signed area of the triangle, positive for counter-clockwise corners.
+fn get_signed_area(a: Vector2<f32>, b: Vector2<f32>, c: Vector2<f32>) -> f32 {
+    return (b - a).perp(&(c - a));
+}
+
+/// Triangles of the polygon as indices of its points, cut off one ear at a time - a convex corner, whose
+/// triangle contains no other corner. Polygon is projected onto the plane of its largest normal component,
+/// oriented so it runs counter-clockwise. When no ear is left, e.g. for self-intersecting polygons, the
+/// flattest corner is cut off instead, which also removes corners lying on a straight edge.
+fn clip_ears(points: &[Vector3<f32>], normal: &Vector3<f32>) -> Vec<[usize; 3]> {
+    let axis = normal.iamax();
+    let (mut u, mut v) = ((axis + 1) % 3, (axis + 2) % 3);
+    if normal[axis] < 0.0 {
+        (u, v) = (v, u);
+    }
+    let projected: Vec<Vector2<f32>> = points
+        .iter()
+        .map(|point| Vector2::new(point[u], point[v]))
+        .collect();
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    let mut triangles = Vec::with_capacity(points.len() - 2);
+    while remaining.len() > 3 {
+        let len = remaining.len();
+        let get_corner = |k: usize| {
+            return [
+                remaining[(k + len - 1) % len],
+                remaining[k],
+                remaining[(k + 1) % len],
+            ];
+        };
+        let is_ear = |k: usize| {
+            let [a, b, c] = get_corner(k).map(|index| projected[index]);
+            if get_signed_area(a, b, c) <= 0.0 {
+                return false;
+            }
+            return remaining.iter().all(|&index| {
+                let p = projected[index];
+                if p == a || p == b || p == c {
+                    return true;
+                }
+                return get_signed_area(a, b, p) < 0.0
+                    || get_signed_area(b, c, p) < 0.0
+                    || get_signed_area(c, a, p) < 0.0;
+            });
+        };
+        let ear = (0..len).find(|&k| is_ear(k)).unwrap_or_else(|| {
+            return (0..len)
+                .min_by(|&k, &l| {
+                    let area = |k: usize| {
+                        let [a, b, c] = get_corner(k).map(|index| projected[index]);
+                        return get_signed_area(a, b, c).abs();
+                    };
+                    return area(k).total_cmp(&area(l));
+                })
+                .unwrap();
+        });
+        let corner = get_corner(ear);
+        let [a, b, c] = corner.map(|index| projected[index]);
+        if get_signed_area(a, b, c) != 0.0 {
+            triangles.push(corner);
+        }
+        remaining.remove(ear);
+    }
+    let corner = [remaining[0], remaining[1], remaining[2]];
+    let [a, b, c] = corner.map(|index| projected[index]);
+    if get_signed_area(a, b, c) != 0.0 {
+        triangles.push(corner);
+    }
+    return triangles;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use obj::raw::parse_obj;
+
+    fn parse(source: &str) -> RawObj {
+        return parse_obj(source.as_bytes()).unwrap();
+    }
+
+    /// Sum of the areas of the triangles of the model.
+    fn get_area(obj: &RawObj) -> f32 {
+        let point = |index: usize| {
+            let (x, y, z, _) = obj.positions[index];
+            return Vector3::new(x, y, z);
+        };
+        return obj
+            .polygons
+            .iter()
+            .map(|polygon| {
+                let positions = get_position_indices(polygon);
+                assert_eq!(positions.len(), 3);
+                let [a, b, c] = [0, 1, 2].map(|k| point(positions[k]));
+                return (b - a).cross(&(c - a)).norm() / 2.0;
+            })
+            .sum();
+    }
+
+    #[test]
+    fn splits_concave_hexagon() {
+        // L-shape, covering 3 of the 4 unit squares of a 2x2 square.
+        let mut obj =
+            parse("v 0 0 0\nv 2 0 0\nv 2 1 0\nv 1 1 0\nv 1 2 0\nv 0 2 0\nf 1 2 3 4 5 6\n");
+        assert_eq!(triangulate(&mut obj), (1, 0));
+        assert_eq!(obj.polygons.len(), 4);
+        assert!((get_area(&obj) - 3.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn skips_repeated_corner() {
+        let mut obj = parse("v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 2 3 4\n");
+        assert_eq!(triangulate(&mut obj), (1, 0));
+        assert_eq!(obj.polygons.len(), 2);
+        assert!((get_area(&obj) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn drops_zero_area_polygon() {
+        let mut obj = parse("v 0 0 0\nv 1 0 0\nv 2 0 0\nv 0 1 0\nf 1 2 3\nf 1 2 4\n");
+        assert_eq!(triangulate(&mut obj), (0, 1));
+        assert_eq!(obj.polygons.len(), 1);
+        assert_eq!(get_position_indices(&obj.polygons[0]), vec![0, 1, 3]);
+    }
+
+    #[test]
+    fn moves_group_ranges() {
+        let mut obj =
+            parse("v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\ng quad\nf 1 2 3 4\ng triangle\nf 1 2 3\n");
+        assert_eq!(triangulate(&mut obj), (1, 0));
+        assert_eq!(obj.polygons.len(), 3);
+        let quad = &obj.groups["quad"].polygons;
+        let triangle = &obj.groups["triangle"].polygons;
+        assert_eq!((quad[0].start, quad[0].end), (0, 2));
+        assert_eq!((triangle[0].start, triangle[0].end), (2, 3));
+    }
+}
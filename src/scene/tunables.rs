@@ -0,0 +1,69 @@
+use super::json::Json;
+use super::shader::BUILTIN_PIPELINE_NAMES;
+use super::tonemap::ToneMapping;
+
+/// Shader parameters, read from a JSON file of constants, which is re-read when it changes, so they can be
+/// tuned in an editor, while the app runs. Settings, which the file leaves out, are None and stay as they are.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Tunables {
+    pub pipeline: Option<String>,     // Name of a built-in pipeline.
+    pub light_azimuth: Option<f32>, // Angle of the main light around the vertical axis in degrees.
+    pub light_elevation: Option<f32>, // Angle of the main light above the horizon in degrees.
+    pub light_intensity: Option<f32>,
+    pub exposure: Option<f32>,
+    pub tone_mapping: Option<ToneMapping>,
+    pub render_scale: Option<u32>,
+    pub shadow_bias: Option<f32>,
+    pub shadow_slope_bias: Option<f32>,
+    pub pcf_radius: Option<u32>,
+}
+
+impl Tunables {
+    /// Parses an object with any of the keys "pipeline", "light_azimuth", "light_elevation", "light_intensity",
+    /// "exposure", "tone_mapping", "render_scale", "shadow_bias", "shadow_slope_bias" and "pcf_radius". None
+    /// if the file is malformed or names an unknown pipeline or tone mapping operator.
+    pub fn parse(text: &str) -> Option<Self> {
+        let document = Json::parse(text)?;
+        if !matches!(document, Json::Object(_)) {
+            return None;
+        }
+        let get_f32 = |key: &str| -> Option<Option<f32>> {
+            match document.get(key) {
+                Some(value) => return Some(Some(value.as_f64()? as f32)),
+                None => return Some(None),
+            }
+        };
+        let get_u32 = |key: &str| -> Option<Option<u32>> {
+            match document.get(key) {
+                Some(value) => return Some(Some(u32::try_from(value.as_usize()?).ok()?)),
+                None => return Some(None),
+            }
+        };
+        let pipeline = match document.get("pipeline") {
+            Some(pipeline) => {
+                let name = pipeline.as_str()?;
+                if !BUILTIN_PIPELINE_NAMES.contains(&name) {
+                    return None;
+                }
+                Some(name.to_string())
+            }
+            None => None,
+        };
+        let tone_mapping = match document.get("tone_mapping") {
+            Some(tone_mapping) => Some(ToneMapping::from_name(tone_mapping.as_str()?)?),
+            None => None,
+        };
+        return Some(Tunables {
+            pipeline,
+            light_azimuth: get_f32("light_azimuth")?,
+            light_elevation: get_f32("light_elevation")?,
+            light_intensity: get_f32("light_intensity")?,
+            exposure: get_f32("exposure")?,
+            tone_mapping,
+            render_scale: get_u32("render_scale")?.filter(|&render_scale| render_scale > 0),
+            shadow_bias: get_f32("shadow_bias")?,
+            shadow_slope_bias: get_f32("shadow_slope_bias")?,
+            pcf_radius: get_u32("pcf_radius")?,
+        });
+    }
+}
@@ -1,84 +1,304 @@
-use image::RgbImage;
-use na::{point, vector, Point3, Vector2, Vector3};
+use std::collections::HashMap;
+
+use image::{Rgb, RgbImage};
+use na::{point, vector, Point3, Vector3, Vector4};
 use nalgebra as na;
+use obj::raw::object::Polygon;
 use obj::raw::RawObj;
 
-/// Utility for getting convex combination of 2 Vector3<u8>'s
-pub fn color_blend(color_1: Vector3<u8>, color_2: Vector3<u8>, t: f32) -> Vector3<u8> {
-    return vector![
-        (t * color_1.x as f32 + (1.0 - t) * color_2.x as f32) as u8,
-        (t * color_1.y as f32 + (1.0 - t) * color_2.y as f32) as u8,
-        (t * color_1.z as f32 + (1.0 - t) * color_2.z as f32) as u8
+use super::displacement::DisplacementVertex;
+use super::frustum::BoundingSphere;
+use super::instance::Instance;
+use super::skin::Skin;
+use super::tangent::VertexKey;
+use super::texture::{decode_srgb, TexCoord, Texture};
+
+/// Utility for getting convex combination of 2 colors, negative channels are clamped to 0.
+pub fn color_blend(color_1: Vector3<f32>, color_2: Vector3<f32>, t: f32) -> Vector3<f32> {
+    return (t * color_1 + (1.0 - t) * color_2).map(|c| c.max(0.0));
+}
+
+/// Kinds of textures, that model holds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TextureKind {
+    Diffuse,
+    NormalMap,
+    NormalMapTangent,
+    SpecularMap,
+    MetallicMap,
+    RoughnessMap,
+    AmbientOcclusionMap,
+    AlphaMap,
+    DisplacementMap,
+}
+
+impl TextureKind {
+    /// Every kind of texture, that model holds.
+    pub const ALL: [TextureKind; 9] = [
+        TextureKind::Diffuse,
+        TextureKind::NormalMap,
+        TextureKind::NormalMapTangent,
+        TextureKind::SpecularMap,
+        TextureKind::MetallicMap,
+        TextureKind::RoughnessMap,
+        TextureKind::AmbientOcclusionMap,
+        TextureKind::AlphaMap,
+        TextureKind::DisplacementMap,
     ];
+
+    /// Name of the map, which is also the file name of the map in an asset folder without the extension.
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            TextureKind::Diffuse => return "texture",
+            TextureKind::NormalMap => return "normal_map",
+            TextureKind::NormalMapTangent => return "normal_map_tangent",
+            TextureKind::SpecularMap => return "specular_map",
+            TextureKind::MetallicMap => return "metallic_map",
+            TextureKind::RoughnessMap => return "roughness_map",
+            TextureKind::AmbientOcclusionMap => return "ao_map",
+            TextureKind::AlphaMap => return "alpha_map",
+            TextureKind::DisplacementMap => return "displacement_map",
+        }
+    }
+
+    /// Parses kind of texture from its name, see `get_name`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        return TextureKind::ALL
+            .into_iter()
+            .find(|kind| kind.get_name() == name);
+    }
+}
+
+/// Texture of a single texel, used in place of the maps, which asset doesn't provide.
+pub fn get_constant_texture(value: u8) -> Texture {
+    return Texture::new(RgbImage::from_pixel(1, 1, Rgb([value, value, value])));
+}
+
+/// Normal map of a single texel, pointing along the normal in tangent coordinates, so the surface isn't
+/// perturbed.
+pub fn get_flat_normal_texture() -> Texture {
+    return Texture::new(RgbImage::from_pixel(1, 1, Rgb([128, 128, 255])));
+}
+
+/// How normals vary across the polygons of the model.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Shading {
+    #[default]
+    Smooth, // Vertex normals are interpolated across the polygon.
+    Flat, // Whole polygon gets its face normal, showing the facets.
+}
+
+impl Shading {
+    /// Mode, following this one, cycling back to the first.
+    pub fn next(&self) -> Self {
+        match self {
+            Shading::Smooth => return Shading::Flat,
+            Shading::Flat => return Shading::Smooth,
+        }
+    }
 }
 
 /// Struct, holding all information about the model, including geometry, texture and normal and specular maps.
 pub struct Model {
     pub obj: RawObj,
-    pub texture: RgbImage,
-    pub normal_map: RgbImage,
-    pub normal_map_tangent: RgbImage,
-    pub specular_map: RgbImage,
+    pub texture: Texture,
+    pub normal_map: Texture,
+    // Object space normals can't be replaced by a constant map, so without one vertex normals are used.
+    pub has_normal_map: bool,
+    pub normal_map_tangent: Texture,
+    pub specular_map: Texture,
+    // Maps of the metallic-roughness material model, constant unless provided.
+    pub metallic_map: Texture,
+    pub roughness_map: Texture,
+    pub ao_map: Texture,
+    // Opacity, taken from the alpha channel of the diffuse texture, fully opaque unless provided.
+    pub alpha_map: Texture,
+    // Height in [0, 1], moving the vertices along their normals by the displacement scale, none unless provided.
+    pub displacement_map: Texture,
+    pub displacement_scale: f32,
+    pub displacement_vertices: Vec<DisplacementVertex>,
+    // Bounds of the vertex positions, used to skip the whole model, when it's out of view.
+    pub bounding_sphere: BoundingSphere,
+    // Skeleton, deforming the vertices in the vertex stage, if the model is animated.
+    pub skin: Option<Skin>,
+    // Copy of the model, which the vertex stage is drawing, set by the scene around it, None for the mesh as is.
+    pub instance: Option<Instance>,
+    // Simplified polygons of the coarser levels of detail, sharing the vertices of the model, and the level the
+    // vertex stage is drawing, set by the scene around it, 0 being the polygons of the model.
+    pub lods: Vec<Vec<Polygon>>,
+    pub lod: usize,
+    // Tangents of the vertices with handedness in w, generated at load time for the tangent space normal map.
+    pub tangents: HashMap<VertexKey, Vector4<f32>>,
+    pub shading: Shading,
+    // Colors of the vertices in [0, 1], sRGB encoded like the diffuse texture and multiplying its color, one
+    // per position, empty when the mesh has none.
+    pub vertex_colors: Vec<Vector3<f32>>,
 }
 
 impl Model {
+    /// Mutable access to a texture of the model, e.g. for changing its sampler.
+    pub fn get_texture_mut(&mut self, kind: TextureKind) -> &mut Texture {
+        match kind {
+            TextureKind::Diffuse => return &mut self.texture,
+            TextureKind::NormalMap => return &mut self.normal_map,
+            TextureKind::NormalMapTangent => return &mut self.normal_map_tangent,
+            TextureKind::SpecularMap => return &mut self.specular_map,
+            TextureKind::MetallicMap => return &mut self.metallic_map,
+            TextureKind::RoughnessMap => return &mut self.roughness_map,
+            TextureKind::AmbientOcclusionMap => return &mut self.ao_map,
+            TextureKind::AlphaMap => return &mut self.alpha_map,
+            TextureKind::DisplacementMap => return &mut self.displacement_map,
+        }
+    }
+
+    /// Polygons of the level of detail, 0 being the polygons of the model.
+    pub fn get_lod_polygons(&self, lod: usize) -> &Vec<Polygon> {
+        match lod {
+            0 => return &self.obj.polygons,
+            _ => return &self.lods[lod - 1],
+        }
+    }
+
+    /// Position of the vertex, displaced along its normal by the displacement map, deformed by the skin in its
+    /// current pose, if model has one, and moved to the instance being drawn. Normals aren't affected by the
+    /// displacement.
     pub fn get_vertex_position_at_index(&self, index: usize) -> Point3<f32> {
-        return point![
+        let mut position = point![
             self.obj.positions[index].0,
             self.obj.positions[index].1,
             self.obj.positions[index].2
         ];
+        if self.displacement_scale != 0.0 {
+            let vertex = &self.displacement_vertices[index];
+            let height = self.displacement_map.sample_vertex(vertex.uv).x / 255.0;
+            position += vertex.normal * height * self.displacement_scale;
+        }
+        if let Some(skin) = &self.skin {
+            position = skin.transform_position(index, position);
+        }
+        match &self.instance {
+            Some(instance) => return instance.transform_position(position),
+            None => return position,
+        }
     }
 
-    /// Returns texture color from texture file at uv.
-    pub fn get_color_at_uv(&self, uv: Vector2<f32>) -> Vector3<u8> {
-        let coord = vector![
-            (uv.x * self.texture.width() as f32) as u32,
-            (uv.y * self.texture.height() as f32) as u32
+    /// Normal of the vertex, deformed by the skin in its current pose, if model has one, and turned with the
+    /// instance being drawn.
+    pub fn get_vertex_normal_at_index(&self, index: usize) -> Vector3<f32> {
+        let mut normal = vector![
+            self.obj.normals[index].0,
+            self.obj.normals[index].1,
+            self.obj.normals[index].2
         ];
+        if let Some(skin) = &self.skin {
+            normal = skin.transform_normal(index, normal);
+        }
+        match &self.instance {
+            Some(instance) => return instance.transform_normal(normal),
+            None => return normal,
+        }
+    }
 
-        return Vector3::<u8>::from_row_slice(&self.texture.get_pixel(coord.x, coord.y).0[0..3]);
+    /// Normals of the polygon corners as lit by the pipelines - vertex normals when smooth, face normal when
+    /// flat. Face normal is turned to the same side as the vertex normals, whatever the winding is.
+    pub fn get_polygon_normals(
+        &self,
+        pos_indices: Vector3<usize>,
+        normal_indices: Vector3<usize>,
+    ) -> [Vector3<f32>; 3] {
+        let vertex_normals = [0, 1, 2].map(|i| self.get_vertex_normal_at_index(normal_indices[i]));
+        if self.shading == Shading::Smooth {
+            return vertex_normals;
+        }
+        let positions = [0, 1, 2].map(|i| self.get_vertex_position_at_index(pos_indices[i]));
+        let face_normal = (positions[1] - positions[0])
+            .cross(&(positions[2] - positions[0]))
+            .try_normalize(0.0)
+            .unwrap_or(vertex_normals[0]);
+        let vertex_normals_sum = vertex_normals[0] + vertex_normals[1] + vertex_normals[2];
+        match face_normal.dot(&vertex_normals_sum) < 0.0 {
+            true => return [-face_normal; 3],
+            false => return [face_normal; 3],
+        }
     }
 
-    /// Returns normalized normal from normal map at uv.
-    pub fn get_normal_at_uv(&self, uv: Vector2<f32>) -> Vector3<f32> {
-        let coord = vector![
-            (uv.x * self.normal_map.width() as f32) as u32,
-            (uv.y * self.normal_map.height() as f32) as u32
-        ];
+    /// Tangent of the polygon corner with handedness in w, deformed by the skin in its current pose, if model
+    /// has one, and turned with the instance being drawn. Corners with degenerate uvs get a zero tangent.
+    pub fn get_vertex_tangent(&self, key: VertexKey) -> Vector4<f32> {
+        let mut tangent = match self.tangents.get(&key) {
+            Some(tangent) => *tangent,
+            None => return Vector4::zeros(),
+        };
+        if let Some(skin) = &self.skin {
+            tangent = skin.transform_normal(key.0, tangent.xyz()).push(tangent.w);
+        }
+        match &self.instance {
+            Some(instance) => return instance.transform_normal(tangent.xyz()).push(tangent.w),
+            None => return tangent,
+        }
+    }
+
+    /// Color of the vertex in [0, 1], decoded to linear together with the diffuse texture, white when the mesh
+    /// has no vertex colors.
+    pub fn get_vertex_color_at_index(&self, index: usize) -> Vector3<f32> {
+        let color = match self.vertex_colors.get(index) {
+            Some(color) => *color,
+            None => return Vector3::repeat(1.0),
+        };
+        if !self.texture.srgb {
+            return color;
+        }
+        return color
+            .map(|value| decode_srgb((value.clamp(0.0, 1.0) * 255.0).round() as u8) / 255.0);
+    }
+
+    /// Returns texture color from texture file at uv.
+    pub fn get_color_at_uv(&self, uv: TexCoord) -> Vector3<f32> {
+        return self.texture.sample(uv);
+    }
 
+    /// Returns normalized normal from normal map at uv.
+    pub fn get_normal_at_uv(&self, uv: TexCoord) -> Vector3<f32> {
         // Subtracting 0.5 to get from [0, 255] to [-0.5, 0.5]
-        return vector![
-            (self.normal_map.get_pixel(coord.x, coord.y).0[0]) as f32 / 255.0 - 0.5,
-            (self.normal_map.get_pixel(coord.x, coord.y).0[1]) as f32 / 255.0 - 0.5,
-            (self.normal_map.get_pixel(coord.x, coord.y).0[2]) as f32 / 255.0 - 0.5
-        ]
-        .normalize();
+        return self
+            .normal_map
+            .sample(uv)
+            .map(|c| c / 255.0 - 0.5)
+            .normalize();
     }
 
     /// Returns normalized normal from normal map in tangent coordinates at uv.
-    pub fn get_normal_tangent_at_uv(&self, uv: Vector2<f32>) -> Vector3<f32> {
-        let coord = vector![
-            (uv.x * self.normal_map.width() as f32) as u32,
-            (uv.y * self.normal_map.height() as f32) as u32
-        ];
-
+    pub fn get_normal_tangent_at_uv(&self, uv: TexCoord) -> Vector3<f32> {
         // Subtracting 0.5 to get from [0, 255] to [-0.5, 0.5]
-        return vector![
-            (self.normal_map_tangent.get_pixel(coord.x, coord.y).0[0]) as f32 / 255.0 - 0.5,
-            (self.normal_map_tangent.get_pixel(coord.x, coord.y).0[1]) as f32 / 255.0 - 0.5,
-            (self.normal_map_tangent.get_pixel(coord.x, coord.y).0[2]) as f32 / 255.0 - 0.5
-        ]
-        .normalize();
+        let mut normal = self.normal_map_tangent.sample(uv).map(|c| c / 255.0 - 0.5);
+        if self.normal_map_tangent.flip_green {
+            normal.y = -normal.y;
+        }
+        return normal.normalize();
     }
 
     /// Returns value in [0, 1] from specular map at uv.
-    pub fn get_specular_value_at_uv(&self, uv: Vector2<f32>) -> f32 {
-        let coord = vector![
-            (uv.x * self.specular_map.width() as f32) as u32,
-            (uv.y * self.specular_map.height() as f32) as u32
-        ];
+    pub fn get_specular_value_at_uv(&self, uv: TexCoord) -> f32 {
+        return self.specular_map.sample(uv).x;
+    }
+
+    /// Returns metalness in [0, 1] from metallic map at uv.
+    pub fn get_metallic_value_at_uv(&self, uv: TexCoord) -> f32 {
+        return self.metallic_map.sample(uv).x / 255.0;
+    }
+
+    /// Returns perceptual roughness in [0, 1] from roughness map at uv.
+    pub fn get_roughness_value_at_uv(&self, uv: TexCoord) -> f32 {
+        return self.roughness_map.sample(uv).x / 255.0;
+    }
+
+    /// Returns ambient occlusion factor in [0, 1] from ambient occlusion map at uv, where 0 is fully occluded.
+    pub fn get_ao_value_at_uv(&self, uv: TexCoord) -> f32 {
+        return self.ao_map.sample(uv).x / 255.0;
+    }
 
-        return self.specular_map.get_pixel(coord.x, coord.y).0[0] as f32;
+    /// Returns opacity in [0, 1] from alpha map at uv, where 0 is fully transparent.
+    pub fn get_alpha_value_at_uv(&self, uv: TexCoord) -> f32 {
+        return self.alpha_map.sample(uv).x / 255.0;
     }
 }
@@ -0,0 +1,84 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+use std::time;
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+/// Time without new events, after which the changes are reported, so that the burst of events of saving a file
+/// reloads it once.
+const SETTLE_TIME: time::Duration = time::Duration::from_millis(100);
+
+/// Watcher of files on disk, notified by the OS. Directories are watched with everything inside of them,
+/// subdirectories included, files through the directory they are in, so they are still seen, after an editor
+/// replaces them with a new file.
+pub struct FileWatcher {
+    _watcher: RecommendedWatcher, // Watching stops, when it's dropped.
+    events: Receiver<notify::Result<Event>>,
+    files: Vec<PathBuf>, // Files, watched on their own, events of their siblings are ignored.
+    changed: BTreeSet<PathBuf>, // Files, changed since the last report.
+    last_event: time::Instant,
+}
+
+impl FileWatcher {
+    pub fn new(paths: &[&str]) -> notify::Result<Self> {
+        let (sender, events) = channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+        let mut files = Vec::new();
+        for path in paths {
+            let path = fs::canonicalize(path)?;
+            if path.is_dir() {
+                watcher.watch(&path, RecursiveMode::Recursive)?;
+                continue;
+            }
+            // Canonical path of a file always has a parent.
+            watcher.watch(path.parent().unwrap(), RecursiveMode::NonRecursive)?;
+            files.push(path);
+        }
+        return Ok(Self {
+            _watcher: watcher,
+            events,
+            files,
+            changed: BTreeSet::new(),
+            last_event: time::Instant::now(),
+        });
+    }
+
+    /// Files, which were changed, created or removed since the last report, empty until the events settle.
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        for event in self.events.try_iter().flatten() {
+            if !matches!(
+                event.kind,
+                EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+            ) {
+                continue;
+            }
+            for path in event.paths {
+                if self.is_watched(&path) {
+                    self.changed.insert(path);
+                    self.last_event = time::Instant::now();
+                }
+            }
+        }
+        if self.changed.is_empty() || self.last_event.elapsed() < SETTLE_TIME {
+            return Vec::new();
+        }
+        return std::mem::take(&mut self.changed).into_iter().collect();
+    }
+
+    /// Whether the path is one of the watched files or a file in one of the watched directories, rather than a
+    /// directory itself or a sibling of a watched file.
+    fn is_watched(&self, path: &Path) -> bool {
+        if path.is_dir() {
+            return false;
+        }
+        let parent = path.parent();
+        let is_sibling = self
+            .files
+            .iter()
+            .any(|file| file.parent() == parent && file != path);
+        let is_file = self.files.iter().any(|file| file == path);
+        return is_file || !is_sibling;
+    }
+}
@@ -0,0 +1,99 @@
+//! Browser front end, exporting plain functions from the WebAssembly module, so a page can drive the renderer
+//! without any bindings generator. Page calls `init` once, then `render` every animation frame and copies
+//! `width * height` RGBA pixels from the returned address of the module memory into a canvas, see web/.
+
+use std::cell::RefCell;
+use std::io::BufReader;
+
+use image::RgbImage;
+use na::vector;
+use nalgebra as na;
+use obj::raw::parse_obj;
+
+use crate::scene::{Scene, BUILTIN_PIPELINE_NAMES};
+
+// There is no file system in the browser, so the default asset is compiled into the module.
+const MODEL: &[u8] = include_bytes!("../assets/diablo/model.obj");
+const TEXTURE: &[u8] = include_bytes!("../assets/diablo/texture.tga");
+const NORMAL_MAP: &[u8] = include_bytes!("../assets/diablo/normal_map.tga");
+const NORMAL_MAP_TANGENT: &[u8] = include_bytes!("../assets/diablo/normal_map_tangent.tga");
+const SPECULAR_MAP: &[u8] = include_bytes!("../assets/diablo/specular_map.tga");
+
+struct WebViewer {
+    scene: Scene,
+    rgba: Vec<u8>, // Last rendered frame, top row first, as canvas expects.
+}
+
+thread_local! {
+    static VIEWER: RefCell<Option<WebViewer>> = const { RefCell::new(None) };
+}
+
+fn decode_tga(bytes: &[u8]) -> RgbImage {
+    return image::load_from_memory_with_format(bytes, image::ImageFormat::Tga)
+        .expect("Embedded map should be a valid TGA image!")
+        .into_rgb8();
+}
+
+/// Creates the scene with the embedded asset, rendering width x height frames.
+#[no_mangle]
+pub extern "C" fn init(width: u32, height: u32) {
+    let obj = parse_obj(BufReader::new(MODEL)).expect("Embedded model should be a valid OBJ!");
+    let scene = Scene::new(
+        width,
+        height,
+        obj,
+        Some(decode_tga(TEXTURE)),
+        Some(decode_tga(NORMAL_MAP)),
+        Some(decode_tga(NORMAL_MAP_TANGENT)),
+        Some(decode_tga(SPECULAR_MAP)),
+        BUILTIN_PIPELINE_NAMES[0].to_string(),
+    )
+    .expect("First built-in pipeline should exist!");
+    let rgba = vec![0; (4 * width * height) as usize];
+    VIEWER.with(|viewer| *viewer.borrow_mut() = Some(WebViewer { scene, rgba }));
+}
+
+/// Number of built-in pipelines, which can be passed to `set_pipeline`.
+#[no_mangle]
+pub extern "C" fn get_pipeline_count() -> u32 {
+    return BUILTIN_PIPELINE_NAMES.len() as u32;
+}
+
+/// Switches to the built-in pipeline with the index, wrapping around.
+#[no_mangle]
+pub extern "C" fn set_pipeline(index: u32) {
+    VIEWER.with(|viewer| {
+        let mut viewer = viewer.borrow_mut();
+        let viewer = viewer.as_mut().expect("init should be called first!");
+        let name = BUILTIN_PIPELINE_NAMES[index as usize % BUILTIN_PIPELINE_NAMES.len()];
+        viewer
+            .scene
+            .set_builtin_shader_pipeline(name.to_string())
+            .expect("Built-in pipeline names should be valid!");
+    });
+}
+
+/// Renders a frame with the camera and the light orbiting the model at the angles in radians, returning the
+/// address of its RGBA pixels, valid until the next call.
+#[no_mangle]
+pub extern "C" fn render(camera_angle: f32, light_angle: f32) -> *const u8 {
+    return VIEWER.with(|viewer| {
+        let mut viewer = viewer.borrow_mut();
+        let viewer = viewer.as_mut().expect("init should be called first!");
+        let scene = &mut viewer.scene;
+        scene.clear();
+        scene.set_light_direction(vector![light_angle.sin(), 0.0, light_angle.cos()]);
+        scene.set_camera(
+            vector![camera_angle.sin(), 0.0, camera_angle.cos()],
+            vector![0.0, 0.0, 0.0],
+            vector![0.0, 1.0, 0.0],
+        );
+        scene.render();
+        let frame = scene.get_frame_buffer();
+        for (rgba, rgb) in viewer.rgba.chunks_mut(4).zip(frame.pixels()) {
+            rgba[..3].copy_from_slice(&rgb.0);
+            rgba[3] = 255;
+        }
+        return viewer.rgba.as_ptr();
+    });
+}